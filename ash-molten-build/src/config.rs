@@ -0,0 +1,305 @@
+use anyhow::{bail, Error};
+use std::collections::BTreeMap;
+
+/// How the value of a MoltenVK setting is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SettingKind {
+    Bool,
+    U32,
+    /// A duration in nanoseconds
+    Nanos,
+    Float,
+    /// A file path, which can't be baked into the build
+    Path,
+    /// Accepts the names of the variants of the Rust enum `ty`, which are converted to their
+    /// values
+    Enum {
+        ty: &'static str,
+        variants: &'static [(&'static str, u32)],
+    },
+}
+
+/// A MoltenVK setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Setting {
+    /// The name without the `MVK_CONFIG_` prefix, eg. `LOG_LEVEL`
+    pub name: &'static str,
+    /// The field of `ash_molten::MoltenVkSettings` holding the setting
+    pub field: &'static str,
+    pub kind: SettingKind,
+}
+
+impl Setting {
+    /// Whether the setting can be baked into the build with [`Config`]
+    pub fn can_bake(&self) -> bool {
+        self.kind != SettingKind::Path
+    }
+
+    /// The type of the field in `ash_molten::MoltenVkSettings`, without the `Option`
+    pub fn field_type(&self) -> &'static str {
+        match self.kind {
+            SettingKind::Bool => "bool",
+            SettingKind::U32 => "u32",
+            SettingKind::Nanos => "Duration",
+            SettingKind::Float => "f32",
+            SettingKind::Path => "String",
+            SettingKind::Enum { ty, .. } => ty,
+        }
+    }
+}
+
+/// The MoltenVK settings `ash-molten` knows, both the ones [`Config`] bakes into the build and
+/// the ones `ash_molten::MoltenVkSettings` passes at runtime
+pub static SETTINGS: &[Setting] = &[
+    Setting {
+        name: "DEBUG",
+        field: "debug",
+        kind: SettingKind::Bool,
+    },
+    Setting {
+        name: "LOG_LEVEL",
+        field: "log_level",
+        kind: SettingKind::Enum {
+            ty: "LogLevel",
+            variants: &[
+                ("none", 0),
+                ("error", 1),
+                ("warning", 2),
+                ("info", 3),
+                ("debug", 4),
+            ],
+        },
+    },
+    Setting {
+        name: "USE_METAL_ARGUMENT_BUFFERS",
+        field: "use_metal_argument_buffers",
+        kind: SettingKind::Bool,
+    },
+    Setting {
+        name: "FAST_MATH_ENABLED",
+        field: "fast_math",
+        kind: SettingKind::Enum {
+            ty: "FastMath",
+            variants: &[("never", 0), ("always", 1), ("on_demand", 2)],
+        },
+    },
+    Setting {
+        name: "METAL_COMPILE_TIMEOUT",
+        field: "metal_compile_timeout",
+        kind: SettingKind::Nanos,
+    },
+    Setting {
+        name: "SHOULD_MAXIMIZE_CONCURRENT_COMPILATION",
+        field: "should_maximize_concurrent_compilation",
+        kind: SettingKind::Bool,
+    },
+    Setting {
+        name: "TIMESTAMP_PERIOD_LOWPASS_ALPHA",
+        field: "timestamp_period_low_pass_alpha",
+        kind: SettingKind::Float,
+    },
+    Setting {
+        name: "AUTO_GPU_CAPTURE_SCOPE",
+        field: "auto_gpu_capture_scope",
+        kind: SettingKind::Enum {
+            ty: "GpuCaptureScope",
+            variants: &[("none", 0), ("device", 1), ("frame", 2)],
+        },
+    },
+    Setting {
+        name: "AUTO_GPU_CAPTURE_OUTPUT_FILE",
+        field: "auto_gpu_capture_output_file",
+        kind: SettingKind::Path,
+    },
+    Setting {
+        name: "SWAPCHAIN_MIN_MAG_FILTER_USE_NEAREST",
+        field: "swapchain_min_mag_filter_use_nearest",
+        kind: SettingKind::Bool,
+    },
+    Setting {
+        name: "PRESENT_WITH_COMMAND_BUFFER",
+        field: "present_with_command_buffer",
+        kind: SettingKind::Bool,
+    },
+    Setting {
+        name: "SYNCHRONOUS_QUEUE_SUBMITS",
+        field: "synchronous_queue_submits",
+        kind: SettingKind::Bool,
+    },
+    Setting {
+        name: "PERFORMANCE_TRACKING",
+        field: "performance_tracking",
+        kind: SettingKind::Bool,
+    },
+    Setting {
+        name: "PERFORMANCE_LOGGING_FRAME_COUNT",
+        field: "performance_logging_frame_count",
+        kind: SettingKind::U32,
+    },
+    Setting {
+        name: "API_VERSION_TO_ADVERTISE",
+        field: "api_version_to_advertise",
+        kind: SettingKind::U32,
+    },
+];
+
+/// The input of the `settings!` macro in `ash-molten`'s `src/settings.rs`, which generates the
+/// conversions of `MoltenVkSettings` from [`SETTINGS`]
+pub fn settings_macro_input() -> String {
+    SETTINGS
+        .iter()
+        .map(|setting| {
+            format!(
+                "    {}: {} => {:?},\n",
+                setting.field,
+                setting.field_type(),
+                setting.name
+            )
+        })
+        .collect()
+}
+
+/// How a [`Config`] reaches MoltenVK
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigMode {
+    /// The configuration is empty, MoltenVK uses its own defaults
+    None,
+    /// The values are MoltenVK's compile-time defaults, it was built from source with them
+    Compiled,
+    /// The crate being built applies the values when it creates a Vulkan instance, see
+    /// [`Artifact::emit_rustc_env`](crate::Artifact::emit_rustc_env)
+    Runtime,
+}
+
+impl ConfigMode {
+    pub(crate) fn new(config: &Config, compiled: bool) -> Self {
+        if config.is_empty() {
+            Self::None
+        } else if compiled {
+            Self::Compiled
+        } else {
+            Self::Runtime
+        }
+    }
+
+    /// The name of the mode, as written in the [`Report`](crate::Report)
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Compiled => "compiled",
+            Self::Runtime => "runtime",
+        }
+    }
+}
+
+/// Default MoltenVK configuration baked into the build.
+///
+/// When MoltenVK is built from source the values become its compile-time defaults for the
+/// corresponding `MVK_CONFIG_*` settings, otherwise they are handed to the crate being built
+/// with [`Artifact::emit_rustc_env`](crate::Artifact::emit_rustc_env) so it can apply them at
+/// runtime. Either way no environment variables are needed when the application runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    values: BTreeMap<&'static str, String>,
+}
+
+impl Config {
+    /// An empty configuration, MoltenVK keeps its own defaults
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the default of `MVK_CONFIG_<name>`, eg. `set("LOG_LEVEL", "warning")`.
+    ///
+    /// Booleans are normalized to `0` and `1` and enum names to their values, unknown
+    /// settings and invalid values are errors.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        let Some(setting) = SETTINGS
+            .iter()
+            .find(|setting| setting.name == name && setting.can_bake())
+        else {
+            bail!("MoltenVK setting MVK_CONFIG_{name} can't be baked into the build");
+        };
+        let name = setting.name;
+
+        let value = value.trim();
+        let normalized = match setting.kind {
+            SettingKind::Bool => match value.to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" => Some("1".to_owned()),
+                "0" | "false" | "no" => Some("0".to_owned()),
+                _ => None,
+            },
+            SettingKind::U32 => value.parse::<u32>().ok().map(|value| value.to_string()),
+            SettingKind::Nanos => value.parse::<u64>().ok().map(|value| value.to_string()),
+            SettingKind::Float => value
+                .parse::<f32>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .map(|value| format!("{value:?}")),
+            SettingKind::Enum { variants, .. } => {
+                let value = value.to_ascii_lowercase();
+                variants
+                    .iter()
+                    .find(|(variant, number)| *variant == value || number.to_string() == value)
+                    .map(|(_, number)| number.to_string())
+            }
+            SettingKind::Path => None,
+        };
+
+        let Some(normalized) = normalized else {
+            bail!("invalid value '{value}' for MoltenVK setting MVK_CONFIG_{name}");
+        };
+
+        self.values.insert(name, normalized);
+        Ok(())
+    }
+
+    /// Reads the settings from environment variables named `prefix` followed by the setting,
+    /// eg. `ASH_MOLTEN_MVK_CONFIG_LOG_LEVEL` for a prefix of `ASH_MOLTEN_MVK_CONFIG_`.
+    ///
+    /// This also prints `cargo:rerun-if-env-changed` for every variable it reads.
+    pub fn from_env(prefix: &str) -> Result<Self, Error> {
+        let mut config = Self::new();
+        for setting in SETTINGS.iter().filter(|setting| setting.can_bake()) {
+            let name = setting.name;
+            let var = format!("{prefix}{name}");
+            println!("cargo:rerun-if-env-changed={var}");
+
+            if let Ok(value) = std::env::var(&var) {
+                config.set(name, &value)?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// Whether no setting is set
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The normalized values, by the setting name without the `MVK_CONFIG_` prefix
+    pub fn values(&self) -> &BTreeMap<&'static str, String> {
+        &self.values
+    }
+
+    /// The xcconfig that makes the values MoltenVK's compile-time defaults
+    pub(crate) fn xcconfig(&self) -> String {
+        let defines = self
+            .values
+            .iter()
+            .map(|(name, value)| format!(" MVK_CONFIG_{name}={value}"))
+            .collect::<String>();
+
+        format!("GCC_PREPROCESSOR_DEFINITIONS = $(inherited){defines}\n")
+    }
+
+    /// The values as `NAME=value` pairs separated by `;`
+    pub(crate) fn to_env_value(&self) -> String {
+        self.values
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+}
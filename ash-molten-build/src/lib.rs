@@ -0,0 +1,532 @@
+//! Build script helpers for resolving and linking [MoltenVK].
+//!
+//! This is the logic `ash-molten` itself uses in its `build.rs`, exposed so that other crates
+//! can resolve MoltenVK (or reuse the xcframework slice resolution) from their own build
+//! scripts.
+//!
+//! ```no_run
+//! let artifact = ash_molten_build::Builder::new("1.2.8")
+//!     .source(ash_molten_build::Source::Prebuilt)
+//!     .resolve()
+//!     .expect("failed to resolve MoltenVK");
+//! artifact.emit_link_directives();
+//! ```
+//!
+//! [MoltenVK]: https://github.com/KhronosGroup/MoltenVK
+
+use anyhow::{bail, Context as _, Error};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::Instant,
+};
+
+mod config;
+mod prebuilt;
+mod report;
+mod source;
+pub mod xcframework;
+
+pub use config::{settings_macro_input, Config, ConfigMode, Setting, SettingKind, SETTINGS};
+pub use report::{Report, Timing};
+
+/// The frameworks MoltenVK needs to be linked against
+pub static FRAMEWORKS: &[&str] = &["Metal", "AppKit", "QuartzCore", "IOKit", "IOSurface"];
+
+/// Where the MoltenVK library comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// Clone the tagged MoltenVK release (or use a local checkout) and build it
+    Build,
+    /// Download a prebuilt xcframework from an `ash-molten` release
+    Prebuilt,
+    /// The library is supplied by the user, nothing is resolved
+    External,
+    /// Nothing is linked, the crate being built provides its own implementation of the Vulkan
+    /// entry point, eg. for testing on platforms without MoltenVK
+    Mock,
+    /// Nothing is linked because the target isn't supported by MoltenVK, the crate being built
+    /// reports that at runtime instead
+    Unsupported,
+}
+
+impl Source {
+    /// The name of the source, as exported in the `source` metadata
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Build => "built",
+            Self::Prebuilt => "prebuilt",
+            Self::External => "external",
+            Self::Mock => "mock",
+            Self::Unsupported => "unsupported",
+        }
+    }
+
+    /// Whether MoltenVK is linked at all, `false` for [`Source::Mock`] and
+    /// [`Source::Unsupported`]
+    pub fn links(self) -> bool {
+        !matches!(self, Self::Mock | Self::Unsupported)
+    }
+}
+
+/// Configures how MoltenVK is resolved
+#[derive(Debug, Clone)]
+pub struct Builder {
+    version: String,
+    patch: Option<String>,
+    source: Source,
+    local_bin: Option<PathBuf>,
+    local_source: Option<PathBuf>,
+    out_dir: Option<PathBuf>,
+    target_os: Option<String>,
+    target_arch: Option<String>,
+    variant: xcframework::Variant,
+    config: Config,
+}
+
+impl Builder {
+    /// Creates a builder for the MoltenVK release tagged `version`, eg. `"1.2.8"`.
+    pub fn new(version: impl Into<String>) -> Self {
+        Self {
+            version: version.into(),
+            patch: None,
+            source: Source::Build,
+            local_bin: None,
+            local_source: None,
+            out_dir: None,
+            target_os: None,
+            target_arch: None,
+            variant: xcframework::Variant::Default,
+            config: Config::new(),
+        }
+    }
+
+    /// Checks out a specific MoltenVK commit on top of the release.
+    ///
+    /// The artifact tag then becomes `<version>#<patch>`.
+    pub fn patch(mut self, patch: impl Into<String>) -> Self {
+        self.patch = Some(patch.into());
+        self
+    }
+
+    /// Selects where the library comes from, defaults to [`Source::Build`].
+    pub fn source(mut self, source: Source) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Uses a local MoltenVK binary directory directly, for example from a Vulkan SDK.
+    ///
+    /// The directory must contain `MoltenVK.xcframework`. This takes precedence over
+    /// [`Source::Build`] and [`Source::Prebuilt`].
+    pub fn local_bin(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.local_bin = Some(dir.into());
+        self
+    }
+
+    /// Builds from a local MoltenVK checkout instead of cloning, useful for `git bisect`.
+    ///
+    /// The checkout is never pulled or modified. This implies [`Source::Build`] unless the
+    /// source is [`Source::External`].
+    pub fn local_source(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.local_source = Some(dir.into());
+        self
+    }
+
+    /// The directory downloads and checkouts are placed in, defaults to `OUT_DIR`.
+    pub fn out_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.out_dir = Some(dir.into());
+        self
+    }
+
+    /// The target to resolve the library for, defaults to `CARGO_CFG_TARGET_OS` and
+    /// `CARGO_CFG_TARGET_ARCH`.
+    pub fn target(mut self, os: impl Into<String>, arch: impl Into<String>) -> Self {
+        self.target_os = Some(os.into());
+        self.target_arch = Some(arch.into());
+        self
+    }
+
+    /// Selects the platform variant of the xcframework slice, eg. the iOS simulator.
+    pub fn variant(mut self, variant: xcframework::Variant) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    /// The default MoltenVK configuration to bake into the build, see [`Config`].
+    ///
+    /// With [`Source::Build`] the values are compiled into MoltenVK, otherwise they are
+    /// exported by [`Artifact::emit_rustc_env`] to be applied at runtime.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// The artifact tag in the form of "x.x.x" or if there is a patch specified "x.x.x#yyyyyyy"
+    pub fn artifact_tag(&self) -> String {
+        if let Some(patch) = &self.patch {
+            format!("{}#{patch}", self.version)
+        } else {
+            self.version.clone()
+        }
+    }
+
+    fn effective_source(&self) -> Source {
+        match self.source {
+            Source::Prebuilt if self.local_source.is_some() => Source::Build,
+            source => source,
+        }
+    }
+
+    /// Resolves the MoltenVK library, building or downloading it if needed.
+    pub fn resolve(self) -> Result<Artifact, Error> {
+        let source = self.effective_source();
+        let artifact_tag = self.artifact_tag();
+
+        let local = self.local_bin.is_some() || self.local_source.is_some();
+
+        // Nothing is linked, so the target doesn't matter
+        if !source.links() {
+            return Ok(Artifact::unresolved(
+                source,
+                self.version,
+                artifact_tag,
+                self.patch,
+                self.config,
+            ));
+        }
+
+        let target_os = match self.target_os.clone() {
+            Some(os) => os,
+            None => env("CARGO_CFG_TARGET_OS")?,
+        };
+        let target_arch = match self.target_arch.clone() {
+            Some(arch) => arch,
+            None => env("CARGO_CFG_TARGET_ARCH")?,
+        };
+
+        // These (currently) match the identifiers used by moltenvk
+        let target_name = match target_os.as_str() {
+            "macos" => "macos",
+            "ios" => "ios",
+            target => bail!("unknown target '{target}'"),
+        };
+
+        if source == Source::External {
+            return Ok(Artifact::unresolved(
+                source,
+                self.version,
+                artifact_tag,
+                self.patch,
+                self.config,
+            ));
+        }
+
+        let out_dir = match self.out_dir.clone() {
+            Some(dir) => dir,
+            None => PathBuf::from(env("OUT_DIR")?),
+        };
+
+        // The MoltenVK package layout keeps the headers next to the xcframework, which is used
+        // if the xcframework slice itself doesn't declare any
+        let mut package_include = None;
+        let mut commit = None;
+        let mut url = None;
+        let mut timings = Vec::new();
+        let mut compiled = false;
+
+        let xcframework_dir = if let Some(local_bin) = &self.local_bin {
+            package_include = Some(local_bin.join("include"));
+            local_bin.join("MoltenVK.xcframework")
+        } else if source == Source::Prebuilt {
+            let target_dir = out_dir.join(format!("Prebuilt-MoltenVK-{artifact_tag}"));
+            let download_url = format!(
+                "https://github.com/EmbarkStudios/ash-molten/releases/download/MoltenVK-{}/MoltenVK.xcframework.zip",
+                artifact_tag.replace('#', "%23")
+            );
+
+            let start = Instant::now();
+            prebuilt::download(&target_dir, &download_url)?;
+            timings.push(Timing::new("download", start.elapsed()));
+            url = Some(download_url);
+
+            target_dir.join("MoltenVK.xcframework")
+        } else {
+            let checkout_dir = if let Some(local) = &self.local_source {
+                local.clone()
+            } else {
+                out_dir.join(format!("MoltenVK-{artifact_tag}"))
+            };
+
+            let xcconfig = if self.config.is_empty() {
+                None
+            } else {
+                let path = out_dir.join("moltenvk-config.xcconfig");
+                std::fs::write(&path, self.config.xcconfig())
+                    .with_context(|| format!("failed to write {path:?}"))?;
+                Some(path)
+            };
+
+            let built = source::build(
+                &checkout_dir,
+                &self.version,
+                self.patch.as_deref(),
+                self.local_source.is_some(),
+                target_name,
+                xcconfig.as_deref(),
+            )?;
+            compiled = true;
+            commit = built.commit;
+            timings.extend(built.timings);
+            if self.local_source.is_none() {
+                url = Some(source::REPOSITORY.to_owned());
+            }
+
+            package_include = Some(checkout_dir.join("Package/Latest/MoltenVK/include"));
+            checkout_dir.join("Package/Latest/MoltenVK/static/MoltenVK.xcframework")
+        };
+
+        let start = Instant::now();
+        let framework = xcframework::XcFramework::parse(&xcframework_dir)
+            .with_context(|| format!("Failed to parse XCFramework from {xcframework_dir:?}"))?;
+
+        let id = xcframework::Identifier::new(
+            target_arch.as_str().into(),
+            target_os.as_str().into(),
+            self.variant,
+        );
+
+        let mut native_lib = None;
+        for lib in framework.AvailableLibraries {
+            for native in lib
+                .universal_to_native(&xcframework_dir)
+                .context("Failed to get native library")?
+            {
+                if native.identifier() == id {
+                    native_lib = Some(native);
+                }
+            }
+        }
+
+        let Some(native_lib) = native_lib else {
+            bail!("Library was not found for {id:?} in {xcframework_dir:?}");
+        };
+
+        let include = native_lib
+            .headers_path()
+            .map(|headers| xcframework_dir.join(headers))
+            .or(package_include)
+            .filter(|include| include.is_dir());
+        timings.push(Timing::new("resolve", start.elapsed()));
+
+        let library = xcframework_dir.join(native_lib.path());
+
+        let start = Instant::now();
+        let checksum = sha256(&library);
+        timings.push(Timing::new("checksum", start.elapsed()));
+
+        let config_mode = ConfigMode::new(&self.config, compiled);
+
+        Ok(Artifact {
+            source,
+            local,
+            version: self.version,
+            artifact_tag,
+            patch: self.patch,
+            commit,
+            url,
+            checksum,
+            library: Some(library),
+            include,
+            xcframework: Some(xcframework_dir),
+            identifier: Some(id),
+            timings,
+            config: self.config,
+            config_mode,
+        })
+    }
+}
+
+/// A resolved MoltenVK library
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    /// Where the library came from
+    pub source: Source,
+    /// Whether the library came from a local checkout or binary directory
+    pub local: bool,
+    /// The MoltenVK release version, eg. `"1.2.8"`
+    pub version: String,
+    /// The artifact tag, see [`Builder::artifact_tag`]
+    pub artifact_tag: String,
+    /// The MoltenVK commit checked out on top of the release, see [`Builder::patch`]
+    pub patch: Option<String>,
+    /// The commit of the MoltenVK checkout that was built, only known for [`Source::Build`]
+    pub commit: Option<String>,
+    /// The repository or archive the library was fetched from, `None` for local and external
+    /// libraries
+    pub url: Option<String>,
+    /// The SHA-256 of the static library, if `shasum` is available
+    pub checksum: Option<String>,
+    /// The xcframework the library was selected from, `None` for [`Source::External`] and
+    /// sources that don't link anything
+    pub xcframework: Option<PathBuf>,
+    /// The full path to the static library, `None` for [`Source::External`] and
+    /// sources that don't link anything
+    pub library: Option<PathBuf>,
+    /// The directory containing the MoltenVK headers, if one could be found
+    pub include: Option<PathBuf>,
+    /// The xcframework slice that was selected, `None` for [`Source::External`] and
+    /// sources that don't link anything
+    pub identifier: Option<xcframework::Identifier>,
+    /// The time spent in each phase of resolving the library
+    pub timings: Vec<Timing>,
+    /// The default MoltenVK configuration, see [`Builder::config`]
+    pub config: Config,
+    /// How [`Artifact::config`] reaches MoltenVK
+    pub config_mode: ConfigMode,
+}
+
+impl Artifact {
+    /// An artifact for sources that don't resolve a library
+    fn unresolved(
+        source: Source,
+        version: String,
+        artifact_tag: String,
+        patch: Option<String>,
+        config: Config,
+    ) -> Self {
+        Self {
+            source,
+            local: false,
+            version,
+            artifact_tag,
+            patch,
+            commit: None,
+            url: None,
+            checksum: None,
+            xcframework: None,
+            library: None,
+            include: None,
+            identifier: None,
+            timings: Vec::new(),
+            config_mode: ConfigMode::new(&config, false),
+            config,
+        }
+    }
+
+    /// The directory containing the static library
+    pub fn lib_dir(&self) -> Option<&Path> {
+        self.library.as_deref().and_then(Path::parent)
+    }
+
+    /// The kind of source the library came from, `"local"` for local checkouts and binaries,
+    /// otherwise [`Source::as_str`]
+    pub fn source_kind(&self) -> &'static str {
+        if self.local {
+            "local"
+        } else {
+            self.source.as_str()
+        }
+    }
+
+    /// Describes where the library came from and how it was resolved
+    pub fn report(&self) -> Report {
+        Report::new(self)
+    }
+
+    /// Prints the metadata dependents can read as `DEP_<links>_<KEY>` when the package declares
+    /// a `links` key.
+    ///
+    /// This emits `version`, `source` and, when known, `include`, `lib_dir` and `xcframework`.
+    pub fn emit_metadata(&self) {
+        println!("cargo:version={}", self.version);
+        println!("cargo:source={}", self.source_kind());
+
+        if let Some(include) = &self.include {
+            println!("cargo:include={}", include.display());
+        }
+        if let Some(lib_dir) = self.lib_dir() {
+            println!("cargo:lib_dir={}", lib_dir.display());
+        }
+        if let Some(xcframework) = &self.xcframework {
+            println!("cargo:xcframework={}", xcframework.display());
+        }
+    }
+
+    /// Prints `cargo:rustc-env` directives so the crate being built can embed where its MoltenVK
+    /// came from.
+    ///
+    /// This sets `MOLTENVK_VERSION`, `MOLTENVK_ARTIFACT_TAG`, `MOLTENVK_SOURCE` and, when known,
+    /// `MOLTENVK_COMMIT`. With [`ConfigMode::Runtime`] it also sets `MOLTENVK_RUNTIME_CONFIG` to
+    /// the configuration as `NAME=value` pairs separated by `;`.
+    pub fn emit_rustc_env(&self) {
+        println!("cargo:rustc-env=MOLTENVK_VERSION={}", self.version);
+        println!(
+            "cargo:rustc-env=MOLTENVK_ARTIFACT_TAG={}",
+            self.artifact_tag
+        );
+        println!("cargo:rustc-env=MOLTENVK_SOURCE={}", self.source_kind());
+
+        if let Some(commit) = self.commit.as_deref().or(self.patch.as_deref()) {
+            println!("cargo:rustc-env=MOLTENVK_COMMIT={commit}");
+        }
+
+        if self.config_mode == ConfigMode::Runtime {
+            println!(
+                "cargo:rustc-env=MOLTENVK_RUNTIME_CONFIG={}",
+                self.config.to_env_value()
+            );
+        }
+    }
+
+    /// The system frameworks that are linked alongside MoltenVK, empty for sources that don't
+    /// link anything
+    pub fn frameworks(&self) -> &'static [&'static str] {
+        if !self.source.links() {
+            &[]
+        } else {
+            FRAMEWORKS
+        }
+    }
+
+    /// Prints the `cargo:` directives to link MoltenVK and the frameworks it depends on.
+    ///
+    /// Nothing is printed for [`Source::Mock`] and [`Source::Unsupported`].
+    pub fn emit_link_directives(&self) {
+        if !self.source.links() {
+            return;
+        }
+
+        if let Some(lib_dir) = self.lib_dir() {
+            println!("cargo:rustc-link-search=native={}", lib_dir.display());
+        }
+
+        for framework in self.frameworks() {
+            println!("cargo:rustc-link-lib=framework={framework}");
+        }
+        println!("cargo:rustc-link-lib=dylib=c++");
+        println!("cargo:rustc-link-lib=static=MoltenVK");
+    }
+}
+
+/// Hashes `path` with the `shasum` tool, which is available on every macOS install
+fn sha256(path: &Path) -> Option<String> {
+    let output = Command::new("shasum")
+        .args(["-a", "256"])
+        .arg(path)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .next()
+        .map(String::from)
+}
+
+fn env(var: &str) -> Result<String, Error> {
+    std::env::var(var).with_context(|| format!("unable to find env:{var}"))
+}
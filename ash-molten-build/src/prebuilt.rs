@@ -0,0 +1,42 @@
+use anyhow::{bail, Context as _, Error};
+use std::{path::Path, process::Command};
+
+/// Downloads and unpacks a zipped `MoltenVK.xcframework` into `target_dir`
+pub(crate) fn download(target_dir: &Path, download_url: &str) -> Result<(), Error> {
+    std::fs::create_dir_all(target_dir).context("Couldn't create directory")?;
+
+    let download_path = target_dir.join("MoltenVK.xcframework.zip");
+
+    let curl_status = Command::new("curl")
+        .args(["--fail", "--location", "--silent", download_url, "-o"])
+        .arg(&download_path)
+        .status()
+        .context("Couldn't launch curl")?;
+
+    if !curl_status.success() {
+        bail!("failed to download prebuilt libraries from {download_url}");
+    }
+
+    let unzip_status = Command::new("unzip")
+        .arg("-o")
+        .arg(&download_path)
+        .arg("-d")
+        .arg(target_dir)
+        .status()
+        .context("Couldn't launch unzip")?;
+
+    if !unzip_status.success() {
+        let bytes = std::fs::read(&download_path)
+            .context("unzip failed, and further, could not open downloaded zip file")?;
+        if let Ok(zip_text) = std::str::from_utf8(&bytes) {
+            bail!("Could not unzip MoltenVK.xcframework.zip. File was utf8, perhaps an error?\n{zip_text}");
+        } else {
+            bail!(
+                "Could not unzip MoltenVK.xcframework.zip: {:?}",
+                unzip_status.code()
+            );
+        }
+    }
+
+    Ok(())
+}
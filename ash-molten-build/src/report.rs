@@ -0,0 +1,84 @@
+use crate::Artifact;
+use anyhow::{Context as _, Error};
+use std::{collections::BTreeMap, path::Path, time::Duration};
+
+/// The time spent in one phase of resolving MoltenVK
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Timing {
+    /// The phase, one of `fetch`, `build`, `download`, `resolve` or `checksum`
+    pub phase: &'static str,
+    /// The time the phase took, in seconds
+    pub seconds: f64,
+}
+
+impl Timing {
+    pub(crate) fn new(phase: &'static str, duration: Duration) -> Self {
+        Self {
+            phase,
+            seconds: duration.as_secs_f64(),
+        }
+    }
+}
+
+/// A machine readable description of the resolved MoltenVK artifact
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Report {
+    /// See [`Artifact::source_kind`]
+    pub source: &'static str,
+    /// The MoltenVK release version
+    pub version: String,
+    /// The MoltenVK commit checked out on top of the release, if any
+    pub patch: Option<String>,
+    /// The commit of the MoltenVK checkout that was built, if known
+    pub commit: Option<String>,
+    /// The repository or archive the library was fetched from
+    pub url: Option<String>,
+    /// The SHA-256 of the static library, if it could be computed
+    pub checksum: Option<String>,
+    /// The selected xcframework slice, eg. `macos-arm64`
+    pub identifier: Option<String>,
+    /// The full path to the static library
+    pub library: Option<String>,
+    /// The system frameworks linked alongside MoltenVK
+    pub frameworks: Vec<&'static str>,
+    /// The time spent in each phase
+    pub timings: Vec<Timing>,
+    /// The baked MoltenVK configuration, by the setting name without the `MVK_CONFIG_` prefix
+    pub config: BTreeMap<&'static str, String>,
+    /// See [`ConfigMode::as_str`](crate::ConfigMode::as_str)
+    pub config_mode: &'static str,
+}
+
+impl Report {
+    pub(crate) fn new(artifact: &Artifact) -> Self {
+        Self {
+            source: artifact.source_kind(),
+            version: artifact.version.clone(),
+            patch: artifact.patch.clone(),
+            commit: artifact.commit.clone(),
+            url: artifact.url.clone(),
+            checksum: artifact.checksum.clone(),
+            identifier: artifact.identifier.map(|id| id.to_string()),
+            library: artifact
+                .library
+                .as_ref()
+                .map(|library| library.display().to_string()),
+            frameworks: artifact.frameworks().to_vec(),
+            timings: artifact.timings.clone(),
+            config: artifact.config.values().clone(),
+            config_mode: artifact.config_mode.as_str(),
+        }
+    }
+
+    /// Serializes the report as pretty printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("report is always serializable")
+    }
+
+    /// Writes the report as JSON to `path`
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_json())
+            .with_context(|| format!("failed to write build report to {path:?}"))
+    }
+}
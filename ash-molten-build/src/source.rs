@@ -0,0 +1,158 @@
+use crate::report::Timing;
+use anyhow::{ensure, Context as _, Error};
+use std::{
+    path::Path,
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+pub(crate) static REPOSITORY: &str = "https://github.com/KhronosGroup/MoltenVK.git";
+
+/// The outcome of building MoltenVK from source
+pub(crate) struct Built {
+    /// The commit that was built, `None` if git couldn't tell
+    pub(crate) commit: Option<String>,
+    pub(crate) timings: Vec<Timing>,
+}
+
+/// Clones (or pulls) MoltenVK into `checkout_dir` and builds the static xcframework for
+/// `target_name`, applying the build settings in `xcconfig` if any
+pub(crate) fn build(
+    checkout_dir: &Path,
+    version: &str,
+    patch: Option<&str>,
+    is_local: bool,
+    target_name: &str,
+    xcconfig: Option<&Path>,
+) -> Result<Built, Error> {
+    let exit = Arc::new(AtomicBool::new(false));
+    let wants_exit = exit.clone();
+
+    // Periodically emit log messages so that Travis doesn't make a sad
+    let handle = std::thread::spawn(move || {
+        let mut counter = 0;
+        while !wants_exit.load(Ordering::Acquire) {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            counter += 100;
+
+            if counter >= 30 * 1000 {
+                counter = 0;
+                println!("still building MoltenVK");
+            }
+        }
+    });
+
+    let result = (|| {
+        let start = Instant::now();
+        checkout(checkout_dir, version, patch, is_local)?;
+        let fetch = Timing::new("fetch", start.elapsed());
+
+        let start = Instant::now();
+        make(checkout_dir, target_name, xcconfig)?;
+        let build = Timing::new("build", start.elapsed());
+
+        Ok(Built {
+            commit: commit(checkout_dir),
+            timings: vec![fetch, build],
+        })
+    })();
+
+    exit.store(true, Ordering::Release);
+    handle.join().unwrap();
+    result
+}
+
+fn checkout(
+    checkout_dir: &Path,
+    version: &str,
+    patch: Option<&str>,
+    is_local: bool,
+) -> Result<(), Error> {
+    if checkout_dir.exists() {
+        // Don't pull if a specific hash has been checked out
+        if patch.is_none() && !is_local {
+            let git_status = Command::new("git")
+                .current_dir(checkout_dir)
+                .arg("pull")
+                .status()
+                .context("failed to spawn git")?;
+
+            ensure!(git_status.success(), "failed to pull MoltenVK from git");
+        }
+    } else if !is_local {
+        let branch = format!("v{version}");
+        let clone_args = if patch.is_none() {
+            vec!["--branch", branch.as_str(), "--depth", "1"]
+        } else {
+            vec!["--single-branch", "--branch", "main"] // Can't specify depth if you switch to a different commit hash later.
+        };
+        let git_status = Command::new("git")
+            .arg("clone")
+            .args(clone_args)
+            .arg(REPOSITORY)
+            .arg(checkout_dir)
+            .status()
+            .context("failed to spawn git")?;
+
+        ensure!(git_status.success(), "failed to clone MoltenVK");
+    }
+
+    if let Some(patch) = patch {
+        let git_status = Command::new("git")
+            .current_dir(checkout_dir)
+            .arg("checkout")
+            .arg(patch)
+            .status()
+            .context("failed to spawn git")?;
+
+        ensure!(git_status.success(), "failed to checkout patch");
+    }
+
+    Ok(())
+}
+
+fn make(checkout_dir: &Path, target_name: &str, xcconfig: Option<&Path>) -> Result<(), Error> {
+    let status = Command::new("sh")
+        .current_dir(checkout_dir)
+        .arg("fetchDependencies")
+        .arg(format!("--{target_name}"))
+        .status()
+        .context("failed to spawn fetchDependencies")?;
+
+    ensure!(status.success(), "failed to fetchDependencies");
+
+    println!("running make in {checkout_dir:?}");
+
+    let mut make = Command::new("make");
+    make.current_dir(checkout_dir).arg(target_name);
+
+    // xcodebuild layers this on top of the project's own settings, which is how the baked
+    // configuration ends up in MoltenVK's `MVK_CONFIG_*` defines
+    if let Some(xcconfig) = xcconfig {
+        make.env("XCODE_XCCONFIG_FILE", xcconfig);
+    }
+
+    let status = make.status().context("failed to run make")?;
+
+    ensure!(status.success(), "failed to build MoltenVK");
+
+    Ok(())
+}
+
+/// The commit currently checked out in `checkout_dir`
+fn commit(checkout_dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .current_dir(checkout_dir)
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
@@ -40,7 +40,7 @@ impl<T: AsRef<str>> From<T> for Arch {
     }
 }
 
-impl<'a> From<Arch> for &'a str {
+impl From<Arch> for &str {
     fn from(arch: Arch) -> Self {
         match arch {
             Arch::Amd64 => "x86_64",
@@ -64,7 +64,7 @@ impl<T: AsRef<str>> From<T> for Platform {
     }
 }
 
-impl<'a> From<Platform> for &'a str {
+impl From<Platform> for &str {
     fn from(platform: Platform) -> Self {
         match platform {
             Platform::TvOs => "tvos",
@@ -85,7 +85,7 @@ impl<T: AsRef<str>> From<T> for Variant {
     }
 }
 
-impl<'a> From<Variant> for &'a str {
+impl From<Variant> for &str {
     fn from(variant: Variant) -> Self {
         match variant {
             Variant::Simulator => "simulator",
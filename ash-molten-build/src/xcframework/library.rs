@@ -1,7 +1,8 @@
-use anyhow::Error;
+use anyhow::{ensure, Context as _, Error};
 
 use super::common::{Arch, Platform, Variant};
 use std::{
+    fmt,
     path::{Path, PathBuf},
     process::Command,
     string::String,
@@ -24,6 +25,22 @@ impl Identifier {
     }
 }
 
+/// Formats the identifier the same way xcframework slices are named, eg. `macos-arm64`
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let platform: &str = self.platform.into();
+        let arch: &str = self.arch.into();
+        write!(f, "{platform}-{arch}")?;
+
+        if self.variant != Variant::Default {
+            let variant: &str = self.variant.into();
+            write!(f, "-{variant}")?;
+        }
+
+        Ok(())
+    }
+}
+
 #[allow(non_snake_case)]
 #[derive(Debug, serde::Deserialize)]
 pub struct UniversalLibrary {
@@ -32,6 +49,7 @@ pub struct UniversalLibrary {
     SupportedPlatformVariant: Option<Variant>,
     SupportedPlatform: Platform,
     LibraryIdentifier: String,
+    HeadersPath: Option<String>,
 }
 
 #[allow(non_snake_case)]
@@ -42,6 +60,7 @@ pub struct NativeLibrary {
     SupportedPlatformVariant: Option<Variant>,
     SupportedPlatform: Platform,
     LibraryIdentifier: String,
+    HeadersPath: Option<String>,
 }
 
 impl UniversalLibrary {
@@ -53,6 +72,11 @@ impl UniversalLibrary {
         let platform = &self.SupportedPlatform;
         let variant = self.SupportedPlatformVariant.as_ref();
         let lib_path = &self.LibraryPath;
+        // Headers are never thinned, so they always stay in the universal library's directory
+        let headers_path = self
+            .HeadersPath
+            .as_ref()
+            .map(|headers| format!("{lib_id}/{headers}"));
 
         if self.SupportedArchitectures.len() == 1 {
             let arch = &self.SupportedArchitectures[0];
@@ -62,6 +86,7 @@ impl UniversalLibrary {
                 SupportedPlatformVariant: variant.cloned(),
                 SupportedPlatform: *platform,
                 LibraryIdentifier: lib_id.into(),
+                HeadersPath: headers_path,
             }])
         } else {
             let mut native_libs = Vec::new();
@@ -82,15 +107,19 @@ impl UniversalLibrary {
                 std::fs::create_dir_all(&out_path)?;
                 out_path.push(lib_path);
 
-                assert!(Command::new("lipo")
+                let lipo_status = Command::new("lipo")
                     .arg(&full_path)
                     .arg("-thin")
                     .arg(arch_str)
                     .arg("-output")
                     .arg(out_path)
                     .status()
-                    .expect("Failed to spawn lipo")
-                    .success());
+                    .context("Failed to spawn lipo")?;
+
+                ensure!(
+                    lipo_status.success(),
+                    "Failed to thin {full_path:?} to {arch_str}"
+                );
 
                 native_libs.push(NativeLibrary {
                     LibraryPath: lib_path.into(),
@@ -98,6 +127,7 @@ impl UniversalLibrary {
                     SupportedPlatformVariant: variant.copied(),
                     SupportedPlatform: *platform,
                     LibraryIdentifier: new_identifier,
+                    HeadersPath: headers_path.clone(),
                 });
             }
 
@@ -111,6 +141,11 @@ impl NativeLibrary {
         Path::new(&format!("{}/{}", self.LibraryIdentifier, self.LibraryPath)).to_path_buf()
     }
 
+    /// The headers directory relative to the xcframework, if the slice has one
+    pub fn headers_path(&self) -> Option<PathBuf> {
+        self.HeadersPath.as_ref().map(PathBuf::from)
+    }
+
     pub fn identifier(&self) -> Identifier {
         Identifier::new(
             self.SupportedArchitectures,
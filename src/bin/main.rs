@@ -1,3 +1,5 @@
+// some of the standard lints below have since been renamed or removed from clippy
+#![allow(renamed_and_removed_lints)]
 // BEGIN - Embark standard lints v0.4
 // do not change or add/remove here, but one can add exceptions after this section
 // for more info see: <https://github.com/EmbarkStudios/rust-ecosystem/issues/59>
@@ -70,22 +72,30 @@
 // crate-specific exceptions:
 #![allow(unsafe_code)]
 
-use ash::vk;
 fn main() {
     unsafe {
-        let entry = ash_molten::load();
+        let entry = ash_molten::MoltenEntry::load().expect("Unable to load Molten");
         let app_name = c"Hello Static Molten";
 
-        let appinfo = vk::ApplicationInfo::default()
+        let instance = ash_molten::InstanceBuilder::new()
             .application_name(app_name)
-            .application_version(0)
             .engine_name(app_name)
-            .engine_version(0)
-            .api_version(vk::make_api_version(0, 1, 0, 0));
-
-        let create_info = vk::InstanceCreateInfo::default().application_info(&appinfo);
-        let instance = entry.create_instance(&create_info, None).expect("Instance");
+            .create(&entry)
+            .expect("Instance");
+        println!("Enabled extensions: {:?}", instance.enabled_extensions());
         let devices = instance.enumerate_physical_devices();
         println!("Physical devices: {:?}", devices);
+
+        for physical_device in devices.unwrap_or_default() {
+            let device = ash_molten::DeviceBuilder::new(physical_device)
+                .instance_api_version(instance.api_version())
+                .queue(0, &[1.0])
+                .create(&entry, &instance)
+                .expect("Device");
+            if let Some(subset) = device.portability_subset() {
+                println!("Unsupported portability features: {:?}", subset.unsupported);
+            }
+            device.destroy_device(None);
+        }
     }
 }
@@ -0,0 +1,584 @@
+use ash::vk;
+use std::{
+    ffi::{CStr, CString},
+    fmt,
+    ops::Deref,
+};
+
+macro_rules! portability_features {
+    ($($(#[doc = $doc:literal])* $variant:ident => $field:ident,)*) => {
+        /// A feature `VK_KHR_portability_subset` allows an implementation to leave out
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum PortabilityFeature {
+            $($(#[doc = $doc])* $variant,)*
+        }
+
+        impl PortabilityFeature {
+            /// Every feature covered by [`vk::PhysicalDevicePortabilitySubsetFeaturesKHR`]
+            pub const ALL: &'static [Self] = &[$(Self::$variant,)*];
+
+            /// The name of the corresponding member of
+            /// [`vk::PhysicalDevicePortabilitySubsetFeaturesKHR`]
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$variant => stringify!($field),)*
+                }
+            }
+
+            fn is_enabled(self, features: &vk::PhysicalDevicePortabilitySubsetFeaturesKHR<'_>) -> bool {
+                match self {
+                    $(Self::$variant => features.$field == vk::TRUE,)*
+                }
+            }
+
+            fn enable(self, features: &mut vk::PhysicalDevicePortabilitySubsetFeaturesKHR<'_>) {
+                match self {
+                    $(Self::$variant => features.$field = vk::TRUE,)*
+                }
+            }
+        }
+    };
+}
+
+portability_features! {
+    /// Blend factors using a constant alpha, see `constantAlphaColorBlendFactors`
+    ConstantAlphaColorBlendFactors => constant_alpha_color_blend_factors,
+    /// `vkCmdSetEvent` and friends
+    Events => events,
+    /// Image views with a different format than their image
+    ImageViewFormatReinterpretation => image_view_format_reinterpretation,
+    /// Component swizzles in image views
+    ImageViewFormatSwizzle => image_view_format_swizzle,
+    /// 2D image views of 3D images
+    ImageView2DOn3DImage => image_view2_d_on3_d_image,
+    /// Multisampled array images
+    MultisampleArrayImage => multisample_array_image,
+    /// Changing the compare op of descriptor set samplers
+    MutableComparisonSamplers => mutable_comparison_samplers,
+    /// `VK_POLYGON_MODE_POINT`
+    PointPolygons => point_polygons,
+    /// `mipLodBias` in samplers
+    SamplerMipLodBias => sampler_mip_lod_bias,
+    /// Separate front and back stencil mask references
+    SeparateStencilMaskRef => separate_stencil_mask_ref,
+    /// `InterpolateAtCentroid`, `InterpolateAtOffset` and `InterpolateAtSample` in shaders
+    ShaderSampleRateInterpolationFunctions => shader_sample_rate_interpolation_functions,
+    /// Isolines in tessellation shaders
+    TessellationIsolines => tessellation_isolines,
+    /// Point mode in tessellation shaders
+    TessellationPointMode => tessellation_point_mode,
+    /// `VK_PRIMITIVE_TOPOLOGY_TRIANGLE_FAN`
+    TriangleFans => triangle_fans,
+    /// Vertex attributes reading past the stride of their binding
+    VertexAttributeAccessBeyondStride => vertex_attribute_access_beyond_stride,
+}
+
+impl fmt::Display for PortabilityFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What a physical device advertising `VK_KHR_portability_subset` supports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortabilitySubset {
+    /// The features the device supports
+    pub supported: Vec<PortabilityFeature>,
+    /// The features the device doesn't support
+    pub unsupported: Vec<PortabilityFeature>,
+    /// The alignment vertex input binding strides must have
+    pub min_vertex_input_binding_stride_alignment: u32,
+}
+
+impl PortabilitySubset {
+    /// Queries the portability subset of `physical_device`, returning `None` if it doesn't
+    /// advertise `VK_KHR_portability_subset` and can be treated as fully conformant.
+    ///
+    /// `instance_api_version` is the API version `instance` was created with, like
+    /// [`CreatedInstance::api_version`](crate::CreatedInstance::api_version). The features are
+    /// queried with Vulkan 1.1 when both the instance and the device support it, and with
+    /// `VK_KHR_get_physical_device_properties2` otherwise.
+    ///
+    /// Instances created for Vulkan 1.0, and instances whose devices report Vulkan 1.0, need
+    /// `VK_KHR_get_physical_device_properties2` enabled, which
+    /// [`InstanceBuilder`](crate::InstanceBuilder) does for the former. Fails with
+    /// [`DeviceError::MissingExtension`] if it isn't.
+    ///
+    /// # Safety
+    ///
+    /// `physical_device` must belong to `instance`, which must have been created from `entry`
+    /// with `instance_api_version`.
+    #[allow(unsafe_code)]
+    pub unsafe fn query(
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+        instance_api_version: u32,
+        physical_device: vk::PhysicalDevice,
+    ) -> Result<Option<Self>, DeviceError> {
+        let extensions = instance.enumerate_device_extension_properties(physical_device)?;
+        if !extensions
+            .iter()
+            .any(|ext| ext.extension_name_as_c_str() == Ok(ash::khr::portability_subset::NAME))
+        {
+            return Ok(None);
+        }
+
+        let mut features = vk::PhysicalDevicePortabilitySubsetFeaturesKHR::default();
+        let mut properties = vk::PhysicalDevicePortabilitySubsetPropertiesKHR::default();
+        let mut features2 = vk::PhysicalDeviceFeatures2::default().push_next(&mut features);
+        let mut properties2 = vk::PhysicalDeviceProperties2::default().push_next(&mut properties);
+
+        let device_api_version = instance
+            .get_physical_device_properties(physical_device)
+            .api_version;
+        let version = |v: u32| (vk::api_version_major(v), vk::api_version_minor(v));
+        // Core commands need both the instance and the device to support their version
+        if version(instance_api_version) >= (1, 1) && version(device_api_version) >= (1, 1) {
+            instance.get_physical_device_features2(physical_device, &mut features2);
+            instance.get_physical_device_properties2(physical_device, &mut properties2);
+        } else {
+            // ash's loader would panic on the first call if the extension isn't enabled
+            let resolves = |name: &CStr| {
+                entry
+                    .get_instance_proc_addr(instance.handle(), name.as_ptr())
+                    .is_some()
+            };
+            if !resolves(c"vkGetPhysicalDeviceFeatures2KHR")
+                || !resolves(c"vkGetPhysicalDeviceProperties2KHR")
+            {
+                return Err(DeviceError::MissingExtension(
+                    ash::khr::get_physical_device_properties2::NAME.to_owned(),
+                ));
+            }
+
+            let properties2_khr =
+                ash::khr::get_physical_device_properties2::Instance::new(entry, instance);
+            properties2_khr.get_physical_device_features2(physical_device, &mut features2);
+            properties2_khr.get_physical_device_properties2(physical_device, &mut properties2);
+        }
+
+        let (supported, unsupported) = PortabilityFeature::ALL
+            .iter()
+            .partition(|feature| feature.is_enabled(&features));
+
+        Ok(Some(Self {
+            supported,
+            unsupported,
+            min_vertex_input_binding_stride_alignment: properties
+                .min_vertex_input_binding_stride_alignment,
+        }))
+    }
+
+    /// Whether the device supports `feature`
+    pub fn is_supported(&self, feature: PortabilityFeature) -> bool {
+        self.supported.contains(&feature)
+    }
+
+    /// The supported features as the struct to chain into [`vk::DeviceCreateInfo`]
+    pub fn features(&self) -> vk::PhysicalDevicePortabilitySubsetFeaturesKHR<'static> {
+        let mut features = vk::PhysicalDevicePortabilitySubsetFeaturesKHR::default();
+        for feature in &self.supported {
+            feature.enable(&mut features);
+        }
+        features
+    }
+}
+
+/// Failed to create a device with [`DeviceBuilder`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DeviceError {
+    /// A Vulkan call failed
+    Vulkan(vk::Result),
+    /// A requested extension isn't available
+    MissingExtension(CString),
+}
+
+impl fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Vulkan(result) => write!(f, "failed to create device: {result}"),
+            Self::MissingExtension(name) => write!(f, "device extension {name:?} is missing"),
+        }
+    }
+}
+
+impl std::error::Error for DeviceError {}
+
+impl From<vk::Result> for DeviceError {
+    fn from(result: vk::Result) -> Self {
+        Self::Vulkan(result)
+    }
+}
+
+/// Creates an [`ash::Device`], enabling `VK_KHR_portability_subset` whenever the physical
+/// device advertises it, as the Vulkan specification requires.
+///
+/// The supported portability subset features are enabled as well, and the ones that aren't
+/// supported are reported through [`CreatedDevice::portability_subset`].
+///
+/// ```no_run
+/// # let entry = ash_molten::MoltenEntry::load().unwrap();
+/// # let instance = unsafe { ash_molten::InstanceBuilder::new().create(&entry) }.unwrap();
+/// let physical_device = unsafe { instance.enumerate_physical_devices() }.unwrap()[0];
+/// let device = unsafe {
+///     ash_molten::DeviceBuilder::new(physical_device)
+///         .instance_api_version(instance.api_version())
+///         .queue(0, &[1.0])
+///         .create(&entry, &instance)
+/// }
+/// .unwrap();
+/// if let Some(subset) = device.portability_subset() {
+///     println!("unsupported: {:?}", subset.unsupported);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct DeviceBuilder {
+    physical_device: vk::PhysicalDevice,
+    instance_api_version: u32,
+    queues: Vec<(u32, Vec<f32>)>,
+    extensions: Vec<CString>,
+    features: vk::PhysicalDeviceFeatures,
+    portability_features: bool,
+}
+
+impl DeviceBuilder {
+    /// Creates a builder for a device on `physical_device`
+    pub fn new(physical_device: vk::PhysicalDevice) -> Self {
+        Self {
+            physical_device,
+            instance_api_version: vk::API_VERSION_1_0,
+            queues: Vec::new(),
+            extensions: Vec::new(),
+            features: vk::PhysicalDeviceFeatures::default(),
+            portability_features: true,
+        }
+    }
+
+    /// The API version the instance was created with, see [`PortabilitySubset::query`].
+    /// Defaults to Vulkan 1.0, like [`InstanceBuilder`](crate::InstanceBuilder)
+    pub fn instance_api_version(mut self, version: u32) -> Self {
+        self.instance_api_version = version;
+        self
+    }
+
+    /// Creates a queue for each of `priorities` in the queue family `family_index`
+    pub fn queue(mut self, family_index: u32, priorities: &[f32]) -> Self {
+        self.queues.push((family_index, priorities.to_vec()));
+        self
+    }
+
+    /// Enables a device extension, failing with [`DeviceError::MissingExtension`] if it isn't
+    /// available
+    pub fn extension(mut self, name: &CStr) -> Self {
+        if !self.extensions.iter().any(|ext| ext.as_c_str() == name) {
+            self.extensions.push(name.to_owned());
+        }
+        self
+    }
+
+    /// Enables several device extensions, see [`DeviceBuilder::extension`]
+    pub fn extensions<'a>(self, names: impl IntoIterator<Item = &'a CStr>) -> Self {
+        names.into_iter().fold(self, Self::extension)
+    }
+
+    /// The core features to enable
+    pub fn features(mut self, features: vk::PhysicalDeviceFeatures) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Whether the supported portability subset features are enabled, defaults to `true`.
+    ///
+    /// `VK_KHR_portability_subset` itself is always enabled when advertised, as required.
+    pub fn portability_features(mut self, enable: bool) -> Self {
+        self.portability_features = enable;
+        self
+    }
+
+    /// Creates the device.
+    ///
+    /// # Safety
+    ///
+    /// The same requirements as [`ash::Instance::create_device`] and
+    /// [`PortabilitySubset::query`] apply.
+    #[allow(unsafe_code)]
+    pub unsafe fn create(
+        self,
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+    ) -> Result<CreatedDevice, DeviceError> {
+        let available = instance.enumerate_device_extension_properties(self.physical_device)?;
+        let is_available = |name: &CStr| {
+            available
+                .iter()
+                .any(|ext| ext.extension_name_as_c_str() == Ok(name))
+        };
+
+        if let Some(missing) = self.extensions.iter().find(|name| !is_available(name)) {
+            return Err(DeviceError::MissingExtension(missing.clone()));
+        }
+
+        let mut extensions = self.extensions;
+        let portability_subset = PortabilitySubset::query(
+            entry,
+            instance,
+            self.instance_api_version,
+            self.physical_device,
+        )?;
+        if portability_subset.is_some()
+            && !extensions
+                .iter()
+                .any(|ext| ext.as_c_str() == ash::khr::portability_subset::NAME)
+        {
+            extensions.push(ash::khr::portability_subset::NAME.to_owned());
+        }
+
+        let queue_create_infos = self
+            .queues
+            .iter()
+            .map(|(family_index, priorities)| {
+                vk::DeviceQueueCreateInfo::default()
+                    .queue_family_index(*family_index)
+                    .queue_priorities(priorities)
+            })
+            .collect::<Vec<_>>();
+        let extension_names = extensions
+            .iter()
+            .map(|ext| ext.as_ptr())
+            .collect::<Vec<_>>();
+
+        let mut create_info = vk::DeviceCreateInfo::default()
+            .queue_create_infos(&queue_create_infos)
+            .enabled_extension_names(&extension_names)
+            .enabled_features(&self.features);
+
+        let mut subset_features = portability_subset
+            .as_ref()
+            .filter(|_| self.portability_features)
+            .map(PortabilitySubset::features);
+        if let Some(subset_features) = &mut subset_features {
+            create_info = create_info.push_next(subset_features);
+        }
+
+        let device = instance.create_device(self.physical_device, &create_info, None)?;
+
+        Ok(CreatedDevice {
+            device,
+            physical_device: self.physical_device,
+            enabled_extensions: extensions,
+            portability_subset,
+        })
+    }
+}
+
+impl fmt::Debug for DeviceBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeviceBuilder")
+            .field("physical_device", &self.physical_device)
+            .field("queues", &self.queues)
+            .field("extensions", &self.extensions)
+            .field("portability_features", &self.portability_features)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A device created by [`DeviceBuilder`], along with what was enabled on it.
+///
+/// Derefs to [`ash::Device`]. The device isn't destroyed on drop.
+#[derive(Clone)]
+pub struct CreatedDevice {
+    device: ash::Device,
+    physical_device: vk::PhysicalDevice,
+    enabled_extensions: Vec<CString>,
+    portability_subset: Option<PortabilitySubset>,
+}
+
+impl CreatedDevice {
+    /// The underlying [`ash::Device`]
+    pub fn device(&self) -> &ash::Device {
+        &self.device
+    }
+
+    /// Unwraps the underlying [`ash::Device`]
+    pub fn into_device(self) -> ash::Device {
+        self.device
+    }
+
+    /// The physical device the device was created on
+    pub fn physical_device(&self) -> vk::PhysicalDevice {
+        self.physical_device
+    }
+
+    /// Every extension enabled on the device
+    pub fn enabled_extensions(&self) -> &[CString] {
+        &self.enabled_extensions
+    }
+
+    /// Whether `name` was enabled on the device
+    pub fn is_extension_enabled(&self, name: &CStr) -> bool {
+        self.enabled_extensions
+            .iter()
+            .any(|ext| ext.as_c_str() == name)
+    }
+
+    /// The portability subset of the physical device, `None` if it is fully conformant
+    pub fn portability_subset(&self) -> Option<&PortabilitySubset> {
+        self.portability_subset.as_ref()
+    }
+}
+
+impl Deref for CreatedDevice {
+    type Target = ash::Device;
+
+    fn deref(&self) -> &Self::Target {
+        &self.device
+    }
+}
+
+impl fmt::Debug for CreatedDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CreatedDevice")
+            .field("handle", &self.device.handle())
+            .field("physical_device", &self.physical_device)
+            .field("enabled_extensions", &self.enabled_extensions)
+            .field("portability_subset", &self.portability_subset)
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+#[allow(unsafe_code)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{self, MockConfig},
+        CreatedInstance, InstanceBuilder,
+    };
+
+    fn with_instance<R>(
+        config: MockConfig,
+        builder: InstanceBuilder,
+        f: impl FnOnce(&ash::Entry, &CreatedInstance, vk::PhysicalDevice) -> R,
+    ) -> R {
+        mock::with_config(config, || {
+            let entry = crate::MoltenEntry::load().unwrap();
+            let instance = unsafe { builder.create(&entry) }.unwrap();
+            let physical_device = unsafe { instance.enumerate_physical_devices() }.unwrap()[0];
+            let result = f(&entry, &instance, physical_device);
+            unsafe { instance.destroy_instance(None) };
+            result
+        })
+    }
+
+    fn create(
+        entry: &ash::Entry,
+        instance: &CreatedInstance,
+        builder: DeviceBuilder,
+    ) -> Result<CreatedDevice, DeviceError> {
+        let device = unsafe {
+            builder
+                .instance_api_version(instance.api_version())
+                .queue(0, &[1.0])
+                .create(entry, instance)
+        }?;
+        unsafe { device.destroy_device(None) };
+        Ok(device)
+    }
+
+    #[test]
+    fn enables_portability_subset_when_advertised() {
+        // Vulkan 1.0 goes through VK_KHR_get_physical_device_properties2, 1.1 through core
+        for api_version in [vk::API_VERSION_1_0, vk::API_VERSION_1_1] {
+            let builder = InstanceBuilder::new().api_version(api_version);
+            let device = with_instance(MockConfig::default(), builder, |entry, instance, pd| {
+                create(entry, instance, DeviceBuilder::new(pd)).unwrap()
+            });
+
+            assert!(device.is_extension_enabled(ash::khr::portability_subset::NAME));
+            let subset = device.portability_subset().unwrap();
+            assert_eq!(
+                subset.unsupported,
+                [
+                    PortabilityFeature::PointPolygons,
+                    PortabilityFeature::SamplerMipLodBias,
+                    PortabilityFeature::TessellationIsolines,
+                    PortabilityFeature::TriangleFans,
+                ]
+            );
+            assert_eq!(
+                subset.supported.len() + subset.unsupported.len(),
+                PortabilityFeature::ALL.len()
+            );
+            assert_eq!(subset.min_vertex_input_binding_stride_alignment, 4);
+        }
+    }
+
+    #[test]
+    fn enables_portability_subset_without_its_features() {
+        let device = with_instance(
+            MockConfig::default(),
+            InstanceBuilder::new(),
+            |entry, instance, pd| {
+                let builder = DeviceBuilder::new(pd)
+                    .extension(ash::khr::portability_subset::NAME)
+                    .portability_features(false);
+                create(entry, instance, builder).unwrap()
+            },
+        );
+        // Requested and added, but only enabled once
+        assert_eq!(
+            device.enabled_extensions(),
+            [ash::khr::portability_subset::NAME]
+        );
+        assert!(device.portability_subset().is_some());
+    }
+
+    #[test]
+    fn skips_portability_subset_when_not_advertised() {
+        let mut config = MockConfig::default();
+        for device in &mut config.physical_devices {
+            device.extensions.retain(|ext| {
+                ext.extension_name_as_c_str() != Ok(ash::khr::portability_subset::NAME)
+            });
+            device.portability_subset_features = None;
+            device.portability_subset_properties = None;
+        }
+
+        let device = with_instance(config, InstanceBuilder::new(), |entry, instance, pd| {
+            create(entry, instance, DeviceBuilder::new(pd)).unwrap()
+        });
+        assert!(device.enabled_extensions().is_empty());
+        assert_eq!(device.portability_subset(), None);
+    }
+
+    #[test]
+    fn rejects_missing_extensions() {
+        let builder = InstanceBuilder::new().portability(false);
+        let (device, query) =
+            with_instance(MockConfig::default(), builder, |entry, instance, pd| {
+                let device = create(
+                    entry,
+                    instance,
+                    DeviceBuilder::new(pd).extension(ash::khr::push_descriptor::NAME),
+                );
+                let query = unsafe {
+                    PortabilitySubset::query(entry, instance, instance.api_version(), pd)
+                };
+                (device.err(), query)
+            });
+        assert_eq!(
+            device,
+            Some(DeviceError::MissingExtension(
+                ash::khr::push_descriptor::NAME.to_owned()
+            ))
+        );
+        // A Vulkan 1.0 instance without VK_KHR_get_physical_device_properties2
+        assert_eq!(
+            query,
+            Err(DeviceError::MissingExtension(
+                ash::khr::get_physical_device_properties2::NAME.to_owned()
+            ))
+        );
+    }
+}
@@ -0,0 +1,382 @@
+use crate::version::{
+    Version, MOLTENVK_ARTIFACT_TAG, MOLTENVK_COMMIT, MOLTENVK_SOURCE, MOLTENVK_VERSION,
+};
+use ash::vk;
+use std::{ffi::CStr, fmt, ops::Deref, sync::OnceLock};
+
+/// The entry points that must resolve for an entry to be considered usable
+const REQUIRED_ENTRY_POINTS: &[&CStr] = &[c"vkCreateInstance", c"vkEnumerateInstanceVersion"];
+
+/// The environment variable that makes [`MoltenEntry::load`] try the system Vulkan loader first
+/// with the `loader-fallback` feature, when set to `1`, `true` or `loader`. The statically
+/// linked MoltenVK is loaded otherwise
+#[cfg(feature = "loader-fallback")]
+pub const LOADER_ENV_VAR: &str = "ASH_MOLTEN_LOADER";
+
+/// Failed to load the statically linked MoltenVK
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LoadError {
+    /// `vkGetInstanceProcAddr` didn't resolve a global entry point
+    MissingEntryPoint(&'static CStr),
+    /// MoltenVK isn't available on this platform, see [`is_supported`](crate::is_supported)
+    UnsupportedPlatform,
+    /// The system Vulkan loader couldn't be loaded
+    SystemLoader(String),
+    /// [`intercept`](crate::intercept) already wraps another `vkGetInstanceProcAddr`
+    #[cfg(feature = "intercept")]
+    AlreadyWrapped,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingEntryPoint(name) => {
+                write!(f, "MoltenVK doesn't provide the {name:?} entry point")
+            }
+            Self::UnsupportedPlatform => f.write_str("MoltenVK isn't supported on this platform"),
+            Self::SystemLoader(error) => write!(f, "failed to load the Vulkan loader: {error}"),
+            #[cfg(feature = "intercept")]
+            Self::AlreadyWrapped => {
+                f.write_str("another vkGetInstanceProcAddr is already intercepted")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Where the linked MoltenVK came from, see the `MOLTENVK_*` constants
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Provenance {
+    /// See [`MOLTENVK_VERSION`]
+    pub version: &'static str,
+    /// See [`MOLTENVK_ARTIFACT_TAG`]
+    pub artifact_tag: &'static str,
+    /// See [`MOLTENVK_SOURCE`]
+    pub source: &'static str,
+    /// See [`MOLTENVK_COMMIT`]
+    pub commit: Option<&'static str>,
+}
+
+impl Provenance {
+    /// The provenance of the MoltenVK linked into this binary
+    pub const LINKED: Self = Self {
+        version: MOLTENVK_VERSION,
+        artifact_tag: MOLTENVK_ARTIFACT_TAG,
+        source: MOLTENVK_SOURCE,
+        commit: MOLTENVK_COMMIT,
+    };
+
+    /// The parsed [`Provenance::version`]
+    pub fn parsed_version(&self) -> Option<Version> {
+        Version::parse(self.version)
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MoltenVK {} ({})", self.artifact_tag, self.source)?;
+        if let Some(commit) = self.commit {
+            write!(f, " {commit}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Where the entry points of a [`MoltenEntry`] are resolved from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntrySource {
+    /// The statically linked MoltenVK
+    Static,
+    /// The system Vulkan loader, which dispatches to the installed drivers and layers
+    SystemLoader,
+    /// The statically linked MoltenVK below the in-process layer host, see
+    /// [`LayerHost`](crate::layers::LayerHost)
+    #[cfg(feature = "layers")]
+    LayerHost,
+}
+
+/// An [`ash::Entry`] backed by the statically linked MoltenVK, or by the system Vulkan loader
+/// with the `loader-fallback` feature.
+///
+/// Derefs to [`ash::Entry`], so it can be used anywhere an entry is expected.
+#[derive(Clone)]
+pub struct MoltenEntry {
+    entry: ash::Entry,
+    get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+    provenance: Provenance,
+    source: EntrySource,
+}
+
+impl MoltenEntry {
+    /// Loads the statically linked MoltenVK, checking that its global entry points resolve.
+    ///
+    /// With the `loader-fallback` feature the system Vulkan loader is tried first when
+    /// `ASH_MOLTEN_LOADER` is set to `1`, `true` or `loader`, so that layers and capture tools
+    /// can be used during development. [`MoltenEntry::source`] tells which one was loaded.
+    pub fn load() -> Result<Self, LoadError> {
+        #[cfg(feature = "loader-fallback")]
+        {
+            let var = std::env::var(LOADER_ENV_VAR).ok();
+            Self::load_preferring(var.as_deref(), Self::load_system_loader)
+        }
+        #[cfg(not(feature = "loader-fallback"))]
+        Self::load_static()
+    }
+
+    /// Loads the entry `load_system_loader` returns if `var`, the value of [`LOADER_ENV_VAR`],
+    /// asks for it, and the statically linked MoltenVK otherwise or if that fails
+    #[cfg(feature = "loader-fallback")]
+    fn load_preferring(
+        var: Option<&str>,
+        load_system_loader: impl FnOnce() -> Result<Self, LoadError>,
+    ) -> Result<Self, LoadError> {
+        if loader_requested(var) {
+            if let Ok(entry) = load_system_loader() {
+                return Ok(entry);
+            }
+        }
+
+        Self::load_static()
+    }
+
+    /// Loads the statically linked MoltenVK, ignoring the system Vulkan loader
+    pub fn load_static() -> Result<Self, LoadError> {
+        if !crate::is_supported() {
+            return Err(LoadError::UnsupportedPlatform);
+        }
+
+        #[allow(unsafe_code)]
+        unsafe {
+            Self::from_get_instance_proc_addr(crate::static_get_instance_proc_addr())
+        }
+    }
+
+    /// Creates an entry from any `vkGetInstanceProcAddr`, checking that `vkCreateInstance` and
+    /// `vkEnumerateInstanceVersion` resolve through it.
+    ///
+    /// This is what [`MoltenEntry::load`] uses, and allows the loading logic to be exercised
+    /// with a stub implementation.
+    ///
+    /// # Safety
+    ///
+    /// `get_instance_proc_addr` must behave like `vkGetInstanceProcAddr`, as specified by the
+    /// Vulkan specification.
+    #[allow(unsafe_code)]
+    pub unsafe fn from_get_instance_proc_addr(
+        get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+    ) -> Result<Self, LoadError> {
+        check_entry_points(get_instance_proc_addr)?;
+
+        let entry = ash::Entry::from_static_fn(ash::StaticFn {
+            get_instance_proc_addr,
+        });
+
+        Ok(Self {
+            entry,
+            get_instance_proc_addr,
+            provenance: Provenance::LINKED,
+            source: EntrySource::Static,
+        })
+    }
+
+    /// Loads the system Vulkan loader with [`ash::Entry::load`], checking that its global entry
+    /// points resolve
+    #[cfg(feature = "loader-fallback")]
+    pub fn load_system_loader() -> Result<Self, LoadError> {
+        #[allow(unsafe_code)]
+        unsafe {
+            let entry =
+                ash::Entry::load().map_err(|error| LoadError::SystemLoader(error.to_string()))?;
+            let get_instance_proc_addr = entry.static_fn().get_instance_proc_addr;
+            check_entry_points(get_instance_proc_addr)?;
+
+            Ok(Self {
+                entry,
+                get_instance_proc_addr,
+                provenance: Provenance::LINKED,
+                source: EntrySource::SystemLoader,
+            })
+        }
+    }
+
+    /// The underlying [`ash::Entry`]
+    pub fn entry(&self) -> &ash::Entry {
+        &self.entry
+    }
+
+    /// Unwraps the underlying [`ash::Entry`]
+    pub fn into_entry(self) -> ash::Entry {
+        self.entry
+    }
+
+    /// The `vkGetInstanceProcAddr` this entry was loaded from
+    pub fn get_instance_proc_addr(&self) -> vk::PFN_vkGetInstanceProcAddr {
+        self.get_instance_proc_addr
+    }
+
+    /// Where the linked MoltenVK came from. With [`EntrySource::SystemLoader`] the loader may
+    /// dispatch to another driver instead
+    pub fn provenance(&self) -> &Provenance {
+        &self.provenance
+    }
+
+    /// Whether the entry points come from the linked MoltenVK or the system Vulkan loader
+    pub fn source(&self) -> EntrySource {
+        self.source
+    }
+
+    #[cfg(any(feature = "layers", feature = "intercept"))]
+    pub(crate) fn with_source(mut self, source: EntrySource) -> Self {
+        self.source = source;
+        self
+    }
+}
+
+#[allow(unsafe_code)]
+unsafe fn check_entry_points(
+    get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+) -> Result<(), LoadError> {
+    for name in REQUIRED_ENTRY_POINTS {
+        if get_instance_proc_addr(vk::Instance::null(), name.as_ptr()).is_none() {
+            return Err(LoadError::MissingEntryPoint(name));
+        }
+    }
+    Ok(())
+}
+
+/// Whether [`LOADER_ENV_VAR`] asks for the system Vulkan loader to be tried first
+#[cfg(feature = "loader-fallback")]
+pub(crate) fn loader_enabled() -> bool {
+    loader_requested(std::env::var(LOADER_ENV_VAR).ok().as_deref())
+}
+
+#[cfg(feature = "loader-fallback")]
+fn loader_requested(var: Option<&str>) -> bool {
+    matches!(var, Some("1" | "true" | "loader"))
+}
+
+impl Deref for MoltenEntry {
+    type Target = ash::Entry;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entry
+    }
+}
+
+impl fmt::Debug for MoltenEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MoltenEntry")
+            .field("provenance", &self.provenance)
+            .field("source", &self.source)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns the process-wide [`MoltenEntry`], loading it on first use.
+///
+/// All callers share the same function table. Use [`MoltenEntry::get_instance_proc_addr`] to
+/// hand the same entry point to other Vulkan bindings, while
+/// [`get_instance_proc_addr`](crate::get_instance_proc_addr) is always the statically linked
+/// one.
+pub fn entry() -> Result<&'static MoltenEntry, LoadError> {
+    static ENTRY: OnceLock<Result<MoltenEntry, LoadError>> = OnceLock::new();
+
+    ENTRY
+        .get_or_init(MoltenEntry::load)
+        .as_ref()
+        .map_err(Clone::clone)
+}
+
+#[cfg(test)]
+#[allow(unsafe_code)]
+mod tests {
+    use super::*;
+    use std::ffi::c_char;
+
+    unsafe extern "system" fn noop() {}
+
+    /// Resolves the global entry points named in `$names` to a function doing nothing
+    macro_rules! stub {
+        ($($name:literal),*) => {{
+            unsafe extern "system" fn get_instance_proc_addr(
+                _instance: vk::Instance,
+                p_name: *const c_char,
+            ) -> vk::PFN_vkVoidFunction {
+                let name = unsafe { CStr::from_ptr(p_name) };
+                [$($name),*].contains(&name).then_some(noop as unsafe extern "system" fn())
+            }
+            get_instance_proc_addr
+        }};
+    }
+
+    #[test]
+    fn missing_create_instance() {
+        let result = unsafe { MoltenEntry::from_get_instance_proc_addr(stub!()) };
+        assert_eq!(
+            result.unwrap_err(),
+            LoadError::MissingEntryPoint(c"vkCreateInstance")
+        );
+    }
+
+    #[test]
+    fn missing_enumerate_instance_version() {
+        let result =
+            unsafe { MoltenEntry::from_get_instance_proc_addr(stub!(c"vkCreateInstance")) };
+        assert_eq!(
+            result.unwrap_err(),
+            LoadError::MissingEntryPoint(c"vkEnumerateInstanceVersion")
+        );
+    }
+
+    #[test]
+    fn global_entry_points() {
+        let get_instance_proc_addr = stub!(c"vkCreateInstance", c"vkEnumerateInstanceVersion");
+        let entry = unsafe { MoltenEntry::from_get_instance_proc_addr(get_instance_proc_addr) };
+        let entry = entry.unwrap();
+        assert_eq!(entry.source(), EntrySource::Static);
+        assert_eq!(*entry.provenance(), Provenance::LINKED);
+    }
+
+    #[cfg(feature = "loader-fallback")]
+    #[test]
+    fn loader_is_opt_in() {
+        let system_loader = || {
+            let get_instance_proc_addr = stub!(c"vkCreateInstance", c"vkEnumerateInstanceVersion");
+            let entry = unsafe { MoltenEntry::from_get_instance_proc_addr(get_instance_proc_addr) };
+            Ok(MoltenEntry {
+                source: EntrySource::SystemLoader,
+                ..entry?
+            })
+        };
+        let source = |var| {
+            MoltenEntry::load_preferring(var, system_loader)
+                .ok()
+                .map(|entry| entry.source())
+        };
+
+        for var in ["1", "true", "loader"] {
+            assert_eq!(source(Some(var)), Some(EntrySource::SystemLoader), "{var}");
+        }
+        // The statically linked MoltenVK, which only loads where it is supported
+        let fallback = MoltenEntry::load_static().ok().map(|entry| entry.source());
+        for var in [
+            None,
+            Some(""),
+            Some("0"),
+            Some("false"),
+            Some("static"),
+            Some("yes"),
+        ] {
+            assert_eq!(source(var), fallback, "{var:?}");
+        }
+
+        // Falls back if the loader is requested but missing
+        let missing = || Err(LoadError::SystemLoader("missing".to_owned()));
+        let source = MoltenEntry::load_preferring(Some("1"), missing)
+            .ok()
+            .map(|entry| entry.source());
+        assert_eq!(source, fallback);
+    }
+}
@@ -0,0 +1,674 @@
+use crate::{mvk, MoltenVkSettings};
+use ash::vk;
+use std::{
+    ffi::{CStr, CString},
+    fmt,
+    ops::Deref,
+};
+
+/// Failed to create an instance with [`InstanceBuilder`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InstanceError {
+    /// A Vulkan call failed
+    Vulkan(vk::Result),
+    /// The requested API version is newer than the instance version MoltenVK supports
+    UnsupportedApiVersion { requested: u32, supported: u32 },
+    /// A requested extension isn't available
+    MissingExtension(CString),
+}
+
+impl fmt::Display for InstanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Vulkan(result) => write!(f, "failed to create instance: {result}"),
+            Self::UnsupportedApiVersion {
+                requested,
+                supported,
+            } => write!(
+                f,
+                "Vulkan {}.{} was requested, but only {}.{} is supported",
+                vk::api_version_major(*requested),
+                vk::api_version_minor(*requested),
+                vk::api_version_major(*supported),
+                vk::api_version_minor(*supported),
+            ),
+            Self::MissingExtension(name) => write!(f, "instance extension {name:?} is missing"),
+        }
+    }
+}
+
+impl std::error::Error for InstanceError {}
+
+impl From<vk::Result> for InstanceError {
+    fn from(result: vk::Result) -> Self {
+        Self::Vulkan(result)
+    }
+}
+
+/// Creates an [`ash::Instance`] that works with MoltenVK's portability requirements.
+///
+/// Since MoltenVK isn't a fully conformant implementation, instances have to opt in to
+/// enumerating it by enabling `VK_KHR_portability_enumeration` and setting
+/// [`vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR`]. Applications targeting Vulkan 1.0
+/// also need `VK_KHR_get_physical_device_properties2` to enable `VK_KHR_portability_subset` on
+/// the device. The builder adds these whenever they are available.
+///
+/// ```no_run
+/// let entry = ash_molten::MoltenEntry::load().unwrap();
+/// let instance = unsafe {
+///     ash_molten::InstanceBuilder::new()
+///         .application_name(c"My App")
+///         .api_version(ash::vk::API_VERSION_1_2)
+///         .create(&entry)
+/// }
+/// .unwrap();
+/// println!("enabled {:?}", instance.enabled_extensions());
+/// ```
+#[derive(Clone)]
+pub struct InstanceBuilder {
+    application_name: Option<CString>,
+    application_version: u32,
+    engine_name: Option<CString>,
+    engine_version: u32,
+    api_version: u32,
+    flags: vk::InstanceCreateFlags,
+    extensions: Vec<CString>,
+    layers: Vec<CString>,
+    portability: bool,
+    metal_exports: vk::ExportMetalObjectTypeFlagsEXT,
+    settings: Option<MoltenVkSettings>,
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    message_level: Option<crate::LogLevel>,
+}
+
+impl Default for InstanceBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InstanceBuilder {
+    /// Creates a builder for a Vulkan 1.0 instance with the portability extensions enabled
+    pub fn new() -> Self {
+        Self {
+            application_name: None,
+            application_version: 0,
+            engine_name: None,
+            engine_version: 0,
+            api_version: vk::API_VERSION_1_0,
+            flags: vk::InstanceCreateFlags::empty(),
+            extensions: Vec::new(),
+            layers: Vec::new(),
+            portability: true,
+            metal_exports: vk::ExportMetalObjectTypeFlagsEXT::empty(),
+            settings: None,
+            #[cfg(any(feature = "log", feature = "tracing"))]
+            message_level: None,
+        }
+    }
+
+    pub fn application_name(mut self, name: &CStr) -> Self {
+        self.application_name = Some(name.to_owned());
+        self
+    }
+
+    pub fn application_version(mut self, version: u32) -> Self {
+        self.application_version = version;
+        self
+    }
+
+    pub fn engine_name(mut self, name: &CStr) -> Self {
+        self.engine_name = Some(name.to_owned());
+        self
+    }
+
+    pub fn engine_version(mut self, version: u32) -> Self {
+        self.engine_version = version;
+        self
+    }
+
+    /// The Vulkan version the application targets, checked against the instance version
+    /// MoltenVK supports
+    pub fn api_version(mut self, version: u32) -> Self {
+        self.api_version = version;
+        self
+    }
+
+    /// Additional instance creation flags, [`vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR`]
+    /// is added automatically
+    pub fn flags(mut self, flags: vk::InstanceCreateFlags) -> Self {
+        self.flags |= flags;
+        self
+    }
+
+    /// Enables an instance extension, failing with [`InstanceError::MissingExtension`] if
+    /// neither the implementation nor an enabled layer provides it. Enabling an extension
+    /// twice has no effect
+    pub fn extension(mut self, name: &CStr) -> Self {
+        if !self.extensions.iter().any(|ext| ext.as_c_str() == name) {
+            self.extensions.push(name.to_owned());
+        }
+        self
+    }
+
+    /// Enables several instance extensions, see [`InstanceBuilder::extension`]
+    pub fn extensions<'a>(self, names: impl IntoIterator<Item = &'a CStr>) -> Self {
+        names.into_iter().fold(self, Self::extension)
+    }
+
+    /// Enables an instance layer
+    pub fn layer(mut self, name: &CStr) -> Self {
+        self.layers.push(name.to_owned());
+        self
+    }
+
+    /// Whether the portability extensions and flags are added when available, defaults to
+    /// `true`
+    pub fn portability(mut self, enable: bool) -> Self {
+        self.portability = enable;
+        self
+    }
+
+    /// Declares which Metal objects will be exported from the devices and queues of the
+    /// instance, see [`metal`](crate::metal).
+    ///
+    /// `VK_EXT_metal_objects` still needs to be enabled on the device.
+    pub fn metal_exports(mut self, types: vk::ExportMetalObjectTypeFlagsEXT) -> Self {
+        self.metal_exports |= types;
+        self
+    }
+
+    /// Configures MoltenVK through `VK_EXT_layer_settings`, which is enabled automatically.
+    ///
+    /// The settings override [`MoltenVkSettings::build_defaults`], which are always applied.
+    /// Fails with [`InstanceError::MissingExtension`] if the extension isn't available, as
+    /// with MoltenVK releases before 1.2.7.
+    pub fn settings(mut self, settings: MoltenVkSettings) -> Self {
+        self.settings = Some(settings);
+        self
+    }
+
+    /// Forwards MoltenVK's messages at or above `level` to `tracing` or `log`, through a
+    /// [`DebugMessenger`](crate::DebugMessenger) on the instance.
+    ///
+    /// `VK_EXT_debug_utils` is enabled automatically, and MoltenVK's log level is set to
+    /// `level` unless [`MoltenVkSettings::log_level`] is set explicitly. Fails with
+    /// [`InstanceError::MissingExtension`] if the extension isn't available.
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    pub fn forward_messages(mut self, level: crate::LogLevel) -> Self {
+        self.message_level = Some(level);
+        self
+    }
+
+    /// Creates the instance.
+    ///
+    /// # Safety
+    ///
+    /// The same requirements as [`ash::Entry::create_instance`] apply.
+    #[allow(unsafe_code)]
+    pub unsafe fn create(self, entry: &ash::Entry) -> Result<CreatedInstance, InstanceError> {
+        let supported = entry
+            .try_enumerate_instance_version()?
+            .unwrap_or(vk::API_VERSION_1_0);
+
+        let version = |v: u32| (vk::api_version_major(v), vk::api_version_minor(v));
+        if version(self.api_version) > version(supported) {
+            return Err(InstanceError::UnsupportedApiVersion {
+                requested: self.api_version,
+                supported,
+            });
+        }
+
+        // Layers may provide extensions the implementation doesn't. Missing layers are
+        // reported by `vkCreateInstance`
+        let mut available = entry.enumerate_instance_extension_properties(None)?;
+        for layer in &self.layers {
+            available.extend(
+                entry
+                    .enumerate_instance_extension_properties(Some(layer))
+                    .unwrap_or_default(),
+            );
+        }
+        let is_available = |name: &CStr| {
+            available
+                .iter()
+                .any(|ext| ext.extension_name_as_c_str() == Ok(name))
+        };
+
+        if let Some(missing) = self.extensions.iter().find(|name| !is_available(name)) {
+            return Err(InstanceError::MissingExtension(missing.clone()));
+        }
+
+        let mut extensions = self.extensions;
+        let mut added_extensions = Vec::new();
+        let mut flags = self.flags;
+
+        let defaults = MoltenVkSettings::build_defaults();
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        let defaults = match self.message_level {
+            Some(level) => defaults.log_level(level),
+            None => defaults,
+        };
+
+        let requested = self.settings.unwrap_or_default();
+        let settings = defaults.merge(requested.clone());
+
+        // Only the defaults can fall back to the MoltenVK configuration, explicitly requested
+        // settings need the extension
+        let mut fallback_settings = None;
+        let mut layer_settings = None;
+        if !settings.is_empty() {
+            let name = ash::ext::layer_settings::NAME;
+            if is_available(name) {
+                if !extensions.iter().any(|ext| ext.as_c_str() == name) {
+                    extensions.push(name.to_owned());
+                    added_extensions.push(name.to_owned());
+                }
+                layer_settings = Some(settings.layer_settings());
+            } else if requested.is_empty() {
+                fallback_settings = Some(settings);
+            } else {
+                return Err(InstanceError::MissingExtension(name.to_owned()));
+            }
+        }
+
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        let message_level = self
+            .message_level
+            .filter(|level| *level != crate::LogLevel::None);
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        if message_level.is_some() {
+            let name = ash::ext::debug_utils::NAME;
+            if !is_available(name) {
+                return Err(InstanceError::MissingExtension(name.to_owned()));
+            }
+            if !extensions.iter().any(|ext| ext.as_c_str() == name) {
+                extensions.push(name.to_owned());
+                added_extensions.push(name.to_owned());
+            }
+        }
+
+        if self.portability {
+            let mut wanted = vec![ash::khr::portability_enumeration::NAME];
+            // Core since Vulkan 1.1
+            if version(self.api_version) < (1, 1) {
+                wanted.push(ash::khr::get_physical_device_properties2::NAME);
+            }
+
+            for name in wanted {
+                if !is_available(name) {
+                    continue;
+                }
+
+                if !extensions.iter().any(|ext| ext.as_c_str() == name) {
+                    extensions.push(name.to_owned());
+                    added_extensions.push(name.to_owned());
+                }
+                if name == ash::khr::portability_enumeration::NAME {
+                    flags |= vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR;
+                }
+            }
+        }
+
+        let mut app_info = vk::ApplicationInfo::default()
+            .application_version(self.application_version)
+            .engine_version(self.engine_version)
+            .api_version(self.api_version);
+        if let Some(name) = &self.application_name {
+            app_info = app_info.application_name(name);
+        }
+        if let Some(name) = &self.engine_name {
+            app_info = app_info.engine_name(name);
+        }
+
+        let extension_names = extensions
+            .iter()
+            .map(|ext| ext.as_ptr())
+            .collect::<Vec<_>>();
+        let layer_names = self
+            .layers
+            .iter()
+            .map(|layer| layer.as_ptr())
+            .collect::<Vec<_>>();
+
+        let mut create_info = vk::InstanceCreateInfo::default()
+            .flags(flags)
+            .application_info(&app_info)
+            .enabled_extension_names(&extension_names)
+            .enabled_layer_names(&layer_names);
+
+        let mut settings_info = layer_settings
+            .as_ref()
+            .map(|settings| settings.create_info());
+        if let Some(settings_info) = &mut settings_info {
+            create_info = create_info.push_next(settings_info);
+        }
+
+        let mut metal_exports = crate::metal::export_info(self.metal_exports);
+        if !self.metal_exports.is_empty() {
+            create_info = create_info.push_next(&mut metal_exports);
+        }
+
+        // Also forwards the messages reported while creating the instance
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        let mut messenger_info = message_level.map(crate::messages::create_info);
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        if let Some(messenger_info) = &mut messenger_info {
+            create_info = create_info.push_next(messenger_info);
+        }
+
+        let instance = entry.create_instance(&create_info, None)?;
+
+        if let Some(settings) = fallback_settings {
+            let mvk = mvk::Instance::new(entry, &instance);
+            let result = mvk
+                .get_moltenvk_configuration()
+                .and_then(|mut configuration| {
+                    settings.apply_to(&mut configuration);
+                    mvk.set_moltenvk_configuration(&configuration)
+                });
+            if let Err(err) = result {
+                instance.destroy_instance(None);
+                return Err(err.into());
+            }
+        }
+
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        let debug_messenger = match message_level {
+            Some(level) => match crate::DebugMessenger::new(entry, &instance, level) {
+                Ok(messenger) => Some(messenger),
+                Err(err) => {
+                    instance.destroy_instance(None);
+                    return Err(err.into());
+                }
+            },
+            None => None,
+        };
+
+        Ok(CreatedInstance {
+            instance,
+            api_version: self.api_version,
+            flags,
+            enabled_extensions: extensions,
+            added_extensions,
+            #[cfg(any(feature = "log", feature = "tracing"))]
+            debug_messenger,
+        })
+    }
+}
+
+/// An instance created by [`InstanceBuilder`], along with what was enabled on it.
+///
+/// Derefs to [`ash::Instance`]. The instance isn't destroyed on drop.
+#[derive(Clone)]
+pub struct CreatedInstance {
+    instance: ash::Instance,
+    api_version: u32,
+    flags: vk::InstanceCreateFlags,
+    enabled_extensions: Vec<CString>,
+    added_extensions: Vec<CString>,
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    debug_messenger: Option<crate::DebugMessenger>,
+}
+
+impl CreatedInstance {
+    /// The underlying [`ash::Instance`]
+    pub fn instance(&self) -> &ash::Instance {
+        &self.instance
+    }
+
+    /// Unwraps the underlying [`ash::Instance`]
+    pub fn into_instance(self) -> ash::Instance {
+        self.instance
+    }
+
+    /// The API version the instance was created with
+    pub fn api_version(&self) -> u32 {
+        self.api_version
+    }
+
+    /// The flags the instance was created with
+    pub fn flags(&self) -> vk::InstanceCreateFlags {
+        self.flags
+    }
+
+    /// Every extension enabled on the instance
+    pub fn enabled_extensions(&self) -> &[CString] {
+        &self.enabled_extensions
+    }
+
+    /// The extensions the builder enabled on its own, for portability, settings and message
+    /// forwarding
+    pub fn added_extensions(&self) -> &[CString] {
+        &self.added_extensions
+    }
+
+    /// Whether `name` was enabled on the instance
+    pub fn is_extension_enabled(&self, name: &CStr) -> bool {
+        self.enabled_extensions
+            .iter()
+            .any(|ext| ext.as_c_str() == name)
+    }
+
+    /// The messenger created by [`InstanceBuilder::forward_messages`], which must be destroyed
+    /// before the instance
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    pub fn debug_messenger(&self) -> Option<&crate::DebugMessenger> {
+        self.debug_messenger.as_ref()
+    }
+}
+
+impl fmt::Debug for InstanceBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstanceBuilder")
+            .field("application_name", &self.application_name)
+            .field("api_version", &self.api_version)
+            .field("flags", &self.flags.as_raw())
+            .field("extensions", &self.extensions)
+            .field("layers", &self.layers)
+            .field("portability", &self.portability)
+            .field("metal_exports", &self.metal_exports.as_raw())
+            .field("settings", &self.settings)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Deref for CreatedInstance {
+    type Target = ash::Instance;
+
+    fn deref(&self) -> &Self::Target {
+        &self.instance
+    }
+}
+
+impl fmt::Debug for CreatedInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CreatedInstance")
+            .field("handle", &self.instance.handle())
+            .field("api_version", &self.api_version)
+            .field("enabled_extensions", &self.enabled_extensions)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+#[allow(unsafe_code)]
+mod tests {
+    use super::*;
+    use crate::mock::{self, MockConfig};
+
+    /// The mock configuration without `name` among its instance extensions
+    fn without_extension(name: &CStr) -> MockConfig {
+        let mut config = MockConfig::default();
+        config
+            .instance_extensions
+            .retain(|ext| ext.extension_name_as_c_str() != Ok(name));
+        config
+    }
+
+    fn create(builder: InstanceBuilder) -> Result<CreatedInstance, InstanceError> {
+        let entry = crate::MoltenEntry::load().unwrap();
+        unsafe { builder.create(&entry) }
+    }
+
+    #[test]
+    fn adds_portability_when_advertised() {
+        let instance = mock::with_config(MockConfig::default(), || {
+            create(InstanceBuilder::new()).unwrap()
+        });
+        assert_eq!(
+            instance.added_extensions(),
+            [
+                ash::khr::portability_enumeration::NAME,
+                ash::khr::get_physical_device_properties2::NAME,
+            ]
+        );
+        assert!(instance
+            .flags()
+            .contains(vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR));
+        unsafe { instance.destroy_instance(None) };
+
+        // Core since Vulkan 1.1
+        let instance = mock::with_config(MockConfig::default(), || {
+            create(InstanceBuilder::new().api_version(vk::API_VERSION_1_1)).unwrap()
+        });
+        assert_eq!(
+            instance.added_extensions(),
+            [ash::khr::portability_enumeration::NAME]
+        );
+        unsafe { instance.destroy_instance(None) };
+    }
+
+    #[test]
+    fn skips_portability_when_not_advertised() {
+        let config = without_extension(ash::khr::portability_enumeration::NAME);
+        let instance = mock::with_config(config, || create(InstanceBuilder::new()).unwrap());
+        assert!(!instance.is_extension_enabled(ash::khr::portability_enumeration::NAME));
+        assert!(!instance
+            .flags()
+            .contains(vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR));
+        unsafe { instance.destroy_instance(None) };
+
+        let instance = mock::with_config(MockConfig::default(), || {
+            create(InstanceBuilder::new().portability(false)).unwrap()
+        });
+        assert!(instance.added_extensions().is_empty());
+        assert!(instance.flags().is_empty());
+        unsafe { instance.destroy_instance(None) };
+    }
+
+    #[test]
+    fn rejects_newer_api_version() {
+        let config = MockConfig {
+            api_version: vk::API_VERSION_1_2,
+            ..MockConfig::default()
+        };
+        let result = mock::with_config(config, || {
+            create(InstanceBuilder::new().api_version(vk::API_VERSION_1_3))
+        });
+        assert_eq!(
+            result.err(),
+            Some(InstanceError::UnsupportedApiVersion {
+                requested: vk::API_VERSION_1_3,
+                supported: vk::API_VERSION_1_2,
+            })
+        );
+    }
+
+    #[test]
+    fn adds_layer_settings_when_requested() {
+        let settings = MoltenVkSettings::new().log_level(crate::LogLevel::Debug);
+        let instance = mock::with_config(MockConfig::default(), || {
+            create(InstanceBuilder::new().settings(settings.clone())).unwrap()
+        });
+        assert!(instance
+            .added_extensions()
+            .iter()
+            .any(|ext| ext.as_c_str() == ash::ext::layer_settings::NAME));
+        let entry = crate::MoltenEntry::load().unwrap();
+        let configuration = unsafe {
+            mvk::Instance::new(&entry, &instance)
+                .get_moltenvk_configuration()
+                .unwrap()
+        };
+        assert_eq!(configuration.log_level, mvk::ConfigLogLevel::DEBUG);
+        unsafe { instance.destroy_instance(None) };
+
+        let config = without_extension(ash::ext::layer_settings::NAME);
+        let result =
+            mock::with_config(config, || create(InstanceBuilder::new().settings(settings)));
+        assert_eq!(
+            result.err(),
+            Some(InstanceError::MissingExtension(
+                ash::ext::layer_settings::NAME.to_owned()
+            ))
+        );
+    }
+
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    #[test]
+    fn adds_debug_utils_when_forwarding() {
+        let instance = mock::with_config(MockConfig::default(), || {
+            create(InstanceBuilder::new().forward_messages(crate::LogLevel::Warning)).unwrap()
+        });
+        assert!(instance
+            .added_extensions()
+            .iter()
+            .any(|ext| ext.as_c_str() == ash::ext::debug_utils::NAME));
+        unsafe {
+            instance.debug_messenger().unwrap().destroy();
+            instance.destroy_instance(None);
+        }
+
+        let config = without_extension(ash::ext::debug_utils::NAME);
+        let result = mock::with_config(config, || {
+            create(InstanceBuilder::new().forward_messages(crate::LogLevel::Warning))
+        });
+        assert_eq!(
+            result.err(),
+            Some(InstanceError::MissingExtension(
+                ash::ext::debug_utils::NAME.to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn dedupes_extensions() {
+        let instance = mock::with_config(MockConfig::default(), || {
+            create(
+                InstanceBuilder::new()
+                    .extension(ash::khr::surface::NAME)
+                    .extensions([
+                        ash::khr::surface::NAME,
+                        ash::khr::portability_enumeration::NAME,
+                    ]),
+            )
+            .unwrap()
+        });
+        // Extensions the application enabled itself aren't added again
+        assert_eq!(
+            instance.enabled_extensions(),
+            [
+                ash::khr::surface::NAME,
+                ash::khr::portability_enumeration::NAME,
+                ash::khr::get_physical_device_properties2::NAME,
+            ]
+        );
+        assert_eq!(
+            instance.added_extensions(),
+            [ash::khr::get_physical_device_properties2::NAME]
+        );
+        unsafe { instance.destroy_instance(None) };
+
+        let result = mock::with_config(MockConfig::default(), || {
+            create(InstanceBuilder::new().extension(c"VK_EXT_missing"))
+        });
+        assert_eq!(
+            result.err(),
+            Some(InstanceError::MissingExtension(
+                c"VK_EXT_missing".to_owned()
+            ))
+        );
+    }
+}
@@ -0,0 +1,2144 @@
+//! Intercepting Vulkan calls in Rust, enabled with the `intercept` feature.
+//!
+//! With MoltenVK linked statically there is no loader to insert layers into, so this module
+//! wraps `vkGetInstanceProcAddr` and `vkGetDeviceProcAddr` instead. Instances and devices
+//! created through [`get_instance_proc_addr`], or an entry returned by [`wrap`], resolve the
+//! functions listed in [`Function`] to trampolines that run the registered [`Interceptor`]s
+//! around the wrapped implementation. Every other function resolves to the wrapped
+//! implementation directly.
+//!
+//! Interceptors see the arguments of a call as [`Value`]s and can replace the call with a
+//! result. [`InterceptorBuilder`] registers them for some functions, instances or devices:
+//!
+//! ```no_run
+//! use ash::vk;
+//! use ash_molten::intercept::{self, Call, Flow, Function, Interceptor, InterceptorBuilder};
+//! use std::sync::Arc;
+//!
+//! struct OutOfMemory;
+//!
+//! impl Interceptor for OutOfMemory {
+//!     fn before(&self, _call: &Call<'_>) -> Flow {
+//!         Flow::Return(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY)
+//!     }
+//! }
+//!
+//! let entry = intercept::wrap(&ash_molten::MoltenEntry::load().unwrap()).unwrap();
+//! InterceptorBuilder::new(Arc::new(OutOfMemory))
+//!     .function(Function::AllocateMemory)
+//!     .register();
+//! ```
+//!
+//! The wrapped `vkGetInstanceProcAddr` can also be handed to other bindings, like
+//! `ash::Entry::from_static_fn`, in which case it wraps the statically linked MoltenVK unless
+//! [`install`] chose another implementation.
+//!
+//! Every call looks up the dispatchable handle it is made through and the matching
+//! interceptors, which is meant for tests and debugging rather than release builds. Calls
+//! made through a handle that wasn't created through this module fail with
+//! `ERROR_INITIALIZATION_FAILED`, or abort the process if the function returns nothing.
+
+// crate-specific exceptions: this module implements Vulkan entry points called through raw
+// pointers
+#![allow(unsafe_code)]
+
+use crate::{LoadError, MoltenEntry};
+use ash::vk::{self, Handle as _};
+use std::{
+    collections::HashMap,
+    ffi::{c_char, c_void, CStr},
+    fmt,
+    mem::{size_of, transmute, MaybeUninit},
+    ptr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+};
+
+pub mod capture;
+pub mod dump;
+pub mod fault;
+pub mod lifetime;
+pub mod metrics;
+
+/// An argument of an intercepted call
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Value {
+    /// A handle and its object type
+    Handle(vk::ObjectType, u64),
+    /// A pointer to one or more handles, either passed to the function or written by it
+    Handles(vk::ObjectType, *const u64),
+    U32(u32),
+    I32(i32),
+    U64(u64),
+    F32(f32),
+    Usize(usize),
+    /// An enum or flags
+    Bits(Bits),
+    /// Any other pointer, like a pointer to a structure, an array or an output parameter
+    Ptr(*const c_void),
+}
+
+impl Value {
+    /// The handle, if this is a [`Value::Handle`]
+    pub fn handle(self) -> Option<u64> {
+        match self {
+            Self::Handle(_, handle) => Some(handle),
+            _ => None,
+        }
+    }
+
+    /// The handles behind a [`Value::Handles`], or `None` for other values and null pointers
+    ///
+    /// # Safety
+    ///
+    /// The pointer must point to `count` handles, so output parameters may only be read after
+    /// the call.
+    pub unsafe fn handles<'a>(self, count: usize) -> Option<&'a [u64]> {
+        match self {
+            Self::Handles(_, handles) if !handles.is_null() => {
+                Some(std::slice::from_raw_parts(handles, count))
+            }
+            _ => None,
+        }
+    }
+
+    /// The address of a pointer, which is `None` for values that aren't pointers
+    pub fn ptr(self) -> Option<*const c_void> {
+        match self {
+            Self::Handles(_, handles) => Some(handles.cast()),
+            Self::Ptr(ptr) => Some(ptr),
+            _ => None,
+        }
+    }
+}
+
+/// The raw value of an enum or flags, which formats with the names of its values
+#[derive(Clone, Copy)]
+pub struct Bits {
+    raw: u64,
+    fmt: fn(u64, &mut fmt::Formatter<'_>) -> fmt::Result,
+}
+
+impl Bits {
+    /// The raw value, like `vk::Format::as_raw`
+    pub fn raw(self) -> u64 {
+        self.raw
+    }
+}
+
+impl fmt::Debug for Bits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.fmt)(self.raw, f)
+    }
+}
+
+impl PartialEq for Bits {
+    fn eq(&self, other: &Self) -> bool {
+        self.raw == other.raw
+    }
+}
+
+/// A named argument of an intercepted call
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Arg {
+    /// The name of the parameter in `ash`, like `p_create_info`
+    pub name: &'static str,
+    pub value: Value,
+}
+
+/// An intercepted call
+pub struct Call<'a> {
+    pub function: Function,
+    /// The instance the call is made through, null for `vkCreateInstance`
+    pub instance: vk::Instance,
+    /// The device the call is made through, null for instance functions
+    pub device: vk::Device,
+    pub args: &'a [Arg],
+}
+
+impl Call<'_> {
+    /// The argument passed for the parameter `name`
+    pub fn arg(&self, name: &str) -> Option<Value> {
+        self.args
+            .iter()
+            .find(|arg| arg.name == name)
+            .map(|arg| arg.value)
+    }
+}
+
+impl fmt::Debug for Call<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Call")
+            .field("function", &self.function)
+            .field("instance", &self.instance.as_raw())
+            .field("device", &self.device.as_raw())
+            .field("args", &self.args)
+            .finish()
+    }
+}
+
+/// What happens to a call after [`Interceptor::before`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Forward the call
+    Continue,
+    /// Return the result without forwarding the call. Functions without a result just return
+    Return(vk::Result),
+}
+
+/// Runs code around intercepted calls
+///
+/// Interceptors run in the order they were registered, and [`Interceptor::after`] in the
+/// reverse order, like a chain of layers.
+pub trait Interceptor: Send + Sync {
+    /// Called before the call is forwarded. Returning [`Flow::Return`] skips the call, along
+    /// with the interceptors registered after this one
+    fn before(&self, call: &Call<'_>) -> Flow {
+        let _ = call;
+        Flow::Continue
+    }
+
+    /// Called after the call returned, with its result, or with the result returned by an
+    /// interceptor instead. `result` is `None` for functions without a result
+    fn after(&self, call: &Call<'_>, result: Option<vk::Result>) {
+        let _ = (call, result);
+    }
+}
+
+/// Identifies a registered interceptor, see [`unregister`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InterceptorId(u64);
+
+/// Registers an [`Interceptor`], for all intercepted calls unless restricted
+#[derive(Clone)]
+pub struct InterceptorBuilder {
+    interceptor: Arc<dyn Interceptor>,
+    functions: Vec<Function>,
+    instance: Option<vk::Instance>,
+    device: Option<vk::Device>,
+}
+
+impl InterceptorBuilder {
+    pub fn new(interceptor: Arc<dyn Interceptor>) -> Self {
+        Self {
+            interceptor,
+            functions: Vec::new(),
+            instance: None,
+            device: None,
+        }
+    }
+
+    /// Only intercepts calls to `function`, in addition to the other functions added
+    pub fn function(mut self, function: Function) -> Self {
+        self.functions.push(function);
+        self
+    }
+
+    /// Only intercepts calls to `functions`, see [`InterceptorBuilder::function`]
+    pub fn functions(self, functions: impl IntoIterator<Item = Function>) -> Self {
+        functions.into_iter().fold(self, Self::function)
+    }
+
+    /// Only intercepts calls through `instance` and its devices
+    pub fn instance(mut self, instance: vk::Instance) -> Self {
+        self.instance = Some(instance);
+        self
+    }
+
+    /// Only intercepts calls through `device`, its queues and command buffers
+    pub fn device(mut self, device: vk::Device) -> Self {
+        self.device = Some(device);
+        self
+    }
+
+    /// Registers the interceptor, which applies to calls starting after this returns
+    pub fn register(self) -> InterceptorId {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+        let id = InterceptorId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+        let mut registrations = REGISTRATIONS.lock().unwrap();
+        let mut updated = registrations.as_deref().unwrap_or_default().to_vec();
+        updated.push(Registration { id, builder: self });
+        *registrations = Some(updated.into());
+        id
+    }
+}
+
+impl fmt::Debug for InterceptorBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InterceptorBuilder")
+            .field("functions", &self.functions)
+            .field("instance", &self.instance.map(vk::Instance::as_raw))
+            .field("device", &self.device.map(vk::Device::as_raw))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Removes a registered interceptor, returning whether it was registered. Calls that already
+/// started may still run it
+pub fn unregister(id: InterceptorId) -> bool {
+    let mut registrations = REGISTRATIONS.lock().unwrap();
+    let Some(current) = registrations.as_deref() else {
+        return false;
+    };
+    let updated = current
+        .iter()
+        .filter(|registration| registration.id != id)
+        .cloned()
+        .collect::<Vec<_>>();
+    let removed = updated.len() != current.len();
+    *registrations = Some(updated.into());
+    removed
+}
+
+#[derive(Clone)]
+struct Registration {
+    id: InterceptorId,
+    builder: InterceptorBuilder,
+}
+
+impl Registration {
+    fn matches(&self, call: &Call<'_>) -> bool {
+        let builder = &self.builder;
+        (builder.functions.is_empty() || builder.functions.contains(&call.function))
+            && builder
+                .instance
+                .is_none_or(|instance| instance == call.instance)
+            && builder.device.is_none_or(|device| device == call.device)
+    }
+}
+
+/// Replaced as a whole on every change, so calls only hold the lock to clone the `Arc`
+static REGISTRATIONS: Mutex<Option<Arc<[Registration]>>> = Mutex::new(None);
+
+/// The `vkGetInstanceProcAddr` that is wrapped, the statically linked one if `None`
+static NEXT: RwLock<Option<vk::PFN_vkGetInstanceProcAddr>> = RwLock::new(None);
+
+/// Wraps `get_instance_proc_addr` instead of the statically linked MoltenVK, for instances
+/// created from now on.
+///
+/// Only one implementation can be wrapped, since the trampolines don't know which entry an
+/// instance is created through, so this fails with [`LoadError::AlreadyWrapped`] if another
+/// one was installed before. Installing the same one again does nothing.
+///
+/// # Safety
+///
+/// `get_instance_proc_addr` must behave like `vkGetInstanceProcAddr`, as specified by the
+/// Vulkan specification.
+pub unsafe fn install(
+    get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+) -> Result<(), LoadError> {
+    let mut next = NEXT.write().unwrap();
+    match *next {
+        Some(installed) if installed as usize != get_instance_proc_addr as usize => {
+            Err(LoadError::AlreadyWrapped)
+        }
+        _ => {
+            *next = Some(get_instance_proc_addr);
+            Ok(())
+        }
+    }
+}
+
+/// Returns an entry intercepting the calls made through `entry`, see [`install`]
+pub fn wrap(entry: &MoltenEntry) -> Result<MoltenEntry, LoadError> {
+    unsafe {
+        install(entry.get_instance_proc_addr())?;
+        MoltenEntry::from_get_instance_proc_addr(get_instance_proc_addr)
+    }
+    .map(|wrapped| wrapped.with_source(entry.source()))
+}
+
+fn next_get_instance_proc_addr() -> vk::PFN_vkGetInstanceProcAddr {
+    NEXT.read()
+        .unwrap()
+        .unwrap_or_else(crate::static_get_instance_proc_addr)
+}
+
+/// The functions of an instance or device, which its dispatchable handles map to
+struct Dispatch {
+    instance: vk::Instance,
+    device: vk::Device,
+    /// `vkGetInstanceProcAddr` for instances, `vkGetDeviceProcAddr` for devices
+    get_proc_addr: unsafe extern "system" fn(u64, *const c_char) -> vk::PFN_vkVoidFunction,
+    /// The wrapped functions, by [`Function`]
+    next: Box<[vk::PFN_vkVoidFunction]>,
+}
+
+impl Dispatch {
+    unsafe fn new(
+        instance: vk::Instance,
+        device: vk::Device,
+        get_proc_addr: vk::PFN_vkVoidFunction,
+    ) -> Option<Self> {
+        let get_proc_addr = transmute::<
+            unsafe extern "system" fn(),
+            unsafe extern "system" fn(u64, *const c_char) -> vk::PFN_vkVoidFunction,
+        >(get_proc_addr?);
+        let handle = if device == vk::Device::null() {
+            instance.as_raw()
+        } else {
+            device.as_raw()
+        };
+        let next = Function::ALL
+            .iter()
+            .map(|function| {
+                if function.is_device_level() == (device != vk::Device::null()) {
+                    get_proc_addr(handle, function.c_name().as_ptr())
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Some(Self {
+            instance,
+            device,
+            get_proc_addr,
+            next,
+        })
+    }
+}
+
+/// The dispatch of every instance, physical device, device, queue and command buffer created
+/// through the trampolines, by handle. Command buffers freed with their pool stay until their
+/// device is destroyed
+static DISPATCH: RwLock<Option<HashMap<u64, Arc<Dispatch>>>> = RwLock::new(None);
+
+fn dispatch(handle: u64) -> Option<Arc<Dispatch>> {
+    DISPATCH.read().unwrap().as_ref()?.get(&handle).cloned()
+}
+
+/// What a trampoline returns when called with a handle that wasn't created through this
+/// module, which has no dispatch table to forward to: `ERROR_INITIALIZATION_FAILED` if the
+/// function returns a result. Functions returning nothing can't report the error, so they
+/// panic, which aborts the process since the panic can't unwind out of an `extern "system"`
+/// function
+fn unknown_handle<R: Returned>(function: Function, handle: u64) -> R {
+    if function.returns_result() {
+        return R::from_result(vk::Result::ERROR_INITIALIZATION_FAILED);
+    }
+    panic!(
+        "{} called with handle {handle:#x}, which wasn't created through `ash_molten::intercept`",
+        function.name()
+    )
+}
+
+/// Whether the call is `vkDestroyInstance` or `vkDestroyDevice` with a null handle, which
+/// does nothing and has no dispatch table to forward to
+fn destroys_null(function: Function, handle: u64) -> bool {
+    handle == 0
+        && matches!(
+            function,
+            Function::DestroyInstance | Function::DestroyDevice
+        )
+}
+
+fn add_dispatch(handles: impl IntoIterator<Item = u64>, dispatch: &Arc<Dispatch>) {
+    let mut map = DISPATCH.write().unwrap();
+    let map = map.get_or_insert_with(HashMap::new);
+    for handle in handles {
+        map.insert(handle, dispatch.clone());
+    }
+}
+
+fn remove_dispatch(handles: impl IntoIterator<Item = u64>) {
+    if let Some(map) = DISPATCH.write().unwrap().as_mut() {
+        for handle in handles {
+            map.remove(&handle);
+        }
+    }
+}
+
+/// Removes `dispatch` along with all handles that map to it
+fn remove_all_dispatch(dispatch: &Arc<Dispatch>) {
+    if let Some(map) = DISPATCH.write().unwrap().as_mut() {
+        map.retain(|_, other| !Arc::ptr_eq(other, dispatch));
+    }
+}
+
+/// The `vkGetInstanceProcAddr` that intercepts calls, see the [module](self) documentation
+///
+/// # Safety
+///
+/// Must be called as specified for `vkGetInstanceProcAddr` by the Vulkan specification.
+pub unsafe extern "system" fn get_instance_proc_addr(
+    instance: vk::Instance,
+    p_name: *const c_char,
+) -> vk::PFN_vkVoidFunction {
+    let name = CStr::from_ptr(p_name);
+    match name.to_bytes() {
+        b"vkGetInstanceProcAddr" => {
+            return Some(transmute::<
+                vk::PFN_vkGetInstanceProcAddr,
+                unsafe extern "system" fn(),
+            >(get_instance_proc_addr))
+        }
+        b"vkGetDeviceProcAddr" if instance != vk::Instance::null() => {
+            return Some(transmute::<
+                vk::PFN_vkGetDeviceProcAddr,
+                unsafe extern "system" fn(),
+            >(get_device_proc_addr))
+        }
+        _ => {}
+    }
+
+    let next = match dispatch(instance.as_raw()) {
+        Some(dispatch) => (dispatch.get_proc_addr)(instance.as_raw(), p_name),
+        None => next_get_instance_proc_addr()(instance, p_name),
+    };
+    next?;
+
+    let function = name.to_str().ok().and_then(Function::from_name);
+    match function {
+        Some(function) if instance != vk::Instance::null() => Some(function.trampoline()),
+        Some(Function::CreateInstance) => Some(function?.trampoline()),
+        _ => next,
+    }
+}
+
+/// The `vkGetDeviceProcAddr` handed out by [`get_instance_proc_addr`]
+unsafe extern "system" fn get_device_proc_addr(
+    device: vk::Device,
+    p_name: *const c_char,
+) -> vk::PFN_vkVoidFunction {
+    let name = CStr::from_ptr(p_name);
+    if name == c"vkGetDeviceProcAddr" {
+        return Some(transmute::<
+            vk::PFN_vkGetDeviceProcAddr,
+            unsafe extern "system" fn(),
+        >(get_device_proc_addr));
+    }
+
+    // Unknown devices resolve nothing, like invalid handles usually do
+    let next = (dispatch(device.as_raw())?.get_proc_addr)(device.as_raw(), p_name);
+    next?;
+
+    match name.to_str().ok().and_then(Function::from_name) {
+        Some(function) if function.is_device_level() => Some(function.trampoline()),
+        _ => next,
+    }
+}
+
+/// Runs the matching interceptors around `next`, then keeps track of the dispatchable handles
+/// the call created or destroyed
+fn intercept<R: Returned>(
+    call: &Call<'_>,
+    dispatch: Option<&Arc<Dispatch>>,
+    next: impl FnOnce() -> R,
+) -> R {
+    let registrations = REGISTRATIONS.lock().unwrap().clone().unwrap_or_default();
+    let interceptors = registrations
+        .iter()
+        .filter(|registration| registration.matches(call))
+        .map(|registration| &registration.builder.interceptor);
+
+    let mut ran = Vec::new();
+    let mut returned = None;
+    for interceptor in interceptors {
+        ran.push(interceptor);
+        if let Flow::Return(result) = interceptor.before(call) {
+            returned = Some(R::from_result(result));
+            break;
+        }
+    }
+
+    let forwarded = returned.is_none();
+    let returned = returned.unwrap_or_else(next);
+    for interceptor in ran.into_iter().rev() {
+        interceptor.after(call, returned.result());
+    }
+
+    if forwarded && returned.result().is_none_or(|result| result.as_raw() >= 0) {
+        unsafe { track(call, dispatch) };
+    }
+    returned
+}
+
+/// The return types of intercepted functions
+trait Returned: Copy {
+    fn from_result(result: vk::Result) -> Self;
+    fn result(self) -> Option<vk::Result>;
+}
+
+impl Returned for () {
+    fn from_result(_result: vk::Result) -> Self {}
+
+    fn result(self) -> Option<vk::Result> {
+        None
+    }
+}
+
+impl Returned for vk::Result {
+    fn from_result(result: vk::Result) -> Self {
+        result
+    }
+
+    fn result(self) -> Option<vk::Result> {
+        Some(self)
+    }
+}
+
+/// Maps the dispatchable handles created by a successful call to their instance or device,
+/// and forgets destroyed ones
+unsafe fn track(call: &Call<'_>, dispatch: Option<&Arc<Dispatch>>) {
+    let arg = |index: usize| call.args[index].value;
+    let handle = |value: Value| value.handles(1).map_or(0, |handles| handles[0]);
+    let count = |value: Value| match value.ptr() {
+        Some(count) if !count.is_null() => *count.cast::<u32>() as usize,
+        _ => 0,
+    };
+
+    match call.function {
+        Function::CreateInstance => {
+            let instance = vk::Instance::from_raw(handle(arg(2)));
+            let get_instance_proc_addr = next_get_instance_proc_addr();
+            let get_proc_addr = transmute::<
+                vk::PFN_vkGetInstanceProcAddr,
+                unsafe extern "system" fn(),
+            >(get_instance_proc_addr);
+            if let Some(created) = Dispatch::new(instance, vk::Device::null(), Some(get_proc_addr))
+            {
+                add_dispatch([instance.as_raw()], &Arc::new(created));
+            }
+        }
+        Function::DestroyInstance | Function::DestroyDevice => {
+            if let Some(dispatch) = dispatch {
+                remove_all_dispatch(dispatch);
+            }
+        }
+        Function::EnumeratePhysicalDevices => {
+            if let Some(physical_devices) = arg(2).handles(count(arg(1))) {
+                add_dispatch(physical_devices.iter().copied(), dispatch.unwrap());
+            }
+        }
+        Function::EnumeratePhysicalDeviceGroups => {
+            if let Some(groups) = arg(2).ptr().filter(|groups| !groups.is_null()) {
+                let groups = std::slice::from_raw_parts(
+                    groups.cast::<vk::PhysicalDeviceGroupProperties<'_>>(),
+                    count(arg(1)),
+                );
+                for group in groups {
+                    add_dispatch(
+                        group
+                            .physical_devices_as_slice()
+                            .iter()
+                            .map(|physical_device| physical_device.as_raw()),
+                        dispatch.unwrap(),
+                    );
+                }
+            }
+        }
+        Function::CreateDevice => {
+            let instance = dispatch.unwrap();
+            let device = vk::Device::from_raw(handle(arg(3)));
+            let get_device_proc_addr = (instance.get_proc_addr)(
+                instance.instance.as_raw(),
+                c"vkGetDeviceProcAddr".as_ptr(),
+            );
+            if let Some(created) = Dispatch::new(instance.instance, device, get_device_proc_addr) {
+                add_dispatch([device.as_raw()], &Arc::new(created));
+            }
+        }
+        Function::GetDeviceQueue => add_dispatch([handle(arg(3))], dispatch.unwrap()),
+        Function::GetDeviceQueue2 => add_dispatch([handle(arg(2))], dispatch.unwrap()),
+        Function::AllocateCommandBuffers => {
+            let allocate_info = &*arg(1)
+                .ptr()
+                .unwrap()
+                .cast::<vk::CommandBufferAllocateInfo<'_>>();
+            if let Some(command_buffers) =
+                arg(2).handles(allocate_info.command_buffer_count as usize)
+            {
+                add_dispatch(command_buffers.iter().copied(), dispatch.unwrap());
+            }
+        }
+        Function::FreeCommandBuffers => {
+            if let (Value::U32(count), Some(command_buffers)) = (arg(2), arg(3).ptr()) {
+                if let Some(command_buffers) =
+                    Value::Handles(vk::ObjectType::COMMAND_BUFFER, command_buffers.cast())
+                        .handles(count as usize)
+                {
+                    remove_dispatch(command_buffers.iter().copied());
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Converts arguments to [`Value`]s through autoref-based specialization: the conversion
+/// whose `self` type needs the most references is preferred, so handles aren't treated as
+/// raw bits and pointers to handles aren't treated as other pointers
+struct Param<T>(T);
+
+trait HandleParam {
+    fn value(&self) -> Value;
+}
+
+impl<H: vk::Handle + Copy> HandleParam for &&&Param<H> {
+    fn value(&self) -> Value {
+        Value::Handle(H::TYPE, self.0.as_raw())
+    }
+}
+
+trait HandlesOrScalarParam {
+    fn value(&self) -> Value;
+}
+
+impl<H: vk::Handle> HandlesOrScalarParam for &&Param<*const H> {
+    fn value(&self) -> Value {
+        Value::Handles(H::TYPE, self.0.cast())
+    }
+}
+
+impl<H: vk::Handle> HandlesOrScalarParam for &&Param<*mut H> {
+    fn value(&self) -> Value {
+        Value::Handles(H::TYPE, self.0.cast_const().cast())
+    }
+}
+
+macro_rules! scalar_params {
+    ($($ty:ty => $variant:ident,)*) => {
+        $(
+            impl HandlesOrScalarParam for &&Param<$ty> {
+                fn value(&self) -> Value {
+                    Value::$variant(self.0)
+                }
+            }
+        )*
+    };
+}
+
+scalar_params! {
+    u32 => U32,
+    i32 => I32,
+    u64 => U64,
+    f32 => F32,
+    usize => Usize,
+}
+
+trait PtrParam {
+    fn value(&self) -> Value;
+}
+
+impl<T> PtrParam for &Param<*const T> {
+    fn value(&self) -> Value {
+        Value::Ptr(self.0.cast())
+    }
+}
+
+impl<T> PtrParam for &Param<*mut T> {
+    fn value(&self) -> Value {
+        Value::Ptr(self.0.cast_const().cast())
+    }
+}
+
+trait BitsParam {
+    fn value(&self) -> Value;
+}
+
+impl<T: Copy + fmt::Debug> BitsParam for Param<T> {
+    fn value(&self) -> Value {
+        let mut raw = 0_u64;
+        unsafe {
+            ptr::copy_nonoverlapping(
+                ptr::from_ref(&self.0).cast::<u8>(),
+                ptr::from_mut(&mut raw).cast::<u8>(),
+                size_of::<T>().min(size_of::<u64>()),
+            );
+        }
+        Value::Bits(Bits {
+            raw,
+            fmt: fmt_bits::<T>,
+        })
+    }
+}
+
+/// Formats the raw value of a `T` copied by [`BitsParam`]. Enums and flags are integers, so
+/// any bit pattern is a valid value
+fn fmt_bits<T: Copy + fmt::Debug>(raw: u64, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut value = MaybeUninit::<T>::zeroed();
+    unsafe {
+        ptr::copy_nonoverlapping(
+            ptr::from_ref(&raw).cast::<u8>(),
+            value.as_mut_ptr().cast::<u8>(),
+            size_of::<T>().min(size_of::<u64>()),
+        );
+        value.assume_init().fmt(f)
+    }
+}
+
+macro_rules! param {
+    ($value:expr) => {
+        (&&&&Param($value)).value()
+    };
+}
+
+macro_rules! functions {
+    (@returns_result) => { false };
+    (@returns_result $ret:ty) => { true };
+    (
+        instance {
+            $(
+                $i_variant:ident => fn $i_name:ident(
+                    $i_first:ident: $i_first_ty:ty $(, $i_param:ident: $i_ty:ty)* $(,)?
+                ) $(-> $i_ret:ty)?;
+            )*
+        }
+        device {
+            $(
+                $d_variant:ident => fn $d_name:ident(
+                    $d_first:ident: $d_first_ty:ty $(, $d_param:ident: $d_ty:ty)* $(,)?
+                ) $(-> $d_ret:ty)?;
+            )*
+        }
+    ) => {
+        /// The functions that can be intercepted, named after the Vulkan function without
+        /// the `vk` prefix
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        #[non_exhaustive]
+        pub enum Function {
+            CreateInstance,
+            $($i_variant,)*
+            $($d_variant,)*
+        }
+
+        impl Function {
+            /// Every function that can be intercepted
+            pub const ALL: &'static [Self] = &[
+                Self::CreateInstance,
+                $(Self::$i_variant,)*
+                $(Self::$d_variant,)*
+            ];
+
+            /// The name of the Vulkan function, like `vkCreateBuffer`
+            pub fn name(self) -> &'static str {
+                self.c_name().to_str().unwrap()
+            }
+
+            /// The name of the Vulkan function as a C string
+            pub fn c_name(self) -> &'static CStr {
+                let name: &'static str = match self {
+                    Self::CreateInstance => "vkCreateInstance\0",
+                    $(Self::$i_variant => concat!(stringify!($i_name), "\0"),)*
+                    $(Self::$d_variant => concat!(stringify!($d_name), "\0"),)*
+                };
+                CStr::from_bytes_with_nul(name.as_bytes()).unwrap()
+            }
+
+            /// The function named `name`, if it can be intercepted
+            pub fn from_name(name: &str) -> Option<Self> {
+                Self::ALL.iter().copied().find(|function| function.name() == name)
+            }
+
+            /// Whether the function is called through a device, queue or command buffer
+            pub fn is_device_level(self) -> bool {
+                matches!(self, $(Self::$d_variant)|*)
+            }
+
+            /// Whether the function returns a `VkResult`, rather than nothing
+            pub fn returns_result(self) -> bool {
+                match self {
+                    Self::CreateInstance => true,
+                    $(Self::$i_variant => functions!(@returns_result $($i_ret)?),)*
+                    $(Self::$d_variant => functions!(@returns_result $($d_ret)?),)*
+                }
+            }
+
+            fn trampoline(self) -> unsafe extern "system" fn() {
+                unsafe {
+                    match self {
+                        Self::CreateInstance => transmute::<
+                            vk::PFN_vkCreateInstance,
+                            unsafe extern "system" fn(),
+                        >(create_instance),
+                        $(
+                            Self::$i_variant => transmute::<
+                                unsafe extern "system" fn($i_first_ty $(, $i_ty)*) $(-> $i_ret)?,
+                                unsafe extern "system" fn(),
+                            >(trampolines::$i_name),
+                        )*
+                        $(
+                            Self::$d_variant => transmute::<
+                                unsafe extern "system" fn($d_first_ty $(, $d_ty)*) $(-> $d_ret)?,
+                                unsafe extern "system" fn(),
+                            >(trampolines::$d_name),
+                        )*
+                    }
+                }
+            }
+        }
+
+        #[allow(non_snake_case)]
+        mod trampolines {
+            use super::*;
+
+            $(
+                pub(super) unsafe extern "system" fn $i_name(
+                    $i_first: $i_first_ty $(, $i_param: $i_ty)*
+                ) $(-> $i_ret)? {
+                    if destroys_null(Function::$i_variant, $i_first.as_raw()) {
+                        return Returned::from_result(vk::Result::SUCCESS);
+                    }
+                    let Some(dispatch) = dispatch($i_first.as_raw()) else {
+                        return unknown_handle(Function::$i_variant, $i_first.as_raw());
+                    };
+                    let call = Call {
+                        function: Function::$i_variant,
+                        instance: dispatch.instance,
+                        device: dispatch.device,
+                        args: &[
+                            Arg { name: stringify!($i_first), value: param!($i_first) },
+                            $(Arg { name: stringify!($i_param), value: param!($i_param) },)*
+                        ],
+                    };
+                    intercept(&call, Some(&dispatch), || {
+                        let next = transmute::<
+                            unsafe extern "system" fn(),
+                            unsafe extern "system" fn($i_first_ty $(, $i_ty)*) $(-> $i_ret)?,
+                        >(dispatch.next[Function::$i_variant as usize].unwrap());
+                        next($i_first $(, $i_param)*)
+                    })
+                }
+            )*
+
+            $(
+                pub(super) unsafe extern "system" fn $d_name(
+                    $d_first: $d_first_ty $(, $d_param: $d_ty)*
+                ) $(-> $d_ret)? {
+                    if destroys_null(Function::$d_variant, $d_first.as_raw()) {
+                        return Returned::from_result(vk::Result::SUCCESS);
+                    }
+                    let Some(dispatch) = dispatch($d_first.as_raw()) else {
+                        return unknown_handle(Function::$d_variant, $d_first.as_raw());
+                    };
+                    let call = Call {
+                        function: Function::$d_variant,
+                        instance: dispatch.instance,
+                        device: dispatch.device,
+                        args: &[
+                            Arg { name: stringify!($d_first), value: param!($d_first) },
+                            $(Arg { name: stringify!($d_param), value: param!($d_param) },)*
+                        ],
+                    };
+                    intercept(&call, Some(&dispatch), || {
+                        let next = transmute::<
+                            unsafe extern "system" fn(),
+                            unsafe extern "system" fn($d_first_ty $(, $d_ty)*) $(-> $d_ret)?,
+                        >(dispatch.next[Function::$d_variant as usize].unwrap());
+                        next($d_first $(, $d_param)*)
+                    })
+                }
+            )*
+        }
+    };
+}
+
+unsafe extern "system" fn create_instance(
+    p_create_info: *const vk::InstanceCreateInfo<'_>,
+    p_allocator: *const vk::AllocationCallbacks<'_>,
+    p_instance: *mut vk::Instance,
+) -> vk::Result {
+    let call = Call {
+        function: Function::CreateInstance,
+        instance: vk::Instance::null(),
+        device: vk::Device::null(),
+        args: &[
+            Arg {
+                name: "p_create_info",
+                value: param!(p_create_info),
+            },
+            Arg {
+                name: "p_allocator",
+                value: param!(p_allocator),
+            },
+            Arg {
+                name: "p_instance",
+                value: param!(p_instance),
+            },
+        ],
+    };
+    intercept(&call, None, || {
+        let Some(next) =
+            next_get_instance_proc_addr()(vk::Instance::null(), c"vkCreateInstance".as_ptr())
+        else {
+            return vk::Result::ERROR_INITIALIZATION_FAILED;
+        };
+        let next = transmute::<unsafe extern "system" fn(), vk::PFN_vkCreateInstance>(next);
+        next(p_create_info, p_allocator, p_instance)
+    })
+}
+
+functions! {
+    instance {
+        DestroyInstance => fn vkDestroyInstance(
+            instance: vk::Instance,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        EnumeratePhysicalDevices => fn vkEnumeratePhysicalDevices(
+            instance: vk::Instance,
+            p_physical_device_count: *mut u32,
+            p_physical_devices: *mut vk::PhysicalDevice,
+        ) -> vk::Result;
+        GetPhysicalDeviceFeatures => fn vkGetPhysicalDeviceFeatures(
+            physical_device: vk::PhysicalDevice,
+            p_features: *mut vk::PhysicalDeviceFeatures,
+        );
+        GetPhysicalDeviceFormatProperties => fn vkGetPhysicalDeviceFormatProperties(
+            physical_device: vk::PhysicalDevice,
+            format: vk::Format,
+            p_format_properties: *mut vk::FormatProperties,
+        );
+        GetPhysicalDeviceImageFormatProperties => fn vkGetPhysicalDeviceImageFormatProperties(
+            physical_device: vk::PhysicalDevice,
+            format: vk::Format,
+            ty: vk::ImageType,
+            tiling: vk::ImageTiling,
+            usage: vk::ImageUsageFlags,
+            flags: vk::ImageCreateFlags,
+            p_image_format_properties: *mut vk::ImageFormatProperties,
+        ) -> vk::Result;
+        GetPhysicalDeviceProperties => fn vkGetPhysicalDeviceProperties(
+            physical_device: vk::PhysicalDevice,
+            p_properties: *mut vk::PhysicalDeviceProperties,
+        );
+        GetPhysicalDeviceQueueFamilyProperties => fn vkGetPhysicalDeviceQueueFamilyProperties(
+            physical_device: vk::PhysicalDevice,
+            p_queue_family_property_count: *mut u32,
+            p_queue_family_properties: *mut vk::QueueFamilyProperties,
+        );
+        GetPhysicalDeviceMemoryProperties => fn vkGetPhysicalDeviceMemoryProperties(
+            physical_device: vk::PhysicalDevice,
+            p_memory_properties: *mut vk::PhysicalDeviceMemoryProperties,
+        );
+        CreateDevice => fn vkCreateDevice(
+            physical_device: vk::PhysicalDevice,
+            p_create_info: *const vk::DeviceCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_device: *mut vk::Device,
+        ) -> vk::Result;
+        EnumerateDeviceExtensionProperties => fn vkEnumerateDeviceExtensionProperties(
+            physical_device: vk::PhysicalDevice,
+            p_layer_name: *const c_char,
+            p_property_count: *mut u32,
+            p_properties: *mut vk::ExtensionProperties,
+        ) -> vk::Result;
+        EnumerateDeviceLayerProperties => fn vkEnumerateDeviceLayerProperties(
+            physical_device: vk::PhysicalDevice,
+            p_property_count: *mut u32,
+            p_properties: *mut vk::LayerProperties,
+        ) -> vk::Result;
+        EnumeratePhysicalDeviceGroups => fn vkEnumeratePhysicalDeviceGroups(
+            instance: vk::Instance,
+            p_physical_device_group_count: *mut u32,
+            p_physical_device_group_properties: *mut vk::PhysicalDeviceGroupProperties<'_>,
+        ) -> vk::Result;
+        GetPhysicalDeviceFeatures2 => fn vkGetPhysicalDeviceFeatures2(
+            physical_device: vk::PhysicalDevice,
+            p_features: *mut vk::PhysicalDeviceFeatures2<'_>,
+        );
+        GetPhysicalDeviceProperties2 => fn vkGetPhysicalDeviceProperties2(
+            physical_device: vk::PhysicalDevice,
+            p_properties: *mut vk::PhysicalDeviceProperties2<'_>,
+        );
+        GetPhysicalDeviceFormatProperties2 => fn vkGetPhysicalDeviceFormatProperties2(
+            physical_device: vk::PhysicalDevice,
+            format: vk::Format,
+            p_format_properties: *mut vk::FormatProperties2<'_>,
+        );
+        GetPhysicalDeviceQueueFamilyProperties2 => fn vkGetPhysicalDeviceQueueFamilyProperties2(
+            physical_device: vk::PhysicalDevice,
+            p_queue_family_property_count: *mut u32,
+            p_queue_family_properties: *mut vk::QueueFamilyProperties2<'_>,
+        );
+        GetPhysicalDeviceMemoryProperties2 => fn vkGetPhysicalDeviceMemoryProperties2(
+            physical_device: vk::PhysicalDevice,
+            p_memory_properties: *mut vk::PhysicalDeviceMemoryProperties2<'_>,
+        );
+        DestroySurfaceKHR => fn vkDestroySurfaceKHR(
+            instance: vk::Instance,
+            surface: vk::SurfaceKHR,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        GetPhysicalDeviceSurfaceSupportKHR => fn vkGetPhysicalDeviceSurfaceSupportKHR(
+            physical_device: vk::PhysicalDevice,
+            queue_family_index: u32,
+            surface: vk::SurfaceKHR,
+            p_supported: *mut vk::Bool32,
+        ) -> vk::Result;
+        GetPhysicalDeviceSurfaceCapabilitiesKHR => fn vkGetPhysicalDeviceSurfaceCapabilitiesKHR(
+            physical_device: vk::PhysicalDevice,
+            surface: vk::SurfaceKHR,
+            p_surface_capabilities: *mut vk::SurfaceCapabilitiesKHR,
+        ) -> vk::Result;
+        GetPhysicalDeviceSurfaceFormatsKHR => fn vkGetPhysicalDeviceSurfaceFormatsKHR(
+            physical_device: vk::PhysicalDevice,
+            surface: vk::SurfaceKHR,
+            p_surface_format_count: *mut u32,
+            p_surface_formats: *mut vk::SurfaceFormatKHR,
+        ) -> vk::Result;
+        GetPhysicalDeviceSurfacePresentModesKHR => fn vkGetPhysicalDeviceSurfacePresentModesKHR(
+            physical_device: vk::PhysicalDevice,
+            surface: vk::SurfaceKHR,
+            p_present_mode_count: *mut u32,
+            p_present_modes: *mut vk::PresentModeKHR,
+        ) -> vk::Result;
+        CreateDebugUtilsMessengerEXT => fn vkCreateDebugUtilsMessengerEXT(
+            instance: vk::Instance,
+            p_create_info: *const vk::DebugUtilsMessengerCreateInfoEXT<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_messenger: *mut vk::DebugUtilsMessengerEXT,
+        ) -> vk::Result;
+        DestroyDebugUtilsMessengerEXT => fn vkDestroyDebugUtilsMessengerEXT(
+            instance: vk::Instance,
+            messenger: vk::DebugUtilsMessengerEXT,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        CreateMetalSurfaceEXT => fn vkCreateMetalSurfaceEXT(
+            instance: vk::Instance,
+            p_create_info: *const vk::MetalSurfaceCreateInfoEXT<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_surface: *mut vk::SurfaceKHR,
+        ) -> vk::Result;
+        CreateHeadlessSurfaceEXT => fn vkCreateHeadlessSurfaceEXT(
+            instance: vk::Instance,
+            p_create_info: *const vk::HeadlessSurfaceCreateInfoEXT<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_surface: *mut vk::SurfaceKHR,
+        ) -> vk::Result;
+    }
+    device {
+        DestroyDevice => fn vkDestroyDevice(
+            device: vk::Device,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        GetDeviceQueue => fn vkGetDeviceQueue(
+            device: vk::Device,
+            queue_family_index: u32,
+            queue_index: u32,
+            p_queue: *mut vk::Queue,
+        );
+        QueueSubmit => fn vkQueueSubmit(
+            queue: vk::Queue,
+            submit_count: u32,
+            p_submits: *const vk::SubmitInfo<'_>,
+            fence: vk::Fence,
+        ) -> vk::Result;
+        QueueWaitIdle => fn vkQueueWaitIdle(queue: vk::Queue) -> vk::Result;
+        DeviceWaitIdle => fn vkDeviceWaitIdle(device: vk::Device) -> vk::Result;
+        AllocateMemory => fn vkAllocateMemory(
+            device: vk::Device,
+            p_allocate_info: *const vk::MemoryAllocateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_memory: *mut vk::DeviceMemory,
+        ) -> vk::Result;
+        FreeMemory => fn vkFreeMemory(
+            device: vk::Device,
+            memory: vk::DeviceMemory,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        MapMemory => fn vkMapMemory(
+            device: vk::Device,
+            memory: vk::DeviceMemory,
+            offset: vk::DeviceSize,
+            size: vk::DeviceSize,
+            flags: vk::MemoryMapFlags,
+            pp_data: *mut *mut c_void,
+        ) -> vk::Result;
+        UnmapMemory => fn vkUnmapMemory(device: vk::Device, memory: vk::DeviceMemory);
+        FlushMappedMemoryRanges => fn vkFlushMappedMemoryRanges(
+            device: vk::Device,
+            memory_range_count: u32,
+            p_memory_ranges: *const vk::MappedMemoryRange<'_>,
+        ) -> vk::Result;
+        InvalidateMappedMemoryRanges => fn vkInvalidateMappedMemoryRanges(
+            device: vk::Device,
+            memory_range_count: u32,
+            p_memory_ranges: *const vk::MappedMemoryRange<'_>,
+        ) -> vk::Result;
+        GetDeviceMemoryCommitment => fn vkGetDeviceMemoryCommitment(
+            device: vk::Device,
+            memory: vk::DeviceMemory,
+            p_committed_memory_in_bytes: *mut vk::DeviceSize,
+        );
+        BindBufferMemory => fn vkBindBufferMemory(
+            device: vk::Device,
+            buffer: vk::Buffer,
+            memory: vk::DeviceMemory,
+            memory_offset: vk::DeviceSize,
+        ) -> vk::Result;
+        BindImageMemory => fn vkBindImageMemory(
+            device: vk::Device,
+            image: vk::Image,
+            memory: vk::DeviceMemory,
+            memory_offset: vk::DeviceSize,
+        ) -> vk::Result;
+        GetBufferMemoryRequirements => fn vkGetBufferMemoryRequirements(
+            device: vk::Device,
+            buffer: vk::Buffer,
+            p_memory_requirements: *mut vk::MemoryRequirements,
+        );
+        GetImageMemoryRequirements => fn vkGetImageMemoryRequirements(
+            device: vk::Device,
+            image: vk::Image,
+            p_memory_requirements: *mut vk::MemoryRequirements,
+        );
+        GetImageSparseMemoryRequirements => fn vkGetImageSparseMemoryRequirements(
+            device: vk::Device,
+            image: vk::Image,
+            p_sparse_memory_requirement_count: *mut u32,
+            p_sparse_memory_requirements: *mut vk::SparseImageMemoryRequirements,
+        );
+        QueueBindSparse => fn vkQueueBindSparse(
+            queue: vk::Queue,
+            bind_info_count: u32,
+            p_bind_info: *const vk::BindSparseInfo<'_>,
+            fence: vk::Fence,
+        ) -> vk::Result;
+        CreateFence => fn vkCreateFence(
+            device: vk::Device,
+            p_create_info: *const vk::FenceCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_fence: *mut vk::Fence,
+        ) -> vk::Result;
+        DestroyFence => fn vkDestroyFence(
+            device: vk::Device,
+            fence: vk::Fence,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        ResetFences => fn vkResetFences(
+            device: vk::Device,
+            fence_count: u32,
+            p_fences: *const vk::Fence,
+        ) -> vk::Result;
+        GetFenceStatus => fn vkGetFenceStatus(device: vk::Device, fence: vk::Fence) -> vk::Result;
+        WaitForFences => fn vkWaitForFences(
+            device: vk::Device,
+            fence_count: u32,
+            p_fences: *const vk::Fence,
+            wait_all: vk::Bool32,
+            timeout: u64,
+        ) -> vk::Result;
+        CreateSemaphore => fn vkCreateSemaphore(
+            device: vk::Device,
+            p_create_info: *const vk::SemaphoreCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_semaphore: *mut vk::Semaphore,
+        ) -> vk::Result;
+        DestroySemaphore => fn vkDestroySemaphore(
+            device: vk::Device,
+            semaphore: vk::Semaphore,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        CreateEvent => fn vkCreateEvent(
+            device: vk::Device,
+            p_create_info: *const vk::EventCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_event: *mut vk::Event,
+        ) -> vk::Result;
+        DestroyEvent => fn vkDestroyEvent(
+            device: vk::Device,
+            event: vk::Event,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        GetEventStatus => fn vkGetEventStatus(device: vk::Device, event: vk::Event) -> vk::Result;
+        SetEvent => fn vkSetEvent(device: vk::Device, event: vk::Event) -> vk::Result;
+        ResetEvent => fn vkResetEvent(device: vk::Device, event: vk::Event) -> vk::Result;
+        CreateQueryPool => fn vkCreateQueryPool(
+            device: vk::Device,
+            p_create_info: *const vk::QueryPoolCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_query_pool: *mut vk::QueryPool,
+        ) -> vk::Result;
+        DestroyQueryPool => fn vkDestroyQueryPool(
+            device: vk::Device,
+            query_pool: vk::QueryPool,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        GetQueryPoolResults => fn vkGetQueryPoolResults(
+            device: vk::Device,
+            query_pool: vk::QueryPool,
+            first_query: u32,
+            query_count: u32,
+            data_size: usize,
+            p_data: *mut c_void,
+            stride: vk::DeviceSize,
+            flags: vk::QueryResultFlags,
+        ) -> vk::Result;
+        CreateBuffer => fn vkCreateBuffer(
+            device: vk::Device,
+            p_create_info: *const vk::BufferCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_buffer: *mut vk::Buffer,
+        ) -> vk::Result;
+        DestroyBuffer => fn vkDestroyBuffer(
+            device: vk::Device,
+            buffer: vk::Buffer,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        CreateBufferView => fn vkCreateBufferView(
+            device: vk::Device,
+            p_create_info: *const vk::BufferViewCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_view: *mut vk::BufferView,
+        ) -> vk::Result;
+        DestroyBufferView => fn vkDestroyBufferView(
+            device: vk::Device,
+            buffer_view: vk::BufferView,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        CreateImage => fn vkCreateImage(
+            device: vk::Device,
+            p_create_info: *const vk::ImageCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_image: *mut vk::Image,
+        ) -> vk::Result;
+        DestroyImage => fn vkDestroyImage(
+            device: vk::Device,
+            image: vk::Image,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        GetImageSubresourceLayout => fn vkGetImageSubresourceLayout(
+            device: vk::Device,
+            image: vk::Image,
+            p_subresource: *const vk::ImageSubresource,
+            p_layout: *mut vk::SubresourceLayout,
+        );
+        CreateImageView => fn vkCreateImageView(
+            device: vk::Device,
+            p_create_info: *const vk::ImageViewCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_view: *mut vk::ImageView,
+        ) -> vk::Result;
+        DestroyImageView => fn vkDestroyImageView(
+            device: vk::Device,
+            image_view: vk::ImageView,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        CreateShaderModule => fn vkCreateShaderModule(
+            device: vk::Device,
+            p_create_info: *const vk::ShaderModuleCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_shader_module: *mut vk::ShaderModule,
+        ) -> vk::Result;
+        DestroyShaderModule => fn vkDestroyShaderModule(
+            device: vk::Device,
+            shader_module: vk::ShaderModule,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        CreatePipelineCache => fn vkCreatePipelineCache(
+            device: vk::Device,
+            p_create_info: *const vk::PipelineCacheCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_pipeline_cache: *mut vk::PipelineCache,
+        ) -> vk::Result;
+        DestroyPipelineCache => fn vkDestroyPipelineCache(
+            device: vk::Device,
+            pipeline_cache: vk::PipelineCache,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        GetPipelineCacheData => fn vkGetPipelineCacheData(
+            device: vk::Device,
+            pipeline_cache: vk::PipelineCache,
+            p_data_size: *mut usize,
+            p_data: *mut c_void,
+        ) -> vk::Result;
+        MergePipelineCaches => fn vkMergePipelineCaches(
+            device: vk::Device,
+            dst_cache: vk::PipelineCache,
+            src_cache_count: u32,
+            p_src_caches: *const vk::PipelineCache,
+        ) -> vk::Result;
+        CreateGraphicsPipelines => fn vkCreateGraphicsPipelines(
+            device: vk::Device,
+            pipeline_cache: vk::PipelineCache,
+            create_info_count: u32,
+            p_create_infos: *const vk::GraphicsPipelineCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_pipelines: *mut vk::Pipeline,
+        ) -> vk::Result;
+        CreateComputePipelines => fn vkCreateComputePipelines(
+            device: vk::Device,
+            pipeline_cache: vk::PipelineCache,
+            create_info_count: u32,
+            p_create_infos: *const vk::ComputePipelineCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_pipelines: *mut vk::Pipeline,
+        ) -> vk::Result;
+        DestroyPipeline => fn vkDestroyPipeline(
+            device: vk::Device,
+            pipeline: vk::Pipeline,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        CreatePipelineLayout => fn vkCreatePipelineLayout(
+            device: vk::Device,
+            p_create_info: *const vk::PipelineLayoutCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_pipeline_layout: *mut vk::PipelineLayout,
+        ) -> vk::Result;
+        DestroyPipelineLayout => fn vkDestroyPipelineLayout(
+            device: vk::Device,
+            pipeline_layout: vk::PipelineLayout,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        CreateSampler => fn vkCreateSampler(
+            device: vk::Device,
+            p_create_info: *const vk::SamplerCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_sampler: *mut vk::Sampler,
+        ) -> vk::Result;
+        DestroySampler => fn vkDestroySampler(
+            device: vk::Device,
+            sampler: vk::Sampler,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        CreateDescriptorSetLayout => fn vkCreateDescriptorSetLayout(
+            device: vk::Device,
+            p_create_info: *const vk::DescriptorSetLayoutCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_set_layout: *mut vk::DescriptorSetLayout,
+        ) -> vk::Result;
+        DestroyDescriptorSetLayout => fn vkDestroyDescriptorSetLayout(
+            device: vk::Device,
+            descriptor_set_layout: vk::DescriptorSetLayout,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        CreateDescriptorPool => fn vkCreateDescriptorPool(
+            device: vk::Device,
+            p_create_info: *const vk::DescriptorPoolCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_descriptor_pool: *mut vk::DescriptorPool,
+        ) -> vk::Result;
+        DestroyDescriptorPool => fn vkDestroyDescriptorPool(
+            device: vk::Device,
+            descriptor_pool: vk::DescriptorPool,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        ResetDescriptorPool => fn vkResetDescriptorPool(
+            device: vk::Device,
+            descriptor_pool: vk::DescriptorPool,
+            flags: vk::DescriptorPoolResetFlags,
+        ) -> vk::Result;
+        AllocateDescriptorSets => fn vkAllocateDescriptorSets(
+            device: vk::Device,
+            p_allocate_info: *const vk::DescriptorSetAllocateInfo<'_>,
+            p_descriptor_sets: *mut vk::DescriptorSet,
+        ) -> vk::Result;
+        FreeDescriptorSets => fn vkFreeDescriptorSets(
+            device: vk::Device,
+            descriptor_pool: vk::DescriptorPool,
+            descriptor_set_count: u32,
+            p_descriptor_sets: *const vk::DescriptorSet,
+        ) -> vk::Result;
+        UpdateDescriptorSets => fn vkUpdateDescriptorSets(
+            device: vk::Device,
+            descriptor_write_count: u32,
+            p_descriptor_writes: *const vk::WriteDescriptorSet<'_>,
+            descriptor_copy_count: u32,
+            p_descriptor_copies: *const vk::CopyDescriptorSet<'_>,
+        );
+        CreateFramebuffer => fn vkCreateFramebuffer(
+            device: vk::Device,
+            p_create_info: *const vk::FramebufferCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_framebuffer: *mut vk::Framebuffer,
+        ) -> vk::Result;
+        DestroyFramebuffer => fn vkDestroyFramebuffer(
+            device: vk::Device,
+            framebuffer: vk::Framebuffer,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        CreateRenderPass => fn vkCreateRenderPass(
+            device: vk::Device,
+            p_create_info: *const vk::RenderPassCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_render_pass: *mut vk::RenderPass,
+        ) -> vk::Result;
+        DestroyRenderPass => fn vkDestroyRenderPass(
+            device: vk::Device,
+            render_pass: vk::RenderPass,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        GetRenderAreaGranularity => fn vkGetRenderAreaGranularity(
+            device: vk::Device,
+            render_pass: vk::RenderPass,
+            p_granularity: *mut vk::Extent2D,
+        );
+        CreateCommandPool => fn vkCreateCommandPool(
+            device: vk::Device,
+            p_create_info: *const vk::CommandPoolCreateInfo<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_command_pool: *mut vk::CommandPool,
+        ) -> vk::Result;
+        DestroyCommandPool => fn vkDestroyCommandPool(
+            device: vk::Device,
+            command_pool: vk::CommandPool,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        ResetCommandPool => fn vkResetCommandPool(
+            device: vk::Device,
+            command_pool: vk::CommandPool,
+            flags: vk::CommandPoolResetFlags,
+        ) -> vk::Result;
+        AllocateCommandBuffers => fn vkAllocateCommandBuffers(
+            device: vk::Device,
+            p_allocate_info: *const vk::CommandBufferAllocateInfo<'_>,
+            p_command_buffers: *mut vk::CommandBuffer,
+        ) -> vk::Result;
+        FreeCommandBuffers => fn vkFreeCommandBuffers(
+            device: vk::Device,
+            command_pool: vk::CommandPool,
+            command_buffer_count: u32,
+            p_command_buffers: *const vk::CommandBuffer,
+        );
+        BeginCommandBuffer => fn vkBeginCommandBuffer(
+            command_buffer: vk::CommandBuffer,
+            p_begin_info: *const vk::CommandBufferBeginInfo<'_>,
+        ) -> vk::Result;
+        EndCommandBuffer => fn vkEndCommandBuffer(command_buffer: vk::CommandBuffer) -> vk::Result;
+        ResetCommandBuffer => fn vkResetCommandBuffer(
+            command_buffer: vk::CommandBuffer,
+            flags: vk::CommandBufferResetFlags,
+        ) -> vk::Result;
+        CmdBindPipeline => fn vkCmdBindPipeline(
+            command_buffer: vk::CommandBuffer,
+            pipeline_bind_point: vk::PipelineBindPoint,
+            pipeline: vk::Pipeline,
+        );
+        CmdSetViewport => fn vkCmdSetViewport(
+            command_buffer: vk::CommandBuffer,
+            first_viewport: u32,
+            viewport_count: u32,
+            p_viewports: *const vk::Viewport,
+        );
+        CmdSetScissor => fn vkCmdSetScissor(
+            command_buffer: vk::CommandBuffer,
+            first_scissor: u32,
+            scissor_count: u32,
+            p_scissors: *const vk::Rect2D,
+        );
+        CmdSetLineWidth => fn vkCmdSetLineWidth(command_buffer: vk::CommandBuffer, line_width: f32);
+        CmdSetDepthBias => fn vkCmdSetDepthBias(
+            command_buffer: vk::CommandBuffer,
+            depth_bias_constant_factor: f32,
+            depth_bias_clamp: f32,
+            depth_bias_slope_factor: f32,
+        );
+        CmdSetBlendConstants => fn vkCmdSetBlendConstants(
+            command_buffer: vk::CommandBuffer,
+            blend_constants: *const [f32; 4usize],
+        );
+        CmdSetDepthBounds => fn vkCmdSetDepthBounds(
+            command_buffer: vk::CommandBuffer,
+            min_depth_bounds: f32,
+            max_depth_bounds: f32,
+        );
+        CmdSetStencilCompareMask => fn vkCmdSetStencilCompareMask(
+            command_buffer: vk::CommandBuffer,
+            face_mask: vk::StencilFaceFlags,
+            compare_mask: u32,
+        );
+        CmdSetStencilWriteMask => fn vkCmdSetStencilWriteMask(
+            command_buffer: vk::CommandBuffer,
+            face_mask: vk::StencilFaceFlags,
+            write_mask: u32,
+        );
+        CmdSetStencilReference => fn vkCmdSetStencilReference(
+            command_buffer: vk::CommandBuffer,
+            face_mask: vk::StencilFaceFlags,
+            reference: u32,
+        );
+        CmdBindDescriptorSets => fn vkCmdBindDescriptorSets(
+            command_buffer: vk::CommandBuffer,
+            pipeline_bind_point: vk::PipelineBindPoint,
+            layout: vk::PipelineLayout,
+            first_set: u32,
+            descriptor_set_count: u32,
+            p_descriptor_sets: *const vk::DescriptorSet,
+            dynamic_offset_count: u32,
+            p_dynamic_offsets: *const u32,
+        );
+        CmdBindIndexBuffer => fn vkCmdBindIndexBuffer(
+            command_buffer: vk::CommandBuffer,
+            buffer: vk::Buffer,
+            offset: vk::DeviceSize,
+            index_type: vk::IndexType,
+        );
+        CmdBindVertexBuffers => fn vkCmdBindVertexBuffers(
+            command_buffer: vk::CommandBuffer,
+            first_binding: u32,
+            binding_count: u32,
+            p_buffers: *const vk::Buffer,
+            p_offsets: *const vk::DeviceSize,
+        );
+        CmdDraw => fn vkCmdDraw(
+            command_buffer: vk::CommandBuffer,
+            vertex_count: u32,
+            instance_count: u32,
+            first_vertex: u32,
+            first_instance: u32,
+        );
+        CmdDrawIndexed => fn vkCmdDrawIndexed(
+            command_buffer: vk::CommandBuffer,
+            index_count: u32,
+            instance_count: u32,
+            first_index: u32,
+            vertex_offset: i32,
+            first_instance: u32,
+        );
+        CmdDrawIndirect => fn vkCmdDrawIndirect(
+            command_buffer: vk::CommandBuffer,
+            buffer: vk::Buffer,
+            offset: vk::DeviceSize,
+            draw_count: u32,
+            stride: u32,
+        );
+        CmdDrawIndexedIndirect => fn vkCmdDrawIndexedIndirect(
+            command_buffer: vk::CommandBuffer,
+            buffer: vk::Buffer,
+            offset: vk::DeviceSize,
+            draw_count: u32,
+            stride: u32,
+        );
+        CmdDispatch => fn vkCmdDispatch(
+            command_buffer: vk::CommandBuffer,
+            group_count_x: u32,
+            group_count_y: u32,
+            group_count_z: u32,
+        );
+        CmdDispatchIndirect => fn vkCmdDispatchIndirect(
+            command_buffer: vk::CommandBuffer,
+            buffer: vk::Buffer,
+            offset: vk::DeviceSize,
+        );
+        CmdCopyBuffer => fn vkCmdCopyBuffer(
+            command_buffer: vk::CommandBuffer,
+            src_buffer: vk::Buffer,
+            dst_buffer: vk::Buffer,
+            region_count: u32,
+            p_regions: *const vk::BufferCopy,
+        );
+        CmdCopyImage => fn vkCmdCopyImage(
+            command_buffer: vk::CommandBuffer,
+            src_image: vk::Image,
+            src_image_layout: vk::ImageLayout,
+            dst_image: vk::Image,
+            dst_image_layout: vk::ImageLayout,
+            region_count: u32,
+            p_regions: *const vk::ImageCopy,
+        );
+        CmdBlitImage => fn vkCmdBlitImage(
+            command_buffer: vk::CommandBuffer,
+            src_image: vk::Image,
+            src_image_layout: vk::ImageLayout,
+            dst_image: vk::Image,
+            dst_image_layout: vk::ImageLayout,
+            region_count: u32,
+            p_regions: *const vk::ImageBlit,
+            filter: vk::Filter,
+        );
+        CmdCopyBufferToImage => fn vkCmdCopyBufferToImage(
+            command_buffer: vk::CommandBuffer,
+            src_buffer: vk::Buffer,
+            dst_image: vk::Image,
+            dst_image_layout: vk::ImageLayout,
+            region_count: u32,
+            p_regions: *const vk::BufferImageCopy,
+        );
+        CmdCopyImageToBuffer => fn vkCmdCopyImageToBuffer(
+            command_buffer: vk::CommandBuffer,
+            src_image: vk::Image,
+            src_image_layout: vk::ImageLayout,
+            dst_buffer: vk::Buffer,
+            region_count: u32,
+            p_regions: *const vk::BufferImageCopy,
+        );
+        CmdUpdateBuffer => fn vkCmdUpdateBuffer(
+            command_buffer: vk::CommandBuffer,
+            dst_buffer: vk::Buffer,
+            dst_offset: vk::DeviceSize,
+            data_size: vk::DeviceSize,
+            p_data: *const c_void,
+        );
+        CmdFillBuffer => fn vkCmdFillBuffer(
+            command_buffer: vk::CommandBuffer,
+            dst_buffer: vk::Buffer,
+            dst_offset: vk::DeviceSize,
+            size: vk::DeviceSize,
+            data: u32,
+        );
+        CmdClearColorImage => fn vkCmdClearColorImage(
+            command_buffer: vk::CommandBuffer,
+            image: vk::Image,
+            image_layout: vk::ImageLayout,
+            p_color: *const vk::ClearColorValue,
+            range_count: u32,
+            p_ranges: *const vk::ImageSubresourceRange,
+        );
+        CmdClearDepthStencilImage => fn vkCmdClearDepthStencilImage(
+            command_buffer: vk::CommandBuffer,
+            image: vk::Image,
+            image_layout: vk::ImageLayout,
+            p_depth_stencil: *const vk::ClearDepthStencilValue,
+            range_count: u32,
+            p_ranges: *const vk::ImageSubresourceRange,
+        );
+        CmdClearAttachments => fn vkCmdClearAttachments(
+            command_buffer: vk::CommandBuffer,
+            attachment_count: u32,
+            p_attachments: *const vk::ClearAttachment,
+            rect_count: u32,
+            p_rects: *const vk::ClearRect,
+        );
+        CmdResolveImage => fn vkCmdResolveImage(
+            command_buffer: vk::CommandBuffer,
+            src_image: vk::Image,
+            src_image_layout: vk::ImageLayout,
+            dst_image: vk::Image,
+            dst_image_layout: vk::ImageLayout,
+            region_count: u32,
+            p_regions: *const vk::ImageResolve,
+        );
+        CmdSetEvent => fn vkCmdSetEvent(
+            command_buffer: vk::CommandBuffer,
+            event: vk::Event,
+            stage_mask: vk::PipelineStageFlags,
+        );
+        CmdResetEvent => fn vkCmdResetEvent(
+            command_buffer: vk::CommandBuffer,
+            event: vk::Event,
+            stage_mask: vk::PipelineStageFlags,
+        );
+        CmdWaitEvents => fn vkCmdWaitEvents(
+            command_buffer: vk::CommandBuffer,
+            event_count: u32,
+            p_events: *const vk::Event,
+            src_stage_mask: vk::PipelineStageFlags,
+            dst_stage_mask: vk::PipelineStageFlags,
+            memory_barrier_count: u32,
+            p_memory_barriers: *const vk::MemoryBarrier<'_>,
+            buffer_memory_barrier_count: u32,
+            p_buffer_memory_barriers: *const vk::BufferMemoryBarrier<'_>,
+            image_memory_barrier_count: u32,
+            p_image_memory_barriers: *const vk::ImageMemoryBarrier<'_>,
+        );
+        CmdPipelineBarrier => fn vkCmdPipelineBarrier(
+            command_buffer: vk::CommandBuffer,
+            src_stage_mask: vk::PipelineStageFlags,
+            dst_stage_mask: vk::PipelineStageFlags,
+            dependency_flags: vk::DependencyFlags,
+            memory_barrier_count: u32,
+            p_memory_barriers: *const vk::MemoryBarrier<'_>,
+            buffer_memory_barrier_count: u32,
+            p_buffer_memory_barriers: *const vk::BufferMemoryBarrier<'_>,
+            image_memory_barrier_count: u32,
+            p_image_memory_barriers: *const vk::ImageMemoryBarrier<'_>,
+        );
+        CmdBeginQuery => fn vkCmdBeginQuery(
+            command_buffer: vk::CommandBuffer,
+            query_pool: vk::QueryPool,
+            query: u32,
+            flags: vk::QueryControlFlags,
+        );
+        CmdEndQuery => fn vkCmdEndQuery(
+            command_buffer: vk::CommandBuffer,
+            query_pool: vk::QueryPool,
+            query: u32,
+        );
+        CmdResetQueryPool => fn vkCmdResetQueryPool(
+            command_buffer: vk::CommandBuffer,
+            query_pool: vk::QueryPool,
+            first_query: u32,
+            query_count: u32,
+        );
+        CmdWriteTimestamp => fn vkCmdWriteTimestamp(
+            command_buffer: vk::CommandBuffer,
+            pipeline_stage: vk::PipelineStageFlags,
+            query_pool: vk::QueryPool,
+            query: u32,
+        );
+        CmdCopyQueryPoolResults => fn vkCmdCopyQueryPoolResults(
+            command_buffer: vk::CommandBuffer,
+            query_pool: vk::QueryPool,
+            first_query: u32,
+            query_count: u32,
+            dst_buffer: vk::Buffer,
+            dst_offset: vk::DeviceSize,
+            stride: vk::DeviceSize,
+            flags: vk::QueryResultFlags,
+        );
+        CmdPushConstants => fn vkCmdPushConstants(
+            command_buffer: vk::CommandBuffer,
+            layout: vk::PipelineLayout,
+            stage_flags: vk::ShaderStageFlags,
+            offset: u32,
+            size: u32,
+            p_values: *const c_void,
+        );
+        CmdBeginRenderPass => fn vkCmdBeginRenderPass(
+            command_buffer: vk::CommandBuffer,
+            p_render_pass_begin: *const vk::RenderPassBeginInfo<'_>,
+            contents: vk::SubpassContents,
+        );
+        CmdNextSubpass => fn vkCmdNextSubpass(
+            command_buffer: vk::CommandBuffer,
+            contents: vk::SubpassContents,
+        );
+        CmdEndRenderPass => fn vkCmdEndRenderPass(command_buffer: vk::CommandBuffer);
+        CmdExecuteCommands => fn vkCmdExecuteCommands(
+            command_buffer: vk::CommandBuffer,
+            command_buffer_count: u32,
+            p_command_buffers: *const vk::CommandBuffer,
+        );
+        GetDeviceQueue2 => fn vkGetDeviceQueue2(
+            device: vk::Device,
+            p_queue_info: *const vk::DeviceQueueInfo2<'_>,
+            p_queue: *mut vk::Queue,
+        );
+        WaitSemaphores => fn vkWaitSemaphores(
+            device: vk::Device,
+            p_wait_info: *const vk::SemaphoreWaitInfo<'_>,
+            timeout: u64,
+        ) -> vk::Result;
+        SignalSemaphore => fn vkSignalSemaphore(
+            device: vk::Device,
+            p_signal_info: *const vk::SemaphoreSignalInfo<'_>,
+        ) -> vk::Result;
+        GetSemaphoreCounterValue => fn vkGetSemaphoreCounterValue(
+            device: vk::Device,
+            semaphore: vk::Semaphore,
+            p_value: *mut u64,
+        ) -> vk::Result;
+        CreateSwapchainKHR => fn vkCreateSwapchainKHR(
+            device: vk::Device,
+            p_create_info: *const vk::SwapchainCreateInfoKHR<'_>,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+            p_swapchain: *mut vk::SwapchainKHR,
+        ) -> vk::Result;
+        DestroySwapchainKHR => fn vkDestroySwapchainKHR(
+            device: vk::Device,
+            swapchain: vk::SwapchainKHR,
+            p_allocator: *const vk::AllocationCallbacks<'_>,
+        );
+        GetSwapchainImagesKHR => fn vkGetSwapchainImagesKHR(
+            device: vk::Device,
+            swapchain: vk::SwapchainKHR,
+            p_swapchain_image_count: *mut u32,
+            p_swapchain_images: *mut vk::Image,
+        ) -> vk::Result;
+        AcquireNextImageKHR => fn vkAcquireNextImageKHR(
+            device: vk::Device,
+            swapchain: vk::SwapchainKHR,
+            timeout: u64,
+            semaphore: vk::Semaphore,
+            fence: vk::Fence,
+            p_image_index: *mut u32,
+        ) -> vk::Result;
+        QueuePresentKHR => fn vkQueuePresentKHR(
+            queue: vk::Queue,
+            p_present_info: *const vk::PresentInfoKHR<'_>,
+        ) -> vk::Result;
+        ExportMetalObjectsEXT => fn vkExportMetalObjectsEXT(
+            device: vk::Device,
+            p_metal_objects_info: *mut vk::ExportMetalObjectsInfoEXT<'_>,
+        );
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use std::sync::{MutexGuard, PoisonError};
+
+    /// Interceptors and dispatch tables are global, so the tests registering them run one at a
+    /// time
+    fn serial() -> MutexGuard<'static, ()> {
+        static SERIAL: Mutex<()> = Mutex::new(());
+        SERIAL.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Logs its calls to a log shared with other interceptors
+    struct Logged {
+        name: &'static str,
+        flow: Flow,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Interceptor for Logged {
+        fn before(&self, _call: &Call<'_>) -> Flow {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("before {}", self.name));
+            self.flow
+        }
+
+        fn after(&self, _call: &Call<'_>, result: Option<vk::Result>) {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("after {} {result:?}", self.name));
+        }
+    }
+
+    /// Registers interceptors named `names` for `vkCreateFence` on `device`, the one named
+    /// `returns` returning `ERROR_OUT_OF_HOST_MEMORY`
+    fn register_logged(
+        device: vk::Device,
+        names: &[&'static str],
+        returns: Option<&str>,
+    ) -> (Vec<InterceptorId>, Arc<Mutex<Vec<String>>>) {
+        let log = Arc::default();
+        let ids = names
+            .iter()
+            .map(|&name| {
+                let flow = if returns == Some(name) {
+                    Flow::Return(vk::Result::ERROR_OUT_OF_HOST_MEMORY)
+                } else {
+                    Flow::Continue
+                };
+                let logged = Logged {
+                    name,
+                    flow,
+                    log: Arc::clone(&log),
+                };
+                InterceptorBuilder::new(Arc::new(logged))
+                    .function(Function::CreateFence)
+                    .device(device)
+                    .register()
+            })
+            .collect();
+        (ids, log)
+    }
+
+    unsafe fn create_device(entry: &MoltenEntry) -> (ash::Instance, ash::Device) {
+        let instance = entry
+            .create_instance(&vk::InstanceCreateInfo::default(), None)
+            .unwrap();
+        let physical_device = instance.enumerate_physical_devices().unwrap()[0];
+        let queue_create_info = vk::DeviceQueueCreateInfo::default().queue_priorities(&[1.0]);
+        let device = instance
+            .create_device(
+                physical_device,
+                &vk::DeviceCreateInfo::default().queue_create_infos(&[queue_create_info]),
+                None,
+            )
+            .unwrap();
+        (instance, device)
+    }
+
+    fn wrapped() -> MoltenEntry {
+        wrap(&MoltenEntry::load().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn interceptors_run_in_order() {
+        let _serial = serial();
+        let entry = wrapped();
+        unsafe {
+            let (instance, device) = create_device(&entry);
+            let (ids, log) = register_logged(device.handle(), &["a", "b", "c"], None);
+
+            let fence = device
+                .create_fence(&vk::FenceCreateInfo::default(), None)
+                .unwrap();
+            assert_eq!(
+                *log.lock().unwrap(),
+                [
+                    "before a",
+                    "before b",
+                    "before c",
+                    "after c Some(SUCCESS)",
+                    "after b Some(SUCCESS)",
+                    "after a Some(SUCCESS)",
+                ]
+            );
+
+            for id in ids {
+                unregister(id);
+            }
+            device.destroy_fence(fence, None);
+            device.destroy_device(None);
+            instance.destroy_instance(None);
+        }
+    }
+
+    #[test]
+    fn return_short_circuits() {
+        let _serial = serial();
+        let entry = wrapped();
+        unsafe {
+            let (instance, device) = create_device(&entry);
+            let (ids, log) = register_logged(device.handle(), &["a", "b", "c"], Some("b"));
+
+            assert_eq!(
+                device.create_fence(&vk::FenceCreateInfo::default(), None),
+                Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY)
+            );
+            // `c` and the driver are skipped, and only the interceptors that ran see the result
+            assert_eq!(
+                *log.lock().unwrap(),
+                [
+                    "before a",
+                    "before b",
+                    "after b Some(ERROR_OUT_OF_HOST_MEMORY)",
+                    "after a Some(ERROR_OUT_OF_HOST_MEMORY)",
+                ]
+            );
+
+            for id in ids {
+                unregister(id);
+            }
+            device.destroy_device(None);
+            instance.destroy_instance(None);
+        }
+    }
+
+    #[test]
+    fn unregister_stops_interceptor() {
+        let _serial = serial();
+        let entry = wrapped();
+        unsafe {
+            let (instance, device) = create_device(&entry);
+            let (ids, log) = register_logged(device.handle(), &["a", "b"], Some("a"));
+
+            assert!(unregister(ids[0]));
+            assert!(!unregister(ids[0]));
+            let fence = device
+                .create_fence(&vk::FenceCreateInfo::default(), None)
+                .unwrap();
+            assert_eq!(*log.lock().unwrap(), ["before b", "after b Some(SUCCESS)"]);
+
+            assert!(unregister(ids[1]));
+            log.lock().unwrap().clear();
+            device.destroy_fence(fence, None);
+            let fence = device
+                .create_fence(&vk::FenceCreateInfo::default(), None)
+                .unwrap();
+            assert!(log.lock().unwrap().is_empty());
+
+            device.destroy_fence(fence, None);
+            device.destroy_device(None);
+            instance.destroy_instance(None);
+        }
+    }
+
+    #[test]
+    fn destroy_removes_dispatch() {
+        let _serial = serial();
+        let entry = wrapped();
+        unsafe {
+            let (instance, device) = create_device(&entry);
+            let physical_device = instance.enumerate_physical_devices().unwrap()[0];
+            let queue = device.get_device_queue(0, 0);
+            for handle in [
+                instance.handle().as_raw(),
+                physical_device.as_raw(),
+                device.handle().as_raw(),
+                queue.as_raw(),
+            ] {
+                assert!(dispatch(handle).is_some());
+            }
+
+            device.destroy_device(None);
+            assert!(dispatch(device.handle().as_raw()).is_none());
+            assert!(dispatch(queue.as_raw()).is_none());
+            assert!(dispatch(physical_device.as_raw()).is_some());
+
+            instance.destroy_instance(None);
+            assert!(dispatch(instance.handle().as_raw()).is_none());
+            assert!(dispatch(physical_device.as_raw()).is_none());
+        }
+    }
+
+    #[test]
+    fn unknown_handle_fails() {
+        let _serial = serial();
+        // Not the address of any object, so never in the dispatch tables
+        let device = vk::Device::from_raw(0x10);
+        unsafe {
+            let device_wait_idle = transmute::<unsafe extern "system" fn(), vk::PFN_vkDeviceWaitIdle>(
+                Function::DeviceWaitIdle.trampoline(),
+            );
+            assert_eq!(
+                device_wait_idle(device),
+                vk::Result::ERROR_INITIALIZATION_FAILED
+            );
+            assert!(get_device_proc_addr(device, c"vkDeviceWaitIdle".as_ptr()).is_none());
+        }
+    }
+
+    #[test]
+    fn wraps_one_entry() {
+        unsafe extern "system" fn other(
+            _instance: vk::Instance,
+            _p_name: *const c_char,
+        ) -> vk::PFN_vkVoidFunction {
+            None
+        }
+
+        let _serial = serial();
+        let entry = MoltenEntry::load().unwrap();
+        assert!(wrap(&entry).is_ok());
+        assert!(wrap(&entry).is_ok());
+        assert_eq!(unsafe { install(other) }, Err(LoadError::AlreadyWrapped));
+        assert_eq!(
+            next_get_instance_proc_addr() as usize,
+            entry.get_instance_proc_addr() as usize
+        );
+    }
+
+    /// Checks the arguments of `vkResetCommandPool`
+    struct ResetArgs(Mutex<Option<bool>>);
+
+    impl Interceptor for ResetArgs {
+        fn before(&self, call: &Call<'_>) -> Flow {
+            let flags = vk::CommandPoolResetFlags::RELEASE_RESOURCES;
+            let matches = matches!(
+                call.arg("device"),
+                Some(Value::Handle(vk::ObjectType::DEVICE, device)) if device == call.device.as_raw()
+            ) && matches!(
+                call.arg("command_pool"),
+                Some(Value::Handle(vk::ObjectType::COMMAND_POOL, _))
+            ) && matches!(
+                call.arg("flags"),
+                Some(Value::Bits(bits))
+                    if bits.raw() == u64::from(flags.as_raw())
+                        && format!("{bits:?}") == format!("{flags:?}")
+            );
+            *self.0.lock().unwrap() = Some(matches);
+            Flow::Continue
+        }
+    }
+
+    #[test]
+    fn classifies_values() {
+        let _serial = serial();
+        let entry = wrapped();
+        unsafe {
+            let (instance, device) = create_device(&entry);
+            let reset_args = Arc::new(ResetArgs(Mutex::new(None)));
+            let id = InterceptorBuilder::new(reset_args.clone())
+                .function(Function::ResetCommandPool)
+                .device(device.handle())
+                .register();
+
+            let command_pool = device
+                .create_command_pool(&vk::CommandPoolCreateInfo::default(), None)
+                .unwrap();
+            device
+                .reset_command_pool(command_pool, vk::CommandPoolResetFlags::RELEASE_RESOURCES)
+                .unwrap();
+            assert_eq!(*reset_args.0.lock().unwrap(), Some(true));
+
+            unregister(id);
+            device.destroy_command_pool(command_pool, None);
+            device.destroy_device(None);
+            instance.destroy_instance(None);
+        }
+    }
+}
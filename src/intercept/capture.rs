@@ -0,0 +1,992 @@
+//! Capturing the Vulkan calls of an application to a file and replaying them.
+//!
+//! A [`Recorder`] is an [`Interceptor`](super::Interceptor) serializing the successful calls
+//! made through intercepted instances and devices as [`Command`]s: the created handles, their
+//! create infos, shader code, the data written to mapped memory and the recorded commands. A
+//! [`Capture`] is written to and read from a compact binary file, and [`Capture::replay`] runs
+//! it against an entry, usually the statically linked MoltenVK, mapping the captured handles to
+//! the replayed ones.
+//!
+//! Compute and transfer work is captured, along with images and image views. Other functions
+//! with side effects, like graphics pipelines, render passes and swapchains, are recorded as
+//! [`Command::Unsupported`] and skipped by the replay, while queries aren't recorded at all.
+//!
+//! ```
+//! # use ash::vk;
+//! # use ash_molten::intercept::{self, capture::{Capture, Recorder}};
+//! # unsafe {
+//! let entry = ash_molten::MoltenEntry::load().unwrap();
+//! let wrapped = intercept::wrap(&entry).unwrap();
+//! let recorder = Recorder::start();
+//!
+//! let instance = wrapped
+//!     .create_instance(&vk::InstanceCreateInfo::default(), None)
+//!     .unwrap();
+//! let physical_device = instance.enumerate_physical_devices().unwrap()[0];
+//! let priorities = [1.0];
+//! let queue_create_info = vk::DeviceQueueCreateInfo::default()
+//!     .queue_family_index(0)
+//!     .queue_priorities(&priorities);
+//! let device = instance
+//!     .create_device(
+//!         physical_device,
+//!         &vk::DeviceCreateInfo::default().queue_create_infos(&[queue_create_info]),
+//!         None,
+//!     )
+//!     .unwrap();
+//! let queue = device.get_device_queue(0, 0);
+//!
+//! // A host visible buffer, filled with a command buffer
+//! let buffer = device
+//!     .create_buffer(
+//!         &vk::BufferCreateInfo::default()
+//!             .size(16)
+//!             .usage(vk::BufferUsageFlags::TRANSFER_DST),
+//!         None,
+//!     )
+//!     .unwrap();
+//! let memory = device
+//!     .allocate_memory(
+//!         &vk::MemoryAllocateInfo::default()
+//!             .allocation_size(256)
+//!             .memory_type_index(1),
+//!         None,
+//!     )
+//!     .unwrap();
+//! device.bind_buffer_memory(buffer, memory, 0).unwrap();
+//! let pool = device
+//!     .create_command_pool(&vk::CommandPoolCreateInfo::default(), None)
+//!     .unwrap();
+//! let command_buffer = device
+//!     .allocate_command_buffers(
+//!         &vk::CommandBufferAllocateInfo::default()
+//!             .command_pool(pool)
+//!             .command_buffer_count(1),
+//!     )
+//!     .unwrap()[0];
+//! device
+//!     .begin_command_buffer(command_buffer, &vk::CommandBufferBeginInfo::default())
+//!     .unwrap();
+//! device.cmd_fill_buffer(command_buffer, buffer, 0, 16, 0x2a2a_2a2a);
+//! device.end_command_buffer(command_buffer).unwrap();
+//! let command_buffers = [command_buffer];
+//! device
+//!     .queue_submit(
+//!         queue,
+//!         &[vk::SubmitInfo::default().command_buffers(&command_buffers)],
+//!         vk::Fence::null(),
+//!     )
+//!     .unwrap();
+//! device.queue_wait_idle(queue).unwrap();
+//!
+//! let capture = recorder.finish().unwrap();
+//! let mut file = Vec::new();
+//! capture.write(&mut file).unwrap();
+//! assert_eq!(Capture::read(file.as_slice()).unwrap(), capture);
+//!
+//! // The replayed buffer is filled like the captured one
+//! let replayer = capture.replay(&entry).unwrap();
+//! let replayed = replayer.device(device.handle()).unwrap();
+//! let data = replayed
+//!     .map_memory(
+//!         replayer.handle(memory).unwrap(),
+//!         0,
+//!         16,
+//!         vk::MemoryMapFlags::empty(),
+//!     )
+//!     .unwrap();
+//! assert_eq!(*data.cast::<[u8; 16]>(), [0x2a; 16]);
+//! # }
+//! ```
+//!
+//! Memory types and queue families are replayed by index, so replaying is meant for the same
+//! kind of device the capture was made on. Data written to mapped memory is captured when it
+//! is flushed, unmapped or submitted, as the bytes that differ from the memory's contents when
+//! it was mapped, and memory mapped before the capture started isn't captured.
+
+use ash::vk::{self, Handle as _};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+    path::Path,
+};
+
+mod record;
+mod replay;
+
+pub use record::Recorder;
+pub use replay::Replayer;
+
+/// The environment variable naming the file [`Recorder::from_env`] captures to
+pub const CAPTURE_ENV_VAR: &str = "ASH_MOLTEN_CAPTURE";
+
+/// The first bytes of a capture file
+const MAGIC: [u8; 4] = *b"AMVC";
+
+/// The version of the capture format, incremented whenever it changes
+const FORMAT_VERSION: u32 = 1;
+
+/// Changes to mapped memory closer than this are captured as one write
+const WRITE_MERGE_DISTANCE: usize = 32;
+
+/// An error reading, writing or replaying a [`Capture`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CaptureError {
+    /// The capture couldn't be read or written
+    Io(io::Error),
+    /// The data isn't a capture of a supported version
+    Format(String),
+    /// A replayed command failed
+    Vulkan {
+        /// The index of the command in the capture
+        index: usize,
+        function: &'static str,
+        result: vk::Result,
+    },
+    /// A command uses a handle that wasn't created by a previous command
+    UnknownHandle { index: usize, handle: u64 },
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => error.fmt(f),
+            Self::Format(message) => write!(f, "invalid capture: {message}"),
+            Self::Vulkan {
+                index,
+                function,
+                result,
+            } => write!(
+                f,
+                "replaying command {index} failed: {function} returned {result}"
+            ),
+            Self::UnknownHandle { index, handle } => {
+                write!(f, "command {index} uses the unknown handle {handle:#x}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+impl From<io::Error> for CaptureError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// A value of the capture format, which is little-endian with length-prefixed sequences
+trait Encode: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError>;
+}
+
+enum DecodeError {
+    /// The input ended in the middle of a value
+    Truncated,
+    Invalid(String),
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], DecodeError> {
+    if input.len() < len {
+        return Err(DecodeError::Truncated);
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+macro_rules! numbers {
+    ($($ty:ty),*) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                    let bytes = take(input, size_of::<$ty>())?;
+                    Ok(<$ty>::from_le_bytes(bytes.try_into().unwrap()))
+                }
+            }
+        )*
+    };
+}
+
+numbers!(u8, u32, i32, u64, f32);
+
+impl Encode for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        u8::from(*self).encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok(u8::decode(input)? != 0)
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode(out);
+        for value in self {
+            value.encode(out);
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let len = u32::decode(input)? as usize;
+        // Don't trust the length for the allocation, each value takes at least a byte
+        let mut values = Vec::with_capacity(len.min(input.len()));
+        for _ in 0..len {
+            values.push(T::decode(input)?);
+        }
+        Ok(values)
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.is_some().encode(out);
+        if let Some(value) = self {
+            value.encode(out);
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok(if bool::decode(input)? {
+            Some(T::decode(input)?)
+        } else {
+            None
+        })
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let len = u32::decode(input)? as usize;
+        String::from_utf8(take(input, len)?.to_vec())
+            .map_err(|_err| DecodeError::Invalid("a string isn't UTF-8".to_owned()))
+    }
+}
+
+/// Handles are encoded as their raw value
+macro_rules! handles {
+    ($($ty:ty),*) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    self.as_raw().encode(out);
+                }
+
+                fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                    Ok(Self::from_raw(u64::decode(input)?))
+                }
+            }
+        )*
+    };
+}
+
+handles!(
+    vk::Instance,
+    vk::PhysicalDevice,
+    vk::Device,
+    vk::Queue,
+    vk::DeviceMemory,
+    vk::Buffer,
+    vk::Image,
+    vk::ImageView,
+    vk::Sampler,
+    vk::ShaderModule,
+    vk::DescriptorSetLayout,
+    vk::PipelineLayout,
+    vk::Pipeline,
+    vk::DescriptorPool,
+    vk::DescriptorSet,
+    vk::CommandPool,
+    vk::CommandBuffer,
+    vk::Fence,
+    vk::Semaphore
+);
+
+/// Enums and flags are encoded as their raw value
+macro_rules! bits {
+    ($raw:ty: $($ty:ty),*) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    self.as_raw().encode(out);
+                }
+
+                fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                    Ok(Self::from_raw(<$raw>::decode(input)?))
+                }
+            }
+        )*
+    };
+}
+
+bits!(u32:
+    vk::InstanceCreateFlags,
+    vk::DeviceQueueCreateFlags,
+    vk::BufferCreateFlags,
+    vk::BufferUsageFlags,
+    vk::ImageCreateFlags,
+    vk::ImageUsageFlags,
+    vk::SampleCountFlags,
+    vk::ImageViewCreateFlags,
+    vk::ImageAspectFlags,
+    vk::DescriptorSetLayoutCreateFlags,
+    vk::ShaderStageFlags,
+    vk::PipelineCreateFlags,
+    vk::DescriptorPoolCreateFlags,
+    vk::CommandPoolCreateFlags,
+    vk::CommandPoolResetFlags,
+    vk::CommandBufferUsageFlags,
+    vk::CommandBufferResetFlags,
+    vk::PipelineStageFlags,
+    vk::AccessFlags,
+    vk::DependencyFlags,
+    vk::FenceCreateFlags
+);
+
+bits!(i32:
+    vk::SharingMode,
+    vk::ImageType,
+    vk::Format,
+    vk::ImageTiling,
+    vk::ImageLayout,
+    vk::ImageViewType,
+    vk::ComponentSwizzle,
+    vk::DescriptorType,
+    vk::CommandBufferLevel,
+    vk::PipelineBindPoint
+);
+
+/// Structures of the capture format, encoded field by field
+macro_rules! records {
+    ($(
+        $(#[$meta:meta])*
+        pub struct $name:ident {
+            $($(#[$field_meta:meta])* pub $field:ident: $ty:ty,)*
+        }
+    )*) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone, PartialEq)]
+            pub struct $name {
+                $($(#[$field_meta])* pub $field: $ty,)*
+            }
+
+            impl Encode for $name {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    $(self.$field.encode(out);)*
+                }
+
+                fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                    Ok(Self {
+                        $($field: Encode::decode(input)?,)*
+                    })
+                }
+            }
+        )*
+    };
+}
+
+records! {
+    /// The queues requested from a queue family
+    pub struct QueueRequest {
+        pub flags: vk::DeviceQueueCreateFlags,
+        pub family: u32,
+        pub priorities: Vec<f32>,
+    }
+
+    /// A `VkExtent3D`
+    pub struct Extent {
+        pub width: u32,
+        pub height: u32,
+        pub depth: u32,
+    }
+
+    /// A `VkComponentMapping`
+    pub struct Components {
+        pub r: vk::ComponentSwizzle,
+        pub g: vk::ComponentSwizzle,
+        pub b: vk::ComponentSwizzle,
+        pub a: vk::ComponentSwizzle,
+    }
+
+    /// A `VkImageSubresourceRange`
+    pub struct SubresourceRange {
+        pub aspect_mask: vk::ImageAspectFlags,
+        pub base_mip_level: u32,
+        pub level_count: u32,
+        pub base_array_layer: u32,
+        pub layer_count: u32,
+    }
+
+    /// A `VkDescriptorSetLayoutBinding`, without immutable samplers
+    pub struct LayoutBinding {
+        pub binding: u32,
+        pub descriptor_type: vk::DescriptorType,
+        pub descriptor_count: u32,
+        pub stage_flags: vk::ShaderStageFlags,
+    }
+
+    /// A `VkPushConstantRange`
+    pub struct PushConstantRange {
+        pub stage_flags: vk::ShaderStageFlags,
+        pub offset: u32,
+        pub size: u32,
+    }
+
+    /// A `VkSpecializationInfo`, with the entries as constant ID, offset and size
+    pub struct Specialization {
+        pub entries: Vec<(u32, u32, u64)>,
+        pub data: Vec<u8>,
+    }
+
+    /// A `VkDescriptorPoolSize`
+    pub struct PoolSize {
+        pub descriptor_type: vk::DescriptorType,
+        pub descriptor_count: u32,
+    }
+
+    /// A `VkWriteDescriptorSet` of buffers or images
+    pub struct DescriptorWrite {
+        pub set: vk::DescriptorSet,
+        pub binding: u32,
+        pub array_element: u32,
+        pub descriptor_type: vk::DescriptorType,
+        /// The buffers, offsets and ranges of buffer descriptors
+        pub buffers: Vec<(vk::Buffer, u64, u64)>,
+        /// The samplers, views and layouts of image and sampler descriptors
+        pub images: Vec<(vk::Sampler, vk::ImageView, vk::ImageLayout)>,
+    }
+
+    /// A `VkBufferCopy`
+    pub struct BufferCopy {
+        pub src_offset: u64,
+        pub dst_offset: u64,
+        pub size: u64,
+    }
+
+    /// A `VkBufferMemoryBarrier`
+    pub struct BufferBarrier {
+        pub src_access_mask: vk::AccessFlags,
+        pub dst_access_mask: vk::AccessFlags,
+        pub src_queue_family_index: u32,
+        pub dst_queue_family_index: u32,
+        pub buffer: vk::Buffer,
+        pub offset: u64,
+        pub size: u64,
+    }
+
+    /// A `VkImageMemoryBarrier`
+    pub struct ImageBarrier {
+        pub src_access_mask: vk::AccessFlags,
+        pub dst_access_mask: vk::AccessFlags,
+        pub old_layout: vk::ImageLayout,
+        pub new_layout: vk::ImageLayout,
+        pub src_queue_family_index: u32,
+        pub dst_queue_family_index: u32,
+        pub image: vk::Image,
+        pub subresource_range: SubresourceRange,
+    }
+
+    /// A `VkSubmitInfo`
+    pub struct Submit {
+        pub wait_semaphores: Vec<(vk::Semaphore, vk::PipelineStageFlags)>,
+        pub command_buffers: Vec<vk::CommandBuffer>,
+        pub signal_semaphores: Vec<vk::Semaphore>,
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok((A::decode(input)?, B::decode(input)?))
+    }
+}
+
+impl<A: Encode, B: Encode, C: Encode> Encode for (A, B, C) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+        self.2.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok((A::decode(input)?, B::decode(input)?, C::decode(input)?))
+    }
+}
+
+impl SubresourceRange {
+    fn new(range: &vk::ImageSubresourceRange) -> Self {
+        Self {
+            aspect_mask: range.aspect_mask,
+            base_mip_level: range.base_mip_level,
+            level_count: range.level_count,
+            base_array_layer: range.base_array_layer,
+            layer_count: range.layer_count,
+        }
+    }
+
+    fn to_vk(&self) -> vk::ImageSubresourceRange {
+        vk::ImageSubresourceRange {
+            aspect_mask: self.aspect_mask,
+            base_mip_level: self.base_mip_level,
+            level_count: self.level_count,
+            base_array_layer: self.base_array_layer,
+            layer_count: self.layer_count,
+        }
+    }
+}
+
+/// The commands, with the opcodes identifying them in the capture format. Opcodes are never
+/// reused, so new commands get new opcodes
+macro_rules! commands {
+    ($(
+        $(#[$meta:meta])*
+        $variant:ident = $opcode:literal {
+            $($(#[$field_meta:meta])* $field:ident: $ty:ty),* $(,)?
+        },
+    )*) => {
+        /// A captured call, with the captured handles
+        #[derive(Debug, Clone, PartialEq)]
+        #[non_exhaustive]
+        pub enum Command {
+            $(
+                $(#[$meta])*
+                $variant { $($(#[$field_meta])* $field: $ty),* },
+            )*
+        }
+
+        impl Command {
+            /// The name of the command
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$variant { .. } => stringify!($variant),)*
+                }
+            }
+
+            fn encode(&self, out: &mut Vec<u8>) {
+                match self {
+                    $(
+                        Self::$variant { $($field),* } => {
+                            out.push($opcode);
+                            $($field.encode(out);)*
+                        }
+                    )*
+                }
+            }
+
+            fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                match u8::decode(input)? {
+                    $($opcode => Ok(Self::$variant { $($field: Encode::decode(input)?),* }),)*
+                    opcode => Err(DecodeError::Invalid(format!("unknown opcode {opcode}"))),
+                }
+            }
+        }
+    };
+}
+
+commands! {
+    CreateInstance = 1 {
+        flags: vk::InstanceCreateFlags,
+        api_version: u32,
+        layers: Vec<String>,
+        extensions: Vec<String>,
+        instance: vk::Instance,
+    },
+    DestroyInstance = 2 { instance: vk::Instance },
+    /// The physical devices of an instance, which the replay maps to its devices in order
+    EnumeratePhysicalDevices = 3 {
+        instance: vk::Instance,
+        physical_devices: Vec<vk::PhysicalDevice>,
+    },
+    CreateDevice = 4 {
+        physical_device: vk::PhysicalDevice,
+        queues: Vec<QueueRequest>,
+        extensions: Vec<String>,
+        /// The `VkBool32`s of the enabled `VkPhysicalDeviceFeatures`
+        features: Option<Vec<u32>>,
+        device: vk::Device,
+    },
+    DestroyDevice = 5 { device: vk::Device },
+    GetDeviceQueue = 6 {
+        device: vk::Device,
+        family: u32,
+        index: u32,
+        queue: vk::Queue,
+    },
+    DeviceWaitIdle = 7 { device: vk::Device },
+    AllocateMemory = 10 {
+        device: vk::Device,
+        size: u64,
+        memory_type_index: u32,
+        memory: vk::DeviceMemory,
+    },
+    FreeMemory = 11 {
+        device: vk::Device,
+        memory: vk::DeviceMemory,
+    },
+    /// Data written to mapped memory by the application
+    WriteMemory = 12 {
+        device: vk::Device,
+        memory: vk::DeviceMemory,
+        offset: u64,
+        data: Vec<u8>,
+    },
+    CreateBuffer = 20 {
+        device: vk::Device,
+        flags: vk::BufferCreateFlags,
+        size: u64,
+        usage: vk::BufferUsageFlags,
+        sharing_mode: vk::SharingMode,
+        queue_family_indices: Vec<u32>,
+        buffer: vk::Buffer,
+    },
+    DestroyBuffer = 21 {
+        device: vk::Device,
+        buffer: vk::Buffer,
+    },
+    BindBufferMemory = 22 {
+        device: vk::Device,
+        buffer: vk::Buffer,
+        memory: vk::DeviceMemory,
+        offset: u64,
+    },
+    CreateImage = 23 {
+        device: vk::Device,
+        flags: vk::ImageCreateFlags,
+        image_type: vk::ImageType,
+        format: vk::Format,
+        extent: Extent,
+        mip_levels: u32,
+        array_layers: u32,
+        samples: vk::SampleCountFlags,
+        tiling: vk::ImageTiling,
+        usage: vk::ImageUsageFlags,
+        sharing_mode: vk::SharingMode,
+        queue_family_indices: Vec<u32>,
+        initial_layout: vk::ImageLayout,
+        image: vk::Image,
+    },
+    DestroyImage = 24 {
+        device: vk::Device,
+        image: vk::Image,
+    },
+    BindImageMemory = 25 {
+        device: vk::Device,
+        image: vk::Image,
+        memory: vk::DeviceMemory,
+        offset: u64,
+    },
+    CreateImageView = 26 {
+        device: vk::Device,
+        flags: vk::ImageViewCreateFlags,
+        image: vk::Image,
+        view_type: vk::ImageViewType,
+        format: vk::Format,
+        components: Components,
+        subresource_range: SubresourceRange,
+        view: vk::ImageView,
+    },
+    DestroyImageView = 27 {
+        device: vk::Device,
+        view: vk::ImageView,
+    },
+    CreateShaderModule = 30 {
+        device: vk::Device,
+        code: Vec<u32>,
+        shader_module: vk::ShaderModule,
+    },
+    DestroyShaderModule = 31 {
+        device: vk::Device,
+        shader_module: vk::ShaderModule,
+    },
+    CreateDescriptorSetLayout = 32 {
+        device: vk::Device,
+        flags: vk::DescriptorSetLayoutCreateFlags,
+        bindings: Vec<LayoutBinding>,
+        set_layout: vk::DescriptorSetLayout,
+    },
+    DestroyDescriptorSetLayout = 33 {
+        device: vk::Device,
+        set_layout: vk::DescriptorSetLayout,
+    },
+    CreatePipelineLayout = 34 {
+        device: vk::Device,
+        set_layouts: Vec<vk::DescriptorSetLayout>,
+        push_constant_ranges: Vec<PushConstantRange>,
+        pipeline_layout: vk::PipelineLayout,
+    },
+    DestroyPipelineLayout = 35 {
+        device: vk::Device,
+        pipeline_layout: vk::PipelineLayout,
+    },
+    /// One of the pipelines created by a `vkCreateComputePipelines` call
+    CreateComputePipeline = 36 {
+        device: vk::Device,
+        flags: vk::PipelineCreateFlags,
+        module: vk::ShaderModule,
+        entry_point: String,
+        specialization: Option<Specialization>,
+        layout: vk::PipelineLayout,
+        pipeline: vk::Pipeline,
+    },
+    DestroyPipeline = 37 {
+        device: vk::Device,
+        pipeline: vk::Pipeline,
+    },
+    CreateDescriptorPool = 40 {
+        device: vk::Device,
+        flags: vk::DescriptorPoolCreateFlags,
+        max_sets: u32,
+        pool_sizes: Vec<PoolSize>,
+        descriptor_pool: vk::DescriptorPool,
+    },
+    DestroyDescriptorPool = 41 {
+        device: vk::Device,
+        descriptor_pool: vk::DescriptorPool,
+    },
+    ResetDescriptorPool = 42 {
+        device: vk::Device,
+        descriptor_pool: vk::DescriptorPool,
+    },
+    AllocateDescriptorSets = 43 {
+        device: vk::Device,
+        descriptor_pool: vk::DescriptorPool,
+        set_layouts: Vec<vk::DescriptorSetLayout>,
+        descriptor_sets: Vec<vk::DescriptorSet>,
+    },
+    FreeDescriptorSets = 44 {
+        device: vk::Device,
+        descriptor_pool: vk::DescriptorPool,
+        descriptor_sets: Vec<vk::DescriptorSet>,
+    },
+    /// The writes of a `vkUpdateDescriptorSets` call, whose copies aren't captured
+    UpdateDescriptorSets = 45 {
+        device: vk::Device,
+        writes: Vec<DescriptorWrite>,
+    },
+    CreateCommandPool = 50 {
+        device: vk::Device,
+        flags: vk::CommandPoolCreateFlags,
+        queue_family_index: u32,
+        command_pool: vk::CommandPool,
+    },
+    DestroyCommandPool = 51 {
+        device: vk::Device,
+        command_pool: vk::CommandPool,
+    },
+    ResetCommandPool = 52 {
+        device: vk::Device,
+        command_pool: vk::CommandPool,
+        flags: vk::CommandPoolResetFlags,
+    },
+    AllocateCommandBuffers = 53 {
+        device: vk::Device,
+        command_pool: vk::CommandPool,
+        level: vk::CommandBufferLevel,
+        command_buffers: Vec<vk::CommandBuffer>,
+    },
+    FreeCommandBuffers = 54 {
+        device: vk::Device,
+        command_pool: vk::CommandPool,
+        command_buffers: Vec<vk::CommandBuffer>,
+    },
+    BeginCommandBuffer = 55 {
+        command_buffer: vk::CommandBuffer,
+        flags: vk::CommandBufferUsageFlags,
+    },
+    EndCommandBuffer = 56 { command_buffer: vk::CommandBuffer },
+    ResetCommandBuffer = 57 {
+        command_buffer: vk::CommandBuffer,
+        flags: vk::CommandBufferResetFlags,
+    },
+    CmdCopyBuffer = 60 {
+        command_buffer: vk::CommandBuffer,
+        src_buffer: vk::Buffer,
+        dst_buffer: vk::Buffer,
+        regions: Vec<BufferCopy>,
+    },
+    CmdFillBuffer = 61 {
+        command_buffer: vk::CommandBuffer,
+        dst_buffer: vk::Buffer,
+        dst_offset: u64,
+        size: u64,
+        data: u32,
+    },
+    CmdUpdateBuffer = 62 {
+        command_buffer: vk::CommandBuffer,
+        dst_buffer: vk::Buffer,
+        dst_offset: u64,
+        data: Vec<u8>,
+    },
+    CmdBindPipeline = 63 {
+        command_buffer: vk::CommandBuffer,
+        bind_point: vk::PipelineBindPoint,
+        pipeline: vk::Pipeline,
+    },
+    CmdBindDescriptorSets = 64 {
+        command_buffer: vk::CommandBuffer,
+        bind_point: vk::PipelineBindPoint,
+        layout: vk::PipelineLayout,
+        first_set: u32,
+        descriptor_sets: Vec<vk::DescriptorSet>,
+        dynamic_offsets: Vec<u32>,
+    },
+    CmdPushConstants = 65 {
+        command_buffer: vk::CommandBuffer,
+        layout: vk::PipelineLayout,
+        stage_flags: vk::ShaderStageFlags,
+        offset: u32,
+        values: Vec<u8>,
+    },
+    CmdDispatch = 66 {
+        command_buffer: vk::CommandBuffer,
+        group_count_x: u32,
+        group_count_y: u32,
+        group_count_z: u32,
+    },
+    CmdPipelineBarrier = 67 {
+        command_buffer: vk::CommandBuffer,
+        src_stage_mask: vk::PipelineStageFlags,
+        dst_stage_mask: vk::PipelineStageFlags,
+        dependency_flags: vk::DependencyFlags,
+        /// The source and destination access masks of the memory barriers
+        memory_barriers: Vec<(vk::AccessFlags, vk::AccessFlags)>,
+        buffer_barriers: Vec<BufferBarrier>,
+        image_barriers: Vec<ImageBarrier>,
+    },
+    CreateFence = 70 {
+        device: vk::Device,
+        flags: vk::FenceCreateFlags,
+        fence: vk::Fence,
+    },
+    DestroyFence = 71 {
+        device: vk::Device,
+        fence: vk::Fence,
+    },
+    ResetFences = 72 {
+        device: vk::Device,
+        fences: Vec<vk::Fence>,
+    },
+    /// A wait that succeeded, which the replay waits for without a timeout
+    WaitForFences = 73 {
+        device: vk::Device,
+        fences: Vec<vk::Fence>,
+        wait_all: bool,
+    },
+    CreateSemaphore = 74 {
+        device: vk::Device,
+        semaphore: vk::Semaphore,
+    },
+    DestroySemaphore = 75 {
+        device: vk::Device,
+        semaphore: vk::Semaphore,
+    },
+    QueueSubmit = 80 {
+        queue: vk::Queue,
+        submits: Vec<Submit>,
+        fence: vk::Fence,
+    },
+    QueueWaitIdle = 81 { queue: vk::Queue },
+    /// A call with side effects that isn't captured, which the replay skips
+    Unsupported = 255 { function: String },
+}
+
+/// A captured sequence of calls
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Capture {
+    pub commands: Vec<Command>,
+}
+
+impl Capture {
+    /// Writes the capture format
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        let mut out = header();
+        for command in &self.commands {
+            command.encode(&mut out);
+        }
+        writer.write_all(&out)?;
+        writer.flush()
+    }
+
+    /// Reads the capture format. A capture that ends in the middle of a command, like one
+    /// whose application crashed, is read up to the last complete command
+    pub fn read(mut reader: impl Read) -> Result<Self, CaptureError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        let mut input = data.as_slice();
+        let invalid = |_err| CaptureError::Format("not a capture".to_owned());
+        let magic = take(&mut input, MAGIC.len()).map_err(invalid)?;
+        let version = u32::decode(&mut input).map_err(invalid)?;
+        if magic != MAGIC {
+            return Err(CaptureError::Format("not a capture".to_owned()));
+        }
+        if version != FORMAT_VERSION {
+            return Err(CaptureError::Format(format!(
+                "unsupported version {version}, expected {FORMAT_VERSION}"
+            )));
+        }
+
+        let mut commands = Vec::new();
+        while !input.is_empty() {
+            match Command::decode(&mut input) {
+                Ok(command) => commands.push(command),
+                Err(DecodeError::Truncated) => break,
+                Err(DecodeError::Invalid(message)) => {
+                    return Err(CaptureError::Format(format!(
+                        "command {}: {message}",
+                        commands.len()
+                    )))
+                }
+            }
+        }
+        Ok(Self { commands })
+    }
+
+    /// Writes the capture to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.write(BufWriter::new(File::create(path)?))
+    }
+
+    /// Reads a capture file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CaptureError> {
+        Self::read(File::open(path)?)
+    }
+
+    /// Replays the commands in order, stopping at the first one that fails
+    ///
+    /// # Safety
+    ///
+    /// The commands must use the Vulkan API of `entry` as specified, like the calls they were
+    /// captured from.
+    pub unsafe fn replay(&self, entry: &ash::Entry) -> Result<Replayer, CaptureError> {
+        let mut replayer = Replayer::new(entry);
+        for command in &self.commands {
+            replayer.run(command)?;
+        }
+        Ok(replayer)
+    }
+}
+
+fn header() -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    FORMAT_VERSION.encode(&mut out);
+    out
+}
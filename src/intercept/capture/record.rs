@@ -0,0 +1,1044 @@
+//! Capturing calls with the [`Recorder`] interceptor.
+
+use super::{
+    header, BufferBarrier, BufferCopy, Capture, CaptureError, Command, Components, DescriptorWrite,
+    Extent, ImageBarrier, LayoutBinding, PoolSize, PushConstantRange, QueueRequest, Specialization,
+    Submit, SubresourceRange, CAPTURE_ENV_VAR, WRITE_MERGE_DISTANCE,
+};
+use crate::intercept::{
+    Call, Flow, Function, Interceptor, InterceptorBuilder, InterceptorId, Value,
+};
+use ash::vk;
+use std::{
+    collections::HashMap,
+    ffi::{c_char, CStr},
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    mem::size_of,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Where a [`Recorder`] puts the commands
+enum Output {
+    Memory(Vec<Command>),
+    File {
+        path: PathBuf,
+        writer: BufWriter<File>,
+        /// The first error writing the file, after which nothing is written
+        error: Option<io::Error>,
+    },
+}
+
+/// A mapped memory range
+struct Mapping {
+    device: vk::Device,
+    /// The offset of the mapping in the memory
+    offset: u64,
+    data: *const u8,
+    /// The contents of the mapping when last captured
+    shadow: Vec<u8>,
+}
+
+// Safety: the mapping is only read while the application has it mapped
+unsafe impl Send for Mapping {}
+
+#[derive(Default)]
+struct RecorderState {
+    /// The sizes of the memory allocated while recording
+    memory_sizes: HashMap<vk::DeviceMemory, u64>,
+    mappings: HashMap<vk::DeviceMemory, Mapping>,
+}
+
+struct RecorderInterceptor {
+    output: Mutex<Output>,
+    state: Mutex<RecorderState>,
+}
+
+/// An [`Interceptor`] capturing the calls made through intercepted instances and devices
+///
+/// Only the calls made after the recorder started are captured, so it should start before the
+/// first instance is created.
+pub struct Recorder {
+    interceptor: Arc<RecorderInterceptor>,
+    id: InterceptorId,
+}
+
+impl Recorder {
+    /// Starts capturing to memory
+    pub fn start() -> Self {
+        Self::register(Output::Memory(Vec::new()))
+    }
+
+    /// Starts capturing to a file, which is flushed after every command so it survives a crash
+    pub fn start_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let mut writer = BufWriter::new(File::create(&path)?);
+        writer.write_all(&header())?;
+        writer.flush()?;
+        Ok(Self::register(Output::File {
+            path,
+            writer,
+            error: None,
+        }))
+    }
+
+    /// Starts capturing to the file named by [`CAPTURE_ENV_VAR`], if it is set
+    pub fn from_env() -> Option<io::Result<Self>> {
+        let path = std::env::var_os(CAPTURE_ENV_VAR).filter(|path| !path.is_empty())?;
+        Some(Self::start_file(path))
+    }
+
+    fn register(output: Output) -> Self {
+        let interceptor = Arc::new(RecorderInterceptor {
+            output: Mutex::new(output),
+            state: Mutex::default(),
+        });
+        let id = InterceptorBuilder::new(interceptor.clone()).register();
+        Self { interceptor, id }
+    }
+
+    /// Stops capturing and returns the capture, reading it back from its file if there is one
+    pub fn finish(self) -> Result<Capture, CaptureError> {
+        crate::intercept::unregister(self.id);
+        let output = std::mem::replace(
+            &mut *self.interceptor.output.lock().unwrap(),
+            Output::Memory(Vec::new()),
+        );
+        match output {
+            Output::Memory(commands) => Ok(Capture { commands }),
+            Output::File {
+                path,
+                mut writer,
+                error,
+            } => {
+                if let Some(error) = error {
+                    return Err(error.into());
+                }
+                writer.flush()?;
+                Capture::load(path)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let output = match &*self.interceptor.output.lock().unwrap() {
+            Output::Memory(commands) => format!("{} commands", commands.len()),
+            Output::File { path, .. } => path.display().to_string(),
+        };
+        f.debug_struct("Recorder")
+            .field("output", &output)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RecorderInterceptor {
+    fn push(&self, commands: Vec<Command>) {
+        if commands.is_empty() {
+            return;
+        }
+
+        match &mut *self.output.lock().unwrap() {
+            Output::Memory(captured) => captured.extend(commands),
+            Output::File { writer, error, .. } => {
+                if error.is_some() {
+                    return;
+                }
+                let mut out = Vec::new();
+                for command in &commands {
+                    command.encode(&mut out);
+                }
+                if let Err(err) = writer.write_all(&out).and_then(|()| writer.flush()) {
+                    *error = Some(err);
+                }
+            }
+        }
+    }
+
+    /// Captures the changes to the mapped memory of `device`, or only to `memory`
+    fn write_mapped_memory(&self, device: vk::Device, memory: Option<vk::DeviceMemory>) {
+        let mut commands = Vec::new();
+        let mut state = self.state.lock().unwrap();
+        for (&handle, mapping) in &mut state.mappings {
+            if mapping.device != device || memory.is_some_and(|memory| memory != handle) {
+                continue;
+            }
+
+            let current = unsafe { std::slice::from_raw_parts(mapping.data, mapping.shadow.len()) };
+            for (start, end) in changes(&mapping.shadow, current) {
+                commands.push(Command::WriteMemory {
+                    device,
+                    memory: handle,
+                    offset: mapping.offset + start as u64,
+                    data: current[start..end].to_vec(),
+                });
+            }
+            mapping.shadow.copy_from_slice(current);
+        }
+        drop(state);
+        self.push(commands);
+    }
+}
+
+/// The ranges of bytes that changed, merging nearby changes
+fn changes(old: &[u8], new: &[u8]) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (index, _) in old.iter().zip(new).enumerate().filter(|(_, (a, b))| a != b) {
+        match ranges.last_mut() {
+            Some((_, end)) if index - *end < WRITE_MERGE_DISTANCE => *end = index + 1,
+            _ => ranges.push((index, index + 1)),
+        }
+    }
+    ranges
+}
+
+impl Interceptor for RecorderInterceptor {
+    fn before(&self, call: &Call<'_>) -> Flow {
+        match call.function {
+            Function::QueueSubmit | Function::FlushMappedMemoryRanges => {
+                self.write_mapped_memory(call.device, None);
+            }
+            Function::UnmapMemory => {
+                let memory = handle(call, "memory");
+                self.write_mapped_memory(call.device, Some(memory));
+                self.state.lock().unwrap().mappings.remove(&memory);
+            }
+            Function::FreeMemory => {
+                let memory = handle(call, "memory");
+                let mut state = self.state.lock().unwrap();
+                state.mappings.remove(&memory);
+                state.memory_sizes.remove(&memory);
+            }
+            _ => {}
+        }
+        Flow::Continue
+    }
+
+    fn after(&self, call: &Call<'_>, result: Option<vk::Result>) {
+        if result.is_some_and(|result| result != vk::Result::SUCCESS) {
+            return;
+        }
+
+        let commands = unsafe { self.record(call) };
+        self.push(commands);
+    }
+}
+
+fn handle<H: vk::Handle>(call: &Call<'_>, name: &str) -> H {
+    H::from_raw(call.arg(name).and_then(Value::handle).unwrap_or_default())
+}
+
+/// The handle created through an output parameter
+unsafe fn created<H: vk::Handle + Copy>(call: &Call<'_>, name: &str) -> H {
+    handles(call, name, 1)
+        .first()
+        .copied()
+        .unwrap_or_else(|| H::from_raw(0))
+}
+
+/// The handles written to or read from a pointer parameter
+unsafe fn handles<H: vk::Handle>(call: &Call<'_>, name: &str, count: u32) -> Vec<H> {
+    call.arg(name)
+        .and_then(|value| value.handles(count as usize))
+        .map_or_else(Vec::new, |handles| {
+            handles.iter().map(|&raw| H::from_raw(raw)).collect()
+        })
+}
+
+/// The structure a pointer parameter points to
+unsafe fn info<'a, T>(call: &Call<'_>, name: &str) -> Option<&'a T> {
+    call.arg(name)?.ptr()?.cast::<T>().as_ref()
+}
+
+/// A scalar, enum or flags parameter
+fn scalar(call: &Call<'_>, name: &str) -> u64 {
+    match call.arg(name) {
+        Some(Value::U32(value)) => value.into(),
+        Some(Value::I32(value)) => value as u64,
+        Some(Value::U64(value)) => value,
+        Some(Value::Usize(value)) => value as u64,
+        Some(Value::Bits(bits)) => bits.raw(),
+        _ => 0,
+    }
+}
+
+unsafe fn slice<'a, T>(ptr: *const T, count: u32) -> &'a [T] {
+    if ptr.is_null() || count == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ptr, count as usize)
+    }
+}
+
+unsafe fn string(name: *const c_char) -> String {
+    if name.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(name).to_string_lossy().into_owned()
+    }
+}
+
+unsafe fn strings(names: *const *const c_char, count: u32) -> Vec<String> {
+    slice(names, count)
+        .iter()
+        .map(|&name| string(name))
+        .collect()
+}
+
+/// The enabled features of a `VkDeviceCreateInfo`, from `pEnabledFeatures` or a chained
+/// `VkPhysicalDeviceFeatures2`
+unsafe fn enabled_features(create_info: &vk::DeviceCreateInfo<'_>) -> Option<Vec<u32>> {
+    let mut features = create_info.p_enabled_features;
+    let mut next = create_info.p_next.cast::<vk::BaseInStructure<'_>>();
+    while features.is_null() && !next.is_null() {
+        if (*next).s_type == vk::StructureType::PHYSICAL_DEVICE_FEATURES_2 {
+            features = &(*next.cast::<vk::PhysicalDeviceFeatures2<'_>>()).features;
+        }
+        next = (*next).p_next;
+    }
+
+    let features = features.as_ref()?;
+    let len = size_of::<vk::PhysicalDeviceFeatures>() / size_of::<vk::Bool32>();
+    Some(
+        std::slice::from_raw_parts((features as *const vk::PhysicalDeviceFeatures).cast(), len)
+            .to_vec(),
+    )
+}
+
+impl RecorderInterceptor {
+    /// The commands a successful call is captured as
+    unsafe fn record(&self, call: &Call<'_>) -> Vec<Command> {
+        let device = call.device;
+        let command_buffer = || handle::<vk::CommandBuffer>(call, "command_buffer");
+        let command = match call.function {
+            Function::CreateInstance => {
+                let Some(create_info) = info::<vk::InstanceCreateInfo<'_>>(call, "p_create_info")
+                else {
+                    return Vec::new();
+                };
+                Command::CreateInstance {
+                    flags: create_info.flags,
+                    api_version: create_info
+                        .p_application_info
+                        .as_ref()
+                        .map_or(0, |application_info| application_info.api_version),
+                    layers: strings(
+                        create_info.pp_enabled_layer_names,
+                        create_info.enabled_layer_count,
+                    ),
+                    extensions: strings(
+                        create_info.pp_enabled_extension_names,
+                        create_info.enabled_extension_count,
+                    ),
+                    instance: created(call, "p_instance"),
+                }
+            }
+            Function::DestroyInstance => Command::DestroyInstance {
+                instance: handle(call, "instance"),
+            },
+            Function::EnumeratePhysicalDevices => {
+                let Some(&count) = info::<u32>(call, "p_physical_device_count") else {
+                    return Vec::new();
+                };
+                let physical_devices = handles(call, "p_physical_devices", count);
+                if physical_devices.is_empty() {
+                    return Vec::new();
+                }
+                Command::EnumeratePhysicalDevices {
+                    instance: handle(call, "instance"),
+                    physical_devices,
+                }
+            }
+            Function::CreateDevice => {
+                let Some(create_info) = info::<vk::DeviceCreateInfo<'_>>(call, "p_create_info")
+                else {
+                    return Vec::new();
+                };
+                let queues = slice(
+                    create_info.p_queue_create_infos,
+                    create_info.queue_create_info_count,
+                )
+                .iter()
+                .map(|queue_create_info| QueueRequest {
+                    flags: queue_create_info.flags,
+                    family: queue_create_info.queue_family_index,
+                    priorities: slice(
+                        queue_create_info.p_queue_priorities,
+                        queue_create_info.queue_count,
+                    )
+                    .to_vec(),
+                })
+                .collect();
+                Command::CreateDevice {
+                    physical_device: handle(call, "physical_device"),
+                    queues,
+                    extensions: strings(
+                        create_info.pp_enabled_extension_names,
+                        create_info.enabled_extension_count,
+                    ),
+                    features: enabled_features(create_info),
+                    device: created(call, "p_device"),
+                }
+            }
+            Function::DestroyDevice => Command::DestroyDevice { device },
+            Function::GetDeviceQueue => Command::GetDeviceQueue {
+                device,
+                family: scalar(call, "queue_family_index") as u32,
+                index: scalar(call, "queue_index") as u32,
+                queue: created(call, "p_queue"),
+            },
+            Function::DeviceWaitIdle => Command::DeviceWaitIdle { device },
+            Function::QueueWaitIdle => Command::QueueWaitIdle {
+                queue: handle(call, "queue"),
+            },
+            Function::AllocateMemory => {
+                let Some(allocate_info) =
+                    info::<vk::MemoryAllocateInfo<'_>>(call, "p_allocate_info")
+                else {
+                    return Vec::new();
+                };
+                let memory = created(call, "p_memory");
+                self.state
+                    .lock()
+                    .unwrap()
+                    .memory_sizes
+                    .insert(memory, allocate_info.allocation_size);
+                Command::AllocateMemory {
+                    device,
+                    size: allocate_info.allocation_size,
+                    memory_type_index: allocate_info.memory_type_index,
+                    memory,
+                }
+            }
+            Function::FreeMemory => Command::FreeMemory {
+                device,
+                memory: handle(call, "memory"),
+            },
+            Function::MapMemory => {
+                let memory = handle(call, "memory");
+                let offset = scalar(call, "offset");
+                let Some(&data) = info::<*const u8>(call, "pp_data") else {
+                    return Vec::new();
+                };
+                let mut state = self.state.lock().unwrap();
+                let Some(&memory_size) = state.memory_sizes.get(&memory) else {
+                    return Vec::new();
+                };
+                let size = match scalar(call, "size") {
+                    vk::WHOLE_SIZE => memory_size.saturating_sub(offset),
+                    size => size,
+                };
+                let shadow = std::slice::from_raw_parts(data, size as usize).to_vec();
+                state.mappings.insert(
+                    memory,
+                    Mapping {
+                        device,
+                        offset,
+                        data,
+                        shadow,
+                    },
+                );
+                return Vec::new();
+            }
+            Function::CreateBuffer => {
+                let Some(create_info) = info::<vk::BufferCreateInfo<'_>>(call, "p_create_info")
+                else {
+                    return Vec::new();
+                };
+                Command::CreateBuffer {
+                    device,
+                    flags: create_info.flags,
+                    size: create_info.size,
+                    usage: create_info.usage,
+                    sharing_mode: create_info.sharing_mode,
+                    queue_family_indices: slice(
+                        create_info.p_queue_family_indices,
+                        create_info.queue_family_index_count,
+                    )
+                    .to_vec(),
+                    buffer: created(call, "p_buffer"),
+                }
+            }
+            Function::DestroyBuffer => Command::DestroyBuffer {
+                device,
+                buffer: handle(call, "buffer"),
+            },
+            Function::BindBufferMemory => Command::BindBufferMemory {
+                device,
+                buffer: handle(call, "buffer"),
+                memory: handle(call, "memory"),
+                offset: scalar(call, "memory_offset"),
+            },
+            Function::CreateImage => {
+                let Some(create_info) = info::<vk::ImageCreateInfo<'_>>(call, "p_create_info")
+                else {
+                    return Vec::new();
+                };
+                let extent = create_info.extent;
+                Command::CreateImage {
+                    device,
+                    flags: create_info.flags,
+                    image_type: create_info.image_type,
+                    format: create_info.format,
+                    extent: Extent {
+                        width: extent.width,
+                        height: extent.height,
+                        depth: extent.depth,
+                    },
+                    mip_levels: create_info.mip_levels,
+                    array_layers: create_info.array_layers,
+                    samples: create_info.samples,
+                    tiling: create_info.tiling,
+                    usage: create_info.usage,
+                    sharing_mode: create_info.sharing_mode,
+                    queue_family_indices: slice(
+                        create_info.p_queue_family_indices,
+                        create_info.queue_family_index_count,
+                    )
+                    .to_vec(),
+                    initial_layout: create_info.initial_layout,
+                    image: created(call, "p_image"),
+                }
+            }
+            Function::DestroyImage => Command::DestroyImage {
+                device,
+                image: handle(call, "image"),
+            },
+            Function::BindImageMemory => Command::BindImageMemory {
+                device,
+                image: handle(call, "image"),
+                memory: handle(call, "memory"),
+                offset: scalar(call, "memory_offset"),
+            },
+            Function::CreateImageView => {
+                let Some(create_info) = info::<vk::ImageViewCreateInfo<'_>>(call, "p_create_info")
+                else {
+                    return Vec::new();
+                };
+                let components = create_info.components;
+                Command::CreateImageView {
+                    device,
+                    flags: create_info.flags,
+                    image: create_info.image,
+                    view_type: create_info.view_type,
+                    format: create_info.format,
+                    components: Components {
+                        r: components.r,
+                        g: components.g,
+                        b: components.b,
+                        a: components.a,
+                    },
+                    subresource_range: SubresourceRange::new(&create_info.subresource_range),
+                    view: created(call, "p_view"),
+                }
+            }
+            Function::DestroyImageView => Command::DestroyImageView {
+                device,
+                view: handle(call, "image_view"),
+            },
+            Function::CreateShaderModule => {
+                let Some(create_info) =
+                    info::<vk::ShaderModuleCreateInfo<'_>>(call, "p_create_info")
+                else {
+                    return Vec::new();
+                };
+                Command::CreateShaderModule {
+                    device,
+                    code: slice(create_info.p_code, (create_info.code_size / 4) as u32).to_vec(),
+                    shader_module: created(call, "p_shader_module"),
+                }
+            }
+            Function::DestroyShaderModule => Command::DestroyShaderModule {
+                device,
+                shader_module: handle(call, "shader_module"),
+            },
+            Function::CreateDescriptorSetLayout => {
+                let Some(create_info) =
+                    info::<vk::DescriptorSetLayoutCreateInfo<'_>>(call, "p_create_info")
+                else {
+                    return Vec::new();
+                };
+                Command::CreateDescriptorSetLayout {
+                    device,
+                    flags: create_info.flags,
+                    bindings: slice(create_info.p_bindings, create_info.binding_count)
+                        .iter()
+                        .map(|binding| LayoutBinding {
+                            binding: binding.binding,
+                            descriptor_type: binding.descriptor_type,
+                            descriptor_count: binding.descriptor_count,
+                            stage_flags: binding.stage_flags,
+                        })
+                        .collect(),
+                    set_layout: created(call, "p_set_layout"),
+                }
+            }
+            Function::DestroyDescriptorSetLayout => Command::DestroyDescriptorSetLayout {
+                device,
+                set_layout: handle(call, "descriptor_set_layout"),
+            },
+            Function::CreatePipelineLayout => {
+                let Some(create_info) =
+                    info::<vk::PipelineLayoutCreateInfo<'_>>(call, "p_create_info")
+                else {
+                    return Vec::new();
+                };
+                Command::CreatePipelineLayout {
+                    device,
+                    set_layouts: slice(create_info.p_set_layouts, create_info.set_layout_count)
+                        .to_vec(),
+                    push_constant_ranges: slice(
+                        create_info.p_push_constant_ranges,
+                        create_info.push_constant_range_count,
+                    )
+                    .iter()
+                    .map(|range| PushConstantRange {
+                        stage_flags: range.stage_flags,
+                        offset: range.offset,
+                        size: range.size,
+                    })
+                    .collect(),
+                    pipeline_layout: created(call, "p_pipeline_layout"),
+                }
+            }
+            Function::DestroyPipelineLayout => Command::DestroyPipelineLayout {
+                device,
+                pipeline_layout: handle(call, "pipeline_layout"),
+            },
+            Function::CreateComputePipelines => {
+                let count = scalar(call, "create_info_count") as u32;
+                let Some(create_infos) =
+                    info::<vk::ComputePipelineCreateInfo<'_>>(call, "p_create_infos")
+                else {
+                    return Vec::new();
+                };
+                let create_infos = slice(create_infos, count);
+                let pipelines = handles::<vk::Pipeline>(call, "p_pipelines", count);
+                return create_infos
+                    .iter()
+                    .zip(pipelines)
+                    .map(|(create_info, pipeline)| Command::CreateComputePipeline {
+                        device,
+                        flags: create_info.flags,
+                        module: create_info.stage.module,
+                        entry_point: string(create_info.stage.p_name),
+                        specialization: create_info.stage.p_specialization_info.as_ref().map(
+                            |specialization| Specialization {
+                                entries: slice(
+                                    specialization.p_map_entries,
+                                    specialization.map_entry_count,
+                                )
+                                .iter()
+                                .map(|entry| (entry.constant_id, entry.offset, entry.size as u64))
+                                .collect(),
+                                data: slice(
+                                    specialization.p_data.cast::<u8>(),
+                                    specialization.data_size as u32,
+                                )
+                                .to_vec(),
+                            },
+                        ),
+                        layout: create_info.layout,
+                        pipeline,
+                    })
+                    .collect();
+            }
+            Function::DestroyPipeline => Command::DestroyPipeline {
+                device,
+                pipeline: handle(call, "pipeline"),
+            },
+            Function::CreateDescriptorPool => {
+                let Some(create_info) =
+                    info::<vk::DescriptorPoolCreateInfo<'_>>(call, "p_create_info")
+                else {
+                    return Vec::new();
+                };
+                Command::CreateDescriptorPool {
+                    device,
+                    flags: create_info.flags,
+                    max_sets: create_info.max_sets,
+                    pool_sizes: slice(create_info.p_pool_sizes, create_info.pool_size_count)
+                        .iter()
+                        .map(|size| PoolSize {
+                            descriptor_type: size.ty,
+                            descriptor_count: size.descriptor_count,
+                        })
+                        .collect(),
+                    descriptor_pool: created(call, "p_descriptor_pool"),
+                }
+            }
+            Function::DestroyDescriptorPool => Command::DestroyDescriptorPool {
+                device,
+                descriptor_pool: handle(call, "descriptor_pool"),
+            },
+            Function::ResetDescriptorPool => Command::ResetDescriptorPool {
+                device,
+                descriptor_pool: handle(call, "descriptor_pool"),
+            },
+            Function::AllocateDescriptorSets => {
+                let Some(allocate_info) =
+                    info::<vk::DescriptorSetAllocateInfo<'_>>(call, "p_allocate_info")
+                else {
+                    return Vec::new();
+                };
+                let count = allocate_info.descriptor_set_count;
+                Command::AllocateDescriptorSets {
+                    device,
+                    descriptor_pool: allocate_info.descriptor_pool,
+                    set_layouts: slice(allocate_info.p_set_layouts, count).to_vec(),
+                    descriptor_sets: handles(call, "p_descriptor_sets", count),
+                }
+            }
+            Function::FreeDescriptorSets => Command::FreeDescriptorSets {
+                device,
+                descriptor_pool: handle(call, "descriptor_pool"),
+                descriptor_sets: handles(
+                    call,
+                    "p_descriptor_sets",
+                    scalar(call, "descriptor_set_count") as u32,
+                ),
+            },
+            Function::UpdateDescriptorSets => {
+                let writes = info::<vk::WriteDescriptorSet<'_>>(call, "p_descriptor_writes")
+                    .map_or(&[][..], |writes| {
+                        slice(writes, scalar(call, "descriptor_write_count") as u32)
+                    });
+                let mut commands = vec![Command::UpdateDescriptorSets {
+                    device,
+                    writes: writes.iter().map(|write| descriptor_write(write)).collect(),
+                }];
+                // Texel buffer views and descriptor copies aren't captured
+                if scalar(call, "descriptor_copy_count") != 0
+                    || writes
+                        .iter()
+                        .any(|write| descriptor_kind(write.descriptor_type) == (false, false))
+                {
+                    commands.push(Command::Unsupported {
+                        function: call.function.name().to_owned(),
+                    });
+                }
+                return commands;
+            }
+            Function::CreateCommandPool => {
+                let Some(create_info) =
+                    info::<vk::CommandPoolCreateInfo<'_>>(call, "p_create_info")
+                else {
+                    return Vec::new();
+                };
+                Command::CreateCommandPool {
+                    device,
+                    flags: create_info.flags,
+                    queue_family_index: create_info.queue_family_index,
+                    command_pool: created(call, "p_command_pool"),
+                }
+            }
+            Function::DestroyCommandPool => Command::DestroyCommandPool {
+                device,
+                command_pool: handle(call, "command_pool"),
+            },
+            Function::ResetCommandPool => Command::ResetCommandPool {
+                device,
+                command_pool: handle(call, "command_pool"),
+                flags: vk::CommandPoolResetFlags::from_raw(scalar(call, "flags") as u32),
+            },
+            Function::AllocateCommandBuffers => {
+                let Some(allocate_info) =
+                    info::<vk::CommandBufferAllocateInfo<'_>>(call, "p_allocate_info")
+                else {
+                    return Vec::new();
+                };
+                Command::AllocateCommandBuffers {
+                    device,
+                    command_pool: allocate_info.command_pool,
+                    level: allocate_info.level,
+                    command_buffers: handles(
+                        call,
+                        "p_command_buffers",
+                        allocate_info.command_buffer_count,
+                    ),
+                }
+            }
+            Function::FreeCommandBuffers => Command::FreeCommandBuffers {
+                device,
+                command_pool: handle(call, "command_pool"),
+                command_buffers: handles(
+                    call,
+                    "p_command_buffers",
+                    scalar(call, "command_buffer_count") as u32,
+                ),
+            },
+            Function::BeginCommandBuffer => Command::BeginCommandBuffer {
+                command_buffer: command_buffer(),
+                flags: info::<vk::CommandBufferBeginInfo<'_>>(call, "p_begin_info")
+                    .map_or_else(vk::CommandBufferUsageFlags::empty, |begin_info| {
+                        begin_info.flags
+                    }),
+            },
+            Function::EndCommandBuffer => Command::EndCommandBuffer {
+                command_buffer: command_buffer(),
+            },
+            Function::ResetCommandBuffer => Command::ResetCommandBuffer {
+                command_buffer: command_buffer(),
+                flags: vk::CommandBufferResetFlags::from_raw(scalar(call, "flags") as u32),
+            },
+            Function::CmdCopyBuffer => Command::CmdCopyBuffer {
+                command_buffer: command_buffer(),
+                src_buffer: handle(call, "src_buffer"),
+                dst_buffer: handle(call, "dst_buffer"),
+                regions: info::<vk::BufferCopy>(call, "p_regions")
+                    .map_or(&[][..], |regions| {
+                        slice(regions, scalar(call, "region_count") as u32)
+                    })
+                    .iter()
+                    .map(|region| BufferCopy {
+                        src_offset: region.src_offset,
+                        dst_offset: region.dst_offset,
+                        size: region.size,
+                    })
+                    .collect(),
+            },
+            Function::CmdFillBuffer => Command::CmdFillBuffer {
+                command_buffer: command_buffer(),
+                dst_buffer: handle(call, "dst_buffer"),
+                dst_offset: scalar(call, "dst_offset"),
+                size: scalar(call, "size"),
+                data: scalar(call, "data") as u32,
+            },
+            Function::CmdUpdateBuffer => Command::CmdUpdateBuffer {
+                command_buffer: command_buffer(),
+                dst_buffer: handle(call, "dst_buffer"),
+                dst_offset: scalar(call, "dst_offset"),
+                data: info::<u8>(call, "p_data")
+                    .map_or(&[][..], |data| {
+                        slice(data, scalar(call, "data_size") as u32)
+                    })
+                    .to_vec(),
+            },
+            Function::CmdBindPipeline => Command::CmdBindPipeline {
+                command_buffer: command_buffer(),
+                bind_point: vk::PipelineBindPoint::from_raw(
+                    scalar(call, "pipeline_bind_point") as i32
+                ),
+                pipeline: handle(call, "pipeline"),
+            },
+            Function::CmdBindDescriptorSets => Command::CmdBindDescriptorSets {
+                command_buffer: command_buffer(),
+                bind_point: vk::PipelineBindPoint::from_raw(
+                    scalar(call, "pipeline_bind_point") as i32
+                ),
+                layout: handle(call, "layout"),
+                first_set: scalar(call, "first_set") as u32,
+                descriptor_sets: handles(
+                    call,
+                    "p_descriptor_sets",
+                    scalar(call, "descriptor_set_count") as u32,
+                ),
+                dynamic_offsets: info::<u32>(call, "p_dynamic_offsets")
+                    .map_or(&[][..], |offsets| {
+                        slice(offsets, scalar(call, "dynamic_offset_count") as u32)
+                    })
+                    .to_vec(),
+            },
+            Function::CmdPushConstants => Command::CmdPushConstants {
+                command_buffer: command_buffer(),
+                layout: handle(call, "layout"),
+                stage_flags: vk::ShaderStageFlags::from_raw(scalar(call, "stage_flags") as u32),
+                offset: scalar(call, "offset") as u32,
+                values: info::<u8>(call, "p_values")
+                    .map_or(&[][..], |values| slice(values, scalar(call, "size") as u32))
+                    .to_vec(),
+            },
+            Function::CmdDispatch => Command::CmdDispatch {
+                command_buffer: command_buffer(),
+                group_count_x: scalar(call, "group_count_x") as u32,
+                group_count_y: scalar(call, "group_count_y") as u32,
+                group_count_z: scalar(call, "group_count_z") as u32,
+            },
+            Function::CmdPipelineBarrier => pipeline_barrier(call),
+            Function::CreateFence => {
+                let Some(create_info) = info::<vk::FenceCreateInfo<'_>>(call, "p_create_info")
+                else {
+                    return Vec::new();
+                };
+                Command::CreateFence {
+                    device,
+                    flags: create_info.flags,
+                    fence: created(call, "p_fence"),
+                }
+            }
+            Function::DestroyFence => Command::DestroyFence {
+                device,
+                fence: handle(call, "fence"),
+            },
+            Function::ResetFences => Command::ResetFences {
+                device,
+                fences: handles(call, "p_fences", scalar(call, "fence_count") as u32),
+            },
+            Function::WaitForFences => Command::WaitForFences {
+                device,
+                fences: handles(call, "p_fences", scalar(call, "fence_count") as u32),
+                wait_all: scalar(call, "wait_all") != 0,
+            },
+            Function::CreateSemaphore => Command::CreateSemaphore {
+                device,
+                semaphore: created(call, "p_semaphore"),
+            },
+            Function::DestroySemaphore => Command::DestroySemaphore {
+                device,
+                semaphore: handle(call, "semaphore"),
+            },
+            Function::QueueSubmit => Command::QueueSubmit {
+                queue: handle(call, "queue"),
+                submits: info::<vk::SubmitInfo<'_>>(call, "p_submits")
+                    .map_or(&[][..], |submits| {
+                        slice(submits, scalar(call, "submit_count") as u32)
+                    })
+                    .iter()
+                    .map(|submit| Submit {
+                        wait_semaphores: slice(
+                            submit.p_wait_semaphores,
+                            submit.wait_semaphore_count,
+                        )
+                        .iter()
+                        .copied()
+                        .zip(
+                            slice(submit.p_wait_dst_stage_mask, submit.wait_semaphore_count)
+                                .iter()
+                                .copied(),
+                        )
+                        .collect(),
+                        command_buffers: slice(
+                            submit.p_command_buffers,
+                            submit.command_buffer_count,
+                        )
+                        .to_vec(),
+                        signal_semaphores: slice(
+                            submit.p_signal_semaphores,
+                            submit.signal_semaphore_count,
+                        )
+                        .to_vec(),
+                    })
+                    .collect(),
+                fence: handle(call, "fence"),
+            },
+            // Mapped memory is captured when it's used, and the replay maps memory as needed
+            Function::UnmapMemory
+            | Function::FlushMappedMemoryRanges
+            | Function::InvalidateMappedMemoryRanges => return Vec::new(),
+            function => {
+                let name = function.name();
+                if name.starts_with("vkGet") || name.starts_with("vkEnumerate") {
+                    return Vec::new();
+                }
+                Command::Unsupported {
+                    function: name.to_owned(),
+                }
+            }
+        };
+        vec![command]
+    }
+}
+
+/// Whether the descriptors of a type are buffers, images, or neither
+fn descriptor_kind(descriptor_type: vk::DescriptorType) -> (bool, bool) {
+    match descriptor_type {
+        vk::DescriptorType::UNIFORM_BUFFER
+        | vk::DescriptorType::STORAGE_BUFFER
+        | vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
+        | vk::DescriptorType::STORAGE_BUFFER_DYNAMIC => (true, false),
+        vk::DescriptorType::SAMPLER
+        | vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+        | vk::DescriptorType::SAMPLED_IMAGE
+        | vk::DescriptorType::STORAGE_IMAGE
+        | vk::DescriptorType::INPUT_ATTACHMENT => (false, true),
+        _ => (false, false),
+    }
+}
+
+unsafe fn descriptor_write(write: &vk::WriteDescriptorSet<'_>) -> DescriptorWrite {
+    let (buffers, images) = descriptor_kind(write.descriptor_type);
+    let count = write.descriptor_count;
+    DescriptorWrite {
+        set: write.dst_set,
+        binding: write.dst_binding,
+        array_element: write.dst_array_element,
+        descriptor_type: write.descriptor_type,
+        buffers: slice(write.p_buffer_info, if buffers { count } else { 0 })
+            .iter()
+            .map(|buffer_info| (buffer_info.buffer, buffer_info.offset, buffer_info.range))
+            .collect(),
+        images: slice(write.p_image_info, if images { count } else { 0 })
+            .iter()
+            .map(|image_info| {
+                (
+                    image_info.sampler,
+                    image_info.image_view,
+                    image_info.image_layout,
+                )
+            })
+            .collect(),
+    }
+}
+
+unsafe fn pipeline_barrier(call: &Call<'_>) -> Command {
+    let barriers = |name: &str, count: &str| {
+        call.arg(name)
+            .and_then(Value::ptr)
+            .map_or((std::ptr::null(), 0), |ptr| {
+                (ptr, scalar(call, count) as u32)
+            })
+    };
+    let (memory_barriers, memory_count) = barriers("p_memory_barriers", "memory_barrier_count");
+    let (buffer_barriers, buffer_count) =
+        barriers("p_buffer_memory_barriers", "buffer_memory_barrier_count");
+    let (image_barriers, image_count) =
+        barriers("p_image_memory_barriers", "image_memory_barrier_count");
+
+    Command::CmdPipelineBarrier {
+        command_buffer: handle(call, "command_buffer"),
+        src_stage_mask: vk::PipelineStageFlags::from_raw(scalar(call, "src_stage_mask") as u32),
+        dst_stage_mask: vk::PipelineStageFlags::from_raw(scalar(call, "dst_stage_mask") as u32),
+        dependency_flags: vk::DependencyFlags::from_raw(scalar(call, "dependency_flags") as u32),
+        memory_barriers: slice(
+            memory_barriers.cast::<vk::MemoryBarrier<'_>>(),
+            memory_count,
+        )
+        .iter()
+        .map(|barrier| (barrier.src_access_mask, barrier.dst_access_mask))
+        .collect(),
+        buffer_barriers: slice(
+            buffer_barriers.cast::<vk::BufferMemoryBarrier<'_>>(),
+            buffer_count,
+        )
+        .iter()
+        .map(|barrier| BufferBarrier {
+            src_access_mask: barrier.src_access_mask,
+            dst_access_mask: barrier.dst_access_mask,
+            src_queue_family_index: barrier.src_queue_family_index,
+            dst_queue_family_index: barrier.dst_queue_family_index,
+            buffer: barrier.buffer,
+            offset: barrier.offset,
+            size: barrier.size,
+        })
+        .collect(),
+        image_barriers: slice(
+            image_barriers.cast::<vk::ImageMemoryBarrier<'_>>(),
+            image_count,
+        )
+        .iter()
+        .map(|barrier| ImageBarrier {
+            src_access_mask: barrier.src_access_mask,
+            dst_access_mask: barrier.dst_access_mask,
+            old_layout: barrier.old_layout,
+            new_layout: barrier.new_layout,
+            src_queue_family_index: barrier.src_queue_family_index,
+            dst_queue_family_index: barrier.dst_queue_family_index,
+            image: barrier.image,
+            subresource_range: SubresourceRange::new(&barrier.subresource_range),
+        })
+        .collect(),
+    }
+}
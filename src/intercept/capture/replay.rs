@@ -0,0 +1,989 @@
+//! Replaying captured commands.
+
+use super::{CaptureError, Command};
+use ash::vk::{self, Handle as _};
+use std::{
+    collections::HashMap,
+    ffi::{c_char, CString},
+    fmt,
+    mem::size_of,
+};
+
+/// Runs [`Command`]s, mapping the captured handles to the replayed ones
+///
+/// Objects the commands don't destroy are left alive.
+pub struct Replayer {
+    entry: ash::Entry,
+    instances: HashMap<vk::Instance, ash::Instance>,
+    devices: HashMap<vk::Device, ash::Device>,
+    /// The replayed handles, by object type and captured handle
+    handles: HashMap<(vk::ObjectType, u64), u64>,
+    /// The instances of physical devices and the devices of queues and command buffers, by
+    /// captured handle
+    parents: HashMap<u64, u64>,
+    /// The replayed memory mapped to write captured data
+    mapped: HashMap<vk::DeviceMemory, *mut u8>,
+    /// The index of the next command
+    index: usize,
+}
+
+impl Replayer {
+    /// Creates a replayer running commands against `entry`
+    pub fn new(entry: &ash::Entry) -> Self {
+        Self {
+            entry: entry.clone(),
+            instances: HashMap::new(),
+            devices: HashMap::new(),
+            handles: HashMap::new(),
+            parents: HashMap::new(),
+            mapped: HashMap::new(),
+            index: 0,
+        }
+    }
+
+    /// The replayed handle of a captured handle, if it was created and not destroyed
+    pub fn handle<H: vk::Handle + Copy>(&self, captured: H) -> Option<H> {
+        if captured.as_raw() == 0 {
+            return Some(captured);
+        }
+        self.handles
+            .get(&(H::TYPE, captured.as_raw()))
+            .map(|&raw| H::from_raw(raw))
+    }
+
+    /// The replayed instance of a captured instance
+    pub fn instance(&self, captured: vk::Instance) -> Option<&ash::Instance> {
+        self.instances.get(&captured)
+    }
+
+    /// The replayed device of a captured device
+    pub fn device(&self, captured: vk::Device) -> Option<&ash::Device> {
+        self.devices.get(&captured)
+    }
+
+    fn unknown(&self, handle: impl vk::Handle) -> CaptureError {
+        CaptureError::UnknownHandle {
+            index: self.index,
+            handle: handle.as_raw(),
+        }
+    }
+
+    fn vulkan(&self, function: &'static str) -> impl Fn(vk::Result) -> CaptureError {
+        let index = self.index;
+        move |result| CaptureError::Vulkan {
+            index,
+            function,
+            result,
+        }
+    }
+
+    fn get<H: vk::Handle + Copy>(&self, captured: H) -> Result<H, CaptureError> {
+        self.handle(captured).ok_or_else(|| self.unknown(captured))
+    }
+
+    fn get_all<H: vk::Handle + Copy>(&self, captured: &[H]) -> Result<Vec<H>, CaptureError> {
+        captured.iter().map(|&handle| self.get(handle)).collect()
+    }
+
+    fn insert<H: vk::Handle>(&mut self, captured: H, replayed: H) {
+        self.handles
+            .insert((H::TYPE, captured.as_raw()), replayed.as_raw());
+    }
+
+    fn remove<H: vk::Handle>(&mut self, captured: H) {
+        self.handles.remove(&(H::TYPE, captured.as_raw()));
+    }
+
+    fn dev(&self, captured: vk::Device) -> Result<&ash::Device, CaptureError> {
+        self.devices
+            .get(&captured)
+            .ok_or_else(|| self.unknown(captured))
+    }
+
+    /// The device a queue or command buffer belongs to
+    fn parent_device(&self, captured: impl vk::Handle) -> Result<&ash::Device, CaptureError> {
+        let raw = captured.as_raw();
+        let device = self.parents.get(&raw).ok_or(CaptureError::UnknownHandle {
+            index: self.index,
+            handle: raw,
+        })?;
+        self.dev(vk::Device::from_raw(*device))
+    }
+
+    /// Runs a command
+    ///
+    /// # Safety
+    ///
+    /// The command must use the Vulkan API of the entry as specified, like the call it was
+    /// captured from.
+    pub unsafe fn run(&mut self, command: &Command) -> Result<(), CaptureError> {
+        match command {
+            Command::CreateInstance {
+                flags,
+                api_version,
+                layers,
+                extensions,
+                instance,
+            } => {
+                let layers = c_strings(layers);
+                let extensions = c_strings(extensions);
+                let layer_names = pointers(&layers);
+                let extension_names = pointers(&extensions);
+                let application_info = vk::ApplicationInfo::default().api_version(*api_version);
+                let create_info = vk::InstanceCreateInfo::default()
+                    .flags(*flags)
+                    .application_info(&application_info)
+                    .enabled_layer_names(&layer_names)
+                    .enabled_extension_names(&extension_names);
+                let replayed = self
+                    .entry
+                    .create_instance(&create_info, None)
+                    .map_err(self.vulkan("vkCreateInstance"))?;
+                self.insert(*instance, replayed.handle());
+                self.instances.insert(*instance, replayed);
+            }
+            Command::DestroyInstance { instance } => {
+                let replayed = self
+                    .instances
+                    .remove(instance)
+                    .ok_or_else(|| self.unknown(*instance))?;
+                replayed.destroy_instance(None);
+                self.remove(*instance);
+            }
+            Command::EnumeratePhysicalDevices {
+                instance,
+                physical_devices,
+            } => {
+                let replayed = self
+                    .instance(*instance)
+                    .ok_or_else(|| self.unknown(*instance))?
+                    .enumerate_physical_devices()
+                    .map_err(self.vulkan("vkEnumeratePhysicalDevices"))?;
+                for (&captured, replayed) in physical_devices.iter().zip(replayed) {
+                    self.insert(captured, replayed);
+                    self.parents.insert(captured.as_raw(), instance.as_raw());
+                }
+            }
+            Command::CreateDevice {
+                physical_device,
+                queues,
+                extensions,
+                features,
+                device,
+            } => {
+                let instance = self
+                    .parents
+                    .get(&physical_device.as_raw())
+                    .and_then(|&instance| self.instances.get(&vk::Instance::from_raw(instance)))
+                    .ok_or_else(|| self.unknown(*physical_device))?;
+                let queue_create_infos = queues
+                    .iter()
+                    .map(|queue| {
+                        vk::DeviceQueueCreateInfo::default()
+                            .flags(queue.flags)
+                            .queue_family_index(queue.family)
+                            .queue_priorities(&queue.priorities)
+                    })
+                    .collect::<Vec<_>>();
+                let extensions = c_strings(extensions);
+                let extension_names = pointers(&extensions);
+                let mut create_info = vk::DeviceCreateInfo::default()
+                    .queue_create_infos(&queue_create_infos)
+                    .enabled_extension_names(&extension_names);
+                let mut enabled_features = vk::PhysicalDeviceFeatures::default();
+                if let Some(features) = features {
+                    let len = size_of::<vk::PhysicalDeviceFeatures>() / size_of::<vk::Bool32>();
+                    std::ptr::copy_nonoverlapping(
+                        features.as_ptr(),
+                        (&mut enabled_features as *mut vk::PhysicalDeviceFeatures).cast(),
+                        features.len().min(len),
+                    );
+                    create_info = create_info.enabled_features(&enabled_features);
+                }
+                let replayed = instance
+                    .create_device(self.get(*physical_device)?, &create_info, None)
+                    .map_err(self.vulkan("vkCreateDevice"))?;
+                self.insert(*device, replayed.handle());
+                self.devices.insert(*device, replayed);
+            }
+            Command::DestroyDevice { device } => {
+                let replayed = self
+                    .devices
+                    .remove(device)
+                    .ok_or_else(|| self.unknown(*device))?;
+                replayed.destroy_device(None);
+                self.remove(*device);
+            }
+            Command::GetDeviceQueue {
+                device,
+                family,
+                index,
+                queue,
+            } => {
+                let replayed = self.dev(*device)?.get_device_queue(*family, *index);
+                self.insert(*queue, replayed);
+                self.parents.insert(queue.as_raw(), device.as_raw());
+            }
+            Command::DeviceWaitIdle { device } => self
+                .dev(*device)?
+                .device_wait_idle()
+                .map_err(self.vulkan("vkDeviceWaitIdle"))?,
+            Command::AllocateMemory {
+                device,
+                size,
+                memory_type_index,
+                memory,
+            } => {
+                let allocate_info = vk::MemoryAllocateInfo::default()
+                    .allocation_size(*size)
+                    .memory_type_index(*memory_type_index);
+                let replayed = self
+                    .dev(*device)?
+                    .allocate_memory(&allocate_info, None)
+                    .map_err(self.vulkan("vkAllocateMemory"))?;
+                self.insert(*memory, replayed);
+            }
+            Command::FreeMemory { device, memory } => {
+                let replayed = self.get(*memory)?;
+                self.dev(*device)?.free_memory(replayed, None);
+                self.mapped.remove(&replayed);
+                self.remove(*memory);
+            }
+            Command::WriteMemory {
+                device,
+                memory,
+                offset,
+                data,
+            } => {
+                let replayed = self.get(*memory)?;
+                let replayed_device = self.dev(*device)?;
+                let mapped = match self.mapped.get(&replayed) {
+                    Some(&mapped) => mapped,
+                    None => replayed_device
+                        .map_memory(replayed, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+                        .map_err(self.vulkan("vkMapMemory"))?
+                        .cast(),
+                };
+                std::ptr::copy_nonoverlapping(
+                    data.as_ptr(),
+                    mapped.add(*offset as usize),
+                    data.len(),
+                );
+                replayed_device
+                    .flush_mapped_memory_ranges(&[vk::MappedMemoryRange::default()
+                        .memory(replayed)
+                        .size(vk::WHOLE_SIZE)])
+                    .map_err(self.vulkan("vkFlushMappedMemoryRanges"))?;
+                self.mapped.insert(replayed, mapped);
+            }
+            Command::CreateBuffer {
+                device,
+                flags,
+                size,
+                usage,
+                sharing_mode,
+                queue_family_indices,
+                buffer,
+            } => {
+                let create_info = vk::BufferCreateInfo::default()
+                    .flags(*flags)
+                    .size(*size)
+                    .usage(*usage)
+                    .sharing_mode(*sharing_mode)
+                    .queue_family_indices(queue_family_indices);
+                let replayed = self
+                    .dev(*device)?
+                    .create_buffer(&create_info, None)
+                    .map_err(self.vulkan("vkCreateBuffer"))?;
+                self.insert(*buffer, replayed);
+            }
+            Command::DestroyBuffer { device, buffer } => {
+                self.dev(*device)?.destroy_buffer(self.get(*buffer)?, None);
+                self.remove(*buffer);
+            }
+            Command::BindBufferMemory {
+                device,
+                buffer,
+                memory,
+                offset,
+            } => self
+                .dev(*device)?
+                .bind_buffer_memory(self.get(*buffer)?, self.get(*memory)?, *offset)
+                .map_err(self.vulkan("vkBindBufferMemory"))?,
+            Command::CreateImage {
+                device,
+                flags,
+                image_type,
+                format,
+                extent,
+                mip_levels,
+                array_layers,
+                samples,
+                tiling,
+                usage,
+                sharing_mode,
+                queue_family_indices,
+                initial_layout,
+                image,
+            } => {
+                let create_info = vk::ImageCreateInfo::default()
+                    .flags(*flags)
+                    .image_type(*image_type)
+                    .format(*format)
+                    .extent(vk::Extent3D {
+                        width: extent.width,
+                        height: extent.height,
+                        depth: extent.depth,
+                    })
+                    .mip_levels(*mip_levels)
+                    .array_layers(*array_layers)
+                    .samples(*samples)
+                    .tiling(*tiling)
+                    .usage(*usage)
+                    .sharing_mode(*sharing_mode)
+                    .queue_family_indices(queue_family_indices)
+                    .initial_layout(*initial_layout);
+                let replayed = self
+                    .dev(*device)?
+                    .create_image(&create_info, None)
+                    .map_err(self.vulkan("vkCreateImage"))?;
+                self.insert(*image, replayed);
+            }
+            Command::DestroyImage { device, image } => {
+                self.dev(*device)?.destroy_image(self.get(*image)?, None);
+                self.remove(*image);
+            }
+            Command::BindImageMemory {
+                device,
+                image,
+                memory,
+                offset,
+            } => self
+                .dev(*device)?
+                .bind_image_memory(self.get(*image)?, self.get(*memory)?, *offset)
+                .map_err(self.vulkan("vkBindImageMemory"))?,
+            Command::CreateImageView {
+                device,
+                flags,
+                image,
+                view_type,
+                format,
+                components,
+                subresource_range,
+                view,
+            } => {
+                let create_info = vk::ImageViewCreateInfo::default()
+                    .flags(*flags)
+                    .image(self.get(*image)?)
+                    .view_type(*view_type)
+                    .format(*format)
+                    .components(vk::ComponentMapping {
+                        r: components.r,
+                        g: components.g,
+                        b: components.b,
+                        a: components.a,
+                    })
+                    .subresource_range(subresource_range.to_vk());
+                let replayed = self
+                    .dev(*device)?
+                    .create_image_view(&create_info, None)
+                    .map_err(self.vulkan("vkCreateImageView"))?;
+                self.insert(*view, replayed);
+            }
+            Command::DestroyImageView { device, view } => {
+                self.dev(*device)?
+                    .destroy_image_view(self.get(*view)?, None);
+                self.remove(*view);
+            }
+            Command::CreateShaderModule {
+                device,
+                code,
+                shader_module,
+            } => {
+                let create_info = vk::ShaderModuleCreateInfo::default().code(code);
+                let replayed = self
+                    .dev(*device)?
+                    .create_shader_module(&create_info, None)
+                    .map_err(self.vulkan("vkCreateShaderModule"))?;
+                self.insert(*shader_module, replayed);
+            }
+            Command::DestroyShaderModule {
+                device,
+                shader_module,
+            } => {
+                self.dev(*device)?
+                    .destroy_shader_module(self.get(*shader_module)?, None);
+                self.remove(*shader_module);
+            }
+            Command::CreateDescriptorSetLayout {
+                device,
+                flags,
+                bindings,
+                set_layout,
+            } => {
+                let bindings = bindings
+                    .iter()
+                    .map(|binding| {
+                        vk::DescriptorSetLayoutBinding::default()
+                            .binding(binding.binding)
+                            .descriptor_type(binding.descriptor_type)
+                            .descriptor_count(binding.descriptor_count)
+                            .stage_flags(binding.stage_flags)
+                    })
+                    .collect::<Vec<_>>();
+                let create_info = vk::DescriptorSetLayoutCreateInfo::default()
+                    .flags(*flags)
+                    .bindings(&bindings);
+                let replayed = self
+                    .dev(*device)?
+                    .create_descriptor_set_layout(&create_info, None)
+                    .map_err(self.vulkan("vkCreateDescriptorSetLayout"))?;
+                self.insert(*set_layout, replayed);
+            }
+            Command::DestroyDescriptorSetLayout { device, set_layout } => {
+                self.dev(*device)?
+                    .destroy_descriptor_set_layout(self.get(*set_layout)?, None);
+                self.remove(*set_layout);
+            }
+            Command::CreatePipelineLayout {
+                device,
+                set_layouts,
+                push_constant_ranges,
+                pipeline_layout,
+            } => {
+                let set_layouts = self.get_all(set_layouts)?;
+                let push_constant_ranges = push_constant_ranges
+                    .iter()
+                    .map(|range| vk::PushConstantRange {
+                        stage_flags: range.stage_flags,
+                        offset: range.offset,
+                        size: range.size,
+                    })
+                    .collect::<Vec<_>>();
+                let create_info = vk::PipelineLayoutCreateInfo::default()
+                    .set_layouts(&set_layouts)
+                    .push_constant_ranges(&push_constant_ranges);
+                let replayed = self
+                    .dev(*device)?
+                    .create_pipeline_layout(&create_info, None)
+                    .map_err(self.vulkan("vkCreatePipelineLayout"))?;
+                self.insert(*pipeline_layout, replayed);
+            }
+            Command::DestroyPipelineLayout {
+                device,
+                pipeline_layout,
+            } => {
+                self.dev(*device)?
+                    .destroy_pipeline_layout(self.get(*pipeline_layout)?, None);
+                self.remove(*pipeline_layout);
+            }
+            Command::CreateComputePipeline {
+                device,
+                flags,
+                module,
+                entry_point,
+                specialization,
+                layout,
+                pipeline,
+            } => {
+                let entry_point = CString::new(entry_point.as_str()).unwrap_or_default();
+                let map_entries = specialization
+                    .as_ref()
+                    .map_or_else(Vec::new, |specialization| {
+                        specialization
+                            .entries
+                            .iter()
+                            .map(|&(constant_id, offset, size)| vk::SpecializationMapEntry {
+                                constant_id,
+                                offset,
+                                size: size as usize,
+                            })
+                            .collect()
+                    });
+                let specialization_info = specialization.as_ref().map(|specialization| {
+                    vk::SpecializationInfo::default()
+                        .map_entries(&map_entries)
+                        .data(&specialization.data)
+                });
+                let mut stage = vk::PipelineShaderStageCreateInfo::default()
+                    .stage(vk::ShaderStageFlags::COMPUTE)
+                    .module(self.get(*module)?)
+                    .name(&entry_point);
+                if let Some(specialization_info) = &specialization_info {
+                    stage = stage.specialization_info(specialization_info);
+                }
+                let create_info = vk::ComputePipelineCreateInfo::default()
+                    .flags(*flags)
+                    .stage(stage)
+                    .layout(self.get(*layout)?);
+                let replayed = self
+                    .dev(*device)?
+                    .create_compute_pipelines(vk::PipelineCache::null(), &[create_info], None)
+                    .map_err(|(_, result)| self.vulkan("vkCreateComputePipelines")(result))?;
+                self.insert(*pipeline, replayed[0]);
+            }
+            Command::DestroyPipeline { device, pipeline } => {
+                self.dev(*device)?
+                    .destroy_pipeline(self.get(*pipeline)?, None);
+                self.remove(*pipeline);
+            }
+            Command::CreateDescriptorPool {
+                device,
+                flags,
+                max_sets,
+                pool_sizes,
+                descriptor_pool,
+            } => {
+                let pool_sizes = pool_sizes
+                    .iter()
+                    .map(|size| vk::DescriptorPoolSize {
+                        ty: size.descriptor_type,
+                        descriptor_count: size.descriptor_count,
+                    })
+                    .collect::<Vec<_>>();
+                let create_info = vk::DescriptorPoolCreateInfo::default()
+                    .flags(*flags)
+                    .max_sets(*max_sets)
+                    .pool_sizes(&pool_sizes);
+                let replayed = self
+                    .dev(*device)?
+                    .create_descriptor_pool(&create_info, None)
+                    .map_err(self.vulkan("vkCreateDescriptorPool"))?;
+                self.insert(*descriptor_pool, replayed);
+            }
+            Command::DestroyDescriptorPool {
+                device,
+                descriptor_pool,
+            } => {
+                self.dev(*device)?
+                    .destroy_descriptor_pool(self.get(*descriptor_pool)?, None);
+                self.remove(*descriptor_pool);
+            }
+            Command::ResetDescriptorPool {
+                device,
+                descriptor_pool,
+            } => self
+                .dev(*device)?
+                .reset_descriptor_pool(
+                    self.get(*descriptor_pool)?,
+                    vk::DescriptorPoolResetFlags::empty(),
+                )
+                .map_err(self.vulkan("vkResetDescriptorPool"))?,
+            Command::AllocateDescriptorSets {
+                device,
+                descriptor_pool,
+                set_layouts,
+                descriptor_sets,
+            } => {
+                let set_layouts = self.get_all(set_layouts)?;
+                let allocate_info = vk::DescriptorSetAllocateInfo::default()
+                    .descriptor_pool(self.get(*descriptor_pool)?)
+                    .set_layouts(&set_layouts);
+                let replayed = self
+                    .dev(*device)?
+                    .allocate_descriptor_sets(&allocate_info)
+                    .map_err(self.vulkan("vkAllocateDescriptorSets"))?;
+                for (&captured, replayed) in descriptor_sets.iter().zip(replayed) {
+                    self.insert(captured, replayed);
+                }
+            }
+            Command::FreeDescriptorSets {
+                device,
+                descriptor_pool,
+                descriptor_sets,
+            } => {
+                self.dev(*device)?
+                    .free_descriptor_sets(
+                        self.get(*descriptor_pool)?,
+                        &self.get_all(descriptor_sets)?,
+                    )
+                    .map_err(self.vulkan("vkFreeDescriptorSets"))?;
+                for &descriptor_set in descriptor_sets {
+                    self.remove(descriptor_set);
+                }
+            }
+            Command::UpdateDescriptorSets { device, writes } => {
+                let mut buffer_infos = Vec::with_capacity(writes.len());
+                let mut image_infos = Vec::with_capacity(writes.len());
+                for write in writes {
+                    buffer_infos.push(
+                        write
+                            .buffers
+                            .iter()
+                            .map(|&(buffer, offset, range)| {
+                                Ok(vk::DescriptorBufferInfo {
+                                    buffer: self.get(buffer)?,
+                                    offset,
+                                    range,
+                                })
+                            })
+                            .collect::<Result<Vec<_>, CaptureError>>()?,
+                    );
+                    image_infos.push(
+                        write
+                            .images
+                            .iter()
+                            .map(|&(sampler, image_view, image_layout)| {
+                                Ok(vk::DescriptorImageInfo {
+                                    sampler: self.get(sampler)?,
+                                    image_view: self.get(image_view)?,
+                                    image_layout,
+                                })
+                            })
+                            .collect::<Result<Vec<_>, CaptureError>>()?,
+                    );
+                }
+                let writes = writes
+                    .iter()
+                    .zip(&buffer_infos)
+                    .zip(&image_infos)
+                    .map(|((write, buffer_info), image_info)| {
+                        let mut replayed = vk::WriteDescriptorSet::default()
+                            .dst_set(self.get(write.set)?)
+                            .dst_binding(write.binding)
+                            .dst_array_element(write.array_element)
+                            .descriptor_type(write.descriptor_type);
+                        if !buffer_info.is_empty() {
+                            replayed = replayed.buffer_info(buffer_info);
+                        }
+                        if !image_info.is_empty() {
+                            replayed = replayed.image_info(image_info);
+                        }
+                        Ok(replayed)
+                    })
+                    .collect::<Result<Vec<_>, CaptureError>>()?;
+                self.dev(*device)?.update_descriptor_sets(&writes, &[]);
+            }
+            Command::CreateCommandPool {
+                device,
+                flags,
+                queue_family_index,
+                command_pool,
+            } => {
+                let create_info = vk::CommandPoolCreateInfo::default()
+                    .flags(*flags)
+                    .queue_family_index(*queue_family_index);
+                let replayed = self
+                    .dev(*device)?
+                    .create_command_pool(&create_info, None)
+                    .map_err(self.vulkan("vkCreateCommandPool"))?;
+                self.insert(*command_pool, replayed);
+            }
+            Command::DestroyCommandPool {
+                device,
+                command_pool,
+            } => {
+                self.dev(*device)?
+                    .destroy_command_pool(self.get(*command_pool)?, None);
+                self.remove(*command_pool);
+            }
+            Command::ResetCommandPool {
+                device,
+                command_pool,
+                flags,
+            } => self
+                .dev(*device)?
+                .reset_command_pool(self.get(*command_pool)?, *flags)
+                .map_err(self.vulkan("vkResetCommandPool"))?,
+            Command::AllocateCommandBuffers {
+                device,
+                command_pool,
+                level,
+                command_buffers,
+            } => {
+                let allocate_info = vk::CommandBufferAllocateInfo::default()
+                    .command_pool(self.get(*command_pool)?)
+                    .level(*level)
+                    .command_buffer_count(command_buffers.len() as u32);
+                let replayed = self
+                    .dev(*device)?
+                    .allocate_command_buffers(&allocate_info)
+                    .map_err(self.vulkan("vkAllocateCommandBuffers"))?;
+                for (&captured, replayed) in command_buffers.iter().zip(replayed) {
+                    self.insert(captured, replayed);
+                    self.parents.insert(captured.as_raw(), device.as_raw());
+                }
+            }
+            Command::FreeCommandBuffers {
+                device,
+                command_pool,
+                command_buffers,
+            } => {
+                self.dev(*device)?.free_command_buffers(
+                    self.get(*command_pool)?,
+                    &self.get_all(command_buffers)?,
+                );
+                for &command_buffer in command_buffers {
+                    self.remove(command_buffer);
+                    self.parents.remove(&command_buffer.as_raw());
+                }
+            }
+            Command::BeginCommandBuffer {
+                command_buffer,
+                flags,
+            } => self
+                .parent_device(*command_buffer)?
+                .begin_command_buffer(
+                    self.get(*command_buffer)?,
+                    &vk::CommandBufferBeginInfo::default().flags(*flags),
+                )
+                .map_err(self.vulkan("vkBeginCommandBuffer"))?,
+            Command::EndCommandBuffer { command_buffer } => self
+                .parent_device(*command_buffer)?
+                .end_command_buffer(self.get(*command_buffer)?)
+                .map_err(self.vulkan("vkEndCommandBuffer"))?,
+            Command::ResetCommandBuffer {
+                command_buffer,
+                flags,
+            } => self
+                .parent_device(*command_buffer)?
+                .reset_command_buffer(self.get(*command_buffer)?, *flags)
+                .map_err(self.vulkan("vkResetCommandBuffer"))?,
+            Command::CmdCopyBuffer {
+                command_buffer,
+                src_buffer,
+                dst_buffer,
+                regions,
+            } => {
+                let regions = regions
+                    .iter()
+                    .map(|region| vk::BufferCopy {
+                        src_offset: region.src_offset,
+                        dst_offset: region.dst_offset,
+                        size: region.size,
+                    })
+                    .collect::<Vec<_>>();
+                self.parent_device(*command_buffer)?.cmd_copy_buffer(
+                    self.get(*command_buffer)?,
+                    self.get(*src_buffer)?,
+                    self.get(*dst_buffer)?,
+                    &regions,
+                );
+            }
+            Command::CmdFillBuffer {
+                command_buffer,
+                dst_buffer,
+                dst_offset,
+                size,
+                data,
+            } => self.parent_device(*command_buffer)?.cmd_fill_buffer(
+                self.get(*command_buffer)?,
+                self.get(*dst_buffer)?,
+                *dst_offset,
+                *size,
+                *data,
+            ),
+            Command::CmdUpdateBuffer {
+                command_buffer,
+                dst_buffer,
+                dst_offset,
+                data,
+            } => self.parent_device(*command_buffer)?.cmd_update_buffer(
+                self.get(*command_buffer)?,
+                self.get(*dst_buffer)?,
+                *dst_offset,
+                data,
+            ),
+            Command::CmdBindPipeline {
+                command_buffer,
+                bind_point,
+                pipeline,
+            } => self.parent_device(*command_buffer)?.cmd_bind_pipeline(
+                self.get(*command_buffer)?,
+                *bind_point,
+                self.get(*pipeline)?,
+            ),
+            Command::CmdBindDescriptorSets {
+                command_buffer,
+                bind_point,
+                layout,
+                first_set,
+                descriptor_sets,
+                dynamic_offsets,
+            } => self
+                .parent_device(*command_buffer)?
+                .cmd_bind_descriptor_sets(
+                    self.get(*command_buffer)?,
+                    *bind_point,
+                    self.get(*layout)?,
+                    *first_set,
+                    &self.get_all(descriptor_sets)?,
+                    dynamic_offsets,
+                ),
+            Command::CmdPushConstants {
+                command_buffer,
+                layout,
+                stage_flags,
+                offset,
+                values,
+            } => self.parent_device(*command_buffer)?.cmd_push_constants(
+                self.get(*command_buffer)?,
+                self.get(*layout)?,
+                *stage_flags,
+                *offset,
+                values,
+            ),
+            Command::CmdDispatch {
+                command_buffer,
+                group_count_x,
+                group_count_y,
+                group_count_z,
+            } => self.parent_device(*command_buffer)?.cmd_dispatch(
+                self.get(*command_buffer)?,
+                *group_count_x,
+                *group_count_y,
+                *group_count_z,
+            ),
+            Command::CmdPipelineBarrier {
+                command_buffer,
+                src_stage_mask,
+                dst_stage_mask,
+                dependency_flags,
+                memory_barriers,
+                buffer_barriers,
+                image_barriers,
+            } => {
+                let memory_barriers = memory_barriers
+                    .iter()
+                    .map(|&(src_access_mask, dst_access_mask)| {
+                        vk::MemoryBarrier::default()
+                            .src_access_mask(src_access_mask)
+                            .dst_access_mask(dst_access_mask)
+                    })
+                    .collect::<Vec<_>>();
+                let buffer_barriers = buffer_barriers
+                    .iter()
+                    .map(|barrier| {
+                        Ok(vk::BufferMemoryBarrier::default()
+                            .src_access_mask(barrier.src_access_mask)
+                            .dst_access_mask(barrier.dst_access_mask)
+                            .src_queue_family_index(barrier.src_queue_family_index)
+                            .dst_queue_family_index(barrier.dst_queue_family_index)
+                            .buffer(self.get(barrier.buffer)?)
+                            .offset(barrier.offset)
+                            .size(barrier.size))
+                    })
+                    .collect::<Result<Vec<_>, CaptureError>>()?;
+                let image_barriers = image_barriers
+                    .iter()
+                    .map(|barrier| {
+                        Ok(vk::ImageMemoryBarrier::default()
+                            .src_access_mask(barrier.src_access_mask)
+                            .dst_access_mask(barrier.dst_access_mask)
+                            .old_layout(barrier.old_layout)
+                            .new_layout(barrier.new_layout)
+                            .src_queue_family_index(barrier.src_queue_family_index)
+                            .dst_queue_family_index(barrier.dst_queue_family_index)
+                            .image(self.get(barrier.image)?)
+                            .subresource_range(barrier.subresource_range.to_vk()))
+                    })
+                    .collect::<Result<Vec<_>, CaptureError>>()?;
+                self.parent_device(*command_buffer)?.cmd_pipeline_barrier(
+                    self.get(*command_buffer)?,
+                    *src_stage_mask,
+                    *dst_stage_mask,
+                    *dependency_flags,
+                    &memory_barriers,
+                    &buffer_barriers,
+                    &image_barriers,
+                );
+            }
+            Command::CreateFence {
+                device,
+                flags,
+                fence,
+            } => {
+                let replayed = self
+                    .dev(*device)?
+                    .create_fence(&vk::FenceCreateInfo::default().flags(*flags), None)
+                    .map_err(self.vulkan("vkCreateFence"))?;
+                self.insert(*fence, replayed);
+            }
+            Command::DestroyFence { device, fence } => {
+                self.dev(*device)?.destroy_fence(self.get(*fence)?, None);
+                self.remove(*fence);
+            }
+            Command::ResetFences { device, fences } => self
+                .dev(*device)?
+                .reset_fences(&self.get_all(fences)?)
+                .map_err(self.vulkan("vkResetFences"))?,
+            Command::WaitForFences {
+                device,
+                fences,
+                wait_all,
+            } => self
+                .dev(*device)?
+                .wait_for_fences(&self.get_all(fences)?, *wait_all, u64::MAX)
+                .map_err(self.vulkan("vkWaitForFences"))?,
+            Command::CreateSemaphore { device, semaphore } => {
+                let replayed = self
+                    .dev(*device)?
+                    .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)
+                    .map_err(self.vulkan("vkCreateSemaphore"))?;
+                self.insert(*semaphore, replayed);
+            }
+            Command::DestroySemaphore { device, semaphore } => {
+                self.dev(*device)?
+                    .destroy_semaphore(self.get(*semaphore)?, None);
+                self.remove(*semaphore);
+            }
+            Command::QueueSubmit {
+                queue,
+                submits,
+                fence,
+            } => {
+                let mut wait_semaphores = Vec::with_capacity(submits.len());
+                let mut wait_stages = Vec::with_capacity(submits.len());
+                let mut command_buffers = Vec::with_capacity(submits.len());
+                let mut signal_semaphores = Vec::with_capacity(submits.len());
+                for submit in submits {
+                    let (semaphores, stages): (Vec<_>, Vec<_>) =
+                        submit.wait_semaphores.iter().copied().unzip();
+                    wait_semaphores.push(self.get_all(&semaphores)?);
+                    wait_stages.push(stages);
+                    command_buffers.push(self.get_all(&submit.command_buffers)?);
+                    signal_semaphores.push(self.get_all(&submit.signal_semaphores)?);
+                }
+                let submits = (0..submits.len())
+                    .map(|index| {
+                        vk::SubmitInfo::default()
+                            .wait_semaphores(&wait_semaphores[index])
+                            .wait_dst_stage_mask(&wait_stages[index])
+                            .command_buffers(&command_buffers[index])
+                            .signal_semaphores(&signal_semaphores[index])
+                    })
+                    .collect::<Vec<_>>();
+                self.parent_device(*queue)?
+                    .queue_submit(self.get(*queue)?, &submits, self.get(*fence)?)
+                    .map_err(self.vulkan("vkQueueSubmit"))?;
+            }
+            Command::QueueWaitIdle { queue } => self
+                .parent_device(*queue)?
+                .queue_wait_idle(self.get(*queue)?)
+                .map_err(self.vulkan("vkQueueWaitIdle"))?,
+            Command::Unsupported { .. } => {}
+        }
+        self.index += 1;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Replayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Replayer")
+            .field("index", &self.index)
+            .field("handles", &self.handles.len())
+            .finish_non_exhaustive()
+    }
+}
+
+fn c_strings(strings: &[String]) -> Vec<CString> {
+    strings
+        .iter()
+        .map(|string| CString::new(string.as_str()).unwrap_or_default())
+        .collect()
+}
+
+fn pointers(strings: &[CString]) -> Vec<*const c_char> {
+    strings.iter().map(|string| string.as_ptr()).collect()
+}
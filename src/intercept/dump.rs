@@ -0,0 +1,941 @@
+//! Logging every intercepted call, like `VK_LAYER_LUNARG_api_dump` does through the loader.
+//!
+//! [`ApiDump`] is an [`Interceptor`] writing each call with the thread it was made on, its
+//! arguments and its result, to `tracing` or as text or JSON Lines:
+//!
+//! ```no_run
+//! use ash_molten::intercept::{self, dump::ApiDump};
+//!
+//! let entry = intercept::wrap(&ash_molten::MoltenEntry::load().unwrap()).unwrap();
+//! ApiDump::json_lines_file("api_dump.jsonl")
+//!     .unwrap()
+//!     .function("vkCreate*")
+//!     .function("vkQueueSubmit")
+//!     .register();
+//! ```
+//!
+//! Handles and other pointers are written in hexadecimal, and enums and flags with the names
+//! of their values. The create and allocate infos of common objects, submits, presents, render
+//! pass begins, viewports, scissors and buffer copies are decoded, along with the handles
+//! created by a call. Other structures are written as their address.
+//!
+//! Calls are written after they return, so a call that crashes isn't written. Errors writing
+//! the dump are ignored.
+
+use super::{Call, Function, Interceptor, InterceptorBuilder, InterceptorId, Value};
+use ash::vk;
+use std::{
+    ffi::{c_char, c_void, CStr},
+    fmt::{self, Write as _},
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// The `tracing` target of the events emitted by [`ApiDump::tracing`]
+#[cfg(feature = "tracing")]
+pub const API_DUMP_TARGET: &str = "moltenvk::api_dump";
+
+/// Writes every intercepted call, or the calls to the functions added with
+/// [`ApiDump::function`]
+pub struct ApiDump {
+    output: Output,
+    functions: Vec<String>,
+    excluded: Vec<String>,
+}
+
+enum Output {
+    Text(Mutex<Box<dyn Write + Send>>),
+    JsonLines(Mutex<Box<dyn Write + Send>>),
+    #[cfg(feature = "tracing")]
+    Tracing,
+}
+
+impl ApiDump {
+    fn new(output: Output) -> Self {
+        Self {
+            output,
+            functions: Vec::new(),
+            excluded: Vec::new(),
+        }
+    }
+
+    /// Writes one line of text per call, like:
+    ///
+    /// ```text
+    /// [main] vkCreateFence(device: 0x1, p_create_info: VkFenceCreateInfo { flags: SIGNALED },
+    /// p_allocator: null, p_fence: 0x2) -> SUCCESS
+    /// ```
+    ///
+    /// without the line break
+    pub fn text(writer: impl Write + Send + 'static) -> Self {
+        Self::new(Output::Text(Mutex::new(Box::new(writer))))
+    }
+
+    /// Writes text to the file at `path`, see [`ApiDump::text`]
+    pub fn text_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::text(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Writes one JSON object per line and call, with the `thread`, the `function`, its `args`
+    /// by name and its `result`, if it has one
+    pub fn json_lines(writer: impl Write + Send + 'static) -> Self {
+        Self::new(Output::JsonLines(Mutex::new(Box::new(writer))))
+    }
+
+    /// Writes JSON Lines to the file at `path`, see [`ApiDump::json_lines`]
+    pub fn json_lines_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::json_lines(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Emits one `tracing` event per call at the trace level with [`API_DUMP_TARGET`], with
+    /// the function as the message and the `thread`, `args` and `result` as fields
+    #[cfg(feature = "tracing")]
+    pub fn tracing() -> Self {
+        Self::new(Output::Tracing)
+    }
+
+    /// Only writes calls to the functions matching `pattern`, in addition to the other
+    /// functions added. Patterns are Vulkan function names like `vkQueueSubmit`, which may end
+    /// with `*` to match every function starting with the rest, like `vkCmd*`
+    pub fn function(mut self, pattern: impl Into<String>) -> Self {
+        self.functions.push(pattern.into());
+        self
+    }
+
+    /// Doesn't write calls to the functions matching `pattern`, even if they match a pattern
+    /// added with [`ApiDump::function`]
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.excluded.push(pattern.into());
+        self
+    }
+
+    /// An [`InterceptorBuilder`] for the dump, to restrict it to an instance or device
+    pub fn builder(self) -> InterceptorBuilder {
+        InterceptorBuilder::new(Arc::new(self))
+    }
+
+    /// Registers the dump for all instances and devices
+    pub fn register(self) -> InterceptorId {
+        self.builder().register()
+    }
+
+    fn matches(&self, function: Function) -> bool {
+        let name = function.name();
+        let matches = |pattern: &String| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern,
+        };
+        (self.functions.is_empty() || self.functions.iter().any(matches))
+            && !self.excluded.iter().any(matches)
+    }
+}
+
+impl fmt::Debug for ApiDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let output = match self.output {
+            Output::Text(_) => "Text",
+            Output::JsonLines(_) => "JsonLines",
+            #[cfg(feature = "tracing")]
+            Output::Tracing => "Tracing",
+        };
+        f.debug_struct("ApiDump")
+            .field("output", &output)
+            .field("functions", &self.functions)
+            .field("excluded", &self.excluded)
+            .finish()
+    }
+}
+
+impl Interceptor for ApiDump {
+    fn after(&self, call: &Call<'_>, result: Option<vk::Result>) {
+        if !self.matches(call.function) {
+            return;
+        }
+
+        let thread = std::thread::current();
+        let thread = thread
+            .name()
+            .map_or_else(|| format!("{:?}", thread.id()), str::to_owned);
+        let args = unsafe { decode(call, result) };
+
+        let (writer, line) = match &self.output {
+            Output::Text(writer) => {
+                let mut line = format!("[{thread}] {}(", call.function.name());
+                write_text_fields(&mut line, &args);
+                line.push(')');
+                if let Some(result) = result {
+                    let _ = write!(line, " -> {result:?}");
+                }
+                (writer, line)
+            }
+            Output::JsonLines(writer) => {
+                let mut line = String::from("{\"thread\":");
+                write_json_string(&mut line, &thread);
+                line.push_str(",\"function\":");
+                write_json_string(&mut line, call.function.name());
+                line.push_str(",\"args\":");
+                write_json_fields(&mut line, &args);
+                if let Some(result) = result {
+                    line.push_str(",\"result\":");
+                    write_json_string(&mut line, &format!("{result:?}"));
+                }
+                line.push('}');
+                (writer, line)
+            }
+            #[cfg(feature = "tracing")]
+            Output::Tracing => {
+                let mut text = String::new();
+                write_text_fields(&mut text, &args);
+                tracing::trace!(
+                    target: API_DUMP_TARGET,
+                    thread,
+                    args = text,
+                    result = result.map(tracing::field::debug),
+                    "{}",
+                    call.function.name(),
+                );
+                return;
+            }
+        };
+
+        let mut writer = writer.lock().unwrap();
+        let _ = writeln!(writer, "{line}").and_then(|()| writer.flush());
+    }
+}
+
+/// A decoded argument or structure member
+enum Field {
+    Null,
+    Handle(u64),
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+    /// An enum or flags, formatted with the names of their values
+    Name(String),
+    Str(String),
+    /// A pointer that isn't decoded
+    Address(usize),
+    Array(Vec<Field>),
+    Struct(&'static str, Vec<(&'static str, Field)>),
+}
+
+type Fields = Vec<(&'static str, Field)>;
+
+fn name(value: impl fmt::Debug) -> Field {
+    match format!("{value:?}") {
+        // Empty flags format as nothing
+        name if name.is_empty() => Field::Name("0".to_owned()),
+        name => Field::Name(name),
+    }
+}
+
+fn handle(handle: impl vk::Handle) -> Field {
+    Field::Handle(handle.as_raw())
+}
+
+unsafe fn string(string: *const c_char) -> Field {
+    if string.is_null() {
+        Field::Null
+    } else {
+        Field::Str(CStr::from_ptr(string).to_string_lossy().into_owned())
+    }
+}
+
+unsafe fn array<T>(items: *const T, count: u32, decode: impl Fn(&T) -> Field) -> Field {
+    if items.is_null() {
+        Field::Null
+    } else {
+        let items = std::slice::from_raw_parts(items, count as usize);
+        Field::Array(items.iter().map(decode).collect())
+    }
+}
+
+unsafe fn handles<H: vk::Handle + Copy>(handles: *const H, count: u32) -> Field {
+    array(handles, count, |&item| handle(item))
+}
+
+unsafe fn strings(strings: *const *const c_char, count: u32) -> Field {
+    array(strings, count, |&item| string(item))
+}
+
+unsafe fn decode(call: &Call<'_>, result: Option<vk::Result>) -> Fields {
+    let succeeded = result.is_none_or(|result| result.as_raw() >= 0);
+    call.args
+        .iter()
+        .enumerate()
+        .map(|(index, arg)| (arg.name, decode_arg(call, index, succeeded)))
+        .collect()
+}
+
+/// Decodes an argument. Output parameters are only read if the call succeeded
+unsafe fn decode_arg(call: &Call<'_>, index: usize, succeeded: bool) -> Field {
+    match call.args[index].value {
+        Value::Handle(_, raw) => Field::Handle(raw),
+        Value::Handles(_, raw) if raw.is_null() => Field::Null,
+        Value::Handles(_, raw) => match handle_count(call, index, succeeded) {
+            Some(count) => array(raw, count, |&raw| Field::Handle(raw)),
+            // Without a count, this is a handle created by the call
+            None if succeeded => Field::Handle(*raw),
+            None => Field::Address(raw as usize),
+        },
+        Value::U32(value) => Field::Unsigned(value.into()),
+        Value::I32(value) => Field::Signed(value.into()),
+        Value::U64(value) => Field::Unsigned(value),
+        Value::F32(value) => Field::Float(value.into()),
+        Value::Usize(value) => Field::Unsigned(value as u64),
+        Value::Bits(bits) => name(bits),
+        Value::Ptr(raw) if raw.is_null() => Field::Null,
+        Value::Ptr(raw) => {
+            decode_ptr(call, index, raw, succeeded).unwrap_or(Field::Address(raw as usize))
+        }
+    }
+}
+
+/// The number of handles behind the argument at `index`, `None` for single handles
+unsafe fn handle_count(call: &Call<'_>, index: usize, succeeded: bool) -> Option<u32> {
+    let info = |index: usize| call.args[index].value.ptr().unwrap();
+    match call.function {
+        Function::AllocateCommandBuffers => {
+            let info = &*info(1).cast::<vk::CommandBufferAllocateInfo<'_>>();
+            succeeded.then_some(info.command_buffer_count)
+        }
+        Function::AllocateDescriptorSets => {
+            let info = &*info(1).cast::<vk::DescriptorSetAllocateInfo<'_>>();
+            succeeded.then_some(info.descriptor_set_count)
+        }
+        Function::CreateGraphicsPipelines | Function::CreateComputePipelines => {
+            match call.args[2].value {
+                Value::U32(count) if succeeded => Some(count),
+                _ => None,
+            }
+        }
+        // Otherwise arrays follow their count
+        _ => {
+            let count = call.args[..index].last()?;
+            if !count.name.ends_with("_count") {
+                return None;
+            }
+            match count.value {
+                Value::U32(count) => Some(count),
+                Value::Ptr(count) if succeeded && !count.is_null() => Some(*count.cast::<u32>()),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Decodes the structures of common calls, `None` for pointers that aren't decoded
+unsafe fn decode_ptr(
+    call: &Call<'_>,
+    index: usize,
+    raw: *const c_void,
+    succeeded: bool,
+) -> Option<Field> {
+    let arg = call.args[index].name;
+    let count = || match call.args[index - 1].value {
+        Value::U32(count) => count,
+        _ => 0,
+    };
+
+    if arg.starts_with("p_") && arg.ends_with("_count") {
+        return succeeded.then(|| Field::Unsigned((*raw.cast::<u32>()).into()));
+    }
+
+    let field = match (call.function, arg) {
+        (_, "p_layer_name") => string(raw.cast()),
+        (Function::CreateInstance, "p_create_info") => instance_create_info(&*raw.cast()),
+        (Function::CreateDevice, "p_create_info") => device_create_info(&*raw.cast()),
+        (Function::CreateBuffer, "p_create_info") => buffer_create_info(&*raw.cast()),
+        (Function::CreateImage, "p_create_info") => image_create_info(&*raw.cast()),
+        (Function::CreateImageView, "p_create_info") => image_view_create_info(&*raw.cast()),
+        (Function::AllocateMemory, "p_allocate_info") => memory_allocate_info(&*raw.cast()),
+        (Function::CreateFence, "p_create_info") => {
+            let info = &*raw.cast::<vk::FenceCreateInfo<'_>>();
+            Field::Struct("VkFenceCreateInfo", vec![("flags", name(info.flags))])
+        }
+        (Function::CreateSemaphore, "p_create_info") => {
+            let info = &*raw.cast::<vk::SemaphoreCreateInfo<'_>>();
+            Field::Struct("VkSemaphoreCreateInfo", vec![("flags", name(info.flags))])
+        }
+        (Function::CreateShaderModule, "p_create_info") => {
+            let info = &*raw.cast::<vk::ShaderModuleCreateInfo<'_>>();
+            Field::Struct(
+                "VkShaderModuleCreateInfo",
+                vec![
+                    ("flags", name(info.flags)),
+                    ("code_size", Field::Unsigned(info.code_size as u64)),
+                ],
+            )
+        }
+        (Function::CreateSampler, "p_create_info") => sampler_create_info(&*raw.cast()),
+        (Function::CreateDescriptorPool, "p_create_info") => {
+            let info = &*raw.cast::<vk::DescriptorPoolCreateInfo<'_>>();
+            let pool_size = |size: &vk::DescriptorPoolSize| {
+                Field::Struct(
+                    "VkDescriptorPoolSize",
+                    vec![
+                        ("ty", name(size.ty)),
+                        (
+                            "descriptor_count",
+                            Field::Unsigned(size.descriptor_count.into()),
+                        ),
+                    ],
+                )
+            };
+            Field::Struct(
+                "VkDescriptorPoolCreateInfo",
+                vec![
+                    ("flags", name(info.flags)),
+                    ("max_sets", Field::Unsigned(info.max_sets.into())),
+                    (
+                        "p_pool_sizes",
+                        array(info.p_pool_sizes, info.pool_size_count, pool_size),
+                    ),
+                ],
+            )
+        }
+        (Function::AllocateDescriptorSets, "p_allocate_info") => {
+            let info = &*raw.cast::<vk::DescriptorSetAllocateInfo<'_>>();
+            Field::Struct(
+                "VkDescriptorSetAllocateInfo",
+                vec![
+                    ("descriptor_pool", handle(info.descriptor_pool)),
+                    (
+                        "p_set_layouts",
+                        handles(info.p_set_layouts, info.descriptor_set_count),
+                    ),
+                ],
+            )
+        }
+        (Function::CreateFramebuffer, "p_create_info") => framebuffer_create_info(&*raw.cast()),
+        (Function::CreateCommandPool, "p_create_info") => {
+            let info = &*raw.cast::<vk::CommandPoolCreateInfo<'_>>();
+            Field::Struct(
+                "VkCommandPoolCreateInfo",
+                vec![
+                    ("flags", name(info.flags)),
+                    (
+                        "queue_family_index",
+                        Field::Unsigned(info.queue_family_index.into()),
+                    ),
+                ],
+            )
+        }
+        (Function::AllocateCommandBuffers, "p_allocate_info") => {
+            let info = &*raw.cast::<vk::CommandBufferAllocateInfo<'_>>();
+            Field::Struct(
+                "VkCommandBufferAllocateInfo",
+                vec![
+                    ("command_pool", handle(info.command_pool)),
+                    ("level", name(info.level)),
+                    (
+                        "command_buffer_count",
+                        Field::Unsigned(info.command_buffer_count.into()),
+                    ),
+                ],
+            )
+        }
+        (Function::BeginCommandBuffer, "p_begin_info") => {
+            let info = &*raw.cast::<vk::CommandBufferBeginInfo<'_>>();
+            Field::Struct(
+                "VkCommandBufferBeginInfo",
+                vec![("flags", name(info.flags))],
+            )
+        }
+        (Function::CreateGraphicsPipelines, "p_create_infos") => {
+            array(raw.cast(), count(), graphics_pipeline_create_info)
+        }
+        (Function::CreateComputePipelines, "p_create_infos") => {
+            array(raw.cast(), count(), compute_pipeline_create_info)
+        }
+        (Function::QueueSubmit, "p_submits") => array(raw.cast(), count(), submit_info),
+        (Function::QueuePresentKHR, "p_present_info") => present_info(&*raw.cast()),
+        (Function::CreateSwapchainKHR, "p_create_info") => swapchain_create_info(&*raw.cast()),
+        (Function::CmdBeginRenderPass, "p_render_pass_begin") => {
+            let info = &*raw.cast::<vk::RenderPassBeginInfo<'_>>();
+            Field::Struct(
+                "VkRenderPassBeginInfo",
+                vec![
+                    ("render_pass", handle(info.render_pass)),
+                    ("framebuffer", handle(info.framebuffer)),
+                    ("render_area", rect_2d(&info.render_area)),
+                    (
+                        "clear_value_count",
+                        Field::Unsigned(info.clear_value_count.into()),
+                    ),
+                ],
+            )
+        }
+        (Function::CmdSetViewport, "p_viewports") => array(raw.cast(), count(), viewport),
+        (Function::CmdSetScissor, "p_scissors") => array(raw.cast(), count(), rect_2d),
+        (Function::CmdCopyBuffer, "p_regions") => array(raw.cast(), count(), buffer_copy),
+        _ => return None,
+    };
+    Some(field)
+}
+
+fn extent_2d(extent: vk::Extent2D) -> Field {
+    Field::Struct(
+        "VkExtent2D",
+        vec![
+            ("width", Field::Unsigned(extent.width.into())),
+            ("height", Field::Unsigned(extent.height.into())),
+        ],
+    )
+}
+
+fn extent_3d(extent: vk::Extent3D) -> Field {
+    Field::Struct(
+        "VkExtent3D",
+        vec![
+            ("width", Field::Unsigned(extent.width.into())),
+            ("height", Field::Unsigned(extent.height.into())),
+            ("depth", Field::Unsigned(extent.depth.into())),
+        ],
+    )
+}
+
+fn rect_2d(rect: &vk::Rect2D) -> Field {
+    let offset = Field::Struct(
+        "VkOffset2D",
+        vec![
+            ("x", Field::Signed(rect.offset.x.into())),
+            ("y", Field::Signed(rect.offset.y.into())),
+        ],
+    );
+    Field::Struct(
+        "VkRect2D",
+        vec![("offset", offset), ("extent", extent_2d(rect.extent))],
+    )
+}
+
+fn viewport(viewport: &vk::Viewport) -> Field {
+    Field::Struct(
+        "VkViewport",
+        vec![
+            ("x", Field::Float(viewport.x.into())),
+            ("y", Field::Float(viewport.y.into())),
+            ("width", Field::Float(viewport.width.into())),
+            ("height", Field::Float(viewport.height.into())),
+            ("min_depth", Field::Float(viewport.min_depth.into())),
+            ("max_depth", Field::Float(viewport.max_depth.into())),
+        ],
+    )
+}
+
+fn buffer_copy(region: &vk::BufferCopy) -> Field {
+    Field::Struct(
+        "VkBufferCopy",
+        vec![
+            ("src_offset", Field::Unsigned(region.src_offset)),
+            ("dst_offset", Field::Unsigned(region.dst_offset)),
+            ("size", Field::Unsigned(region.size)),
+        ],
+    )
+}
+
+unsafe fn instance_create_info(info: &vk::InstanceCreateInfo<'_>) -> Field {
+    let application_info = match info.p_application_info.as_ref() {
+        Some(application) => Field::Struct(
+            "VkApplicationInfo",
+            vec![
+                ("p_application_name", string(application.p_application_name)),
+                (
+                    "application_version",
+                    Field::Unsigned(application.application_version.into()),
+                ),
+                ("p_engine_name", string(application.p_engine_name)),
+                (
+                    "engine_version",
+                    Field::Unsigned(application.engine_version.into()),
+                ),
+                (
+                    "api_version",
+                    Field::Unsigned(application.api_version.into()),
+                ),
+            ],
+        ),
+        None => Field::Null,
+    };
+    Field::Struct(
+        "VkInstanceCreateInfo",
+        vec![
+            ("flags", name(info.flags)),
+            ("p_application_info", application_info),
+            (
+                "pp_enabled_layer_names",
+                strings(info.pp_enabled_layer_names, info.enabled_layer_count),
+            ),
+            (
+                "pp_enabled_extension_names",
+                strings(
+                    info.pp_enabled_extension_names,
+                    info.enabled_extension_count,
+                ),
+            ),
+        ],
+    )
+}
+
+unsafe fn device_create_info(info: &vk::DeviceCreateInfo<'_>) -> Field {
+    let queue_create_info = |queue: &vk::DeviceQueueCreateInfo<'_>| {
+        Field::Struct(
+            "VkDeviceQueueCreateInfo",
+            vec![
+                ("flags", name(queue.flags)),
+                (
+                    "queue_family_index",
+                    Field::Unsigned(queue.queue_family_index.into()),
+                ),
+                ("queue_count", Field::Unsigned(queue.queue_count.into())),
+            ],
+        )
+    };
+    Field::Struct(
+        "VkDeviceCreateInfo",
+        vec![
+            ("flags", name(info.flags)),
+            (
+                "p_queue_create_infos",
+                array(
+                    info.p_queue_create_infos,
+                    info.queue_create_info_count,
+                    queue_create_info,
+                ),
+            ),
+            (
+                "pp_enabled_extension_names",
+                strings(
+                    info.pp_enabled_extension_names,
+                    info.enabled_extension_count,
+                ),
+            ),
+        ],
+    )
+}
+
+fn buffer_create_info(info: &vk::BufferCreateInfo<'_>) -> Field {
+    Field::Struct(
+        "VkBufferCreateInfo",
+        vec![
+            ("flags", name(info.flags)),
+            ("size", Field::Unsigned(info.size)),
+            ("usage", name(info.usage)),
+            ("sharing_mode", name(info.sharing_mode)),
+        ],
+    )
+}
+
+fn image_create_info(info: &vk::ImageCreateInfo<'_>) -> Field {
+    Field::Struct(
+        "VkImageCreateInfo",
+        vec![
+            ("flags", name(info.flags)),
+            ("image_type", name(info.image_type)),
+            ("format", name(info.format)),
+            ("extent", extent_3d(info.extent)),
+            ("mip_levels", Field::Unsigned(info.mip_levels.into())),
+            ("array_layers", Field::Unsigned(info.array_layers.into())),
+            ("samples", name(info.samples)),
+            ("tiling", name(info.tiling)),
+            ("usage", name(info.usage)),
+            ("sharing_mode", name(info.sharing_mode)),
+            ("initial_layout", name(info.initial_layout)),
+        ],
+    )
+}
+
+fn image_view_create_info(info: &vk::ImageViewCreateInfo<'_>) -> Field {
+    let range = &info.subresource_range;
+    let subresource_range = Field::Struct(
+        "VkImageSubresourceRange",
+        vec![
+            ("aspect_mask", name(range.aspect_mask)),
+            (
+                "base_mip_level",
+                Field::Unsigned(range.base_mip_level.into()),
+            ),
+            ("level_count", Field::Unsigned(range.level_count.into())),
+            (
+                "base_array_layer",
+                Field::Unsigned(range.base_array_layer.into()),
+            ),
+            ("layer_count", Field::Unsigned(range.layer_count.into())),
+        ],
+    );
+    Field::Struct(
+        "VkImageViewCreateInfo",
+        vec![
+            ("flags", name(info.flags)),
+            ("image", handle(info.image)),
+            ("view_type", name(info.view_type)),
+            ("format", name(info.format)),
+            ("subresource_range", subresource_range),
+        ],
+    )
+}
+
+fn memory_allocate_info(info: &vk::MemoryAllocateInfo<'_>) -> Field {
+    Field::Struct(
+        "VkMemoryAllocateInfo",
+        vec![
+            ("allocation_size", Field::Unsigned(info.allocation_size)),
+            (
+                "memory_type_index",
+                Field::Unsigned(info.memory_type_index.into()),
+            ),
+        ],
+    )
+}
+
+fn sampler_create_info(info: &vk::SamplerCreateInfo<'_>) -> Field {
+    Field::Struct(
+        "VkSamplerCreateInfo",
+        vec![
+            ("flags", name(info.flags)),
+            ("mag_filter", name(info.mag_filter)),
+            ("min_filter", name(info.min_filter)),
+            ("mipmap_mode", name(info.mipmap_mode)),
+            ("address_mode_u", name(info.address_mode_u)),
+            ("address_mode_v", name(info.address_mode_v)),
+            ("address_mode_w", name(info.address_mode_w)),
+            (
+                "anisotropy_enable",
+                Field::Unsigned(info.anisotropy_enable.into()),
+            ),
+            (
+                "compare_enable",
+                Field::Unsigned(info.compare_enable.into()),
+            ),
+        ],
+    )
+}
+
+unsafe fn framebuffer_create_info(info: &vk::FramebufferCreateInfo<'_>) -> Field {
+    Field::Struct(
+        "VkFramebufferCreateInfo",
+        vec![
+            ("flags", name(info.flags)),
+            ("render_pass", handle(info.render_pass)),
+            (
+                "p_attachments",
+                handles(info.p_attachments, info.attachment_count),
+            ),
+            ("width", Field::Unsigned(info.width.into())),
+            ("height", Field::Unsigned(info.height.into())),
+            ("layers", Field::Unsigned(info.layers.into())),
+        ],
+    )
+}
+
+fn graphics_pipeline_create_info(info: &vk::GraphicsPipelineCreateInfo<'_>) -> Field {
+    Field::Struct(
+        "VkGraphicsPipelineCreateInfo",
+        vec![
+            ("flags", name(info.flags)),
+            ("stage_count", Field::Unsigned(info.stage_count.into())),
+            ("layout", handle(info.layout)),
+            ("render_pass", handle(info.render_pass)),
+            ("subpass", Field::Unsigned(info.subpass.into())),
+        ],
+    )
+}
+
+fn compute_pipeline_create_info(info: &vk::ComputePipelineCreateInfo<'_>) -> Field {
+    let stage = Field::Struct(
+        "VkPipelineShaderStageCreateInfo",
+        vec![
+            ("stage", name(info.stage.stage)),
+            ("module", handle(info.stage.module)),
+            ("p_name", unsafe { string(info.stage.p_name) }),
+        ],
+    );
+    Field::Struct(
+        "VkComputePipelineCreateInfo",
+        vec![
+            ("flags", name(info.flags)),
+            ("stage", stage),
+            ("layout", handle(info.layout)),
+        ],
+    )
+}
+
+fn submit_info(submit: &vk::SubmitInfo<'_>) -> Field {
+    unsafe {
+        Field::Struct(
+            "VkSubmitInfo",
+            vec![
+                (
+                    "p_wait_semaphores",
+                    handles(submit.p_wait_semaphores, submit.wait_semaphore_count),
+                ),
+                (
+                    "p_wait_dst_stage_mask",
+                    array(
+                        submit.p_wait_dst_stage_mask,
+                        submit.wait_semaphore_count,
+                        |&stages| name(stages),
+                    ),
+                ),
+                (
+                    "p_command_buffers",
+                    handles(submit.p_command_buffers, submit.command_buffer_count),
+                ),
+                (
+                    "p_signal_semaphores",
+                    handles(submit.p_signal_semaphores, submit.signal_semaphore_count),
+                ),
+            ],
+        )
+    }
+}
+
+unsafe fn present_info(info: &vk::PresentInfoKHR<'_>) -> Field {
+    Field::Struct(
+        "VkPresentInfoKHR",
+        vec![
+            (
+                "p_wait_semaphores",
+                handles(info.p_wait_semaphores, info.wait_semaphore_count),
+            ),
+            (
+                "p_swapchains",
+                handles(info.p_swapchains, info.swapchain_count),
+            ),
+            (
+                "p_image_indices",
+                array(info.p_image_indices, info.swapchain_count, |&index| {
+                    Field::Unsigned(index.into())
+                }),
+            ),
+        ],
+    )
+}
+
+fn swapchain_create_info(info: &vk::SwapchainCreateInfoKHR<'_>) -> Field {
+    Field::Struct(
+        "VkSwapchainCreateInfoKHR",
+        vec![
+            ("flags", name(info.flags)),
+            ("surface", handle(info.surface)),
+            (
+                "min_image_count",
+                Field::Unsigned(info.min_image_count.into()),
+            ),
+            ("image_format", name(info.image_format)),
+            ("image_color_space", name(info.image_color_space)),
+            ("image_extent", extent_2d(info.image_extent)),
+            ("image_usage", name(info.image_usage)),
+            ("pre_transform", name(info.pre_transform)),
+            ("composite_alpha", name(info.composite_alpha)),
+            ("present_mode", name(info.present_mode)),
+            ("old_swapchain", handle(info.old_swapchain)),
+        ],
+    )
+}
+
+fn write_text_fields(out: &mut String, fields: &[(&'static str, Field)]) {
+    for (index, (name, field)) in fields.iter().enumerate() {
+        if index > 0 {
+            out.push_str(", ");
+        }
+        out.push_str(name);
+        out.push_str(": ");
+        write_text(out, field);
+    }
+}
+
+fn write_text(out: &mut String, field: &Field) {
+    let _ = match field {
+        Field::Null => write!(out, "null"),
+        Field::Handle(raw) => write!(out, "{raw:#x}"),
+        Field::Unsigned(value) => write!(out, "{value}"),
+        Field::Signed(value) => write!(out, "{value}"),
+        Field::Float(value) => write!(out, "{value}"),
+        Field::Name(name) => write!(out, "{name}"),
+        Field::Str(string) => write!(out, "{string:?}"),
+        Field::Address(address) => write!(out, "{address:#x}"),
+        Field::Array(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push_str(", ");
+                }
+                write_text(out, item);
+            }
+            write!(out, "]")
+        }
+        Field::Struct(name, fields) => {
+            out.push_str(name);
+            out.push_str(" { ");
+            write_text_fields(out, fields);
+            write!(out, " }}")
+        }
+    };
+}
+
+fn write_json_fields(out: &mut String, fields: &[(&'static str, Field)]) {
+    out.push('{');
+    for (index, (name, field)) in fields.iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        write_json_string(out, name);
+        out.push(':');
+        write_json(out, field);
+    }
+    out.push('}');
+}
+
+fn write_json(out: &mut String, field: &Field) {
+    match field {
+        Field::Handle(raw) => {
+            let _ = write!(out, "\"{raw:#x}\"");
+        }
+        Field::Unsigned(value) => {
+            let _ = write!(out, "{value}");
+        }
+        Field::Signed(value) => {
+            let _ = write!(out, "{value}");
+        }
+        Field::Float(value) if value.is_finite() => {
+            let _ = write!(out, "{value}");
+        }
+        Field::Null | Field::Float(_) => out.push_str("null"),
+        Field::Name(string) | Field::Str(string) => write_json_string(out, string),
+        Field::Address(address) => {
+            let _ = write!(out, "\"{address:#x}\"");
+        }
+        Field::Array(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_json(out, item);
+            }
+            out.push(']');
+        }
+        Field::Struct(_, fields) => write_json_fields(out, fields),
+    }
+}
+
+fn write_json_string(out: &mut String, string: &str) {
+    out.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,414 @@
+//! Making intercepted calls fail on demand, to exercise error handling.
+//!
+//! [`FaultInjector`] is an [`Interceptor`] returning chosen errors from chosen functions
+//! instead of calling them, when a [`Trigger`] fires. Device loss, running out of memory and
+//! lost surfaces can be tested without waiting for MoltenVK to produce them:
+//!
+//! ```
+//! use ash::vk;
+//! use ash_molten::intercept::{self, fault::{FaultInjector, Trigger}};
+//!
+//! # unsafe {
+//! let entry = intercept::wrap(&ash_molten::MoltenEntry::load().unwrap()).unwrap();
+//! let id = FaultInjector::new()
+//!     .fail(
+//!         "vkAllocateMemory",
+//!         vk::Result::ERROR_OUT_OF_DEVICE_MEMORY,
+//!         Trigger::Nth(2),
+//!     )
+//!     .register();
+//!
+//! let instance = entry
+//!     .create_instance(&vk::InstanceCreateInfo::default(), None)
+//!     .unwrap();
+//! let physical_device = instance.enumerate_physical_devices().unwrap()[0];
+//! let queue_create_info = vk::DeviceQueueCreateInfo::default().queue_priorities(&[1.0]);
+//! let device = instance
+//!     .create_device(
+//!         physical_device,
+//!         &vk::DeviceCreateInfo::default().queue_create_infos(&[queue_create_info]),
+//!         None,
+//!     )
+//!     .unwrap();
+//!
+//! let allocate_info = vk::MemoryAllocateInfo::default().allocation_size(256);
+//! assert!(device.allocate_memory(&allocate_info, None).is_ok());
+//! assert_eq!(
+//!     device.allocate_memory(&allocate_info, None),
+//!     Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY)
+//! );
+//! assert!(device.allocate_memory(&allocate_info, None).is_ok());
+//! intercept::unregister(id);
+//! # }
+//! ```
+//!
+//! Faults can also be described by a spec, like the value of [`FAULTS_ENV_VAR`] read by
+//! [`FaultInjector::from_env`], so CI can run the same binary with different faults. The spec
+//! is a comma-separated list of `function=RESULT@trigger` entries, where the function may end
+//! with `*` to match a prefix, and the trigger is one of
+//!
+//! - nothing, to fail every call,
+//! - `N`, to fail the Nth call,
+//! - `N..`, to fail the Nth call and every one after it,
+//! - `everyN`, to fail every Nth call,
+//! - `pX`, to fail each call with the probability `X`.
+//!
+//! A `seed=N` entry seeds the probabilities, which are reproducible for a given seed:
+//!
+//! ```
+//! use ash_molten::intercept::fault::FaultInjector;
+//!
+//! let injector = FaultInjector::from_spec(
+//!     "vkQueueSubmit=ERROR_DEVICE_LOST@3, vkAcquireNextImageKHR=ERROR_SURFACE_LOST_KHR@p0.1, seed=7",
+//! )
+//! .unwrap();
+//! assert_eq!(
+//!     format!("{injector:?}"),
+//!     "[vkQueueSubmit=ERROR_DEVICE_LOST@Nth(3), \
+//!      vkAcquireNextImageKHR=ERROR_SURFACE_LOST_KHR@Probability(0.1)]",
+//! );
+//! assert!(FaultInjector::from_spec("vkQueueSubmit=ERROR_NOT_AN_ERROR").is_err());
+//! assert!(FaultInjector::from_spec("vkAcquireNextImageKHR=SUBOPTIMAL_KHR").is_err());
+//! assert!(FaultInjector::from_spec("vkCmd*=ERROR_DEVICE_LOST").is_err());
+//! ```
+//!
+//! Calls are counted per fault, among the calls to its functions that reach the injector. A
+//! call is failed by the first fault that fires, and is skipped along with the interceptors
+//! registered after the injector. Faults only fail functions returning a `VkResult`, calls
+//! to other functions matching their pattern are forwarded and not counted.
+
+use super::{Call, Flow, Function, Interceptor, InterceptorBuilder, InterceptorId};
+use ash::vk;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// The environment variable holding the spec read by [`FaultInjector::from_env`]
+pub const FAULTS_ENV_VAR: &str = "ASH_MOLTEN_FAULTS";
+
+/// The results a spec may name, which are the errors of the core API and the surface and
+/// swapchain extensions. Success codes like `SUBOPTIMAL_KHR` can't be injected, since the
+/// outputs of a call returning them must be written
+const RESULTS: &[vk::Result] = &[
+    vk::Result::ERROR_OUT_OF_HOST_MEMORY,
+    vk::Result::ERROR_OUT_OF_DEVICE_MEMORY,
+    vk::Result::ERROR_INITIALIZATION_FAILED,
+    vk::Result::ERROR_DEVICE_LOST,
+    vk::Result::ERROR_MEMORY_MAP_FAILED,
+    vk::Result::ERROR_LAYER_NOT_PRESENT,
+    vk::Result::ERROR_EXTENSION_NOT_PRESENT,
+    vk::Result::ERROR_FEATURE_NOT_PRESENT,
+    vk::Result::ERROR_INCOMPATIBLE_DRIVER,
+    vk::Result::ERROR_TOO_MANY_OBJECTS,
+    vk::Result::ERROR_FORMAT_NOT_SUPPORTED,
+    vk::Result::ERROR_FRAGMENTED_POOL,
+    vk::Result::ERROR_UNKNOWN,
+    vk::Result::ERROR_OUT_OF_POOL_MEMORY,
+    vk::Result::ERROR_INVALID_EXTERNAL_HANDLE,
+    vk::Result::ERROR_FRAGMENTATION,
+    vk::Result::ERROR_SURFACE_LOST_KHR,
+    vk::Result::ERROR_NATIVE_WINDOW_IN_USE_KHR,
+    vk::Result::ERROR_OUT_OF_DATE_KHR,
+];
+
+/// When a fault fails a call
+#[derive(Clone)]
+#[non_exhaustive]
+pub enum Trigger {
+    /// Every call
+    Always,
+    /// Only the Nth call, counting from 1
+    Nth(u64),
+    /// The Nth call and every call after it
+    From(u64),
+    /// Every Nth call
+    Every(u64),
+    /// Each call, with a probability between 0 and 1
+    Probability(f64),
+    /// The calls the predicate returns `true` for
+    Predicate(Arc<dyn Fn(&Call<'_>) -> bool + Send + Sync>),
+}
+
+impl Trigger {
+    /// A trigger firing for the calls the predicate returns `true` for
+    pub fn predicate(predicate: impl Fn(&Call<'_>) -> bool + Send + Sync + 'static) -> Self {
+        Self::Predicate(Arc::new(predicate))
+    }
+}
+
+impl fmt::Debug for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Always => f.write_str("Always"),
+            Self::Nth(n) => f.debug_tuple("Nth").field(n).finish(),
+            Self::From(n) => f.debug_tuple("From").field(n).finish(),
+            Self::Every(n) => f.debug_tuple("Every").field(n).finish(),
+            Self::Probability(p) => f.debug_tuple("Probability").field(p).finish(),
+            Self::Predicate(_) => f.write_str("Predicate"),
+        }
+    }
+}
+
+/// A result returned instead of calling some functions
+struct Fault {
+    /// A function name, or a prefix followed by `*`
+    pattern: String,
+    result: vk::Result,
+    trigger: Trigger,
+    /// The calls to the functions seen so far
+    calls: AtomicU64,
+}
+
+impl Fault {
+    fn matches(&self, function: Function) -> bool {
+        function.returns_result() && matches_pattern(&self.pattern, function)
+    }
+}
+
+fn matches_pattern(pattern: &str, function: Function) -> bool {
+    let name = function.name();
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern,
+    }
+}
+
+/// Whether `pattern` matches a function that can be failed
+fn matches_results(pattern: &str) -> bool {
+    Function::ALL
+        .iter()
+        .any(|&function| function.returns_result() && matches_pattern(pattern, function))
+}
+
+/// An error in a fault spec
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaultSpecError {
+    /// The invalid entry of the spec
+    pub entry: String,
+    pub message: String,
+}
+
+impl fmt::Display for FaultSpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid fault {:?}: {}", self.entry, self.message)
+    }
+}
+
+impl std::error::Error for FaultSpecError {}
+
+/// Fails intercepted calls with the results of the faults added with
+/// [`FaultInjector::fail`]
+pub struct FaultInjector {
+    faults: Vec<Fault>,
+    /// The state of the random numbers behind [`Trigger::Probability`]
+    random: AtomicU64,
+    injected: AtomicU64,
+}
+
+impl Default for FaultInjector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FaultInjector {
+    /// An injector without faults
+    pub fn new() -> Self {
+        Self {
+            faults: Vec::new(),
+            random: AtomicU64::new(0),
+            injected: AtomicU64::new(0),
+        }
+    }
+
+    /// Fails the calls to the functions matching `pattern`, a function name like
+    /// `vkQueueSubmit` or a prefix followed by `*` like `vkCreate*`, with `result` when
+    /// `trigger` fires
+    ///
+    /// # Panics
+    ///
+    /// If `result` isn't an error, or if `pattern` matches no function returning a
+    /// `VkResult`.
+    pub fn fail(
+        mut self,
+        pattern: impl Into<String>,
+        result: vk::Result,
+        trigger: Trigger,
+    ) -> Self {
+        let pattern = pattern.into();
+        assert!(
+            result.as_raw() < 0,
+            "{result:?} isn't an error, only errors can be injected"
+        );
+        assert!(
+            matches_results(&pattern),
+            "{pattern:?} matches no function returning a VkResult"
+        );
+        self.faults.push(Fault {
+            pattern,
+            result,
+            trigger,
+            calls: AtomicU64::new(0),
+        });
+        self
+    }
+
+    /// Seeds the random numbers of [`Trigger::Probability`], which are seeded with 0 by
+    /// default
+    pub fn seed(self, seed: u64) -> Self {
+        self.random.store(seed, Ordering::Relaxed);
+        self
+    }
+
+    /// Parses a spec, see the [module documentation](self)
+    pub fn from_spec(spec: &str) -> Result<Self, FaultSpecError> {
+        let mut injector = Self::new();
+        for entry in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let error = |message: &str| FaultSpecError {
+                entry: entry.to_owned(),
+                message: message.to_owned(),
+            };
+
+            let Some((function, fault)) = entry.split_once('=') else {
+                return Err(error("expected `function=RESULT`"));
+            };
+            let function = function.trim();
+            if function == "seed" {
+                let seed = fault.trim().parse().map_err(|_err| error("invalid seed"))?;
+                injector = injector.seed(seed);
+                continue;
+            }
+
+            let (result, trigger) = match fault.split_once('@') {
+                Some((result, trigger)) => (result.trim(), parse_trigger(trigger.trim())),
+                None => (fault.trim(), Some(Trigger::Always)),
+            };
+            let result = parse_result(result).ok_or_else(|| error("unknown result"))?;
+            let trigger = trigger.ok_or_else(|| error("invalid trigger"))?;
+            if function.is_empty() {
+                return Err(error("missing function"));
+            }
+            if !matches_results(function) {
+                return Err(error("matches no function returning a VkResult"));
+            }
+            injector = injector.fail(function, result, trigger);
+        }
+        Ok(injector)
+    }
+
+    /// Parses the spec in [`FAULTS_ENV_VAR`], if it is set
+    pub fn from_env() -> Option<Result<Self, FaultSpecError>> {
+        let spec = std::env::var(FAULTS_ENV_VAR).ok()?;
+        Some(Self::from_spec(&spec))
+    }
+
+    /// The number of calls failed so far
+    pub fn injected(&self) -> u64 {
+        self.injected.load(Ordering::Relaxed)
+    }
+
+    /// An [`InterceptorBuilder`] for the injector, which may restrict it to some instances or
+    /// devices. Keep a clone of an `Arc` of the injector and use [`InterceptorBuilder::new`]
+    /// instead to read [`FaultInjector::injected`] later
+    pub fn builder(self) -> InterceptorBuilder {
+        InterceptorBuilder::new(Arc::new(self))
+    }
+
+    /// Registers the injector for all instances and devices
+    pub fn register(self) -> InterceptorId {
+        self.builder().register()
+    }
+
+    /// A random number between 0 and 1, from `SplitMix64`
+    fn random(&self) -> f64 {
+        let mut z = self
+            .random
+            .fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed)
+            .wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    fn fires(&self, fault: &Fault, call: &Call<'_>) -> bool {
+        let count = fault.calls.fetch_add(1, Ordering::Relaxed) + 1;
+        match &fault.trigger {
+            Trigger::Always => true,
+            Trigger::Nth(n) => count == *n,
+            Trigger::From(n) => count >= *n,
+            Trigger::Every(n) => *n != 0 && count.is_multiple_of(*n),
+            Trigger::Probability(p) => self.random() < *p,
+            Trigger::Predicate(predicate) => predicate(call),
+        }
+    }
+}
+
+fn parse_trigger(trigger: &str) -> Option<Trigger> {
+    if let Some(n) = trigger.strip_prefix("every") {
+        n.parse().ok().filter(|&n| n != 0).map(Trigger::Every)
+    } else if let Some(p) = trigger.strip_prefix('p') {
+        p.parse()
+            .ok()
+            .filter(|p| (0.0..=1.0).contains(p))
+            .map(Trigger::Probability)
+    } else if let Some(n) = trigger.strip_suffix("..") {
+        n.parse().ok().map(Trigger::From)
+    } else {
+        trigger.parse().ok().map(Trigger::Nth)
+    }
+}
+
+/// Parses a result by name, like `ERROR_DEVICE_LOST` or `VK_ERROR_DEVICE_LOST`
+fn parse_result(name: &str) -> Option<vk::Result> {
+    let name = name.strip_prefix("VK_").unwrap_or(name);
+    RESULTS
+        .iter()
+        .copied()
+        .find(|result| format!("{result:?}") == name)
+}
+
+impl fmt::Debug for FaultInjector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut list = f.debug_list();
+        for fault in &self.faults {
+            list.entry(&format_args!(
+                "{}={:?}@{:?}",
+                fault.pattern, fault.result, fault.trigger
+            ));
+        }
+        list.finish()
+    }
+}
+
+impl Interceptor for FaultInjector {
+    fn before(&self, call: &Call<'_>) -> Flow {
+        let mut failed = None;
+        // Every matching fault counts the call, even after one fired
+        for fault in self
+            .faults
+            .iter()
+            .filter(|fault| fault.matches(call.function))
+        {
+            if self.fires(fault, call) && failed.is_none() {
+                failed = Some(fault.result);
+            }
+        }
+
+        match failed {
+            Some(result) => {
+                self.injected.fetch_add(1, Ordering::Relaxed);
+                Flow::Return(result)
+            }
+            None => Flow::Continue,
+        }
+    }
+}
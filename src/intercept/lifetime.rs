@@ -0,0 +1,586 @@
+//! Tracking the lifetimes of Vulkan objects, to find leaks and uses of destroyed objects
+//! without validation layers.
+//!
+//! [`LifetimeTracker`] is an [`Interceptor`] recording the objects created through intercepted
+//! instances and devices along with their parents: the instance of devices, surfaces and
+//! messengers, the pool of command buffers and descriptor sets, and the device of everything
+//! else. It reports an [`Issue`] when
+//!
+//! - a device is destroyed while objects created from it are alive,
+//! - a destroyed object is passed to a function, including destroying it twice,
+//! - an instance is destroyed, listing the objects created from it that are still alive.
+//!
+//! ```
+//! use ash::vk;
+//! use ash_molten::intercept::{self, lifetime::{Issue, LifetimeTracker}};
+//! use std::sync::{Arc, Mutex};
+//!
+//! # unsafe {
+//! let entry = intercept::wrap(&ash_molten::MoltenEntry::load().unwrap()).unwrap();
+//! let issues = Arc::new(Mutex::new(Vec::new()));
+//! let reported = issues.clone();
+//! let id = LifetimeTracker::new()
+//!     .on_issue(move |issue| reported.lock().unwrap().push(issue.clone()))
+//!     .register();
+//!
+//! let instance = entry
+//!     .create_instance(&vk::InstanceCreateInfo::default(), None)
+//!     .unwrap();
+//! let physical_device = instance.enumerate_physical_devices().unwrap()[0];
+//! let queue_create_info = vk::DeviceQueueCreateInfo::default().queue_priorities(&[1.0]);
+//! let device = instance
+//!     .create_device(
+//!         physical_device,
+//!         &vk::DeviceCreateInfo::default().queue_create_infos(&[queue_create_info]),
+//!         None,
+//!     )
+//!     .unwrap();
+//!
+//! let fence = device
+//!     .create_fence(&vk::FenceCreateInfo::default(), None)
+//!     .unwrap();
+//! device.destroy_fence(fence, None);
+//! let _ = device.get_fence_status(fence);
+//! device
+//!     .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)
+//!     .unwrap();
+//! instance.destroy_instance(None);
+//!
+//! let issues = issues.lock().unwrap();
+//! assert!(matches!(issues[0], Issue::UseAfterDestroy { .. }));
+//! let Issue::Leaks { objects, .. } = &issues[1] else {
+//!     panic!("expected a leak report");
+//! };
+//! assert_eq!(objects.len(), 2);
+//! assert_eq!(objects[0].object.object_type, vk::ObjectType::DEVICE);
+//! assert_eq!(objects[1].object.object_type, vk::ObjectType::SEMAPHORE);
+//! intercept::unregister(id);
+//! # }
+//! ```
+//!
+//! Issues are written to stderr unless [`LifetimeTracker::on_issue`] handles them. In debug
+//! builds, the backtrace of the creation of every object is captured and included in the
+//! reports, which makes creating objects slower.
+//!
+//! Only the objects created while the tracker is registered are tracked, and only the uses of
+//! the most recently destroyed ones are reported, see [`LifetimeTracker::remember_destroyed`].
+//! Handles are checked when passed directly or in arrays following their count, not inside
+//! structures. Physical devices, queues and swapchain images aren't tracked since they aren't
+//! destroyed, and the command buffers and descriptor sets of a pool are freed along with it.
+
+use super::{Call, Function, Interceptor, InterceptorBuilder, InterceptorId, Value};
+use ash::vk;
+use std::{
+    backtrace::Backtrace,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+/// A Vulkan object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Object {
+    pub object_type: vk::ObjectType,
+    pub handle: u64,
+}
+
+impl Object {
+    fn new<H: vk::Handle>(handle: H) -> Self {
+        Self {
+            object_type: H::TYPE,
+            handle: handle.as_raw(),
+        }
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {:#x}", self.object_type, self.handle)
+    }
+}
+
+/// A live object
+#[derive(Debug, Clone)]
+pub struct TrackedObject {
+    pub object: Object,
+    /// The object it was created from, `None` for instances
+    pub parent: Option<Object>,
+    /// The function that created it
+    pub created_by: Function,
+    /// Where it was created, in debug builds
+    pub backtrace: Option<Arc<Backtrace>>,
+}
+
+impl fmt::Display for TrackedObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} created by {}", self.object, self.created_by.name())?;
+        if let Some(backtrace) = &self.backtrace {
+            for line in backtrace.to_string().lines() {
+                write!(f, "\n    {line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A problem found by a [`LifetimeTracker`]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Issue {
+    /// An object was destroyed while objects created from it were alive
+    DestroyedWithChildren {
+        function: Function,
+        parent: Object,
+        /// The objects created from the parent, and from those objects
+        children: Vec<TrackedObject>,
+    },
+    /// A destroyed object was passed to a function
+    UseAfterDestroy {
+        function: Function,
+        object: Object,
+        destroyed_by: Function,
+    },
+    /// An instance was destroyed while objects created from it were alive
+    Leaks {
+        instance: Object,
+        objects: Vec<TrackedObject>,
+    },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |f: &mut fmt::Formatter<'_>, objects: &[TrackedObject]| {
+            objects
+                .iter()
+                .try_for_each(|object| write!(f, "\n  {object}"))
+        };
+        match self {
+            Self::DestroyedWithChildren {
+                function,
+                parent,
+                children,
+            } => {
+                write!(
+                    f,
+                    "{} destroyed {parent} with {} live objects:",
+                    function.name(),
+                    children.len()
+                )?;
+                list(f, children)
+            }
+            Self::UseAfterDestroy {
+                function,
+                object,
+                destroyed_by,
+            } => write!(
+                f,
+                "{} used {object}, which was destroyed by {}",
+                function.name(),
+                destroyed_by.name()
+            ),
+            Self::Leaks { instance, objects } => {
+                write!(
+                    f,
+                    "{instance} was destroyed with {} leaked objects:",
+                    objects.len()
+                )?;
+                list(f, objects)
+            }
+        }
+    }
+}
+
+struct Tracked {
+    parent: Option<Object>,
+    created_by: Function,
+    backtrace: Option<Arc<Backtrace>>,
+    /// The order of creation, which reports list objects in
+    sequence: u64,
+}
+
+struct State {
+    live: HashMap<Object, Tracked>,
+    children: HashMap<Object, HashSet<Object>>,
+    /// The most recently destroyed objects whose handles weren't reused, with the function
+    /// destroying them and the sequence number of their destruction
+    destroyed: HashMap<Object, (Function, u64)>,
+    /// The order of destruction, in which objects are forgotten past `remembered`, including
+    /// those whose handles were reused since
+    destructions: VecDeque<(Object, u64)>,
+    remembered: usize,
+    next_sequence: u64,
+}
+
+impl State {
+    fn new(remembered: usize) -> Self {
+        Self {
+            live: HashMap::new(),
+            children: HashMap::new(),
+            destroyed: HashMap::new(),
+            destructions: VecDeque::new(),
+            remembered,
+            next_sequence: 0,
+        }
+    }
+
+    fn sequence(&mut self) -> u64 {
+        self.next_sequence += 1;
+        self.next_sequence
+    }
+
+    /// Remembers that `object` was destroyed, forgetting the oldest destructions
+    fn forget(&mut self, object: Object, function: Function) {
+        let sequence = self.sequence();
+        self.destroyed.insert(object, (function, sequence));
+        self.destructions.push_back((object, sequence));
+        while self.destructions.len() > self.remembered {
+            let Some((object, sequence)) = self.destructions.pop_front() else {
+                break;
+            };
+            if self.destroyed.get(&object).map(|&(_, destroyed)| destroyed) == Some(sequence) {
+                self.destroyed.remove(&object);
+            }
+        }
+    }
+
+    fn create(
+        &mut self,
+        object: Object,
+        parent: Option<Object>,
+        created_by: Function,
+        backtrace: Option<Arc<Backtrace>>,
+    ) {
+        self.destroyed.remove(&object);
+        if let Some(parent) = parent {
+            self.children.entry(parent).or_default().insert(object);
+        }
+        let sequence = self.sequence();
+        self.live.insert(
+            object,
+            Tracked {
+                parent,
+                created_by,
+                backtrace,
+                sequence,
+            },
+        );
+    }
+
+    /// Forgets an object and its descendants, returning the descendants in creation order
+    fn destroy(&mut self, object: Object, function: Function) -> Vec<TrackedObject> {
+        let Some(tracked) = self.live.remove(&object) else {
+            return Vec::new();
+        };
+        self.forget(object, function);
+        if let Some(siblings) = tracked
+            .parent
+            .and_then(|parent| self.children.get_mut(&parent))
+        {
+            siblings.remove(&object);
+        }
+
+        let mut descendants = Vec::new();
+        let mut pending = self
+            .children
+            .remove(&object)
+            .map_or_else(Vec::new, |children| children.into_iter().collect());
+        while let Some(child) = pending.pop() {
+            let Some(tracked) = self.live.remove(&child) else {
+                continue;
+            };
+            self.forget(child, function);
+            pending.extend(self.children.remove(&child).into_iter().flatten());
+            descendants.push((
+                tracked.sequence,
+                TrackedObject {
+                    object: child,
+                    parent: tracked.parent,
+                    created_by: tracked.created_by,
+                    backtrace: tracked.backtrace,
+                },
+            ));
+        }
+        descendants.sort_by_key(|(sequence, _)| *sequence);
+        descendants.into_iter().map(|(_, object)| object).collect()
+    }
+}
+
+/// The number of destroyed objects [`LifetimeTracker::remember_destroyed`] defaults to
+const DEFAULT_REMEMBERED: usize = 16384;
+
+/// Tracks the objects created and destroyed through intercepted instances and devices
+pub struct LifetimeTracker {
+    state: Mutex<State>,
+    on_issue: Box<dyn Fn(&Issue) + Send + Sync>,
+    backtraces: bool,
+}
+
+impl Default for LifetimeTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LifetimeTracker {
+    /// A tracker writing issues to stderr, capturing backtraces in debug builds
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State::new(DEFAULT_REMEMBERED)),
+            on_issue: Box::new(|issue| eprintln!("{issue}")),
+            backtraces: cfg!(debug_assertions),
+        }
+    }
+
+    /// Calls `on_issue` with the issues found, instead of writing them to stderr
+    pub fn on_issue(mut self, on_issue: impl Fn(&Issue) + Send + Sync + 'static) -> Self {
+        self.on_issue = Box::new(on_issue);
+        self
+    }
+
+    /// Whether to capture the backtrace of the creation of every object, which defaults to
+    /// whether this is a debug build
+    pub fn backtraces(mut self, backtraces: bool) -> Self {
+        self.backtraces = backtraces;
+        self
+    }
+
+    /// How many of the most recently destroyed objects to remember and report the uses of,
+    /// 16384 by default. The tracker keeps a few dozen bytes for each
+    pub fn remember_destroyed(mut self, remembered: usize) -> Self {
+        self.state.get_mut().unwrap().remembered = remembered;
+        self
+    }
+
+    /// The live objects, in creation order
+    pub fn live_objects(&self) -> Vec<TrackedObject> {
+        let state = self.state.lock().unwrap();
+        let mut objects = state.live.iter().collect::<Vec<_>>();
+        objects.sort_by_key(|(_, tracked)| tracked.sequence);
+        objects
+            .into_iter()
+            .map(|(&object, tracked)| TrackedObject {
+                object,
+                parent: tracked.parent,
+                created_by: tracked.created_by,
+                backtrace: tracked.backtrace.clone(),
+            })
+            .collect()
+    }
+
+    /// An [`InterceptorBuilder`] for the tracker. Keep a clone of an `Arc` of the tracker and
+    /// use [`InterceptorBuilder::new`] instead to read [`LifetimeTracker::live_objects`] later
+    pub fn builder(self) -> InterceptorBuilder {
+        InterceptorBuilder::new(Arc::new(self))
+    }
+
+    /// Registers the tracker for all instances and devices
+    pub fn register(self) -> InterceptorId {
+        self.builder().register()
+    }
+}
+
+impl fmt::Debug for LifetimeTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LifetimeTracker")
+            .field("live_objects", &self.state.lock().unwrap().live.len())
+            .field("backtraces", &self.backtraces)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The handles passed to a call, directly or in arrays following their count
+unsafe fn used_handles(call: &Call<'_>) -> Vec<Object> {
+    let mut objects = Vec::new();
+    for (index, arg) in call.args.iter().enumerate() {
+        match arg.value {
+            Value::Handle(object_type, handle) if handle != 0 => objects.push(Object {
+                object_type,
+                handle,
+            }),
+            Value::Handles(object_type, _) if index > 0 => {
+                let count = &call.args[index - 1];
+                let Value::U32(count) = count.value else {
+                    continue;
+                };
+                if !call.args[index - 1].name.ends_with("_count") {
+                    continue;
+                }
+                let handles = arg.value.handles(count as usize).unwrap_or_default();
+                objects.extend(
+                    handles
+                        .iter()
+                        .filter(|&&handle| handle != 0)
+                        .map(|&handle| Object {
+                            object_type,
+                            handle,
+                        }),
+                );
+            }
+            _ => {}
+        }
+    }
+    objects
+}
+
+/// The objects created by a successful call, and their parent
+unsafe fn created(call: &Call<'_>) -> Option<(Vec<Object>, Option<Object>)> {
+    let name = call.function.name();
+    if !name.starts_with("vkCreate") && !name.starts_with("vkAllocate") {
+        return None;
+    }
+    let output = call.args.last()?;
+    let Value::Handles(object_type, _) = output.value else {
+        return None;
+    };
+    let info = |name: &str| {
+        call.arg(name)
+            .and_then(Value::ptr)
+            .filter(|info| !info.is_null())
+    };
+
+    let (count, parent) = match call.function {
+        Function::CreateInstance => (1, None),
+        Function::AllocateCommandBuffers => {
+            let allocate_info =
+                &*info("p_allocate_info")?.cast::<vk::CommandBufferAllocateInfo<'_>>();
+            (
+                allocate_info.command_buffer_count,
+                Some(Object::new(allocate_info.command_pool)),
+            )
+        }
+        Function::AllocateDescriptorSets => {
+            let allocate_info =
+                &*info("p_allocate_info")?.cast::<vk::DescriptorSetAllocateInfo<'_>>();
+            (
+                allocate_info.descriptor_set_count,
+                Some(Object::new(allocate_info.descriptor_pool)),
+            )
+        }
+        Function::CreateGraphicsPipelines | Function::CreateComputePipelines => {
+            let Some(Value::U32(count)) = call.arg("create_info_count") else {
+                return None;
+            };
+            (count, Some(Object::new(call.device)))
+        }
+        function if function.is_device_level() => (1, Some(Object::new(call.device))),
+        _ => (1, Some(Object::new(call.instance))),
+    };
+
+    let handles = output.value.handles(count as usize)?;
+    let objects = handles
+        .iter()
+        .filter(|&&handle| handle != 0)
+        .map(|&handle| Object {
+            object_type,
+            handle,
+        })
+        .collect();
+    Some((objects, parent))
+}
+
+/// The objects destroyed by a call
+unsafe fn destroyed(call: &Call<'_>) -> Vec<Object> {
+    let handle = |index: usize| match call.args.get(index).map(|arg| arg.value) {
+        Some(Value::Handle(object_type, handle)) if handle != 0 => vec![Object {
+            object_type,
+            handle,
+        }],
+        _ => Vec::new(),
+    };
+    match call.function {
+        Function::DestroyInstance | Function::DestroyDevice => handle(0),
+        Function::FreeCommandBuffers | Function::FreeDescriptorSets => used_handles(call)
+            .into_iter()
+            .filter(|object| {
+                matches!(
+                    object.object_type,
+                    vk::ObjectType::COMMAND_BUFFER | vk::ObjectType::DESCRIPTOR_SET
+                )
+            })
+            .collect(),
+        function
+            if function.name().starts_with("vkDestroy") || function == Function::FreeMemory =>
+        {
+            handle(1)
+        }
+        _ => Vec::new(),
+    }
+}
+
+impl Interceptor for LifetimeTracker {
+    fn before(&self, call: &Call<'_>) -> super::Flow {
+        let issues = {
+            let state = self.state.lock().unwrap();
+            if state.destroyed.is_empty() {
+                return super::Flow::Continue;
+            }
+            unsafe { used_handles(call) }
+                .into_iter()
+                .filter_map(|object| {
+                    state
+                        .destroyed
+                        .get(&object)
+                        .map(|&(destroyed_by, _)| Issue::UseAfterDestroy {
+                            function: call.function,
+                            object,
+                            destroyed_by,
+                        })
+                })
+                .collect::<Vec<_>>()
+        };
+        issues.iter().for_each(&self.on_issue);
+        super::Flow::Continue
+    }
+
+    fn after(&self, call: &Call<'_>, result: Option<vk::Result>) {
+        if result.is_some_and(|result| result != vk::Result::SUCCESS) {
+            return;
+        }
+
+        let mut issues = Vec::new();
+        if let Some((objects, parent)) = unsafe { created(call) } {
+            let mut state = self.state.lock().unwrap();
+            for object in objects {
+                let backtrace = self
+                    .backtraces
+                    .then(|| Arc::new(Backtrace::force_capture()));
+                state.create(object, parent, call.function, backtrace);
+            }
+        }
+
+        for object in unsafe { destroyed(call) } {
+            let descendants = self.state.lock().unwrap().destroy(object, call.function);
+            if descendants.is_empty() {
+                continue;
+            }
+            match object.object_type {
+                vk::ObjectType::INSTANCE => issues.push(Issue::Leaks {
+                    instance: object,
+                    objects: descendants,
+                }),
+                // Pools free their command buffers and descriptor sets
+                vk::ObjectType::COMMAND_POOL | vk::ObjectType::DESCRIPTOR_POOL => {}
+                _ => issues.push(Issue::DestroyedWithChildren {
+                    function: call.function,
+                    parent: object,
+                    children: descendants,
+                }),
+            }
+        }
+        if call.function == Function::ResetDescriptorPool {
+            if let Some(Value::Handle(object_type, handle)) = call.arg("descriptor_pool") {
+                let pool = Object {
+                    object_type,
+                    handle,
+                };
+                let mut state = self.state.lock().unwrap();
+                let sets = state.children.get(&pool).cloned().unwrap_or_default();
+                for set in sets {
+                    state.destroy(set, call.function);
+                }
+            }
+        }
+
+        issues.iter().for_each(&self.on_issue);
+    }
+}
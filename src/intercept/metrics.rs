@@ -0,0 +1,310 @@
+//! Counting the calls to each Vulkan function and measuring their latency on the CPU.
+//!
+//! [`CallMetrics`] is an [`Interceptor`] keeping per-function call and error counters and a
+//! latency histogram in atomics, so measuring a call never takes a lock. [`CallMetrics::poll`]
+//! summarizes the calls made since the previous poll, usually one frame, and
+//! [`CallMetrics::snapshot`] the calls made since the metrics were created:
+//!
+//! ```
+//! use ash::vk;
+//! use ash_molten::intercept::{self, metrics::CallMetrics, Function, InterceptorBuilder};
+//! use std::sync::Arc;
+//!
+//! # unsafe {
+//! let entry = intercept::wrap(&ash_molten::MoltenEntry::load().unwrap()).unwrap();
+//! let metrics = Arc::new(CallMetrics::new());
+//! let id = InterceptorBuilder::new(metrics.clone()).register();
+//!
+//! let instance = entry
+//!     .create_instance(&vk::InstanceCreateInfo::default(), None)
+//!     .unwrap();
+//! let physical_device = instance.enumerate_physical_devices().unwrap()[0];
+//! let queue_create_info = vk::DeviceQueueCreateInfo::default().queue_priorities(&[1.0]);
+//! let device = instance
+//!     .create_device(
+//!         physical_device,
+//!         &vk::DeviceCreateInfo::default().queue_create_infos(&[queue_create_info]),
+//!         None,
+//!     )
+//!     .unwrap();
+//! metrics.poll();
+//!
+//! for _ in 0..3 {
+//!     let fence = device
+//!         .create_fence(&vk::FenceCreateInfo::default(), None)
+//!         .unwrap();
+//!     device.destroy_fence(fence, None);
+//! }
+//! let frame = metrics.poll();
+//! let create_fence = frame.function(Function::CreateFence).unwrap();
+//! assert_eq!(create_fence.calls, 3);
+//! assert_eq!(create_fence.errors, 0);
+//! assert!(create_fence.quantile_ns(0.5) > 0);
+//! assert_eq!(frame.total_calls(), 6);
+//!
+//! device.destroy_device(None);
+//! instance.destroy_instance(None);
+//! intercept::unregister(id);
+//! # }
+//! ```
+//!
+//! The latency of a call includes the interceptors registered after the metrics, so register
+//! them last to measure the driver alone. With the `tracing` feature, `CallMetrics::spans`
+//! also enters a `tracing` span around every call, which `tracing-tracy` turns into Tracy
+//! zones.
+
+use super::{Call, Flow, Function, Interceptor, InterceptorBuilder, InterceptorId};
+use ash::vk;
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+/// The `tracing` target of the spans entered by [`CallMetrics::spans`]
+#[cfg(feature = "tracing")]
+pub const CALLS_TARGET: &str = "moltenvk::calls";
+
+/// The number of buckets of the latency histograms. Bucket 0 counts the calls taking no
+/// measurable time, bucket `i` the calls taking from `2^(i-1)` up to `2^i` nanoseconds, and
+/// the last bucket every call taking 2^30 nanoseconds, about a second, or longer
+pub const BUCKETS: usize = 32;
+
+/// The bucket counting calls taking `nanos`
+fn bucket(nanos: u64) -> usize {
+    ((u64::BITS - nanos.leading_zeros()) as usize).min(BUCKETS - 1)
+}
+
+#[derive(Default)]
+struct Counters {
+    calls: AtomicU64,
+    errors: AtomicU64,
+    total_ns: AtomicU64,
+    histogram: [AtomicU64; BUCKETS],
+}
+
+impl Counters {
+    fn load(&self) -> FunctionCalls {
+        FunctionCalls {
+            calls: self.calls.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            total_ns: self.total_ns.load(Ordering::Relaxed),
+            histogram: std::array::from_fn(|bucket| self.histogram[bucket].load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// The calls made to one function
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FunctionCalls {
+    /// The number of calls
+    pub calls: u64,
+    /// The number of calls returning an error
+    pub errors: u64,
+    /// The time spent in the calls, in nanoseconds
+    pub total_ns: u64,
+    /// The number of calls in each latency bucket, see [`BUCKETS`]
+    pub histogram: [u64; BUCKETS],
+}
+
+impl FunctionCalls {
+    /// The mean latency, in nanoseconds
+    pub fn mean_ns(&self) -> u64 {
+        self.total_ns.checked_div(self.calls).unwrap_or(0)
+    }
+
+    /// An upper bound of the latency under which a `quantile` of the calls took, in
+    /// nanoseconds, eg. `0.99` for the 99th percentile. The bound is a power of two, or
+    /// `u64::MAX` for the calls in the last bucket
+    pub fn quantile_ns(&self, quantile: f64) -> u64 {
+        let rank = (quantile.clamp(0.0, 1.0) * self.calls as f64).ceil() as u64;
+        let mut seen = 0;
+        for (bucket, &count) in self.histogram.iter().enumerate() {
+            seen += count;
+            if count > 0 && seen >= rank {
+                return if bucket == BUCKETS - 1 {
+                    u64::MAX
+                } else {
+                    1 << bucket
+                };
+            }
+        }
+        0
+    }
+
+    /// The calls made since `earlier`, a measurement of the same function taken before. The
+    /// counts saturate at 0 if `earlier` was taken after all
+    fn since(&self, earlier: &Self) -> Self {
+        Self {
+            calls: self.calls.saturating_sub(earlier.calls),
+            errors: self.errors.saturating_sub(earlier.errors),
+            total_ns: self.total_ns.saturating_sub(earlier.total_ns),
+            histogram: std::array::from_fn(|bucket| {
+                self.histogram[bucket].saturating_sub(earlier.histogram[bucket])
+            }),
+        }
+    }
+}
+
+/// The calls made to each function, between two polls of a [`CallMetrics`] or since its
+/// creation
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CallSnapshot {
+    /// The functions that were called, by their name, eg. `vkQueueSubmit`
+    pub functions: BTreeMap<&'static str, FunctionCalls>,
+}
+
+impl CallSnapshot {
+    /// The calls made to `function`, `None` if there were none
+    pub fn function(&self, function: Function) -> Option<&FunctionCalls> {
+        self.functions.get(function.name())
+    }
+
+    /// The number of calls made to all functions
+    pub fn total_calls(&self) -> u64 {
+        self.functions.values().map(|calls| calls.calls).sum()
+    }
+
+    /// The calls made since `earlier`, a snapshot of the same metrics taken before
+    pub fn since(&self, earlier: &Self) -> Self {
+        let functions = self
+            .functions
+            .iter()
+            .map(|(&name, calls)| match earlier.functions.get(name) {
+                Some(earlier) => (name, calls.since(earlier)),
+                None => (name, calls.clone()),
+            })
+            .filter(|(_, calls)| calls.calls > 0)
+            .collect();
+        Self { functions }
+    }
+}
+
+/// The start of a call on this thread, which the matching [`Interceptor::after`] pops
+struct Started {
+    at: Instant,
+    #[cfg(feature = "tracing")]
+    _span: Option<tracing::span::EnteredSpan>,
+}
+
+thread_local! {
+    // Calls nest, and the interceptors that ran before a call run after it in reverse order
+    static STARTED: RefCell<Vec<Started>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Counts the calls to every intercepted function and measures their latency
+pub struct CallMetrics {
+    functions: Box<[Counters]>,
+    previous: Mutex<CallSnapshot>,
+    #[cfg(feature = "tracing")]
+    spans: bool,
+}
+
+impl Default for CallMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CallMetrics {
+    /// Metrics for every function, without spans
+    pub fn new() -> Self {
+        Self {
+            functions: Function::ALL.iter().map(|_| Counters::default()).collect(),
+            previous: Mutex::default(),
+            #[cfg(feature = "tracing")]
+            spans: false,
+        }
+    }
+
+    /// Also enters a span at the trace level with [`CALLS_TARGET`] around every call, named
+    /// `vulkan` with the function name as its `function` field
+    #[cfg(feature = "tracing")]
+    pub fn spans(mut self) -> Self {
+        self.spans = true;
+        self
+    }
+
+    /// The calls made since the metrics were created
+    pub fn snapshot(&self) -> CallSnapshot {
+        let functions = Function::ALL
+            .iter()
+            .zip(self.functions.iter())
+            .map(|(function, counters)| (function.name(), counters.load()))
+            .filter(|(_, calls)| calls.calls > 0)
+            .collect();
+        CallSnapshot { functions }
+    }
+
+    /// The calls made since the previous poll, or since the metrics were created
+    pub fn poll(&self) -> CallSnapshot {
+        // Snapshotting under the lock keeps concurrent polls ordered
+        let mut previous = self.previous.lock().unwrap();
+        let current = self.snapshot();
+        let calls = current.since(&previous);
+        *previous = current;
+        calls
+    }
+
+    /// An [`InterceptorBuilder`] for the metrics. Keep a clone of an `Arc` of the metrics and
+    /// use [`InterceptorBuilder::new`] instead to read them later
+    pub fn builder(self) -> InterceptorBuilder {
+        InterceptorBuilder::new(std::sync::Arc::new(self))
+    }
+
+    /// Registers the metrics for all instances and devices
+    pub fn register(self) -> InterceptorId {
+        self.builder().register()
+    }
+}
+
+impl fmt::Debug for CallMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallMetrics")
+            .field("calls", &self.snapshot().total_calls())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Interceptor for CallMetrics {
+    fn before(&self, call: &Call<'_>) -> Flow {
+        #[cfg(feature = "tracing")]
+        let span = self.spans.then(|| {
+            tracing::trace_span!(target: CALLS_TARGET, "vulkan", function = call.function.name())
+                .entered()
+        });
+        #[cfg(not(feature = "tracing"))]
+        let _ = call;
+
+        let started = Started {
+            #[cfg(feature = "tracing")]
+            _span: span,
+            // Last, to leave the span creation out of the measurement
+            at: Instant::now(),
+        };
+        STARTED.with(|started_calls| started_calls.borrow_mut().push(started));
+        Flow::Continue
+    }
+
+    fn after(&self, call: &Call<'_>, result: Option<vk::Result>) {
+        let Some(started) = STARTED.with(|started_calls| started_calls.borrow_mut().pop()) else {
+            return;
+        };
+        let nanos = u64::try_from(started.at.elapsed().as_nanos()).unwrap_or(u64::MAX);
+
+        let counters = &self.functions[call.function as usize];
+        counters.calls.fetch_add(1, Ordering::Relaxed);
+        if result.is_some_and(|result| result.as_raw() < 0) {
+            counters.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        counters.total_ns.fetch_add(nanos, Ordering::Relaxed);
+        counters.histogram[bucket(nanos)].fetch_add(1, Ordering::Relaxed);
+    }
+}
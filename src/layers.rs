@@ -0,0 +1,1668 @@
+//! Hosting Vulkan layers in-process on top of the static MoltenVK, enabled with the `layers`
+//! feature.
+//!
+//! Without the Vulkan loader nothing builds a layer chain, so [`LayerHost`] implements the
+//! loader side of the loader-layer interface itself. It reads explicit layer manifests from
+//! the same directories as the loader, opens the layer libraries when an instance enables
+//! them, negotiates with `vkNegotiateLoaderLayerInterfaceVersion` and chains the layers over
+//! the statically linked `vkGetInstanceProcAddr`, so layers like `VK_LAYER_KHRONOS_validation`
+//! run against MoltenVK without the loader.
+//!
+//! ```no_run
+//! let entry = ash_molten::layers::LayerHost::new().install().unwrap();
+//! let instance = unsafe {
+//!     ash_molten::InstanceBuilder::new()
+//!         .layer(c"VK_LAYER_KHRONOS_validation")
+//!         .create(&entry)
+//! }
+//! .unwrap();
+//! ```
+//!
+//! Layers listed in `VK_INSTANCE_LAYERS` are enabled on every instance, ahead of the layers the
+//! application enables. Implicit layers aren't loaded.
+//!
+//! # Dispatch
+//!
+//! Layers find their state through the dispatch key of a handle, the pointer its first word
+//! points to. Like the loader, the host overwrites the first word of instances, physical
+//! devices, devices, queues and command buffers that MoltenVK creates with a pointer to its own
+//! per-instance or per-device state, as the loader-ICD interface allows.
+
+use self::chain::get_instance_proc_addr;
+use crate::{EntrySource, LoadError, MoltenEntry};
+use ash::vk;
+use std::{
+    collections::HashMap,
+    ffi::{c_char, c_void, CStr, CString},
+    fmt, fs,
+    mem::transmute,
+    path::{Path, PathBuf},
+    ptr,
+    sync::{Arc, Mutex},
+};
+
+/// The newest loader-layer interface version the host supports
+pub const CURRENT_LOADER_LAYER_INTERFACE_VERSION: u32 = 2;
+
+/// The environment variable listing layers enabled on every instance, separated by `:`
+pub const INSTANCE_LAYERS_ENV_VAR: &str = "VK_INSTANCE_LAYERS";
+
+/// `PFN_GetPhysicalDeviceProcAddr` from `vk_layer.h`
+#[allow(non_camel_case_types)]
+pub type PFN_GetPhysicalDeviceProcAddr =
+    unsafe extern "system" fn(vk::Instance, *const c_char) -> vk::PFN_vkVoidFunction;
+
+/// `PFN_vkNegotiateLoaderLayerInterfaceVersion` from `vk_layer.h`
+#[allow(non_camel_case_types)]
+pub type PFN_vkNegotiateLoaderLayerInterfaceVersion =
+    unsafe extern "system" fn(*mut NegotiateLayerInterface) -> vk::Result;
+
+/// `PFN_vkSetInstanceLoaderData` from `vk_layer.h`
+#[allow(non_camel_case_types)]
+pub type PFN_vkSetInstanceLoaderData =
+    unsafe extern "system" fn(vk::Instance, *mut c_void) -> vk::Result;
+
+/// `PFN_vkSetDeviceLoaderData` from `vk_layer.h`
+#[allow(non_camel_case_types)]
+pub type PFN_vkSetDeviceLoaderData =
+    unsafe extern "system" fn(vk::Device, *mut c_void) -> vk::Result;
+
+/// `VK_LAYER_LINK_INFO` of `VkLayerFunction` from `vk_layer.h`
+pub const LAYER_LINK_INFO: u32 = 0;
+/// `VK_LOADER_DATA_CALLBACK` of `VkLayerFunction` from `vk_layer.h`
+pub const LOADER_DATA_CALLBACK: u32 = 1;
+
+/// `LAYER_NEGOTIATE_INTERFACE_STRUCT` of `VkNegotiateLayerStructType` from `vk_layer.h`
+pub const LAYER_NEGOTIATE_INTERFACE_STRUCT: u32 = 1;
+
+/// `VkNegotiateLayerInterface` from `vk_layer.h`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct NegotiateLayerInterface {
+    pub s_type: u32,
+    pub p_next: *mut c_void,
+    pub loader_layer_interface_version: u32,
+    pub pfn_get_instance_proc_addr: Option<vk::PFN_vkGetInstanceProcAddr>,
+    pub pfn_get_device_proc_addr: Option<vk::PFN_vkGetDeviceProcAddr>,
+    pub pfn_get_physical_device_proc_addr: Option<PFN_GetPhysicalDeviceProcAddr>,
+}
+
+/// `VkLayerInstanceLink` from `vk_layer.h`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct LayerInstanceLink {
+    pub p_next: *mut LayerInstanceLink,
+    pub pfn_next_get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+    pub pfn_next_get_physical_device_proc_addr: Option<PFN_GetPhysicalDeviceProcAddr>,
+}
+
+/// The union in `VkLayerInstanceCreateInfo`, with the members the host passes
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union LayerInstanceCreateInfoUnion {
+    pub p_layer_info: *mut LayerInstanceLink,
+    pub pfn_set_instance_loader_data: PFN_vkSetInstanceLoaderData,
+    /// `layerDevice`, which has the largest size
+    pub layer_device: [*const c_void; 2],
+}
+
+/// `VkLayerInstanceCreateInfo` from `vk_layer.h`, chained into `vkCreateInstance` with
+/// [`vk::StructureType::LOADER_INSTANCE_CREATE_INFO`]
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct LayerInstanceCreateInfo {
+    pub s_type: vk::StructureType,
+    pub p_next: *const c_void,
+    pub function: u32,
+    pub u: LayerInstanceCreateInfoUnion,
+}
+
+/// `VkLayerDeviceLink` from `vk_layer.h`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct LayerDeviceLink {
+    pub p_next: *mut LayerDeviceLink,
+    pub pfn_next_get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+    pub pfn_next_get_device_proc_addr: vk::PFN_vkGetDeviceProcAddr,
+}
+
+/// The union in `VkLayerDeviceCreateInfo`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union LayerDeviceCreateInfoUnion {
+    pub p_layer_info: *mut LayerDeviceLink,
+    pub pfn_set_device_loader_data: PFN_vkSetDeviceLoaderData,
+}
+
+/// `VkLayerDeviceCreateInfo` from `vk_layer.h`, chained into `vkCreateDevice` with
+/// [`vk::StructureType::LOADER_DEVICE_CREATE_INFO`]
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct LayerDeviceCreateInfo {
+    pub s_type: vk::StructureType,
+    pub p_next: *const c_void,
+    pub function: u32,
+    pub u: LayerDeviceCreateInfoUnion,
+}
+
+/// Failed to find or load a layer
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LayerError {
+    /// A layer manifest couldn't be read or parsed
+    Manifest { path: PathBuf, message: String },
+    /// No installed layer has the name
+    NotFound(CString),
+    /// The library of a layer couldn't be opened, or doesn't export the layer's entry points
+    Library { name: CString, message: String },
+    /// The layer doesn't support any loader-layer interface version the host supports
+    Negotiation { name: CString, result: vk::Result },
+    /// The statically linked MoltenVK couldn't be loaded
+    Load(LoadError),
+}
+
+impl fmt::Display for LayerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Manifest { path, message } => {
+                write!(f, "invalid layer manifest {}: {message}", path.display())
+            }
+            Self::NotFound(name) => write!(f, "layer {name:?} isn't installed"),
+            Self::Library { name, message } => {
+                write!(f, "failed to load layer {name:?}: {message}")
+            }
+            Self::Negotiation { name, result } => {
+                write!(f, "failed to negotiate with layer {name:?}: {result}")
+            }
+            Self::Load(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for LayerError {}
+
+impl From<LoadError> for LayerError {
+    fn from(error: LoadError) -> Self {
+        Self::Load(error)
+    }
+}
+
+/// An extension provided by a layer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerExtension {
+    pub name: CString,
+    pub spec_version: u32,
+}
+
+/// What `vkEnumerateInstanceLayerProperties` reports for a layer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerProperties {
+    pub name: CString,
+    /// The Vulkan version the layer was written against
+    pub spec_version: u32,
+    pub implementation_version: u32,
+    pub description: String,
+    pub instance_extensions: Vec<LayerExtension>,
+    pub device_extensions: Vec<LayerExtension>,
+}
+
+impl LayerProperties {
+    /// Properties with only a name, for [`LayerHost::static_layer`]
+    pub fn new(name: &CStr) -> Self {
+        Self {
+            name: name.to_owned(),
+            spec_version: vk::HEADER_VERSION_COMPLETE,
+            implementation_version: 1,
+            description: String::new(),
+            instance_extensions: Vec::new(),
+            device_extensions: Vec::new(),
+        }
+    }
+
+    fn to_vk(&self) -> vk::LayerProperties {
+        let mut properties = vk::LayerProperties {
+            spec_version: self.spec_version,
+            implementation_version: self.implementation_version,
+            ..Default::default()
+        };
+        copy_str(self.name.to_bytes(), &mut properties.layer_name);
+        copy_str(self.description.as_bytes(), &mut properties.description);
+        properties
+    }
+}
+
+/// A layer described by a JSON manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerManifest {
+    /// The manifest file
+    pub path: PathBuf,
+    pub properties: LayerProperties,
+    /// The layer library, resolved relative to the manifest if it contains a directory,
+    /// otherwise found through the dynamic linker's search path
+    pub library_path: PathBuf,
+    /// Entry point names overridden by the manifest's `functions`, by their Vulkan name
+    pub functions: HashMap<String, String>,
+}
+
+impl LayerManifest {
+    /// Reads the layers of a manifest, which may describe one layer or several. Meta layers,
+    /// which have no library, are skipped
+    pub fn read(path: &Path) -> Result<Vec<Self>, LayerError> {
+        let invalid = |message: String| LayerError::Manifest {
+            path: path.to_owned(),
+            message,
+        };
+
+        let json = fs::read_to_string(path).map_err(|error| invalid(error.to_string()))?;
+        let json: serde_json::Value =
+            serde_json::from_str(&json).map_err(|error| invalid(error.to_string()))?;
+
+        let layers = match (json.get("layer"), json.get("layers")) {
+            (Some(layer), _) => std::slice::from_ref(layer),
+            (None, Some(serde_json::Value::Array(layers))) => layers.as_slice(),
+            _ => {
+                return Err(invalid(
+                    "neither `layer` nor `layers` is present".to_owned(),
+                ))
+            }
+        };
+
+        layers
+            .iter()
+            .filter(|layer| layer.get("library_path").is_some())
+            .map(|layer| Self::parse_layer(path, layer).map_err(invalid))
+            .collect()
+    }
+
+    fn parse_layer(path: &Path, layer: &serde_json::Value) -> Result<Self, String> {
+        let string = |key: &str| {
+            layer
+                .get(key)
+                .and_then(serde_json::Value::as_str)
+                .ok_or_else(|| format!("`{key}` is missing"))
+        };
+        let c_string = |name: &str| CString::new(name).map_err(|error| error.to_string());
+        let extensions = |key: &str| {
+            layer
+                .get(key)
+                .and_then(serde_json::Value::as_array)
+                .map_or(&[][..], Vec::as_slice)
+                .iter()
+                .map(|extension| {
+                    let name = extension
+                        .get("name")
+                        .and_then(serde_json::Value::as_str)
+                        .ok_or_else(|| format!("an extension in `{key}` has no name"))?;
+                    Ok(LayerExtension {
+                        name: c_string(name)?,
+                        spec_version: extension.get("spec_version").map_or(0, parse_u32),
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()
+        };
+
+        let library_path = Path::new(string("library_path")?);
+        let library_path = if library_path.is_relative() && library_path.components().count() > 1 {
+            path.parent().unwrap_or(Path::new("")).join(library_path)
+        } else {
+            library_path.to_owned()
+        };
+
+        let functions = layer
+            .get("functions")
+            .and_then(serde_json::Value::as_object)
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, symbol)| Some((name.clone(), symbol.as_str()?.to_owned())))
+            .collect();
+
+        Ok(Self {
+            path: path.to_owned(),
+            properties: LayerProperties {
+                name: c_string(string("name")?)?,
+                spec_version: parse_api_version(string("api_version")?),
+                implementation_version: layer.get("implementation_version").map_or(0, parse_u32),
+                description: layer
+                    .get("description")
+                    .and_then(serde_json::Value::as_str)
+                    .unwrap_or_default()
+                    .to_owned(),
+                instance_extensions: extensions("instance_extensions")?,
+                device_extensions: extensions("device_extensions")?,
+            },
+            library_path,
+            functions,
+        })
+    }
+
+    /// The symbol implementing the Vulkan function `name` in the library
+    fn symbol(&self, name: &str) -> String {
+        self.functions
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_owned())
+    }
+}
+
+/// Manifest versions are strings, but some manifests use numbers
+fn parse_u32(value: &serde_json::Value) -> u32 {
+    match value {
+        serde_json::Value::Number(number) => number.as_u64().map_or(0, |n| n as u32),
+        serde_json::Value::String(string) => string.trim().parse().unwrap_or_default(),
+        _ => 0,
+    }
+}
+
+/// Parses `major.minor.patch` into a packed Vulkan version
+fn parse_api_version(version: &str) -> u32 {
+    let mut parts = version
+        .split('.')
+        .map(|part| part.trim().parse().unwrap_or(0));
+    let mut next = || parts.next().unwrap_or(0);
+    vk::make_api_version(0, next(), next(), next())
+}
+
+fn copy_str(value: &[u8], buffer: &mut [c_char]) {
+    let len = value.len().min(buffer.len() - 1);
+    for (dst, src) in buffer.iter_mut().zip(&value[..len]) {
+        *dst = *src as c_char;
+    }
+    buffer[len] = 0;
+}
+
+/// The directories the Vulkan loader reads explicit layer manifests from on macOS and Linux.
+///
+/// `VK_LAYER_PATH` replaces the defaults and `VK_ADD_LAYER_PATH` is searched before them,
+/// both separated by `:`.
+pub fn default_search_paths() -> Vec<PathBuf> {
+    let split = |name: &str| {
+        std::env::var_os(name)
+            .map(|paths| std::env::split_paths(&paths).collect::<Vec<_>>())
+            .unwrap_or_default()
+    };
+
+    let mut paths = split("VK_ADD_LAYER_PATH");
+    if std::env::var_os("VK_LAYER_PATH").is_some() {
+        paths.extend(split("VK_LAYER_PATH"));
+        return paths;
+    }
+
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let xdg = |name: &str, home_default: &str, defaults: &[&str]| {
+        let mut dirs = split(name);
+        if dirs.is_empty() {
+            dirs.extend(home.iter().map(|home| home.join(home_default)));
+            dirs.extend(defaults.iter().map(PathBuf::from));
+        }
+        dirs
+    };
+
+    let config = xdg("XDG_CONFIG_HOME", ".config", &[])
+        .into_iter()
+        .chain(
+            xdg("XDG_CONFIG_DIRS", "", &["/etc/xdg"])
+                .into_iter()
+                .skip(1),
+        )
+        .chain([PathBuf::from("/etc")]);
+    let data = xdg("XDG_DATA_HOME", ".local/share", &[]).into_iter().chain(
+        xdg("XDG_DATA_DIRS", "", &["/usr/local/share", "/usr/share"])
+            .into_iter()
+            .skip(1),
+    );
+
+    paths.extend(
+        config
+            .chain(data)
+            .map(|dir| dir.join("vulkan/explicit_layer.d")),
+    );
+    paths
+}
+
+/// Where a layer's entry points come from
+#[derive(Clone)]
+enum LayerSource {
+    Manifest(LayerManifest),
+    Static(LayerProperties, PFN_vkNegotiateLoaderLayerInterfaceVersion),
+}
+
+impl LayerSource {
+    fn properties(&self) -> &LayerProperties {
+        match self {
+            Self::Manifest(manifest) => &manifest.properties,
+            Self::Static(properties, _) => properties,
+        }
+    }
+}
+
+/// Discovers layers and installs the in-process layer host.
+///
+/// The host is process-wide, installing it again replaces the layers new instances can enable.
+#[derive(Clone)]
+pub struct LayerHost {
+    search_paths: Vec<PathBuf>,
+    manifests: Vec<PathBuf>,
+    static_layers: Vec<(LayerProperties, PFN_vkNegotiateLoaderLayerInterfaceVersion)>,
+}
+
+impl Default for LayerHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LayerHost {
+    /// Creates a host reading manifests from [`default_search_paths`]
+    pub fn new() -> Self {
+        Self {
+            search_paths: default_search_paths(),
+            manifests: Vec::new(),
+            static_layers: Vec::new(),
+        }
+    }
+
+    /// Creates a host without any search paths
+    pub fn empty() -> Self {
+        Self {
+            search_paths: Vec::new(),
+            manifests: Vec::new(),
+            static_layers: Vec::new(),
+        }
+    }
+
+    /// Reads every `*.json` manifest in `dir` too, skipping the ones that can't be read
+    pub fn search_path(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.search_paths.push(dir.into());
+        self
+    }
+
+    /// Reads a manifest, [`LayerHost::install`] fails if it can't be read
+    pub fn manifest(mut self, path: impl Into<PathBuf>) -> Self {
+        self.manifests.push(path.into());
+        self
+    }
+
+    /// Adds a layer implemented in this process, such as one written in Rust, whose
+    /// `vkNegotiateLoaderLayerInterfaceVersion` is `negotiate`
+    pub fn static_layer(
+        mut self,
+        properties: LayerProperties,
+        negotiate: PFN_vkNegotiateLoaderLayerInterfaceVersion,
+    ) -> Self {
+        self.static_layers.push((properties, negotiate));
+        self
+    }
+
+    /// The layers that can be enabled, the first one wins if several have the same name
+    pub fn layers(&self) -> Result<Vec<LayerProperties>, LayerError> {
+        Ok(self
+            .sources()?
+            .iter()
+            .map(|source| source.properties().clone())
+            .collect())
+    }
+
+    fn sources(&self) -> Result<Vec<LayerSource>, LayerError> {
+        let mut sources = self
+            .static_layers
+            .iter()
+            .map(|(properties, negotiate)| LayerSource::Static(properties.clone(), *negotiate))
+            .collect::<Vec<_>>();
+
+        for path in &self.manifests {
+            sources.extend(
+                LayerManifest::read(path)?
+                    .into_iter()
+                    .map(LayerSource::Manifest),
+            );
+        }
+
+        for dir in &self.search_paths {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            let mut paths = entries
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .collect::<Vec<_>>();
+            paths.sort();
+
+            for path in paths {
+                let manifests = LayerManifest::read(&path).unwrap_or_default();
+                sources.extend(manifests.into_iter().map(LayerSource::Manifest));
+            }
+        }
+
+        let mut seen = Vec::new();
+        sources.retain(|source| {
+            let name = &source.properties().name;
+            let first = !seen.contains(name);
+            seen.push(name.clone());
+            first
+        });
+        Ok(sources)
+    }
+
+    /// Installs the host and returns an entry whose instances can enable the discovered
+    /// layers, with [`EntrySource::LayerHost`]
+    pub fn install(self) -> Result<MoltenEntry, LayerError> {
+        if !crate::is_supported() {
+            return Err(LoadError::UnsupportedPlatform.into());
+        }
+
+        let sources = self.sources()?;
+        HOST.lock().unwrap().sources = sources;
+
+        #[allow(unsafe_code)]
+        let entry = unsafe { MoltenEntry::from_get_instance_proc_addr(get_instance_proc_addr)? };
+        Ok(entry.with_source(EntrySource::LayerHost))
+    }
+}
+
+impl fmt::Debug for LayerHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LayerHost")
+            .field("search_paths", &self.search_paths)
+            .field("manifests", &self.manifests)
+            .field(
+                "static_layers",
+                &self
+                    .static_layers
+                    .iter()
+                    .map(|(properties, _)| &properties.name)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Loads an installed layer ahead of instance creation, reporting why it can't be loaded.
+///
+/// Instance creation only fails with `VK_ERROR_LAYER_NOT_PRESENT` in that case.
+pub fn load_layer(name: &CStr) -> Result<(), LayerError> {
+    HOST.lock().unwrap().load(name).map(drop)
+}
+
+/// A layer whose entry points were negotiated
+struct LoadedLayer {
+    properties: LayerProperties,
+    /// Keeps the entry points of layers from manifests alive
+    _library: Option<libloading::Library>,
+    get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+    get_device_proc_addr: Option<vk::PFN_vkGetDeviceProcAddr>,
+    get_physical_device_proc_addr: Option<PFN_GetPhysicalDeviceProcAddr>,
+}
+
+impl LoadedLayer {
+    #[allow(unsafe_code)]
+    unsafe fn open(manifest: &LayerManifest) -> Result<Self, LayerError> {
+        let name = &manifest.properties.name;
+        let library_error = |message: String| LayerError::Library {
+            name: name.clone(),
+            message,
+        };
+
+        let library = libloading::Library::new(&manifest.library_path)
+            .map_err(|error| library_error(error.to_string()))?;
+        let symbol = |function: &str| {
+            let symbol = manifest.symbol(function);
+            library
+                .get::<unsafe extern "system" fn()>(symbol.as_bytes())
+                .ok()
+                .map(|symbol| *symbol)
+        };
+
+        let negotiate = symbol("vkNegotiateLoaderLayerInterfaceVersion");
+        let exported = (
+            symbol("vkGetInstanceProcAddr"),
+            symbol("vkGetDeviceProcAddr"),
+        );
+
+        // Layers older than interface version 2 only export their entry points
+        let mut layer = if let Some(negotiate) = negotiate {
+            Self::negotiate(
+                manifest.properties.clone(),
+                transmute::<unsafe extern "system" fn(), PFN_vkNegotiateLoaderLayerInterfaceVersion>(
+                    negotiate,
+                ),
+            )?
+        } else {
+            let get_instance_proc_addr = exported
+                .0
+                .ok_or_else(|| library_error("vkGetInstanceProcAddr isn't exported".to_owned()))?;
+            Self {
+                properties: manifest.properties.clone(),
+                _library: None,
+                get_instance_proc_addr: transmute::<
+                    unsafe extern "system" fn(),
+                    vk::PFN_vkGetInstanceProcAddr,
+                >(get_instance_proc_addr),
+                get_device_proc_addr: None,
+                get_physical_device_proc_addr: None,
+            }
+        };
+
+        if layer.get_device_proc_addr.is_none() {
+            layer.get_device_proc_addr = exported.1.map(|get_device_proc_addr| {
+                transmute::<unsafe extern "system" fn(), vk::PFN_vkGetDeviceProcAddr>(
+                    get_device_proc_addr,
+                )
+            });
+        }
+        layer._library = Some(library);
+        Ok(layer)
+    }
+
+    #[allow(unsafe_code)]
+    unsafe fn negotiate(
+        properties: LayerProperties,
+        negotiate: PFN_vkNegotiateLoaderLayerInterfaceVersion,
+    ) -> Result<Self, LayerError> {
+        let mut interface = NegotiateLayerInterface {
+            s_type: LAYER_NEGOTIATE_INTERFACE_STRUCT,
+            p_next: ptr::null_mut(),
+            loader_layer_interface_version: CURRENT_LOADER_LAYER_INTERFACE_VERSION,
+            pfn_get_instance_proc_addr: None,
+            pfn_get_device_proc_addr: None,
+            pfn_get_physical_device_proc_addr: None,
+        };
+
+        let result = negotiate(&mut interface);
+        let negotiation_error = |result| LayerError::Negotiation {
+            name: properties.name.clone(),
+            result,
+        };
+        if result != vk::Result::SUCCESS {
+            return Err(negotiation_error(result));
+        }
+        if interface.loader_layer_interface_version < 2 {
+            return Err(negotiation_error(vk::Result::ERROR_INCOMPATIBLE_DRIVER));
+        }
+        let Some(get_instance_proc_addr) = interface.pfn_get_instance_proc_addr else {
+            return Err(negotiation_error(vk::Result::ERROR_INITIALIZATION_FAILED));
+        };
+
+        Ok(Self {
+            properties,
+            _library: None,
+            get_instance_proc_addr,
+            get_device_proc_addr: interface.pfn_get_device_proc_addr,
+            get_physical_device_proc_addr: interface.pfn_get_physical_device_proc_addr,
+        })
+    }
+}
+
+/// The process-wide state of the host
+struct Host {
+    sources: Vec<LayerSource>,
+    /// Layers are loaded on first use and stay loaded, by name
+    loaded: Vec<Arc<LoadedLayer>>,
+}
+
+static HOST: Mutex<Host> = Mutex::new(Host {
+    sources: Vec::new(),
+    loaded: Vec::new(),
+});
+
+impl Host {
+    fn load(&mut self, name: &CStr) -> Result<Arc<LoadedLayer>, LayerError> {
+        if let Some(layer) = self
+            .loaded
+            .iter()
+            .find(|layer| layer.properties.name.as_c_str() == name)
+        {
+            return Ok(layer.clone());
+        }
+
+        let source = self
+            .sources
+            .iter()
+            .find(|source| source.properties().name.as_c_str() == name)
+            .ok_or_else(|| LayerError::NotFound(name.to_owned()))?;
+
+        #[allow(unsafe_code)]
+        let layer = Arc::new(unsafe {
+            match source {
+                LayerSource::Manifest(manifest) => LoadedLayer::open(manifest)?,
+                LayerSource::Static(properties, negotiate) => {
+                    LoadedLayer::negotiate(properties.clone(), *negotiate)?
+                }
+            }
+        });
+        self.loaded.push(layer.clone());
+        Ok(layer)
+    }
+}
+
+// The entry points of the host. The functions called by the application build the layer chain,
+// and the terminator functions below the last layer call MoltenVK and maintain dispatch keys.
+#[allow(unsafe_code)]
+mod chain {
+    use super::*;
+    use std::mem::transmute_copy;
+
+    macro_rules! void_fn {
+        ($f:expr, $pfn:ty) => {
+            Some(transmute::<$pfn, unsafe extern "system" fn()>($f))
+        };
+    }
+
+    /// Resolves a function pointer of type `F`
+    unsafe fn load<F: Copy>(function: vk::PFN_vkVoidFunction) -> Option<F> {
+        function.map(|function| transmute_copy::<unsafe extern "system" fn(), F>(&function))
+    }
+
+    /// The dispatch key of a dispatchable handle
+    unsafe fn key<T>(handle: impl vk::Handle) -> *mut T {
+        *(handle.as_raw() as *const *mut T)
+    }
+
+    unsafe fn set_key<T>(handle: impl vk::Handle, key: *const T) {
+        *(handle.as_raw() as *mut *const T) = key;
+    }
+
+    unsafe fn c_strs<'a>(names: *const *const c_char, count: u32) -> Vec<&'a CStr> {
+        if names.is_null() {
+            return Vec::new();
+        }
+        std::slice::from_raw_parts(names, count as usize)
+            .iter()
+            .map(|&name| CStr::from_ptr(name))
+            .collect()
+    }
+
+    /// The dispatch key of an instance and its physical devices
+    struct InstanceState {
+        /// The enabled layers, closest to the application first
+        layers: Vec<Arc<LoadedLayer>>,
+        /// The top of the instance chain
+        get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+        instance: vk::Instance,
+        destroy_instance: vk::PFN_vkDestroyInstance,
+        enumerate_physical_devices: vk::PFN_vkEnumeratePhysicalDevices,
+        enumerate_physical_device_groups: Option<vk::PFN_vkEnumeratePhysicalDeviceGroups>,
+        enumerate_device_extension_properties: vk::PFN_vkEnumerateDeviceExtensionProperties,
+        create_device: vk::PFN_vkCreateDevice,
+        get_device_proc_addr: vk::PFN_vkGetDeviceProcAddr,
+    }
+
+    /// The dispatch key of a device, its queues and command buffers
+    struct DeviceState {
+        get_device_proc_addr: vk::PFN_vkGetDeviceProcAddr,
+        destroy_device: vk::PFN_vkDestroyDevice,
+        get_device_queue: vk::PFN_vkGetDeviceQueue,
+        get_device_queue2: Option<vk::PFN_vkGetDeviceQueue2>,
+        allocate_command_buffers: Option<vk::PFN_vkAllocateCommandBuffers>,
+    }
+
+    /// The `vkGetInstanceProcAddr` of the host
+    pub(super) unsafe extern "system" fn get_instance_proc_addr(
+        instance: vk::Instance,
+        p_name: *const c_char,
+    ) -> vk::PFN_vkVoidFunction {
+        let global = match CStr::from_ptr(p_name).to_bytes() {
+            b"vkGetInstanceProcAddr" => {
+                void_fn!(get_instance_proc_addr, vk::PFN_vkGetInstanceProcAddr)
+            }
+            b"vkEnumerateInstanceExtensionProperties" => void_fn!(
+                enumerate_instance_extension_properties,
+                vk::PFN_vkEnumerateInstanceExtensionProperties
+            ),
+            b"vkEnumerateInstanceLayerProperties" => void_fn!(
+                enumerate_instance_layer_properties,
+                vk::PFN_vkEnumerateInstanceLayerProperties
+            ),
+            b"vkCreateInstance" => void_fn!(create_instance, vk::PFN_vkCreateInstance),
+            b"vkCreateDevice" if instance != vk::Instance::null() => {
+                void_fn!(create_device, vk::PFN_vkCreateDevice)
+            }
+            _ => None,
+        };
+
+        if global.is_some() {
+            global
+        } else if instance == vk::Instance::null() {
+            crate::static_get_instance_proc_addr()(instance, p_name)
+        } else {
+            ((*key::<InstanceState>(instance)).get_instance_proc_addr)(instance, p_name)
+        }
+    }
+
+    unsafe extern "system" fn enumerate_instance_extension_properties(
+        p_layer_name: *const c_char,
+        p_property_count: *mut u32,
+        p_properties: *mut vk::ExtensionProperties,
+    ) -> vk::Result {
+        if p_layer_name.is_null() {
+            let next = load::<vk::PFN_vkEnumerateInstanceExtensionProperties>(
+                crate::static_get_instance_proc_addr()(
+                    vk::Instance::null(),
+                    c"vkEnumerateInstanceExtensionProperties".as_ptr(),
+                ),
+            );
+            return match next {
+                Some(next) => next(p_layer_name, p_property_count, p_properties),
+                None => vk::Result::ERROR_INITIALIZATION_FAILED,
+            };
+        }
+
+        let name = CStr::from_ptr(p_layer_name);
+        let host = HOST.lock().unwrap();
+        match host
+            .sources
+            .iter()
+            .find(|source| source.properties().name.as_c_str() == name)
+        {
+            Some(source) => enumerate_extensions(
+                &source.properties().instance_extensions,
+                p_property_count,
+                p_properties,
+            ),
+            None => vk::Result::ERROR_LAYER_NOT_PRESENT,
+        }
+    }
+
+    unsafe fn enumerate_extensions(
+        extensions: &[LayerExtension],
+        p_property_count: *mut u32,
+        p_properties: *mut vk::ExtensionProperties,
+    ) -> vk::Result {
+        let extensions = extensions
+            .iter()
+            .map(|extension| {
+                let mut properties = vk::ExtensionProperties {
+                    spec_version: extension.spec_version,
+                    ..Default::default()
+                };
+                copy_str(extension.name.to_bytes(), &mut properties.extension_name);
+                properties
+            })
+            .collect::<Vec<_>>();
+        enumerate(&extensions, p_property_count, p_properties)
+    }
+
+    unsafe fn enumerate<T: Copy>(items: &[T], p_count: *mut u32, p_items: *mut T) -> vk::Result {
+        if p_items.is_null() {
+            *p_count = items.len() as u32;
+            return vk::Result::SUCCESS;
+        }
+
+        let count = (*p_count as usize).min(items.len());
+        ptr::copy_nonoverlapping(items.as_ptr(), p_items, count);
+        *p_count = count as u32;
+        if count < items.len() {
+            vk::Result::INCOMPLETE
+        } else {
+            vk::Result::SUCCESS
+        }
+    }
+
+    unsafe extern "system" fn enumerate_instance_layer_properties(
+        p_property_count: *mut u32,
+        p_properties: *mut vk::LayerProperties,
+    ) -> vk::Result {
+        let layers = HOST
+            .lock()
+            .unwrap()
+            .sources
+            .iter()
+            .map(|source| source.properties().to_vk())
+            .collect::<Vec<_>>();
+        enumerate(&layers, p_property_count, p_properties)
+    }
+
+    /// Builds the instance chain over the enabled layers
+    unsafe extern "system" fn create_instance(
+        p_create_info: *const vk::InstanceCreateInfo<'_>,
+        p_allocator: *const vk::AllocationCallbacks<'_>,
+        p_instance: *mut vk::Instance,
+    ) -> vk::Result {
+        let create_info = &*p_create_info;
+
+        let env_layers = std::env::var(INSTANCE_LAYERS_ENV_VAR).unwrap_or_default();
+        let env_layers = env_layers
+            .split(':')
+            .filter(|name| !name.is_empty())
+            .filter_map(|name| CString::new(name).ok())
+            .collect::<Vec<_>>();
+        let mut names = env_layers.iter().map(CString::as_c_str).collect::<Vec<_>>();
+        for name in c_strs(
+            create_info.pp_enabled_layer_names,
+            create_info.enabled_layer_count,
+        ) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+
+        let layers = {
+            let mut host = HOST.lock().unwrap();
+            match names
+                .iter()
+                .map(|name| host.load(name))
+                .collect::<Result<Vec<_>, _>>()
+            {
+                Ok(layers) => layers,
+                Err(_) => return vk::Result::ERROR_LAYER_NOT_PRESENT,
+            }
+        };
+
+        // Each layer gets the entry points of the layer below it
+        let mut next_get_physical_device_proc_addr: PFN_GetPhysicalDeviceProcAddr =
+            terminator_get_instance_proc_addr;
+        let mut links = Vec::with_capacity(layers.len());
+        for (index, layer) in layers.iter().enumerate().rev() {
+            links.push(LayerInstanceLink {
+                p_next: ptr::null_mut(),
+                pfn_next_get_instance_proc_addr: layers
+                    .get(index + 1)
+                    .map_or(terminator_get_instance_proc_addr, |next| {
+                        next.get_instance_proc_addr
+                    }),
+                pfn_next_get_physical_device_proc_addr: Some(next_get_physical_device_proc_addr),
+            });
+            if let Some(get_physical_device_proc_addr) = layer.get_physical_device_proc_addr {
+                next_get_physical_device_proc_addr = get_physical_device_proc_addr;
+            }
+        }
+        links.reverse();
+        for index in 1..links.len() {
+            links[index - 1].p_next = &mut links[index];
+        }
+
+        let mut loader_data = LayerInstanceCreateInfo {
+            s_type: vk::StructureType::LOADER_INSTANCE_CREATE_INFO,
+            p_next: create_info.p_next,
+            function: LOADER_DATA_CALLBACK,
+            u: LayerInstanceCreateInfoUnion {
+                pfn_set_instance_loader_data: set_instance_loader_data,
+            },
+        };
+        let mut link_info = LayerInstanceCreateInfo {
+            s_type: vk::StructureType::LOADER_INSTANCE_CREATE_INFO,
+            p_next: ptr::addr_of_mut!(loader_data).cast(),
+            function: LAYER_LINK_INFO,
+            u: LayerInstanceCreateInfoUnion {
+                p_layer_info: links.first_mut().map_or(ptr::null_mut(), ptr::from_mut),
+            },
+        };
+        let mut chained_info = *create_info;
+        chained_info.p_next = ptr::addr_of_mut!(link_info).cast();
+
+        let top: vk::PFN_vkGetInstanceProcAddr = layers
+            .first()
+            .map_or(terminator_get_instance_proc_addr, |layer| {
+                layer.get_instance_proc_addr
+            });
+        let Some(create) = load::<vk::PFN_vkCreateInstance>(top(
+            vk::Instance::null(),
+            c"vkCreateInstance".as_ptr(),
+        )) else {
+            return vk::Result::ERROR_INITIALIZATION_FAILED;
+        };
+
+        let result = create(&chained_info, p_allocator, p_instance);
+        if result == vk::Result::SUCCESS {
+            let state = &mut *key::<InstanceState>(*p_instance);
+            state.layers = layers;
+            state.get_instance_proc_addr = top;
+        }
+        result
+    }
+
+    /// Builds the device chain over the enabled layers that have device entry points
+    unsafe extern "system" fn create_device(
+        physical_device: vk::PhysicalDevice,
+        p_create_info: *const vk::DeviceCreateInfo<'_>,
+        p_allocator: *const vk::AllocationCallbacks<'_>,
+        p_device: *mut vk::Device,
+    ) -> vk::Result {
+        let instance_state = &*key::<InstanceState>(physical_device);
+        let layers = instance_state
+            .layers
+            .iter()
+            .filter_map(|layer| Some((layer.get_instance_proc_addr, layer.get_device_proc_addr?)))
+            .collect::<Vec<_>>();
+
+        let mut links = layers
+            .iter()
+            .enumerate()
+            .map(|(index, _)| {
+                let (get_instance_proc_addr, get_device_proc_addr) =
+                    layers.get(index + 1).copied().unwrap_or((
+                        terminator_get_instance_proc_addr,
+                        terminator_get_device_proc_addr,
+                    ));
+                LayerDeviceLink {
+                    p_next: ptr::null_mut(),
+                    pfn_next_get_instance_proc_addr: get_instance_proc_addr,
+                    pfn_next_get_device_proc_addr: get_device_proc_addr,
+                }
+            })
+            .collect::<Vec<_>>();
+        for index in 1..links.len() {
+            links[index - 1].p_next = &mut links[index];
+        }
+
+        let create_info = &*p_create_info;
+        let mut loader_data = LayerDeviceCreateInfo {
+            s_type: vk::StructureType::LOADER_DEVICE_CREATE_INFO,
+            p_next: create_info.p_next,
+            function: LOADER_DATA_CALLBACK,
+            u: LayerDeviceCreateInfoUnion {
+                pfn_set_device_loader_data: set_device_loader_data,
+            },
+        };
+        let mut link_info = LayerDeviceCreateInfo {
+            s_type: vk::StructureType::LOADER_DEVICE_CREATE_INFO,
+            p_next: ptr::addr_of_mut!(loader_data).cast(),
+            function: LAYER_LINK_INFO,
+            u: LayerDeviceCreateInfoUnion {
+                p_layer_info: links.first_mut().map_or(ptr::null_mut(), ptr::from_mut),
+            },
+        };
+        let mut chained_info = *create_info;
+        chained_info.p_next = ptr::addr_of_mut!(link_info).cast();
+
+        let top: vk::PFN_vkGetInstanceProcAddr = layers
+            .first()
+            .map_or(terminator_get_instance_proc_addr, |layer| layer.0);
+        let Some(create) = load::<vk::PFN_vkCreateDevice>(top(
+            instance_state.instance,
+            c"vkCreateDevice".as_ptr(),
+        )) else {
+            return vk::Result::ERROR_INITIALIZATION_FAILED;
+        };
+        create(physical_device, &chained_info, p_allocator, p_device)
+    }
+
+    unsafe extern "system" fn set_instance_loader_data(
+        instance: vk::Instance,
+        object: *mut c_void,
+    ) -> vk::Result {
+        *object.cast::<*mut c_void>() = key(instance);
+        vk::Result::SUCCESS
+    }
+
+    unsafe extern "system" fn set_device_loader_data(
+        device: vk::Device,
+        object: *mut c_void,
+    ) -> vk::Result {
+        *object.cast::<*mut c_void>() = key(device);
+        vk::Result::SUCCESS
+    }
+
+    /// The `vkGetInstanceProcAddr` below the last layer
+    unsafe extern "system" fn terminator_get_instance_proc_addr(
+        instance: vk::Instance,
+        p_name: *const c_char,
+    ) -> vk::PFN_vkVoidFunction {
+        let terminator = match CStr::from_ptr(p_name).to_bytes() {
+            b"vkGetInstanceProcAddr" => void_fn!(
+                terminator_get_instance_proc_addr,
+                vk::PFN_vkGetInstanceProcAddr
+            ),
+            b"vkCreateInstance" => {
+                void_fn!(terminator_create_instance, vk::PFN_vkCreateInstance)
+            }
+            b"vkDestroyInstance" => {
+                void_fn!(terminator_destroy_instance, vk::PFN_vkDestroyInstance)
+            }
+            b"vkEnumeratePhysicalDevices" => void_fn!(
+                terminator_enumerate_physical_devices,
+                vk::PFN_vkEnumeratePhysicalDevices
+            ),
+            b"vkEnumeratePhysicalDeviceGroups" | b"vkEnumeratePhysicalDeviceGroupsKHR" => {
+                void_fn!(
+                    terminator_enumerate_physical_device_groups,
+                    vk::PFN_vkEnumeratePhysicalDeviceGroups
+                )
+            }
+            b"vkEnumerateDeviceExtensionProperties" => void_fn!(
+                terminator_enumerate_device_extension_properties,
+                vk::PFN_vkEnumerateDeviceExtensionProperties
+            ),
+            b"vkCreateDevice" => void_fn!(terminator_create_device, vk::PFN_vkCreateDevice),
+            b"vkGetDeviceProcAddr" => {
+                void_fn!(terminator_get_device_proc_addr, vk::PFN_vkGetDeviceProcAddr)
+            }
+            _ => None,
+        };
+
+        let next = crate::static_get_instance_proc_addr()(instance, p_name);
+        if terminator.is_some() && (next.is_some() || instance == vk::Instance::null()) {
+            terminator
+        } else {
+            next
+        }
+    }
+
+    /// Creates the MoltenVK instance without the layers and the extensions only they provide
+    unsafe extern "system" fn terminator_create_instance(
+        p_create_info: *const vk::InstanceCreateInfo<'_>,
+        p_allocator: *const vk::AllocationCallbacks<'_>,
+        p_instance: *mut vk::Instance,
+    ) -> vk::Result {
+        let next = crate::static_get_instance_proc_addr();
+        let entry = ash::Entry::from_static_fn(ash::StaticFn {
+            get_instance_proc_addr: next,
+        });
+        let Ok(available) = entry.enumerate_instance_extension_properties(None) else {
+            return vk::Result::ERROR_INITIALIZATION_FAILED;
+        };
+
+        let create_info = &*p_create_info;
+        let extensions = c_strs(
+            create_info.pp_enabled_extension_names,
+            create_info.enabled_extension_count,
+        )
+        .into_iter()
+        .filter(|&name| {
+            available
+                .iter()
+                .any(|ext| ext.extension_name_as_c_str() == Ok(name))
+        })
+        .map(CStr::as_ptr)
+        .collect::<Vec<_>>();
+
+        let mut driver_info = *create_info;
+        driver_info.enabled_layer_count = 0;
+        driver_info.pp_enabled_layer_names = ptr::null();
+        driver_info.enabled_extension_count = extensions.len() as u32;
+        driver_info.pp_enabled_extension_names = extensions.as_ptr();
+
+        let result = (entry.fp_v1_0().create_instance)(&driver_info, p_allocator, p_instance);
+        if result != vk::Result::SUCCESS {
+            return result;
+        }
+
+        let instance = *p_instance;
+        let proc_addr = |name: &CStr| next(instance, name.as_ptr());
+        let (
+            Some(destroy_instance),
+            Some(enumerate_physical_devices),
+            Some(enumerate_device_extension_properties),
+            Some(create_device),
+            Some(get_device_proc_addr),
+        ) = (
+            load(proc_addr(c"vkDestroyInstance")),
+            load(proc_addr(c"vkEnumeratePhysicalDevices")),
+            load(proc_addr(c"vkEnumerateDeviceExtensionProperties")),
+            load(proc_addr(c"vkCreateDevice")),
+            load(proc_addr(c"vkGetDeviceProcAddr")),
+        )
+        else {
+            return vk::Result::ERROR_INITIALIZATION_FAILED;
+        };
+
+        let state = Box::new(InstanceState {
+            layers: Vec::new(),
+            get_instance_proc_addr: terminator_get_instance_proc_addr,
+            instance,
+            destroy_instance,
+            enumerate_physical_devices,
+            enumerate_physical_device_groups: load(proc_addr(c"vkEnumeratePhysicalDeviceGroups"))
+                .or_else(|| load(proc_addr(c"vkEnumeratePhysicalDeviceGroupsKHR"))),
+            enumerate_device_extension_properties,
+            create_device,
+            get_device_proc_addr,
+        });
+        set_key(instance, Box::into_raw(state));
+        vk::Result::SUCCESS
+    }
+
+    unsafe extern "system" fn terminator_destroy_instance(
+        instance: vk::Instance,
+        p_allocator: *const vk::AllocationCallbacks<'_>,
+    ) {
+        if instance == vk::Instance::null() {
+            return;
+        }
+
+        let state = Box::from_raw(key::<InstanceState>(instance));
+        (state.destroy_instance)(instance, p_allocator);
+    }
+
+    unsafe extern "system" fn terminator_enumerate_physical_devices(
+        instance: vk::Instance,
+        p_physical_device_count: *mut u32,
+        p_physical_devices: *mut vk::PhysicalDevice,
+    ) -> vk::Result {
+        let state = key::<InstanceState>(instance);
+        let result = ((*state).enumerate_physical_devices)(
+            instance,
+            p_physical_device_count,
+            p_physical_devices,
+        );
+        if !p_physical_devices.is_null()
+            && matches!(result, vk::Result::SUCCESS | vk::Result::INCOMPLETE)
+        {
+            for &physical_device in
+                std::slice::from_raw_parts(p_physical_devices, *p_physical_device_count as usize)
+            {
+                set_key(physical_device, state);
+            }
+        }
+        result
+    }
+
+    unsafe extern "system" fn terminator_enumerate_physical_device_groups(
+        instance: vk::Instance,
+        p_physical_device_group_count: *mut u32,
+        p_physical_device_group_properties: *mut vk::PhysicalDeviceGroupProperties<'_>,
+    ) -> vk::Result {
+        let state = key::<InstanceState>(instance);
+        let Some(enumerate_physical_device_groups) = (*state).enumerate_physical_device_groups
+        else {
+            return vk::Result::ERROR_INITIALIZATION_FAILED;
+        };
+
+        let result = enumerate_physical_device_groups(
+            instance,
+            p_physical_device_group_count,
+            p_physical_device_group_properties,
+        );
+        if !p_physical_device_group_properties.is_null()
+            && matches!(result, vk::Result::SUCCESS | vk::Result::INCOMPLETE)
+        {
+            for group in std::slice::from_raw_parts(
+                p_physical_device_group_properties,
+                *p_physical_device_group_count as usize,
+            ) {
+                for &physical_device in group.physical_devices_as_slice() {
+                    set_key(physical_device, state);
+                }
+            }
+        }
+        result
+    }
+
+    /// Answers for the device extensions of layers, since MoltenVK doesn't know about them
+    unsafe extern "system" fn terminator_enumerate_device_extension_properties(
+        physical_device: vk::PhysicalDevice,
+        p_layer_name: *const c_char,
+        p_property_count: *mut u32,
+        p_properties: *mut vk::ExtensionProperties,
+    ) -> vk::Result {
+        if p_layer_name.is_null() {
+            let state = &*key::<InstanceState>(physical_device);
+            return (state.enumerate_device_extension_properties)(
+                physical_device,
+                p_layer_name,
+                p_property_count,
+                p_properties,
+            );
+        }
+
+        let name = CStr::from_ptr(p_layer_name);
+        let state = &*key::<InstanceState>(physical_device);
+        match state
+            .layers
+            .iter()
+            .find(|layer| layer.properties.name.as_c_str() == name)
+        {
+            Some(layer) => enumerate_extensions(
+                &layer.properties.device_extensions,
+                p_property_count,
+                p_properties,
+            ),
+            None => vk::Result::ERROR_LAYER_NOT_PRESENT,
+        }
+    }
+
+    /// Creates the MoltenVK device without the extensions only layers provide
+    unsafe extern "system" fn terminator_create_device(
+        physical_device: vk::PhysicalDevice,
+        p_create_info: *const vk::DeviceCreateInfo<'_>,
+        p_allocator: *const vk::AllocationCallbacks<'_>,
+        p_device: *mut vk::Device,
+    ) -> vk::Result {
+        let state = &*key::<InstanceState>(physical_device);
+
+        let enumerate = |count: &mut u32, properties: *mut vk::ExtensionProperties| {
+            (state.enumerate_device_extension_properties)(
+                physical_device,
+                ptr::null(),
+                count,
+                properties,
+            )
+        };
+        let mut count = 0;
+        let mut available = Vec::new();
+        let mut result = vk::Result::INCOMPLETE;
+        while result == vk::Result::INCOMPLETE {
+            result = enumerate(&mut count, ptr::null_mut());
+            if result != vk::Result::SUCCESS {
+                return result;
+            }
+            available.resize(count as usize, vk::ExtensionProperties::default());
+            result = enumerate(&mut count, available.as_mut_ptr());
+            available.truncate(count as usize);
+        }
+        if result != vk::Result::SUCCESS {
+            return result;
+        }
+
+        let create_info = &*p_create_info;
+        let extensions = c_strs(
+            create_info.pp_enabled_extension_names,
+            create_info.enabled_extension_count,
+        )
+        .into_iter()
+        .filter(|&name| {
+            available
+                .iter()
+                .any(|ext| ext.extension_name_as_c_str() == Ok(name))
+        })
+        .map(CStr::as_ptr)
+        .collect::<Vec<_>>();
+
+        let mut driver_info = *create_info;
+        driver_info.enabled_extension_count = extensions.len() as u32;
+        driver_info.pp_enabled_extension_names = extensions.as_ptr();
+        #[allow(deprecated)]
+        {
+            driver_info.enabled_layer_count = 0;
+            driver_info.pp_enabled_layer_names = ptr::null();
+        }
+
+        let result = (state.create_device)(physical_device, &driver_info, p_allocator, p_device);
+        if result != vk::Result::SUCCESS {
+            return result;
+        }
+
+        let device = *p_device;
+        let get_device_proc_addr = state.get_device_proc_addr;
+        let proc_addr = |name: &CStr| get_device_proc_addr(device, name.as_ptr());
+        let (Some(destroy_device), Some(get_device_queue)) = (
+            load(proc_addr(c"vkDestroyDevice")),
+            load(proc_addr(c"vkGetDeviceQueue")),
+        ) else {
+            return vk::Result::ERROR_INITIALIZATION_FAILED;
+        };
+
+        let device_state = Box::new(DeviceState {
+            get_device_proc_addr,
+            destroy_device,
+            get_device_queue,
+            get_device_queue2: load(proc_addr(c"vkGetDeviceQueue2")),
+            allocate_command_buffers: load(proc_addr(c"vkAllocateCommandBuffers")),
+        });
+        set_key(device, Box::into_raw(device_state));
+        vk::Result::SUCCESS
+    }
+
+    /// The `vkGetDeviceProcAddr` below the last layer
+    unsafe extern "system" fn terminator_get_device_proc_addr(
+        device: vk::Device,
+        p_name: *const c_char,
+    ) -> vk::PFN_vkVoidFunction {
+        let state = &*key::<DeviceState>(device);
+        // Only wrap functions MoltenVK implements
+        let next = (state.get_device_proc_addr)(device, p_name);
+        next?;
+
+        match CStr::from_ptr(p_name).to_bytes() {
+            b"vkGetDeviceProcAddr" => {
+                void_fn!(terminator_get_device_proc_addr, vk::PFN_vkGetDeviceProcAddr)
+            }
+            b"vkDestroyDevice" => void_fn!(terminator_destroy_device, vk::PFN_vkDestroyDevice),
+            b"vkGetDeviceQueue" => {
+                void_fn!(terminator_get_device_queue, vk::PFN_vkGetDeviceQueue)
+            }
+            b"vkGetDeviceQueue2" => {
+                void_fn!(terminator_get_device_queue2, vk::PFN_vkGetDeviceQueue2)
+            }
+            b"vkAllocateCommandBuffers" => void_fn!(
+                terminator_allocate_command_buffers,
+                vk::PFN_vkAllocateCommandBuffers
+            ),
+            _ => next,
+        }
+    }
+
+    unsafe extern "system" fn terminator_destroy_device(
+        device: vk::Device,
+        p_allocator: *const vk::AllocationCallbacks<'_>,
+    ) {
+        if device == vk::Device::null() {
+            return;
+        }
+
+        let state = Box::from_raw(key::<DeviceState>(device));
+        (state.destroy_device)(device, p_allocator);
+    }
+
+    unsafe extern "system" fn terminator_get_device_queue(
+        device: vk::Device,
+        queue_family_index: u32,
+        queue_index: u32,
+        p_queue: *mut vk::Queue,
+    ) {
+        let state = key::<DeviceState>(device);
+        ((*state).get_device_queue)(device, queue_family_index, queue_index, p_queue);
+        if *p_queue != vk::Queue::null() {
+            set_key(*p_queue, state);
+        }
+    }
+
+    unsafe extern "system" fn terminator_get_device_queue2(
+        device: vk::Device,
+        p_queue_info: *const vk::DeviceQueueInfo2<'_>,
+        p_queue: *mut vk::Queue,
+    ) {
+        let state = key::<DeviceState>(device);
+        if let Some(get_device_queue2) = (*state).get_device_queue2 {
+            get_device_queue2(device, p_queue_info, p_queue);
+            if *p_queue != vk::Queue::null() {
+                set_key(*p_queue, state);
+            }
+        }
+    }
+
+    unsafe extern "system" fn terminator_allocate_command_buffers(
+        device: vk::Device,
+        p_allocate_info: *const vk::CommandBufferAllocateInfo<'_>,
+        p_command_buffers: *mut vk::CommandBuffer,
+    ) -> vk::Result {
+        let state = key::<DeviceState>(device);
+        let Some(allocate_command_buffers) = (*state).allocate_command_buffers else {
+            return vk::Result::ERROR_INITIALIZATION_FAILED;
+        };
+
+        let result = allocate_command_buffers(device, p_allocate_info, p_command_buffers);
+        if result == vk::Result::SUCCESS {
+            for &command_buffer in std::slice::from_raw_parts(
+                p_command_buffers,
+                (*p_allocate_info).command_buffer_count as usize,
+            ) {
+                set_key(command_buffer, state);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+#[allow(unsafe_code)]
+mod tests {
+    use super::*;
+
+    /// Writes a manifest to a file of its own in the temporary directory
+    fn write_manifest(name: &str, json: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ash-molten-layers-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{name}.json"));
+        fs::write(&path, json).unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_single_layer() {
+        let path = write_manifest(
+            "single",
+            r#"{
+                "file_format_version": "1.0.0",
+                "layer": {
+                    "name": "VK_LAYER_single",
+                    "type": "GLOBAL",
+                    "library_path": "libsingle.dylib",
+                    "api_version": "1.3.250",
+                    "implementation_version": "2",
+                    "description": "one layer",
+                    "instance_extensions": [{ "name": "VK_EXT_debug_report", "spec_version": 9 }],
+                    "device_extensions": [{ "name": "VK_EXT_tooling_info", "spec_version": "1" }],
+                    "functions": { "vkGetInstanceProcAddr": "single_GetInstanceProcAddr" }
+                }
+            }"#,
+        );
+
+        let manifests = LayerManifest::read(&path).unwrap();
+        assert_eq!(manifests.len(), 1);
+        let manifest = &manifests[0];
+        assert_eq!(manifest.properties.name.as_c_str(), c"VK_LAYER_single");
+        assert_eq!(
+            manifest.properties.spec_version,
+            vk::make_api_version(0, 1, 3, 250)
+        );
+        assert_eq!(manifest.properties.implementation_version, 2);
+        assert_eq!(manifest.properties.description, "one layer");
+        assert_eq!(
+            manifest.properties.instance_extensions,
+            [LayerExtension {
+                name: c"VK_EXT_debug_report".to_owned(),
+                spec_version: 9,
+            }]
+        );
+        assert_eq!(manifest.properties.device_extensions[0].spec_version, 1);
+        // A bare file name is left to the dynamic linker
+        assert_eq!(manifest.library_path, Path::new("libsingle.dylib"));
+        assert_eq!(
+            manifest.symbol("vkGetInstanceProcAddr"),
+            "single_GetInstanceProcAddr"
+        );
+        assert_eq!(manifest.symbol("vkCreateDevice"), "vkCreateDevice");
+    }
+
+    #[test]
+    fn reads_layer_array() {
+        let path = write_manifest(
+            "array",
+            r#"{
+                "file_format_version": "1.0.1",
+                "layers": [
+                    {
+                        "name": "VK_LAYER_first",
+                        "library_path": "./libfirst.dylib",
+                        "api_version": "1.2"
+                    },
+                    {
+                        "name": "VK_LAYER_meta",
+                        "api_version": "1.3.0",
+                        "component_layers": ["VK_LAYER_first"]
+                    },
+                    {
+                        "name": "VK_LAYER_second",
+                        "library_path": "/usr/local/lib/libsecond.dylib",
+                        "api_version": "1.3.0"
+                    }
+                ]
+            }"#,
+        );
+
+        let manifests = LayerManifest::read(&path).unwrap();
+        let names = manifests
+            .iter()
+            .map(|manifest| manifest.properties.name.as_c_str())
+            .collect::<Vec<_>>();
+        // The meta layer has no library
+        assert_eq!(names, [c"VK_LAYER_first", c"VK_LAYER_second"]);
+        assert_eq!(
+            manifests[0].properties.spec_version,
+            vk::make_api_version(0, 1, 2, 0)
+        );
+        // Relative paths with a directory are relative to the manifest
+        assert_eq!(
+            manifests[0].library_path,
+            path.parent().unwrap().join("./libfirst.dylib")
+        );
+        assert_eq!(
+            manifests[1].library_path,
+            Path::new("/usr/local/lib/libsecond.dylib")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_manifests() {
+        let missing_name = write_manifest(
+            "missing-name",
+            r#"{ "layer": { "library_path": "libnameless.dylib", "api_version": "1.3.0" } }"#,
+        );
+        assert!(matches!(
+            LayerManifest::read(&missing_name),
+            Err(LayerError::Manifest { message, .. }) if message == "`name` is missing"
+        ));
+
+        let no_layers = write_manifest("no-layers", r#"{ "file_format_version": "1.0.0" }"#);
+        assert!(matches!(
+            LayerManifest::read(&no_layers),
+            Err(LayerError::Manifest { .. })
+        ));
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn chains_static_layer() {
+        use crate::mock::layer;
+
+        let entry = LayerHost::empty()
+            .static_layer(
+                layer::properties(),
+                layer::negotiate_loader_layer_interface_version,
+            )
+            .install()
+            .unwrap();
+        assert_eq!(entry.source(), EntrySource::LayerHost);
+        layer::clear_calls();
+
+        unsafe {
+            let layers = entry.enumerate_instance_layer_properties().unwrap();
+            assert!(layers
+                .iter()
+                .any(|properties| properties.layer_name_as_c_str() == Ok(layer::NAME)));
+
+            let instance = entry
+                .create_instance(
+                    &vk::InstanceCreateInfo::default().enabled_layer_names(&[layer::NAME.as_ptr()]),
+                    None,
+                )
+                .unwrap();
+            let physical_device = instance.enumerate_physical_devices().unwrap()[0];
+            let queue_create_info = vk::DeviceQueueCreateInfo::default().queue_priorities(&[1.0]);
+            let device = instance
+                .create_device(
+                    physical_device,
+                    &vk::DeviceCreateInfo::default().queue_create_infos(&[queue_create_info]),
+                    None,
+                )
+                .unwrap();
+            device.get_device_queue(0, 0);
+            device.destroy_device(None);
+            instance.destroy_instance(None);
+        }
+
+        assert_eq!(
+            layer::calls(),
+            [
+                "vkCreateInstance",
+                // ash queries the count first
+                "vkEnumeratePhysicalDevices",
+                "vkEnumeratePhysicalDevices",
+                "vkCreateDevice",
+                "vkGetDeviceQueue",
+                "vkDestroyDevice",
+                "vkDestroyInstance",
+            ]
+        );
+    }
+}
@@ -1,5 +1,50 @@
 use ash::vk;
 
+mod device;
+mod entry;
+mod instance;
+#[cfg(feature = "intercept")]
+pub mod intercept;
+#[cfg(feature = "layers")]
+pub mod layers;
+#[cfg(any(feature = "log", feature = "tracing"))]
+pub mod messages;
+pub mod metal;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod mvk;
+mod performance;
+mod settings;
+#[cfg(feature = "surface")]
+pub mod surface;
+mod version;
+
+pub use device::{
+    CreatedDevice, DeviceBuilder, DeviceError, PortabilityFeature, PortabilitySubset,
+};
+#[cfg(feature = "loader-fallback")]
+pub use entry::LOADER_ENV_VAR;
+pub use entry::{entry, EntrySource, LoadError, MoltenEntry, Provenance};
+pub use instance::{CreatedInstance, InstanceBuilder, InstanceError};
+#[cfg(any(feature = "log", feature = "tracing"))]
+pub use messages::DebugMessenger;
+#[cfg(feature = "tracing")]
+pub use performance::STATISTICS_TARGET;
+pub use performance::{PerformanceCollector, PerformanceSummary, TrackerDelta};
+pub use settings::{
+    FastMath, GpuCaptureScope, LayerSettings, LogLevel, MoltenVkSettings, SettingsError, LAYER_NAME,
+};
+pub use version::{
+    check_driver_version, Version, VersionMismatch, MOLTENVK_ARTIFACT_TAG, MOLTENVK_COMMIT,
+    MOLTENVK_RUNTIME_CONFIG, MOLTENVK_SOURCE, MOLTENVK_VERSION,
+};
+
+/// A JSON report describing the MoltenVK library that was linked: where it came from, its
+/// version and commit, the xcframework slice that was selected and how long each build phase
+/// took.
+pub const BUILD_REPORT: &str = include_str!(concat!(env!("OUT_DIR"), "/moltenvk-report.json"));
+
+#[cfg(all(not(feature = "mock"), any(target_os = "macos", target_os = "ios")))]
 extern "system" {
     fn vkGetInstanceProcAddr(
         instance: vk::Instance,
@@ -7,13 +52,82 @@ extern "system" {
     ) -> vk::PFN_vkVoidFunction;
 }
 
+/// The statically linked `vkGetInstanceProcAddr`
+#[cfg(all(not(feature = "mock"), any(target_os = "macos", target_os = "ios")))]
+fn static_get_instance_proc_addr() -> vk::PFN_vkGetInstanceProcAddr {
+    vkGetInstanceProcAddr
+}
+
+/// The mock `vkGetInstanceProcAddr`, see [`mock`]
+#[cfg(feature = "mock")]
+fn static_get_instance_proc_addr() -> vk::PFN_vkGetInstanceProcAddr {
+    mock::get_instance_proc_addr
+}
+
+/// Nothing is linked on targets MoltenVK doesn't support (with the `unsupported-noop`
+/// feature), so no entry point resolves
+#[cfg(not(any(feature = "mock", target_os = "macos", target_os = "ios")))]
+fn static_get_instance_proc_addr() -> vk::PFN_vkGetInstanceProcAddr {
+    #[allow(unsafe_code)]
+    unsafe extern "system" fn get_instance_proc_addr(
+        _instance: vk::Instance,
+        _p_name: *const std::os::raw::c_char,
+    ) -> vk::PFN_vkVoidFunction {
+        None
+    }
+
+    get_instance_proc_addr
+}
+
+/// Whether a Vulkan implementation is available on this target.
+///
+/// This is `false` when building for targets other than macOS and iOS with the
+/// `unsupported-noop` feature, in which case [`MoltenEntry::load`] and [`entry`] return
+/// [`LoadError::UnsupportedPlatform`].
+pub const fn is_supported() -> bool {
+    cfg!(any(
+        feature = "mock",
+        target_os = "macos",
+        target_os = "ios"
+    ))
+}
+
+/// The raw, statically linked `vkGetInstanceProcAddr`.
+///
+/// This can be handed to other Vulkan bindings or C libraries so they resolve their function
+/// pointers from the same MoltenVK as [`entry`].
+pub fn get_instance_proc_addr() -> vk::PFN_vkGetInstanceProcAddr {
+    static_get_instance_proc_addr()
+}
+
 /// Fetches the function pointer to `vkGetInstanceProcAddr` which is statically linked.
+///
+/// On unsupported targets (see [`is_supported`]) the returned entry is dead: it resolves no
+/// function, so the first call through it fails. Use [`try_load`] to get
+/// [`LoadError::UnsupportedPlatform`] instead, or [`MoltenEntry::load`], which also checks
+/// that the entry points resolve and reports where they came from.
+///
+/// With the `loader-fallback` feature the system Vulkan loader is returned instead if
+/// `ASH_MOLTEN_LOADER` is set to `1`, `true` or `loader` and it can be loaded.
 pub fn load() -> ash::Entry {
+    #[cfg(feature = "loader-fallback")]
+    if entry::loader_enabled() {
+        if let Ok(entry) = MoltenEntry::load_system_loader() {
+            return entry.into_entry();
+        }
+    }
+
     let static_fn = ash::StaticFn {
-        get_instance_proc_addr: vkGetInstanceProcAddr,
+        get_instance_proc_addr: static_get_instance_proc_addr(),
     };
     #[allow(unsafe_code)]
     unsafe {
         ash::Entry::from_static_fn(static_fn)
     }
 }
+
+/// Like [`load`], but fails with [`LoadError::UnsupportedPlatform`] on unsupported targets and
+/// with [`LoadError::MissingEntryPoint`] if the global entry points don't resolve
+pub fn try_load() -> Result<ash::Entry, LoadError> {
+    MoltenEntry::load().map(MoltenEntry::into_entry)
+}
@@ -0,0 +1,446 @@
+use crate::LogLevel;
+use ash::{prelude::VkResult, vk};
+use std::{
+    borrow::Cow,
+    ffi::{c_void, CStr},
+    fmt,
+};
+
+/// The target of general MoltenVK messages
+pub const TARGET: &str = "moltenvk";
+/// The target of MoltenVK messages about invalid API usage
+pub const VALIDATION_TARGET: &str = "moltenvk::validation";
+/// The target of MoltenVK messages about potentially slow API usage
+pub const PERFORMANCE_TARGET: &str = "moltenvk::performance";
+
+/// A `VK_EXT_debug_utils` messenger forwarding MoltenVK's messages to `tracing` or `log`,
+/// created by [`InstanceBuilder::forward_messages`](crate::InstanceBuilder::forward_messages).
+///
+/// Errors, warnings, info and verbose messages become `error`, `warn`, `info` and `debug`
+/// events, with [`TARGET`], [`VALIDATION_TARGET`] or [`PERFORMANCE_TARGET`] depending on the
+/// message type. With the `tracing` feature enabled messages only go to `tracing`.
+///
+/// The messenger must be destroyed before the instance it was created for.
+#[derive(Clone)]
+pub struct DebugMessenger {
+    loader: ash::ext::debug_utils::Instance,
+    handle: vk::DebugUtilsMessengerEXT,
+}
+
+impl DebugMessenger {
+    /// Creates a messenger forwarding the messages at or above `level`
+    ///
+    /// # Safety
+    ///
+    /// `VK_EXT_debug_utils` must be enabled on `instance`.
+    #[allow(unsafe_code)]
+    pub unsafe fn new(
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+        level: LogLevel,
+    ) -> VkResult<Self> {
+        let loader = ash::ext::debug_utils::Instance::new(entry, instance);
+        let handle = loader.create_debug_utils_messenger(&create_info(level), None)?;
+        Ok(Self { loader, handle })
+    }
+
+    /// The underlying messenger
+    pub fn handle(&self) -> vk::DebugUtilsMessengerEXT {
+        self.handle
+    }
+
+    /// Destroys the messenger
+    ///
+    /// # Safety
+    ///
+    /// The messenger must not be destroyed twice, and not after its instance.
+    #[allow(unsafe_code)]
+    pub unsafe fn destroy(&self) {
+        self.loader.destroy_debug_utils_messenger(self.handle, None);
+    }
+}
+
+impl fmt::Debug for DebugMessenger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebugMessenger")
+            .field("handle", &self.handle)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The messenger create info forwarding the messages at or above `level`, which can also be
+/// chained into instance creation to forward the messages reported while creating it
+pub fn create_info(level: LogLevel) -> vk::DebugUtilsMessengerCreateInfoEXT<'static> {
+    type Severity = vk::DebugUtilsMessageSeverityFlagsEXT;
+
+    let severity = match level {
+        LogLevel::None => Severity::empty(),
+        LogLevel::Error => Severity::ERROR,
+        LogLevel::Warning => Severity::ERROR | Severity::WARNING,
+        LogLevel::Info => Severity::ERROR | Severity::WARNING | Severity::INFO,
+        LogLevel::Debug => Severity::ERROR | Severity::WARNING | Severity::INFO | Severity::VERBOSE,
+    };
+
+    vk::DebugUtilsMessengerCreateInfoEXT::default()
+        .message_severity(severity)
+        .message_type(
+            vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+        )
+        .pfn_user_callback(Some(forward))
+}
+
+#[allow(unsafe_code)]
+unsafe extern "system" fn forward(
+    severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    types: vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT<'_>,
+    _p_user_data: *mut c_void,
+) -> vk::Bool32 {
+    if p_callback_data.is_null() {
+        return vk::FALSE;
+    }
+
+    let data = &*p_callback_data;
+    let text = |ptr: *const std::ffi::c_char| {
+        if ptr.is_null() {
+            Cow::Borrowed("")
+        } else {
+            CStr::from_ptr(ptr).to_string_lossy()
+        }
+    };
+
+    emit(
+        severity,
+        types,
+        &text(data.p_message_id_name),
+        &text(data.p_message),
+    );
+    vk::FALSE
+}
+
+#[cfg(feature = "tracing")]
+fn emit(
+    severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    types: vk::DebugUtilsMessageTypeFlagsEXT,
+    id: &str,
+    message: &str,
+) {
+    type Severity = vk::DebugUtilsMessageSeverityFlagsEXT;
+
+    // The target and level of `tracing` events have to be constants
+    macro_rules! event {
+        ($target:expr) => {
+            if severity.contains(Severity::ERROR) {
+                tracing::error!(target: $target, message_id = id, "{message}");
+            } else if severity.contains(Severity::WARNING) {
+                tracing::warn!(target: $target, message_id = id, "{message}");
+            } else if severity.contains(Severity::INFO) {
+                tracing::info!(target: $target, message_id = id, "{message}");
+            } else {
+                tracing::debug!(target: $target, message_id = id, "{message}");
+            }
+        };
+    }
+
+    if types.contains(vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION) {
+        event!(VALIDATION_TARGET);
+    } else if types.contains(vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE) {
+        event!(PERFORMANCE_TARGET);
+    } else {
+        event!(TARGET);
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+fn emit(
+    severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    types: vk::DebugUtilsMessageTypeFlagsEXT,
+    id: &str,
+    message: &str,
+) {
+    type Severity = vk::DebugUtilsMessageSeverityFlagsEXT;
+
+    let level = if severity.contains(Severity::ERROR) {
+        log::Level::Error
+    } else if severity.contains(Severity::WARNING) {
+        log::Level::Warn
+    } else if severity.contains(Severity::INFO) {
+        log::Level::Info
+    } else {
+        log::Level::Debug
+    };
+
+    let target = if types.contains(vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION) {
+        VALIDATION_TARGET
+    } else if types.contains(vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE) {
+        PERFORMANCE_TARGET
+    } else {
+        TARGET
+    };
+
+    if id.is_empty() {
+        log::log!(target: target, level, "{message}");
+    } else {
+        log::log!(target: target, level, "{id}: {message}");
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+#[allow(unsafe_code)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{self, MockConfig},
+        InstanceBuilder,
+    };
+    use std::cell::RefCell;
+
+    type Severity = vk::DebugUtilsMessageSeverityFlagsEXT;
+    type Types = vk::DebugUtilsMessageTypeFlagsEXT;
+
+    /// A message forwarded to `log` or `tracing`
+    #[derive(Debug, PartialEq, Eq)]
+    struct Event {
+        level: String,
+        target: String,
+        message: String,
+    }
+
+    impl Event {
+        fn new(level: &str, target: &str, message: &str) -> Self {
+            Self {
+                level: level.to_owned(),
+                target: target.to_owned(),
+                message: message.to_owned(),
+            }
+        }
+    }
+
+    // The messengers are called on the thread submitting the messages
+    thread_local! {
+        static LOG_EVENTS: RefCell<Vec<Event>> = const { RefCell::new(Vec::new()) };
+        static TRACING_EVENTS: RefCell<Vec<Event>> = const { RefCell::new(Vec::new()) };
+    }
+
+    #[cfg(feature = "log")]
+    struct Logger;
+
+    #[cfg(feature = "log")]
+    impl log::Log for Logger {
+        fn enabled(&self, _metadata: &log::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record<'_>) {
+            let event = Event::new(
+                record.level().as_str(),
+                record.target(),
+                &record.args().to_string(),
+            );
+            LOG_EVENTS.with_borrow_mut(|events| events.push(event));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[cfg(feature = "tracing")]
+    struct Subscriber;
+
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for Subscriber {
+        fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            tracing::span::Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            #[derive(Default)]
+            struct Visitor {
+                id: String,
+                message: String,
+            }
+
+            impl tracing::field::Visit for Visitor {
+                fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+                    match field.name() {
+                        "message_id" => self.id = value.to_owned(),
+                        _ => self.record_debug(field, &value),
+                    }
+                }
+
+                fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn fmt::Debug) {
+                    if field.name() == "message" {
+                        self.message = format!("{value:?}");
+                    }
+                }
+            }
+
+            let mut visitor = Visitor::default();
+            event.record(&mut visitor);
+            // Written like the `log` messages, to compare them
+            let message = if visitor.id.is_empty() {
+                visitor.message
+            } else {
+                format!("{}: {}", visitor.id, visitor.message)
+            };
+
+            let metadata = event.metadata();
+            let event = Event::new(metadata.level().as_str(), metadata.target(), &message);
+            TRACING_EVENTS.with_borrow_mut(|events| events.push(event));
+        }
+
+        fn enter(&self, _span: &tracing::span::Id) {}
+
+        fn exit(&self, _span: &tracing::span::Id) {}
+    }
+
+    /// Creates an instance forwarding the messages at or above `level`, submits `messages` to
+    /// its messenger and returns the events forwarded to `log` and `tracing`
+    fn forward(level: LogLevel, messages: &[(Severity, Types, &CStr)]) -> (Vec<Event>, Vec<Event>) {
+        #[cfg(feature = "log")]
+        {
+            static LOGGER: std::sync::Once = std::sync::Once::new();
+            LOGGER.call_once(|| {
+                log::set_logger(&Logger).unwrap();
+                log::set_max_level(log::LevelFilter::Trace);
+            });
+        }
+
+        let submit = || {
+            let entry = crate::MoltenEntry::load().unwrap();
+            let instance = mock::with_config(MockConfig::default(), || unsafe {
+                InstanceBuilder::new()
+                    .forward_messages(level)
+                    .create(&entry)
+                    .unwrap()
+            });
+            let debug_utils = ash::ext::debug_utils::Instance::new(&entry, &instance);
+
+            // Only the submitted messages, not the ones about creating the instance
+            LOG_EVENTS.take();
+            TRACING_EVENTS.take();
+            for &(severity, types, message) in messages {
+                let data = vk::DebugUtilsMessengerCallbackDataEXT::default()
+                    .message_id_name(c"MVK-Test")
+                    .message(message);
+                unsafe { debug_utils.submit_debug_utils_message(severity, types, &data) };
+            }
+
+            unsafe {
+                instance.debug_messenger().unwrap().destroy();
+                instance.destroy_instance(None);
+            }
+        };
+
+        #[cfg(feature = "tracing")]
+        tracing::subscriber::with_default(Subscriber, submit);
+        #[cfg(not(feature = "tracing"))]
+        submit();
+
+        (LOG_EVENTS.take(), TRACING_EVENTS.take())
+    }
+
+    /// The events forwarded to the backend the enabled features pick
+    fn forwarded(level: LogLevel, messages: &[(Severity, Types, &CStr)]) -> Vec<Event> {
+        let (log_events, tracing_events) = forward(level, messages);
+        if cfg!(feature = "tracing") {
+            assert_eq!(log_events, []);
+            tracing_events
+        } else {
+            assert_eq!(tracing_events, []);
+            log_events
+        }
+    }
+
+    #[test]
+    fn maps_severity_to_level() {
+        let events = forwarded(
+            LogLevel::Debug,
+            &[
+                (Severity::ERROR, Types::GENERAL, c"error"),
+                (Severity::WARNING, Types::GENERAL, c"warning"),
+                (Severity::INFO, Types::GENERAL, c"info"),
+                (Severity::VERBOSE, Types::GENERAL, c"verbose"),
+            ],
+        );
+        assert_eq!(
+            events,
+            [
+                Event::new("ERROR", TARGET, "MVK-Test: error"),
+                Event::new("WARN", TARGET, "MVK-Test: warning"),
+                Event::new("INFO", TARGET, "MVK-Test: info"),
+                Event::new("DEBUG", TARGET, "MVK-Test: verbose"),
+            ]
+        );
+    }
+
+    #[test]
+    fn filters_by_level() {
+        let events = forwarded(
+            LogLevel::Warning,
+            &[
+                (Severity::ERROR, Types::GENERAL, c"error"),
+                (Severity::WARNING, Types::GENERAL, c"warning"),
+                (Severity::INFO, Types::GENERAL, c"info"),
+                (Severity::VERBOSE, Types::GENERAL, c"verbose"),
+            ],
+        );
+        assert_eq!(
+            events,
+            [
+                Event::new("ERROR", TARGET, "MVK-Test: error"),
+                Event::new("WARN", TARGET, "MVK-Test: warning"),
+            ]
+        );
+    }
+
+    #[test]
+    fn maps_type_to_target() {
+        let events = forwarded(
+            LogLevel::Debug,
+            &[
+                (Severity::WARNING, Types::GENERAL, c"general"),
+                (Severity::WARNING, Types::VALIDATION, c"validation"),
+                (Severity::WARNING, Types::PERFORMANCE, c"performance"),
+                (
+                    Severity::WARNING,
+                    Types::VALIDATION | Types::PERFORMANCE,
+                    c"both",
+                ),
+            ],
+        );
+        assert_eq!(
+            events,
+            [
+                Event::new("WARN", TARGET, "MVK-Test: general"),
+                Event::new("WARN", VALIDATION_TARGET, "MVK-Test: validation"),
+                Event::new("WARN", PERFORMANCE_TARGET, "MVK-Test: performance"),
+                // Validation wins
+                Event::new("WARN", VALIDATION_TARGET, "MVK-Test: both"),
+            ]
+        );
+    }
+
+    #[cfg(all(feature = "log", feature = "tracing"))]
+    #[test]
+    fn prefers_tracing_over_log() {
+        let (log_events, tracing_events) = forward(
+            LogLevel::Error,
+            &[(Severity::ERROR, Types::GENERAL, c"error")],
+        );
+        assert_eq!(log_events, []);
+        assert_eq!(
+            tracing_events,
+            [Event::new("ERROR", TARGET, "MVK-Test: error")]
+        );
+    }
+}
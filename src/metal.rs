@@ -0,0 +1,358 @@
+//! Sharing Metal objects with Vulkan through `VK_EXT_metal_objects`.
+//!
+//! [`MetalObjects`] exports the `MTLDevice`, `MTLCommandQueue`, `MTLBuffer`, `MTLTexture`,
+//! `IOSurfaceRef` and `MTLSharedEvent` behind Vulkan objects, and the `import_*` functions
+//! create the structs that make Vulkan objects wrap existing Metal objects.
+//!
+//! # Ownership
+//!
+//! Exported handles are borrowed from the Vulkan object they were exported from. They are not
+//! retained, and are only valid until that object is destroyed, so retain them on the Metal
+//! side to use them for longer.
+//!
+//! Imported Metal objects are retained by MoltenVK until the Vulkan object wrapping them is
+//! destroyed, so the caller may release its own reference as soon as the object is created.
+//!
+//! # Exporting
+//!
+//! Memory, images, image views, buffer views, semaphores and events need to be created with
+//! [`export_info`] chained into their create info, declaring what will be exported from them.
+//! The device and its queues are exportable if the instance was created with
+//! [`vk::ExportMetalObjectTypeFlagsEXT::METAL_DEVICE`] and
+//! [`vk::ExportMetalObjectTypeFlagsEXT::METAL_COMMAND_QUEUE`], see
+//! [`InstanceBuilder::metal_exports`](crate::InstanceBuilder::metal_exports).
+//!
+//! ```no_run
+//! # unsafe fn share(instance: &ash::Instance, device: &ash::Device, image_info: ash::vk::ImageCreateInfo<'_>) -> ash::prelude::VkResult<()> {
+//! use ash::vk;
+//! use ash_molten::metal;
+//!
+//! let mut export = metal::export_info(vk::ExportMetalObjectTypeFlagsEXT::METAL_IOSURFACE);
+//! let image = device.create_image(&image_info.push_next(&mut export), None)?;
+//!
+//! let objects = metal::MetalObjects::new(instance, device);
+//! let io_surface = objects.export_io_surface(image);
+//! # Ok(())
+//! # }
+//! ```
+
+use ash::vk;
+use std::fmt;
+
+pub use ash::ext::metal_objects::NAME;
+
+/// Declares which Metal objects will be exported from a Vulkan object, chain it into the
+/// object's create info
+pub fn export_info(
+    types: vk::ExportMetalObjectTypeFlagsEXT,
+) -> vk::ExportMetalObjectCreateInfoEXT<'static> {
+    vk::ExportMetalObjectCreateInfoEXT::default().export_object_type(types)
+}
+
+/// Makes memory wrap `buffer`, chain it into [`vk::MemoryAllocateInfo`]
+pub fn import_buffer(buffer: vk::MTLBuffer_id) -> vk::ImportMetalBufferInfoEXT<'static> {
+    vk::ImportMetalBufferInfoEXT::default().mtl_buffer(buffer)
+}
+
+/// Makes a plane of an image wrap `texture`, chain it into [`vk::ImageCreateInfo`] once per
+/// plane
+pub fn import_texture(
+    texture: vk::MTLTexture_id,
+    plane: vk::ImageAspectFlags,
+) -> vk::ImportMetalTextureInfoEXT<'static> {
+    vk::ImportMetalTextureInfoEXT::default()
+        .mtl_texture(texture)
+        .plane(plane)
+}
+
+/// Makes an image wrap `io_surface`, chain it into [`vk::ImageCreateInfo`]
+pub fn import_io_surface(io_surface: vk::IOSurfaceRef) -> vk::ImportMetalIOSurfaceInfoEXT<'static> {
+    vk::ImportMetalIOSurfaceInfoEXT::default().io_surface(io_surface)
+}
+
+/// Makes a semaphore or event wrap `event`, chain it into [`vk::SemaphoreCreateInfo`] or
+/// [`vk::EventCreateInfo`]
+pub fn import_shared_event(
+    event: vk::MTLSharedEvent_id,
+) -> vk::ImportMetalSharedEventInfoEXT<'static> {
+    vk::ImportMetalSharedEventInfoEXT::default().mtl_shared_event(event)
+}
+
+/// The Vulkan object an `MTLTexture` is exported from
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TextureSource {
+    /// A plane of an image, [`vk::ImageAspectFlags::COLOR`] for single-planar images
+    Image(vk::Image, vk::ImageAspectFlags),
+    ImageView(vk::ImageView),
+    BufferView(vk::BufferView),
+}
+
+impl fmt::Debug for TextureSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Image(image, plane) => f
+                .debug_tuple("Image")
+                .field(image)
+                .field(&plane.as_raw())
+                .finish(),
+            Self::ImageView(view) => f.debug_tuple("ImageView").field(view).finish(),
+            Self::BufferView(view) => f.debug_tuple("BufferView").field(view).finish(),
+        }
+    }
+}
+
+/// The Vulkan object an `MTLSharedEvent` is exported from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharedEventSource {
+    Semaphore(vk::Semaphore),
+    Event(vk::Event),
+}
+
+/// Exports the Metal objects behind Vulkan objects with `vkExportMetalObjectsEXT`.
+///
+/// Every export returns `None` if the driver didn't fill in the handle, eg. because the
+/// object wasn't created exportable. See the [module documentation](self) for who owns the
+/// returned handles.
+#[derive(Clone)]
+pub struct MetalObjects {
+    loader: ash::ext::metal_objects::Device,
+}
+
+impl MetalObjects {
+    /// Loads `vkExportMetalObjectsEXT`, [`NAME`] must be enabled on `device`
+    pub fn new(instance: &ash::Instance, device: &ash::Device) -> Self {
+        Self {
+            loader: ash::ext::metal_objects::Device::new(instance, device),
+        }
+    }
+
+    /// The underlying extension loader
+    pub fn loader(&self) -> &ash::ext::metal_objects::Device {
+        &self.loader
+    }
+
+    /// Calls `vkExportMetalObjectsEXT` with `info` as the only struct in the chain
+    #[allow(unsafe_code)]
+    unsafe fn export<T: vk::ExtendsExportMetalObjectsInfoEXT + ?Sized>(&self, info: &mut T) {
+        let mut objects = vk::ExportMetalObjectsInfoEXT::default().push_next(info);
+        (self.loader.fp().export_metal_objects_ext)(self.loader.device(), &mut objects);
+    }
+
+    /// The `MTLDevice` of the device
+    ///
+    /// # Safety
+    ///
+    /// The device must be valid.
+    #[allow(unsafe_code)]
+    pub unsafe fn export_device(&self) -> Option<vk::MTLDevice_id> {
+        let mut info = vk::ExportMetalDeviceInfoEXT::default();
+        self.export(&mut info);
+        non_null(info.mtl_device)
+    }
+
+    /// The `MTLCommandQueue` behind `queue`
+    ///
+    /// # Safety
+    ///
+    /// `queue` must be a valid queue of the device.
+    #[allow(unsafe_code)]
+    pub unsafe fn export_command_queue(&self, queue: vk::Queue) -> Option<vk::MTLCommandQueue_id> {
+        let mut info = vk::ExportMetalCommandQueueInfoEXT::default().queue(queue);
+        self.export(&mut info);
+        non_null(info.mtl_command_queue)
+    }
+
+    /// The `MTLBuffer` behind `memory`
+    ///
+    /// # Safety
+    ///
+    /// `memory` must be valid memory of the device, allocated with
+    /// [`vk::ExportMetalObjectTypeFlagsEXT::METAL_BUFFER`] exportable.
+    #[allow(unsafe_code)]
+    pub unsafe fn export_buffer(&self, memory: vk::DeviceMemory) -> Option<vk::MTLBuffer_id> {
+        let mut info = vk::ExportMetalBufferInfoEXT::default().memory(memory);
+        self.export(&mut info);
+        non_null(info.mtl_buffer)
+    }
+
+    /// The `MTLTexture` behind an image plane, image view or buffer view
+    ///
+    /// # Safety
+    ///
+    /// The object must be a valid object of the device, created with
+    /// [`vk::ExportMetalObjectTypeFlagsEXT::METAL_TEXTURE`] exportable.
+    #[allow(unsafe_code)]
+    pub unsafe fn export_texture(&self, source: TextureSource) -> Option<vk::MTLTexture_id> {
+        // Exactly one of the objects may be set
+        let mut info = vk::ExportMetalTextureInfoEXT::default();
+        match source {
+            TextureSource::Image(image, plane) => info = info.image(image).plane(plane),
+            TextureSource::ImageView(view) => info = info.image_view(view),
+            TextureSource::BufferView(view) => info = info.buffer_view(view),
+        }
+
+        self.export(&mut info);
+        non_null(info.mtl_texture)
+    }
+
+    /// The `IOSurfaceRef` behind `image`
+    ///
+    /// # Safety
+    ///
+    /// `image` must be a valid image of the device, created with
+    /// [`vk::ExportMetalObjectTypeFlagsEXT::METAL_IOSURFACE`] exportable.
+    #[allow(unsafe_code)]
+    pub unsafe fn export_io_surface(&self, image: vk::Image) -> Option<vk::IOSurfaceRef> {
+        let mut info = vk::ExportMetalIOSurfaceInfoEXT::default().image(image);
+        self.export(&mut info);
+        (!info.io_surface.is_null()).then_some(info.io_surface)
+    }
+
+    /// The `MTLSharedEvent` behind a semaphore or event
+    ///
+    /// # Safety
+    ///
+    /// The object must be a valid object of the device, created with
+    /// [`vk::ExportMetalObjectTypeFlagsEXT::METAL_SHARED_EVENT`] exportable.
+    #[allow(unsafe_code)]
+    pub unsafe fn export_shared_event(
+        &self,
+        source: SharedEventSource,
+    ) -> Option<vk::MTLSharedEvent_id> {
+        // Exactly one of the objects may be set
+        let mut info = vk::ExportMetalSharedEventInfoEXT::default();
+        match source {
+            SharedEventSource::Semaphore(semaphore) => info = info.semaphore(semaphore),
+            SharedEventSource::Event(event) => info = info.event(event),
+        }
+
+        self.export(&mut info);
+        non_null(info.mtl_shared_event)
+    }
+}
+
+impl fmt::Debug for MetalObjects {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetalObjects")
+            .field("device", &self.loader.device())
+            .finish_non_exhaustive()
+    }
+}
+
+fn non_null(handle: *mut std::ffi::c_void) -> Option<*mut std::ffi::c_void> {
+    (!handle.is_null()).then_some(handle)
+}
+
+#[cfg(all(test, feature = "mock"))]
+#[allow(unsafe_code)]
+mod tests {
+    use super::*;
+    use ash::vk::Handle;
+
+    /// The mock stands in the handles of the Vulkan objects for the exported Metal objects
+    fn stand_in(handle: impl Handle) -> *mut std::ffi::c_void {
+        handle.as_raw() as *mut std::ffi::c_void
+    }
+
+    #[test]
+    fn export_chain() {
+        let entry = crate::MoltenEntry::load().unwrap();
+        unsafe {
+            let instance = entry
+                .create_instance(&vk::InstanceCreateInfo::default(), None)
+                .unwrap();
+            let physical_device = instance.enumerate_physical_devices().unwrap()[0];
+            let queue_create_info = vk::DeviceQueueCreateInfo::default().queue_priorities(&[1.0]);
+            let device = instance
+                .create_device(
+                    physical_device,
+                    &vk::DeviceCreateInfo::default()
+                        .queue_create_infos(&[queue_create_info])
+                        .enabled_extension_names(&[NAME.as_ptr()]),
+                    None,
+                )
+                .unwrap();
+            let queue = device.get_device_queue(0, 0);
+
+            let mut export = export_info(vk::ExportMetalObjectTypeFlagsEXT::METAL_BUFFER);
+            let memory = device
+                .allocate_memory(
+                    &vk::MemoryAllocateInfo::default()
+                        .allocation_size(256)
+                        .push_next(&mut export),
+                    None,
+                )
+                .unwrap();
+            let mut export = export_info(vk::ExportMetalObjectTypeFlagsEXT::METAL_SHARED_EVENT);
+            let semaphore = device
+                .create_semaphore(
+                    &vk::SemaphoreCreateInfo::default().push_next(&mut export),
+                    None,
+                )
+                .unwrap();
+
+            let mut export = export_info(
+                vk::ExportMetalObjectTypeFlagsEXT::METAL_TEXTURE
+                    | vk::ExportMetalObjectTypeFlagsEXT::METAL_IOSURFACE,
+            );
+            let image = device
+                .create_image(
+                    &vk::ImageCreateInfo::default()
+                        .image_type(vk::ImageType::TYPE_2D)
+                        .format(vk::Format::R8G8B8A8_UNORM)
+                        .extent(vk::Extent3D {
+                            width: 4,
+                            height: 4,
+                            depth: 1,
+                        })
+                        .mip_levels(1)
+                        .array_layers(1)
+                        .samples(vk::SampleCountFlags::TYPE_1)
+                        .usage(vk::ImageUsageFlags::SAMPLED)
+                        .push_next(&mut export),
+                    None,
+                )
+                .unwrap();
+
+            // Several exports chained into a single call
+            let objects = MetalObjects::new(&instance, &device);
+            let mut device_info = vk::ExportMetalDeviceInfoEXT::default();
+            let mut queue_info = vk::ExportMetalCommandQueueInfoEXT::default().queue(queue);
+            let mut buffer_info = vk::ExportMetalBufferInfoEXT::default().memory(memory);
+            let mut event_info = vk::ExportMetalSharedEventInfoEXT::default().semaphore(semaphore);
+            let mut info = vk::ExportMetalObjectsInfoEXT::default()
+                .push_next(&mut device_info)
+                .push_next(&mut queue_info)
+                .push_next(&mut buffer_info)
+                .push_next(&mut event_info);
+            (objects.loader().fp().export_metal_objects_ext)(device.handle(), &mut info);
+            assert_eq!(device_info.mtl_device, stand_in(device.handle()));
+            assert_eq!(queue_info.mtl_command_queue, stand_in(queue));
+            assert_eq!(buffer_info.mtl_buffer, stand_in(memory));
+            assert_eq!(event_info.mtl_shared_event, stand_in(semaphore));
+
+            // The same objects one at a time
+            assert_eq!(objects.export_device(), Some(stand_in(device.handle())));
+            assert_eq!(objects.export_command_queue(queue), Some(stand_in(queue)));
+            assert_eq!(objects.export_buffer(memory), Some(stand_in(memory)));
+            assert_eq!(
+                objects.export_shared_event(SharedEventSource::Semaphore(semaphore)),
+                Some(stand_in(semaphore))
+            );
+            assert_eq!(
+                objects.export_texture(TextureSource::Image(image, vk::ImageAspectFlags::COLOR)),
+                Some(stand_in(image))
+            );
+            assert_eq!(
+                objects.export_io_surface(image),
+                Some(stand_in(image).cast())
+            );
+
+            device.destroy_image(image, None);
+            device.destroy_semaphore(semaphore, None);
+            device.free_memory(memory, None);
+            device.destroy_device(None);
+            instance.destroy_instance(None);
+        }
+    }
+}
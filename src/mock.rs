@@ -0,0 +1,1682 @@
+//! A Rust implementation of a MoltenVK-like driver, enabled with the `mock` feature.
+//!
+//! Instead of linking MoltenVK, [`load`](crate::load) and friends resolve their entry points
+//! from this module. It emulates an instance with one or more physical devices whose
+//! properties, features, extensions and `VK_KHR_portability_subset` support can be configured
+//! with [`configure`], so instance and device bring-up code can be exercised without a GPU or
+//! on platforms MoltenVK doesn't support.
+//!
+//! The instance and device creation and query functions are implemented, along with
+//! `VK_EXT_debug_utils` messengers and `vkSubmitDebugUtilsMessageEXT`, Metal and headless surfaces and their queries,
+//! `vkExportMetalObjectsEXT` and the configuration, Metal features, performance statistics and
+//! version queries of the [`mvk`] API. Memory, buffers, images, shaders, pipelines,
+//! descriptors, fences, semaphores and command buffers are tracked, device memory
+//! is host memory, and buffer copies, fills and updates run when submitted; other commands
+//! are recorded and ignored. Everything else resolves to null. Like
+//! MoltenVK, instance and device creation are reported to the messengers when the configured
+//! log level includes info messages, and dispatchable handles point to objects whose first
+//! word a loader or layer may overwrite.
+
+// crate-specific exceptions: this module implements Vulkan entry points called through raw
+// pointers
+#![allow(unsafe_code)]
+
+use crate::{mvk, version::Version};
+use ash::vk::{self, Handle as _};
+use std::{
+    ffi::{c_char, c_void, CStr, CString},
+    mem::{self, transmute},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+#[cfg(feature = "layers")]
+pub mod layer;
+
+/// The configuration instances created by the mock driver are based on
+#[derive(Clone)]
+pub struct MockConfig {
+    /// The instance version reported by `vkEnumerateInstanceVersion`
+    pub api_version: u32,
+    /// The instance extensions that are advertised and may be enabled
+    pub instance_extensions: Vec<vk::ExtensionProperties>,
+    /// Fails instance creation with `VK_ERROR_INCOMPATIBLE_DRIVER` unless
+    /// `VK_KHR_portability_enumeration` and `ENUMERATE_PORTABILITY_KHR` are enabled, like the
+    /// Vulkan loader does for portability drivers
+    pub require_portability_enumeration: bool,
+    /// The physical devices every instance enumerates
+    pub physical_devices: Vec<MockPhysicalDevice>,
+    /// The MoltenVK configuration of new instances, with the `MoltenVK` settings passed
+    /// through `VK_EXT_layer_settings` applied. `vkGetMoltenVKConfigurationMVK` and
+    /// `vkSetMoltenVKConfigurationMVK` read and replace the copy of the instance they are
+    /// called with, or this one with a null instance
+    pub moltenvk_configuration: mvk::Configuration,
+    /// Returned by `vkGetPerformanceStatisticsMVK` for new devices, until
+    /// [`set_performance_statistics`] replaces them
+    pub performance_statistics: mvk::PerformanceStatistics,
+}
+
+impl Default for MockConfig {
+    /// Mirrors MoltenVK running on Apple silicon
+    fn default() -> Self {
+        Self {
+            api_version: vk::API_VERSION_1_2,
+            instance_extensions: vec![
+                extension(ash::khr::surface::NAME, ash::khr::surface::SPEC_VERSION),
+                extension(
+                    ash::ext::metal_surface::NAME,
+                    ash::ext::metal_surface::SPEC_VERSION,
+                ),
+                extension(
+                    ash::ext::headless_surface::NAME,
+                    ash::ext::headless_surface::SPEC_VERSION,
+                ),
+                extension(
+                    ash::khr::portability_enumeration::NAME,
+                    ash::khr::portability_enumeration::SPEC_VERSION,
+                ),
+                extension(
+                    ash::khr::get_physical_device_properties2::NAME,
+                    ash::khr::get_physical_device_properties2::SPEC_VERSION,
+                ),
+                extension(
+                    ash::ext::debug_utils::NAME,
+                    ash::ext::debug_utils::SPEC_VERSION,
+                ),
+                extension(
+                    ash::ext::layer_settings::NAME,
+                    ash::ext::layer_settings::SPEC_VERSION,
+                ),
+            ],
+            require_portability_enumeration: false,
+            physical_devices: vec![MockPhysicalDevice::default()],
+            moltenvk_configuration: default_moltenvk_configuration(),
+            performance_statistics: mvk::PerformanceStatistics::default(),
+        }
+    }
+}
+
+/// The defaults MoltenVK documents for its `MVK_CONFIG_*` settings
+fn default_moltenvk_configuration() -> mvk::Configuration {
+    mvk::Configuration {
+        synchronous_queue_submits: vk::TRUE,
+        max_active_metal_command_buffers_per_queue: 64,
+        swapchain_min_mag_filter_use_nearest: vk::TRUE,
+        metal_compile_timeout: i64::MAX as u64,
+        fast_math_enabled: mvk::ConfigFastMath::ALWAYS,
+        log_level: mvk::ConfigLogLevel::INFO,
+        semaphore_support_style: mvk::SemaphoreSupportStyle::METAL_EVENTS_WHERE_SAFE,
+        texture_1d_as_2d: vk::TRUE,
+        preallocate_descriptors: vk::TRUE,
+        use_command_pooling: vk::TRUE,
+        api_version_to_advertise: vk::API_VERSION_1_2,
+        advertise_extensions: mvk::ConfigAdvertiseExtensions::ALL,
+        #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+        timestamp_period_low_pass_alpha: 1.0,
+        ..Default::default()
+    }
+}
+
+/// A physical device enumerated by the mock driver
+#[derive(Clone)]
+pub struct MockPhysicalDevice {
+    pub properties: vk::PhysicalDeviceProperties,
+    pub features: vk::PhysicalDeviceFeatures,
+    pub queue_families: Vec<vk::QueueFamilyProperties>,
+    pub memory_properties: vk::PhysicalDeviceMemoryProperties,
+    /// The device extensions that are advertised and may be enabled
+    pub extensions: Vec<vk::ExtensionProperties>,
+    /// Reported through `vkGetPhysicalDeviceFeatures2` if `Some`, should be accompanied by
+    /// `VK_KHR_portability_subset` in [`MockPhysicalDevice::extensions`]
+    pub portability_subset_features:
+        Option<vk::PhysicalDevicePortabilitySubsetFeaturesKHR<'static>>,
+    /// Reported through `vkGetPhysicalDeviceProperties2` if `Some`
+    pub portability_subset_properties:
+        Option<vk::PhysicalDevicePortabilitySubsetPropertiesKHR<'static>>,
+    /// Returned by `vkGetPhysicalDeviceMetalFeaturesMVK`
+    pub metal_features: mvk::PhysicalDeviceMetalFeatures,
+}
+
+impl Default for MockPhysicalDevice {
+    /// Mirrors what MoltenVK reports for an Apple silicon GPU
+    fn default() -> Self {
+        let linked = Version::linked();
+
+        let properties = vk::PhysicalDeviceProperties {
+            api_version: vk::API_VERSION_1_2,
+            driver_version: linked.major * 10000 + linked.minor * 100 + linked.patch,
+            vendor_id: 0x106b,
+            device_type: vk::PhysicalDeviceType::INTEGRATED_GPU,
+            limits: vk::PhysicalDeviceLimits {
+                max_image_dimension1_d: 16384,
+                max_image_dimension2_d: 16384,
+                max_image_dimension3_d: 2048,
+                max_image_dimension_cube: 16384,
+                max_image_array_layers: 2048,
+                max_bound_descriptor_sets: 8,
+                max_push_constants_size: 4096,
+                max_vertex_input_bindings: 31,
+                max_vertex_input_attributes: 31,
+                max_compute_work_group_invocations: 1024,
+                max_compute_work_group_size: [1024, 1024, 1024],
+                max_viewports: 16,
+                timestamp_compute_and_graphics: vk::TRUE,
+                timestamp_period: 1.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .device_name(c"Apple M1 (mock)")
+        .expect("device name fits");
+
+        let features = vk::PhysicalDeviceFeatures {
+            robust_buffer_access: vk::TRUE,
+            full_draw_index_uint32: vk::TRUE,
+            image_cube_array: vk::TRUE,
+            independent_blend: vk::TRUE,
+            dual_src_blend: vk::TRUE,
+            depth_clamp: vk::TRUE,
+            fill_mode_non_solid: vk::TRUE,
+            sampler_anisotropy: vk::TRUE,
+            texture_compression_bc: vk::TRUE,
+            texture_compression_astc_ldr: vk::TRUE,
+            texture_compression_etc2: vk::TRUE,
+            fragment_stores_and_atomics: vk::TRUE,
+            shader_int16: vk::TRUE,
+            ..Default::default()
+        };
+
+        let queue_families = vec![vk::QueueFamilyProperties {
+            queue_flags: vk::QueueFlags::GRAPHICS
+                | vk::QueueFlags::COMPUTE
+                | vk::QueueFlags::TRANSFER,
+            queue_count: 1,
+            timestamp_valid_bits: 64,
+            min_image_transfer_granularity: vk::Extent3D {
+                width: 1,
+                height: 1,
+                depth: 1,
+            },
+        }];
+
+        // Apple silicon has unified memory, so everything is device local
+        let mut memory_properties = vk::PhysicalDeviceMemoryProperties {
+            memory_type_count: 2,
+            memory_heap_count: 1,
+            ..Default::default()
+        };
+        memory_properties.memory_heaps[0] = vk::MemoryHeap {
+            size: 8 << 30,
+            flags: vk::MemoryHeapFlags::DEVICE_LOCAL,
+        };
+        memory_properties.memory_types[0] = vk::MemoryType {
+            property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            heap_index: 0,
+        };
+        memory_properties.memory_types[1] = vk::MemoryType {
+            property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL
+                | vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+            heap_index: 0,
+        };
+
+        Self {
+            properties,
+            features,
+            queue_families,
+            memory_properties,
+            extensions: vec![
+                extension(ash::khr::swapchain::NAME, ash::khr::swapchain::SPEC_VERSION),
+                extension(
+                    ash::khr::portability_subset::NAME,
+                    ash::khr::portability_subset::SPEC_VERSION,
+                ),
+                extension(
+                    ash::ext::metal_objects::NAME,
+                    ash::ext::metal_objects::SPEC_VERSION,
+                ),
+            ],
+            portability_subset_features: Some(vk::PhysicalDevicePortabilitySubsetFeaturesKHR {
+                constant_alpha_color_blend_factors: vk::TRUE,
+                events: vk::TRUE,
+                image_view_format_reinterpretation: vk::TRUE,
+                image_view_format_swizzle: vk::TRUE,
+                image_view2_d_on3_d_image: vk::TRUE,
+                multisample_array_image: vk::TRUE,
+                mutable_comparison_samplers: vk::TRUE,
+                point_polygons: vk::FALSE,
+                sampler_mip_lod_bias: vk::FALSE,
+                separate_stencil_mask_ref: vk::TRUE,
+                shader_sample_rate_interpolation_functions: vk::TRUE,
+                tessellation_isolines: vk::FALSE,
+                tessellation_point_mode: vk::TRUE,
+                triangle_fans: vk::FALSE,
+                vertex_attribute_access_beyond_stride: vk::TRUE,
+                ..Default::default()
+            }),
+            portability_subset_properties: Some(vk::PhysicalDevicePortabilitySubsetPropertiesKHR {
+                min_vertex_input_binding_stride_alignment: 4,
+                ..Default::default()
+            }),
+            metal_features: mvk::PhysicalDeviceMetalFeatures {
+                msl_version: 30000,
+                indirect_drawing: vk::TRUE,
+                base_vertex_instance_drawing: vk::TRUE,
+                dynamic_mtl_buffer_size: 4096,
+                shader_specialization: vk::TRUE,
+                io_surfaces: vk::TRUE,
+                texel_buffers: vk::TRUE,
+                layered_rendering: vk::TRUE,
+                max_texture_dimension: 16384,
+                max_per_stage_buffer_count: 31,
+                max_per_stage_texture_count: 128,
+                max_per_stage_sampler_count: 16,
+                max_mtl_buffer_size: 8 << 30,
+                mtl_buffer_alignment: 16,
+                supported_sample_counts: vk::SampleCountFlags::TYPE_1
+                    | vk::SampleCountFlags::TYPE_4,
+                min_swapchain_image_count: 2,
+                max_swapchain_image_count: 3,
+                argument_buffers: vk::TRUE,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Creates the [`vk::ExtensionProperties`] for an extension
+pub fn extension(name: &CStr, spec_version: u32) -> vk::ExtensionProperties {
+    vk::ExtensionProperties {
+        spec_version,
+        ..Default::default()
+    }
+    .extension_name(name)
+    .expect("extension name fits")
+}
+
+static CONFIG: Mutex<Option<MockConfig>> = Mutex::new(None);
+
+#[cfg(test)]
+thread_local! {
+    /// Overrides [`CONFIG`] on a test's thread, so tests running in parallel don't see each
+    /// other's configuration
+    static THREAD_CONFIG: std::cell::RefCell<Option<MockConfig>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Sets the configuration instances created from now on are based on.
+///
+/// Existing instances keep the configuration they were created with, including their
+/// MoltenVK configuration.
+pub fn configure(config: MockConfig) {
+    *CONFIG.lock().unwrap() = Some(config);
+}
+
+/// The configuration new instances are based on
+pub fn config() -> MockConfig {
+    #[cfg(test)]
+    if let Some(config) = THREAD_CONFIG.with_borrow(Clone::clone) {
+        return config;
+    }
+    CONFIG.lock().unwrap().clone().unwrap_or_default()
+}
+
+/// Runs `f` with `config` as the configuration of the instances created on this thread
+#[cfg(test)]
+pub(crate) fn with_config<R>(config: MockConfig, f: impl FnOnce() -> R) -> R {
+    let previous = THREAD_CONFIG.replace(Some(config));
+    let result = f();
+    THREAD_CONFIG.set(previous);
+    result
+}
+
+/// Replaces the statistics `vkGetPerformanceStatisticsMVK` returns for `device`
+///
+/// # Safety
+///
+/// `device` must be a device created by the mock driver that wasn't destroyed.
+pub unsafe fn set_performance_statistics(
+    device: vk::Device,
+    statistics: mvk::PerformanceStatistics,
+) {
+    *as_object::<DeviceObject>(device)
+        .performance_statistics
+        .lock()
+        .unwrap() = statistics;
+}
+
+/// The initial value of [`LoaderData`], like in MoltenVK
+const ICD_LOADER_MAGIC: usize = 0x01CD_C0DE;
+
+/// The first member of every dispatchable object, which a loader or layer host may overwrite
+/// with its dispatch key as specified by the loader-ICD interface
+#[repr(transparent)]
+struct LoaderData(*mut c_void);
+
+impl LoaderData {
+    fn new() -> Self {
+        Self(ICD_LOADER_MAGIC as *mut c_void)
+    }
+}
+
+#[repr(C)]
+struct InstanceObject {
+    loader_data: LoaderData,
+    /// Never modified after creation, so the addresses of the devices can be used as handles
+    physical_devices: Vec<PhysicalDeviceObject>,
+    /// The `VK_EXT_debug_utils` messengers, by handle
+    messengers: Mutex<Vec<(vk::DebugUtilsMessengerEXT, MessengerObject)>>,
+    /// The Metal and headless surfaces, which all behave like headless surfaces
+    surfaces: Mutex<Vec<vk::SurfaceKHR>>,
+    moltenvk_configuration: Mutex<mvk::Configuration>,
+    /// The statistics of new devices
+    performance_statistics: mvk::PerformanceStatistics,
+    /// Whether `VK_KHR_get_physical_device_properties2` is enabled, without which its commands
+    /// don't resolve, like in MoltenVK
+    properties2_enabled: bool,
+}
+
+#[repr(C)]
+struct PhysicalDeviceObject {
+    loader_data: LoaderData,
+    device: MockPhysicalDevice,
+    /// The instance the device was enumerated from
+    instance: *const InstanceObject,
+}
+
+struct MessengerObject {
+    severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    types: vk::DebugUtilsMessageTypeFlagsEXT,
+    callback: vk::PFN_vkDebugUtilsMessengerCallbackEXT,
+    user_data: *mut c_void,
+}
+
+impl MessengerObject {
+    fn new(create_info: &vk::DebugUtilsMessengerCreateInfoEXT<'_>) -> Self {
+        Self {
+            severity: create_info.message_severity,
+            types: create_info.message_type,
+            callback: create_info.pfn_user_callback,
+            user_data: create_info.p_user_data,
+        }
+    }
+
+    /// Calls the callback if the messenger accepts `severity` and `types`
+    unsafe fn deliver(
+        &self,
+        severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+        types: vk::DebugUtilsMessageTypeFlagsEXT,
+        data: &vk::DebugUtilsMessengerCallbackDataEXT<'_>,
+    ) {
+        if let Some(callback) = self.callback {
+            if self.severity.intersects(severity) && self.types.intersects(types) {
+                callback(severity, types, data, self.user_data);
+            }
+        }
+    }
+}
+
+#[repr(C)]
+struct DeviceObject {
+    loader_data: LoaderData,
+    /// The queues that were requested, by family. Never modified after creation, so the
+    /// addresses of the queues can be used as handles
+    queues: Vec<Vec<QueueObject>>,
+    resources: Mutex<resources::Resources>,
+    performance_statistics: Mutex<mvk::PerformanceStatistics>,
+}
+
+#[repr(C)]
+struct QueueObject {
+    loader_data: LoaderData,
+    /// The device the queue belongs to
+    device: *const DeviceObject,
+}
+
+unsafe fn properties2_enabled(instance: vk::Instance) -> bool {
+    as_object::<InstanceObject>(instance).properties2_enabled
+}
+
+unsafe fn as_object<'a, T>(handle: impl vk::Handle) -> &'a T {
+    &*(handle.as_raw() as *const T)
+}
+
+fn into_handle<H: vk::Handle, T>(object: Box<T>) -> H {
+    H::from_raw(Box::into_raw(object) as u64)
+}
+
+/// A unique handle for non-dispatchable objects that are tracked by their instance
+fn next_handle<H: vk::Handle>() -> H {
+    static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
+    H::from_raw(NEXT_HANDLE.fetch_add(1, Ordering::Relaxed))
+}
+
+unsafe fn c_strs<'a>(names: *const *const c_char, count: u32) -> Vec<&'a CStr> {
+    if names.is_null() {
+        return Vec::new();
+    }
+
+    std::slice::from_raw_parts(names, count as usize)
+        .iter()
+        .map(|name| CStr::from_ptr(*name))
+        .collect()
+}
+
+fn supports(extensions: &[vk::ExtensionProperties], name: &CStr) -> bool {
+    extensions
+        .iter()
+        .any(|extension| extension.extension_name_as_c_str() == Ok(name))
+}
+
+/// Implements the two-call enumeration idiom
+unsafe fn enumerate<T: Copy>(items: &[T], p_count: *mut u32, p_items: *mut T) -> vk::Result {
+    if p_items.is_null() {
+        *p_count = items.len() as u32;
+        return vk::Result::SUCCESS;
+    }
+
+    let count = (*p_count as usize).min(items.len());
+    std::ptr::copy_nonoverlapping(items.as_ptr(), p_items, count);
+    *p_count = count as u32;
+
+    if count < items.len() {
+        vk::Result::INCOMPLETE
+    } else {
+        vk::Result::SUCCESS
+    }
+}
+
+/// Implements the size-checked queries of the `mvk` API
+unsafe fn copy_sized<T: Copy>(value: &T, p_value: *mut T, p_size: *mut usize) -> vk::Result {
+    let size = mem::size_of::<T>();
+    if p_value.is_null() {
+        *p_size = size;
+        return vk::Result::SUCCESS;
+    }
+
+    let count = (*p_size).min(size);
+    std::ptr::copy_nonoverlapping(
+        (value as *const T).cast::<u8>(),
+        p_value.cast::<u8>(),
+        count,
+    );
+
+    if *p_size == size {
+        vk::Result::SUCCESS
+    } else {
+        *p_size = size;
+        vk::Result::INCOMPLETE
+    }
+}
+
+/// Copies `value` into a C string buffer, truncating it if needed
+unsafe fn copy_str(value: &str, buffer: *mut c_char, length: u32) {
+    if buffer.is_null() || length == 0 {
+        return;
+    }
+
+    let count = value.len().min(length as usize - 1);
+    std::ptr::copy_nonoverlapping(value.as_ptr().cast::<c_char>(), buffer, count);
+    *buffer.add(count) = 0;
+}
+
+/// Walks a `pNext` chain
+unsafe fn for_each_in_chain(
+    mut next: *mut vk::BaseOutStructure<'_>,
+    mut f: impl FnMut(*mut vk::BaseOutStructure<'_>),
+) {
+    while !next.is_null() {
+        f(next);
+        next = (*next).p_next;
+    }
+}
+
+/// Finds a struct in a `pNext` chain
+unsafe fn find_in_chain(
+    next: *const std::ffi::c_void,
+    s_type: vk::StructureType,
+) -> *const vk::BaseInStructure<'static> {
+    let mut next = next.cast::<vk::BaseInStructure<'_>>();
+    while !next.is_null() && (*next).s_type != s_type {
+        next = (*next).p_next;
+    }
+    next
+}
+
+/// Reports a general message to `messengers` like MoltenVK does, if the log level of
+/// `configuration` includes `level`
+unsafe fn report<'a>(
+    configuration: &mvk::Configuration,
+    messengers: impl IntoIterator<Item = &'a MessengerObject>,
+    level: mvk::ConfigLogLevel,
+    message: &str,
+) {
+    if configuration.log_level.0 < level.0 {
+        return;
+    }
+
+    let severity = match level {
+        mvk::ConfigLogLevel::ERROR => vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+        mvk::ConfigLogLevel::WARNING => vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
+        mvk::ConfigLogLevel::INFO => vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
+        _ => vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+    };
+    let types = vk::DebugUtilsMessageTypeFlagsEXT::GENERAL;
+
+    let message = CString::new(message).expect("messages have no nul bytes");
+    let data = vk::DebugUtilsMessengerCallbackDataEXT {
+        p_message_id_name: c"MoltenVK".as_ptr(),
+        p_message: message.as_ptr(),
+        ..Default::default()
+    };
+
+    for messenger in messengers {
+        messenger.deliver(severity, types, &data);
+    }
+}
+
+/// Applies the `MoltenVK` layer settings, the mock's counterpart of MoltenVK reading them
+unsafe fn apply_layer_settings(
+    configuration: &mut mvk::Configuration,
+    create_info: &vk::LayerSettingsCreateInfoEXT<'_>,
+) {
+    let settings = if create_info.p_settings.is_null() {
+        &[]
+    } else {
+        std::slice::from_raw_parts(create_info.p_settings, create_info.setting_count as usize)
+    };
+
+    for setting in settings {
+        if setting.value_count == 0 || CStr::from_ptr(setting.p_layer_name) != crate::LAYER_NAME {
+            continue;
+        }
+
+        let name = CStr::from_ptr(setting.p_setting_name).to_bytes();
+        match setting.ty {
+            vk::LayerSettingTypeEXT::BOOL32
+            | vk::LayerSettingTypeEXT::UINT32
+            | vk::LayerSettingTypeEXT::INT32 => {
+                apply_u32_setting(configuration, name, *setting.p_values.cast::<u32>());
+            }
+            vk::LayerSettingTypeEXT::UINT64 if name == b"METAL_COMPILE_TIMEOUT" => {
+                configuration.metal_compile_timeout = *setting.p_values.cast::<u64>();
+            }
+            #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+            vk::LayerSettingTypeEXT::FLOAT32 if name == b"TIMESTAMP_PERIOD_LOWPASS_ALPHA" => {
+                configuration.timestamp_period_low_pass_alpha = *setting.p_values.cast::<f32>();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn apply_u32_setting(configuration: &mut mvk::Configuration, name: &[u8], value: u32) {
+    match name {
+        b"DEBUG" => configuration.debug_mode = value,
+        b"LOG_LEVEL" => configuration.log_level = mvk::ConfigLogLevel(value as i32),
+        b"USE_METAL_ARGUMENT_BUFFERS" => configuration.use_metal_argument_buffers = value,
+        b"FAST_MATH_ENABLED" => configuration.fast_math_enabled = mvk::ConfigFastMath(value as i32),
+        #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+        b"SHOULD_MAXIMIZE_CONCURRENT_COMPILATION" => {
+            configuration.should_maximize_concurrent_compilation = value;
+        }
+        b"AUTO_GPU_CAPTURE_SCOPE" => {
+            configuration.auto_gpu_capture_scope = mvk::ConfigAutoGpuCaptureScope(value as i32);
+        }
+        b"SWAPCHAIN_MIN_MAG_FILTER_USE_NEAREST" => {
+            configuration.swapchain_min_mag_filter_use_nearest = value;
+        }
+        b"PRESENT_WITH_COMMAND_BUFFER" => configuration.present_with_command_buffer = value,
+        b"SYNCHRONOUS_QUEUE_SUBMITS" => configuration.synchronous_queue_submits = value,
+        b"PERFORMANCE_TRACKING" => configuration.performance_tracking = value,
+        b"PERFORMANCE_LOGGING_FRAME_COUNT" => {
+            configuration.performance_logging_frame_count = value;
+        }
+        b"API_VERSION_TO_ADVERTISE" => configuration.api_version_to_advertise = value,
+        _ => {}
+    }
+}
+
+macro_rules! void_fn {
+    ($f:expr, $pfn:ty) => {
+        Some(transmute::<$pfn, unsafe extern "system" fn()>($f))
+    };
+}
+
+// Declared after `void_fn!` so the module can use it
+mod resources;
+
+/// The mock `vkGetInstanceProcAddr`
+///
+/// # Safety
+///
+/// Must be called as specified for `vkGetInstanceProcAddr` by the Vulkan specification.
+pub unsafe extern "system" fn get_instance_proc_addr(
+    instance: vk::Instance,
+    p_name: *const c_char,
+) -> vk::PFN_vkVoidFunction {
+    let name = CStr::from_ptr(p_name).to_bytes();
+
+    let global = match name {
+        b"vkGetInstanceProcAddr" => {
+            void_fn!(get_instance_proc_addr, vk::PFN_vkGetInstanceProcAddr)
+        }
+        b"vkEnumerateInstanceVersion" => {
+            void_fn!(
+                enumerate_instance_version,
+                vk::PFN_vkEnumerateInstanceVersion
+            )
+        }
+        b"vkEnumerateInstanceExtensionProperties" => void_fn!(
+            enumerate_instance_extension_properties,
+            vk::PFN_vkEnumerateInstanceExtensionProperties
+        ),
+        b"vkEnumerateInstanceLayerProperties" => void_fn!(
+            enumerate_instance_layer_properties,
+            vk::PFN_vkEnumerateInstanceLayerProperties
+        ),
+        b"vkCreateInstance" => void_fn!(create_instance, vk::PFN_vkCreateInstance),
+        _ => None,
+    };
+
+    if global.is_some() || instance == vk::Instance::null() {
+        return global;
+    }
+
+    match name {
+        b"vkDestroyInstance" => void_fn!(destroy_instance, vk::PFN_vkDestroyInstance),
+        b"vkCreateDebugUtilsMessengerEXT" => void_fn!(
+            create_debug_utils_messenger,
+            vk::PFN_vkCreateDebugUtilsMessengerEXT
+        ),
+        b"vkDestroyDebugUtilsMessengerEXT" => void_fn!(
+            destroy_debug_utils_messenger,
+            vk::PFN_vkDestroyDebugUtilsMessengerEXT
+        ),
+        b"vkSubmitDebugUtilsMessageEXT" => void_fn!(
+            submit_debug_utils_message,
+            vk::PFN_vkSubmitDebugUtilsMessageEXT
+        ),
+        b"vkCreateMetalSurfaceEXT" => {
+            void_fn!(create_metal_surface, vk::PFN_vkCreateMetalSurfaceEXT)
+        }
+        b"vkCreateHeadlessSurfaceEXT" => {
+            void_fn!(create_headless_surface, vk::PFN_vkCreateHeadlessSurfaceEXT)
+        }
+        b"vkDestroySurfaceKHR" => void_fn!(destroy_surface, vk::PFN_vkDestroySurfaceKHR),
+        b"vkGetPhysicalDeviceSurfaceSupportKHR" => void_fn!(
+            get_physical_device_surface_support,
+            vk::PFN_vkGetPhysicalDeviceSurfaceSupportKHR
+        ),
+        b"vkGetPhysicalDeviceSurfaceCapabilitiesKHR" => void_fn!(
+            get_physical_device_surface_capabilities,
+            vk::PFN_vkGetPhysicalDeviceSurfaceCapabilitiesKHR
+        ),
+        b"vkGetPhysicalDeviceSurfaceFormatsKHR" => void_fn!(
+            get_physical_device_surface_formats,
+            vk::PFN_vkGetPhysicalDeviceSurfaceFormatsKHR
+        ),
+        b"vkGetPhysicalDeviceSurfacePresentModesKHR" => void_fn!(
+            get_physical_device_surface_present_modes,
+            vk::PFN_vkGetPhysicalDeviceSurfacePresentModesKHR
+        ),
+        b"vkEnumeratePhysicalDevices" => {
+            void_fn!(
+                enumerate_physical_devices,
+                vk::PFN_vkEnumeratePhysicalDevices
+            )
+        }
+        b"vkGetPhysicalDeviceProperties" => void_fn!(
+            get_physical_device_properties,
+            vk::PFN_vkGetPhysicalDeviceProperties
+        ),
+        b"vkGetPhysicalDeviceProperties2" => void_fn!(
+            get_physical_device_properties2,
+            vk::PFN_vkGetPhysicalDeviceProperties2
+        ),
+        b"vkGetPhysicalDeviceProperties2KHR" if properties2_enabled(instance) => void_fn!(
+            get_physical_device_properties2,
+            vk::PFN_vkGetPhysicalDeviceProperties2
+        ),
+        b"vkGetPhysicalDeviceFeatures" => void_fn!(
+            get_physical_device_features,
+            vk::PFN_vkGetPhysicalDeviceFeatures
+        ),
+        b"vkGetPhysicalDeviceFeatures2" => void_fn!(
+            get_physical_device_features2,
+            vk::PFN_vkGetPhysicalDeviceFeatures2
+        ),
+        b"vkGetPhysicalDeviceFeatures2KHR" if properties2_enabled(instance) => void_fn!(
+            get_physical_device_features2,
+            vk::PFN_vkGetPhysicalDeviceFeatures2
+        ),
+        b"vkGetPhysicalDeviceQueueFamilyProperties" => void_fn!(
+            get_physical_device_queue_family_properties,
+            vk::PFN_vkGetPhysicalDeviceQueueFamilyProperties
+        ),
+        b"vkGetPhysicalDeviceMemoryProperties" => void_fn!(
+            get_physical_device_memory_properties,
+            vk::PFN_vkGetPhysicalDeviceMemoryProperties
+        ),
+        b"vkEnumerateDeviceExtensionProperties" => void_fn!(
+            enumerate_device_extension_properties,
+            vk::PFN_vkEnumerateDeviceExtensionProperties
+        ),
+        b"vkCreateDevice" => void_fn!(create_device, vk::PFN_vkCreateDevice),
+        b"vkGetMoltenVKConfigurationMVK" => void_fn!(
+            get_moltenvk_configuration,
+            mvk::PFN_vkGetMoltenVKConfigurationMVK
+        ),
+        b"vkSetMoltenVKConfigurationMVK" => void_fn!(
+            set_moltenvk_configuration,
+            mvk::PFN_vkSetMoltenVKConfigurationMVK
+        ),
+        b"vkGetPhysicalDeviceMetalFeaturesMVK" => void_fn!(
+            get_physical_device_metal_features,
+            mvk::PFN_vkGetPhysicalDeviceMetalFeaturesMVK
+        ),
+        b"vkGetVersionStringsMVK" => {
+            void_fn!(get_version_strings, mvk::PFN_vkGetVersionStringsMVK)
+        }
+        _ => get_device_proc_addr(vk::Device::null(), p_name),
+    }
+}
+
+/// The mock `vkGetDeviceProcAddr`
+unsafe extern "system" fn get_device_proc_addr(
+    _device: vk::Device,
+    p_name: *const c_char,
+) -> vk::PFN_vkVoidFunction {
+    match CStr::from_ptr(p_name).to_bytes() {
+        b"vkGetDeviceProcAddr" => void_fn!(get_device_proc_addr, vk::PFN_vkGetDeviceProcAddr),
+        b"vkDestroyDevice" => void_fn!(destroy_device, vk::PFN_vkDestroyDevice),
+        b"vkGetDeviceQueue" => void_fn!(get_device_queue, vk::PFN_vkGetDeviceQueue),
+        b"vkDeviceWaitIdle" => void_fn!(device_wait_idle, vk::PFN_vkDeviceWaitIdle),
+        b"vkExportMetalObjectsEXT" => {
+            void_fn!(export_metal_objects, vk::PFN_vkExportMetalObjectsEXT)
+        }
+        b"vkGetPerformanceStatisticsMVK" => void_fn!(
+            get_performance_statistics,
+            mvk::PFN_vkGetPerformanceStatisticsMVK
+        ),
+        _ => resources::get_device_proc_addr(CStr::from_ptr(p_name)),
+    }
+}
+
+unsafe extern "system" fn enumerate_instance_version(p_api_version: *mut u32) -> vk::Result {
+    *p_api_version = config().api_version;
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn enumerate_instance_extension_properties(
+    p_layer_name: *const c_char,
+    p_property_count: *mut u32,
+    p_properties: *mut vk::ExtensionProperties,
+) -> vk::Result {
+    if !p_layer_name.is_null() {
+        return vk::Result::ERROR_LAYER_NOT_PRESENT;
+    }
+
+    enumerate(
+        &config().instance_extensions,
+        p_property_count,
+        p_properties,
+    )
+}
+
+unsafe extern "system" fn enumerate_instance_layer_properties(
+    p_property_count: *mut u32,
+    p_properties: *mut vk::LayerProperties,
+) -> vk::Result {
+    enumerate(&[], p_property_count, p_properties)
+}
+
+unsafe extern "system" fn create_instance(
+    p_create_info: *const vk::InstanceCreateInfo<'_>,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+    p_instance: *mut vk::Instance,
+) -> vk::Result {
+    let create_info = &*p_create_info;
+    let config = config();
+
+    if create_info.enabled_layer_count > 0 {
+        return vk::Result::ERROR_LAYER_NOT_PRESENT;
+    }
+
+    let extensions = c_strs(
+        create_info.pp_enabled_extension_names,
+        create_info.enabled_extension_count,
+    );
+    if !extensions
+        .iter()
+        .all(|name| supports(&config.instance_extensions, name))
+    {
+        return vk::Result::ERROR_EXTENSION_NOT_PRESENT;
+    }
+
+    if config.require_portability_enumeration
+        && !(create_info
+            .flags
+            .contains(vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR)
+            && extensions.contains(&ash::khr::portability_enumeration::NAME))
+    {
+        return vk::Result::ERROR_INCOMPATIBLE_DRIVER;
+    }
+
+    let mut configuration = config.moltenvk_configuration;
+    let settings = find_in_chain(
+        create_info.p_next,
+        vk::StructureType::LAYER_SETTINGS_CREATE_INFO_EXT,
+    )
+    .cast::<vk::LayerSettingsCreateInfoEXT<'_>>();
+    if !settings.is_null() {
+        if !extensions.contains(&ash::ext::layer_settings::NAME) {
+            return vk::Result::ERROR_EXTENSION_NOT_PRESENT;
+        }
+        apply_layer_settings(&mut configuration, &*settings);
+    }
+
+    let messenger = find_in_chain(
+        create_info.p_next,
+        vk::StructureType::DEBUG_UTILS_MESSENGER_CREATE_INFO_EXT,
+    )
+    .cast::<vk::DebugUtilsMessengerCreateInfoEXT<'_>>();
+    if !messenger.is_null() {
+        if !extensions.contains(&ash::ext::debug_utils::NAME) {
+            return vk::Result::ERROR_EXTENSION_NOT_PRESENT;
+        }
+
+        let version = if create_info.p_application_info.is_null() {
+            vk::API_VERSION_1_0
+        } else {
+            (*create_info.p_application_info).api_version
+        };
+        report(
+            &configuration,
+            [&MessengerObject::new(&*messenger)],
+            mvk::ConfigLogLevel::INFO,
+            &format!(
+                "Created VkInstance for Vulkan version {}.{}.{}, with {} Vulkan extensions enabled",
+                vk::api_version_major(version),
+                vk::api_version_minor(version),
+                vk::api_version_patch(version),
+                extensions.len(),
+            ),
+        );
+    }
+
+    let mut instance = Box::new(InstanceObject {
+        loader_data: LoaderData::new(),
+        physical_devices: config
+            .physical_devices
+            .into_iter()
+            .map(|device| PhysicalDeviceObject {
+                loader_data: LoaderData::new(),
+                device,
+                instance: std::ptr::null(),
+            })
+            .collect(),
+        messengers: Mutex::new(Vec::new()),
+        surfaces: Mutex::new(Vec::new()),
+        moltenvk_configuration: Mutex::new(configuration),
+        performance_statistics: config.performance_statistics,
+        properties2_enabled: extensions.contains(&ash::khr::get_physical_device_properties2::NAME),
+    });
+    let instance_ptr: *const InstanceObject = &*instance;
+    for physical_device in &mut instance.physical_devices {
+        physical_device.instance = instance_ptr;
+    }
+
+    *p_instance = into_handle(instance);
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn destroy_instance(
+    instance: vk::Instance,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+) {
+    if instance != vk::Instance::null() {
+        drop(Box::from_raw(instance.as_raw() as *mut InstanceObject));
+    }
+}
+
+unsafe extern "system" fn create_debug_utils_messenger(
+    instance: vk::Instance,
+    p_create_info: *const vk::DebugUtilsMessengerCreateInfoEXT<'_>,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+    p_messenger: *mut vk::DebugUtilsMessengerEXT,
+) -> vk::Result {
+    let instance = as_object::<InstanceObject>(instance);
+    let handle = next_handle();
+    instance
+        .messengers
+        .lock()
+        .unwrap()
+        .push((handle, MessengerObject::new(&*p_create_info)));
+
+    *p_messenger = handle;
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn destroy_debug_utils_messenger(
+    instance: vk::Instance,
+    messenger: vk::DebugUtilsMessengerEXT,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+) {
+    let instance = as_object::<InstanceObject>(instance);
+    instance
+        .messengers
+        .lock()
+        .unwrap()
+        .retain(|(handle, _)| *handle != messenger);
+}
+
+unsafe extern "system" fn submit_debug_utils_message(
+    instance: vk::Instance,
+    severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    types: vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT<'_>,
+) {
+    let instance = as_object::<InstanceObject>(instance);
+    for (_, messenger) in instance.messengers.lock().unwrap().iter() {
+        messenger.deliver(severity, types, &*p_callback_data);
+    }
+}
+
+unsafe extern "system" fn create_metal_surface(
+    instance: vk::Instance,
+    p_create_info: *const vk::MetalSurfaceCreateInfoEXT<'_>,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+    p_surface: *mut vk::SurfaceKHR,
+) -> vk::Result {
+    // MoltenVK can't create a surface without a layer
+    if (*p_create_info).p_layer.is_null() {
+        return vk::Result::ERROR_INITIALIZATION_FAILED;
+    }
+
+    *p_surface = add_surface(as_object(instance));
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn create_headless_surface(
+    instance: vk::Instance,
+    _p_create_info: *const vk::HeadlessSurfaceCreateInfoEXT<'_>,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+    p_surface: *mut vk::SurfaceKHR,
+) -> vk::Result {
+    *p_surface = add_surface(as_object(instance));
+    vk::Result::SUCCESS
+}
+
+fn add_surface(instance: &InstanceObject) -> vk::SurfaceKHR {
+    let handle = next_handle();
+    instance.surfaces.lock().unwrap().push(handle);
+    handle
+}
+
+unsafe extern "system" fn destroy_surface(
+    instance: vk::Instance,
+    surface: vk::SurfaceKHR,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+) {
+    let instance = as_object::<InstanceObject>(instance);
+    instance
+        .surfaces
+        .lock()
+        .unwrap()
+        .retain(|handle| *handle != surface);
+}
+
+/// Whether `surface` was created from the instance of `physical_device` and not destroyed yet
+unsafe fn is_surface_alive(physical_device: vk::PhysicalDevice, surface: vk::SurfaceKHR) -> bool {
+    let instance = &*as_object::<PhysicalDeviceObject>(physical_device).instance;
+    instance.surfaces.lock().unwrap().contains(&surface)
+}
+
+unsafe extern "system" fn get_physical_device_surface_support(
+    physical_device: vk::PhysicalDevice,
+    _queue_family_index: u32,
+    surface: vk::SurfaceKHR,
+    p_supported: *mut vk::Bool32,
+) -> vk::Result {
+    if !is_surface_alive(physical_device, surface) {
+        return vk::Result::ERROR_SURFACE_LOST_KHR;
+    }
+
+    // Every queue family of MoltenVK can present
+    *p_supported = vk::TRUE;
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn get_physical_device_surface_capabilities(
+    physical_device: vk::PhysicalDevice,
+    surface: vk::SurfaceKHR,
+    p_surface_capabilities: *mut vk::SurfaceCapabilitiesKHR,
+) -> vk::Result {
+    if !is_surface_alive(physical_device, surface) {
+        return vk::Result::ERROR_SURFACE_LOST_KHR;
+    }
+
+    let limits = &as_object::<PhysicalDeviceObject>(physical_device)
+        .device
+        .properties
+        .limits;
+    *p_surface_capabilities = vk::SurfaceCapabilitiesKHR {
+        min_image_count: 2,
+        max_image_count: 3,
+        // Like headless surfaces, the swapchain decides the extent
+        current_extent: vk::Extent2D {
+            width: u32::MAX,
+            height: u32::MAX,
+        },
+        min_image_extent: vk::Extent2D {
+            width: 1,
+            height: 1,
+        },
+        max_image_extent: vk::Extent2D {
+            width: limits.max_image_dimension2_d,
+            height: limits.max_image_dimension2_d,
+        },
+        max_image_array_layers: 1,
+        supported_transforms: vk::SurfaceTransformFlagsKHR::IDENTITY,
+        current_transform: vk::SurfaceTransformFlagsKHR::IDENTITY,
+        supported_composite_alpha: vk::CompositeAlphaFlagsKHR::OPAQUE
+            | vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED
+            | vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED
+            | vk::CompositeAlphaFlagsKHR::INHERIT,
+        supported_usage_flags: vk::ImageUsageFlags::COLOR_ATTACHMENT
+            | vk::ImageUsageFlags::TRANSFER_SRC
+            | vk::ImageUsageFlags::TRANSFER_DST
+            | vk::ImageUsageFlags::SAMPLED
+            | vk::ImageUsageFlags::STORAGE,
+    };
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn get_physical_device_surface_formats(
+    physical_device: vk::PhysicalDevice,
+    surface: vk::SurfaceKHR,
+    p_surface_format_count: *mut u32,
+    p_surface_formats: *mut vk::SurfaceFormatKHR,
+) -> vk::Result {
+    if !is_surface_alive(physical_device, surface) {
+        return vk::Result::ERROR_SURFACE_LOST_KHR;
+    }
+
+    let formats = [
+        vk::Format::B8G8R8A8_UNORM,
+        vk::Format::B8G8R8A8_SRGB,
+        vk::Format::R16G16B16A16_SFLOAT,
+        vk::Format::A2B10G10R10_UNORM_PACK32,
+    ]
+    .map(|format| vk::SurfaceFormatKHR {
+        format,
+        color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+    });
+    enumerate(&formats, p_surface_format_count, p_surface_formats)
+}
+
+unsafe extern "system" fn get_physical_device_surface_present_modes(
+    physical_device: vk::PhysicalDevice,
+    surface: vk::SurfaceKHR,
+    p_present_mode_count: *mut u32,
+    p_present_modes: *mut vk::PresentModeKHR,
+) -> vk::Result {
+    if !is_surface_alive(physical_device, surface) {
+        return vk::Result::ERROR_SURFACE_LOST_KHR;
+    }
+
+    let modes = [
+        vk::PresentModeKHR::FIFO,
+        vk::PresentModeKHR::IMMEDIATE,
+        vk::PresentModeKHR::MAILBOX,
+    ];
+    enumerate(&modes, p_present_mode_count, p_present_modes)
+}
+
+unsafe extern "system" fn enumerate_physical_devices(
+    instance: vk::Instance,
+    p_physical_device_count: *mut u32,
+    p_physical_devices: *mut vk::PhysicalDevice,
+) -> vk::Result {
+    let instance = as_object::<InstanceObject>(instance);
+    let handles = instance
+        .physical_devices
+        .iter()
+        .map(|device| vk::PhysicalDevice::from_raw(device as *const PhysicalDeviceObject as u64))
+        .collect::<Vec<_>>();
+
+    enumerate(&handles, p_physical_device_count, p_physical_devices)
+}
+
+unsafe extern "system" fn get_physical_device_properties(
+    physical_device: vk::PhysicalDevice,
+    p_properties: *mut vk::PhysicalDeviceProperties,
+) {
+    *p_properties = as_object::<PhysicalDeviceObject>(physical_device)
+        .device
+        .properties;
+}
+
+unsafe extern "system" fn get_physical_device_properties2(
+    physical_device: vk::PhysicalDevice,
+    p_properties: *mut vk::PhysicalDeviceProperties2<'_>,
+) {
+    let device = &as_object::<PhysicalDeviceObject>(physical_device).device;
+    (*p_properties).properties = device.properties;
+
+    for_each_in_chain((*p_properties).p_next.cast(), |next| {
+        if (*next).s_type == vk::StructureType::PHYSICAL_DEVICE_PORTABILITY_SUBSET_PROPERTIES_KHR {
+            if let Some(properties) = device.portability_subset_properties {
+                let out = next.cast::<vk::PhysicalDevicePortabilitySubsetPropertiesKHR<'_>>();
+                (*out).min_vertex_input_binding_stride_alignment =
+                    properties.min_vertex_input_binding_stride_alignment;
+            }
+        }
+    });
+}
+
+unsafe extern "system" fn get_physical_device_features(
+    physical_device: vk::PhysicalDevice,
+    p_features: *mut vk::PhysicalDeviceFeatures,
+) {
+    *p_features = as_object::<PhysicalDeviceObject>(physical_device)
+        .device
+        .features;
+}
+
+unsafe extern "system" fn get_physical_device_features2(
+    physical_device: vk::PhysicalDevice,
+    p_features: *mut vk::PhysicalDeviceFeatures2<'_>,
+) {
+    let device = &as_object::<PhysicalDeviceObject>(physical_device).device;
+    (*p_features).features = device.features;
+
+    for_each_in_chain((*p_features).p_next.cast(), |next| {
+        if (*next).s_type == vk::StructureType::PHYSICAL_DEVICE_PORTABILITY_SUBSET_FEATURES_KHR {
+            if let Some(features) = device.portability_subset_features {
+                let out = next.cast::<vk::PhysicalDevicePortabilitySubsetFeaturesKHR<'_>>();
+                let p_next = (*out).p_next;
+                *out = features;
+                (*out).p_next = p_next;
+            }
+        }
+    });
+}
+
+unsafe extern "system" fn get_physical_device_queue_family_properties(
+    physical_device: vk::PhysicalDevice,
+    p_queue_family_property_count: *mut u32,
+    p_queue_family_properties: *mut vk::QueueFamilyProperties,
+) {
+    let device = &as_object::<PhysicalDeviceObject>(physical_device).device;
+    let _ = enumerate(
+        &device.queue_families,
+        p_queue_family_property_count,
+        p_queue_family_properties,
+    );
+}
+
+unsafe extern "system" fn get_physical_device_memory_properties(
+    physical_device: vk::PhysicalDevice,
+    p_memory_properties: *mut vk::PhysicalDeviceMemoryProperties,
+) {
+    *p_memory_properties = as_object::<PhysicalDeviceObject>(physical_device)
+        .device
+        .memory_properties;
+}
+
+unsafe extern "system" fn enumerate_device_extension_properties(
+    physical_device: vk::PhysicalDevice,
+    p_layer_name: *const c_char,
+    p_property_count: *mut u32,
+    p_properties: *mut vk::ExtensionProperties,
+) -> vk::Result {
+    if !p_layer_name.is_null() {
+        return vk::Result::ERROR_LAYER_NOT_PRESENT;
+    }
+
+    let device = &as_object::<PhysicalDeviceObject>(physical_device).device;
+    enumerate(&device.extensions, p_property_count, p_properties)
+}
+
+unsafe extern "system" fn create_device(
+    physical_device: vk::PhysicalDevice,
+    p_create_info: *const vk::DeviceCreateInfo<'_>,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+    p_device: *mut vk::Device,
+) -> vk::Result {
+    let physical_device_object = as_object::<PhysicalDeviceObject>(physical_device);
+    let physical_device = &physical_device_object.device;
+    let create_info = &*p_create_info;
+
+    let extensions = c_strs(
+        create_info.pp_enabled_extension_names,
+        create_info.enabled_extension_count,
+    );
+    if !extensions
+        .iter()
+        .all(|name| supports(&physical_device.extensions, name))
+    {
+        return vk::Result::ERROR_EXTENSION_NOT_PRESENT;
+    }
+
+    let mut queues = physical_device
+        .queue_families
+        .iter()
+        .map(|_| Vec::new())
+        .collect::<Vec<_>>();
+
+    let queue_create_infos = if create_info.p_queue_create_infos.is_null() {
+        &[]
+    } else {
+        std::slice::from_raw_parts(
+            create_info.p_queue_create_infos,
+            create_info.queue_create_info_count as usize,
+        )
+    };
+    for queue_create_info in queue_create_infos {
+        let Some(family) = queues.get_mut(queue_create_info.queue_family_index as usize) else {
+            return vk::Result::ERROR_INITIALIZATION_FAILED;
+        };
+        let count = family.len() + queue_create_info.queue_count as usize;
+        family.resize_with(count, || QueueObject {
+            loader_data: LoaderData::new(),
+            device: std::ptr::null(),
+        });
+    }
+
+    let instance = &*physical_device_object.instance;
+    report(
+        &instance.moltenvk_configuration.lock().unwrap(),
+        instance
+            .messengers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, messenger)| messenger),
+        mvk::ConfigLogLevel::INFO,
+        &format!(
+            "Created VkDevice to run on GPU {}, with {} Vulkan extensions enabled",
+            physical_device
+                .properties
+                .device_name_as_c_str()
+                .unwrap_or_default()
+                .to_string_lossy(),
+            extensions.len(),
+        ),
+    );
+
+    let mut device = Box::new(DeviceObject {
+        loader_data: LoaderData::new(),
+        queues,
+        resources: Mutex::default(),
+        performance_statistics: Mutex::new(instance.performance_statistics),
+    });
+    let device_ptr = &*device as *const DeviceObject;
+    for queue in device.queues.iter_mut().flatten() {
+        queue.device = device_ptr;
+    }
+    *p_device = into_handle(device);
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn destroy_device(
+    device: vk::Device,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+) {
+    if device != vk::Device::null() {
+        drop(Box::from_raw(device.as_raw() as *mut DeviceObject));
+    }
+}
+
+unsafe extern "system" fn get_device_queue(
+    device: vk::Device,
+    queue_family_index: u32,
+    queue_index: u32,
+    p_queue: *mut vk::Queue,
+) {
+    let device = as_object::<DeviceObject>(device);
+    *p_queue = device
+        .queues
+        .get(queue_family_index as usize)
+        .and_then(|family| family.get(queue_index as usize))
+        .map_or(vk::Queue::null(), |queue| {
+            vk::Queue::from_raw(queue as *const QueueObject as u64)
+        });
+}
+
+unsafe extern "system" fn device_wait_idle(_device: vk::Device) -> vk::Result {
+    vk::Result::SUCCESS
+}
+
+/// There is no Metal, so the handles of the Vulkan objects stand in for the exported objects
+unsafe extern "system" fn export_metal_objects(
+    device: vk::Device,
+    p_metal_objects_info: *mut vk::ExportMetalObjectsInfoEXT<'_>,
+) {
+    fn stand_in(handle: impl vk::Handle) -> *mut c_void {
+        handle.as_raw() as *mut c_void
+    }
+
+    for_each_in_chain(
+        (*p_metal_objects_info).p_next.cast_mut().cast(),
+        |next| match (*next).s_type {
+            vk::StructureType::EXPORT_METAL_DEVICE_INFO_EXT => {
+                let info = &mut *next.cast::<vk::ExportMetalDeviceInfoEXT<'_>>();
+                info.mtl_device = stand_in(device);
+            }
+            vk::StructureType::EXPORT_METAL_COMMAND_QUEUE_INFO_EXT => {
+                let info = &mut *next.cast::<vk::ExportMetalCommandQueueInfoEXT<'_>>();
+                info.mtl_command_queue = stand_in(info.queue);
+            }
+            vk::StructureType::EXPORT_METAL_BUFFER_INFO_EXT => {
+                let info = &mut *next.cast::<vk::ExportMetalBufferInfoEXT<'_>>();
+                info.mtl_buffer = stand_in(info.memory);
+            }
+            vk::StructureType::EXPORT_METAL_TEXTURE_INFO_EXT => {
+                let info = &mut *next.cast::<vk::ExportMetalTextureInfoEXT<'_>>();
+                info.mtl_texture = if info.image != vk::Image::null() {
+                    stand_in(info.image)
+                } else if info.image_view != vk::ImageView::null() {
+                    stand_in(info.image_view)
+                } else {
+                    stand_in(info.buffer_view)
+                };
+            }
+            vk::StructureType::EXPORT_METAL_IO_SURFACE_INFO_EXT => {
+                let info = &mut *next.cast::<vk::ExportMetalIOSurfaceInfoEXT<'_>>();
+                info.io_surface = stand_in(info.image).cast();
+            }
+            vk::StructureType::EXPORT_METAL_SHARED_EVENT_INFO_EXT => {
+                let info = &mut *next.cast::<vk::ExportMetalSharedEventInfoEXT<'_>>();
+                info.mtl_shared_event = if info.semaphore != vk::Semaphore::null() {
+                    stand_in(info.semaphore)
+                } else {
+                    stand_in(info.event)
+                };
+            }
+            _ => {}
+        },
+    );
+}
+
+unsafe extern "system" fn get_moltenvk_configuration(
+    instance: vk::Instance,
+    p_configuration: *mut mvk::Configuration,
+    p_configuration_size: *mut usize,
+) -> vk::Result {
+    let configuration = if instance == vk::Instance::null() {
+        config().moltenvk_configuration
+    } else {
+        *as_object::<InstanceObject>(instance)
+            .moltenvk_configuration
+            .lock()
+            .unwrap()
+    };
+    copy_sized(&configuration, p_configuration, p_configuration_size)
+}
+
+unsafe extern "system" fn set_moltenvk_configuration(
+    instance: vk::Instance,
+    p_configuration: *const mvk::Configuration,
+    p_configuration_size: *mut usize,
+) -> vk::Result {
+    if instance != vk::Instance::null() {
+        let mut configuration = as_object::<InstanceObject>(instance)
+            .moltenvk_configuration
+            .lock()
+            .unwrap();
+        return copy_sized(&*p_configuration, &mut *configuration, p_configuration_size);
+    }
+
+    let mut guard = CONFIG.lock().unwrap();
+    let config = guard.get_or_insert_with(MockConfig::default);
+    copy_sized(
+        &*p_configuration,
+        &mut config.moltenvk_configuration,
+        p_configuration_size,
+    )
+}
+
+unsafe extern "system" fn get_physical_device_metal_features(
+    physical_device: vk::PhysicalDevice,
+    p_metal_features: *mut mvk::PhysicalDeviceMetalFeatures,
+    p_metal_features_size: *mut usize,
+) -> vk::Result {
+    copy_sized(
+        &as_object::<PhysicalDeviceObject>(physical_device)
+            .device
+            .metal_features,
+        p_metal_features,
+        p_metal_features_size,
+    )
+}
+
+unsafe extern "system" fn get_performance_statistics(
+    device: vk::Device,
+    p_perf: *mut mvk::PerformanceStatistics,
+    p_perf_size: *mut usize,
+) -> vk::Result {
+    let statistics = *as_object::<DeviceObject>(device)
+        .performance_statistics
+        .lock()
+        .unwrap();
+    copy_sized(&statistics, p_perf, p_perf_size)
+}
+
+unsafe extern "system" fn get_version_strings(
+    p_molten_version_string_buffer: *mut c_char,
+    molten_version_string_buffer_length: u32,
+    p_vulkan_version_string_buffer: *mut c_char,
+    vulkan_version_string_buffer_length: u32,
+) {
+    let api_version = config().api_version;
+    let vulkan_version = format!(
+        "{}.{}.{}",
+        vk::api_version_major(api_version),
+        vk::api_version_minor(api_version),
+        vk::api_version_patch(api_version)
+    );
+
+    copy_str(
+        &Version::linked().to_string(),
+        p_molten_version_string_buffer,
+        molten_version_string_buffer_length,
+    );
+    copy_str(
+        &vulkan_version,
+        p_vulkan_version_string_buffer,
+        vulkan_version_string_buffer_length,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MoltenEntry;
+
+    unsafe fn create_instance(entry: &ash::Entry) -> ash::Instance {
+        let extensions = [
+            ash::ext::headless_surface::NAME.as_ptr(),
+            ash::ext::metal_surface::NAME.as_ptr(),
+            ash::khr::surface::NAME.as_ptr(),
+        ];
+        entry
+            .create_instance(
+                &vk::InstanceCreateInfo::default().enabled_extension_names(&extensions),
+                None,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn physical_device_defaults() {
+        let entry = MoltenEntry::load().unwrap();
+        let expected = MockPhysicalDevice::default();
+        with_config(MockConfig::default(), || unsafe {
+            let instance = create_instance(&entry);
+            let physical_devices = instance.enumerate_physical_devices().unwrap();
+            assert_eq!(physical_devices.len(), 1);
+            let physical_device = physical_devices[0];
+
+            let properties = instance.get_physical_device_properties(physical_device);
+            assert_eq!(
+                properties.device_name_as_c_str(),
+                expected.properties.device_name_as_c_str()
+            );
+            assert_eq!(properties.api_version, expected.properties.api_version);
+            assert_eq!(
+                instance
+                    .get_physical_device_queue_family_properties(physical_device)
+                    .len(),
+                expected.queue_families.len()
+            );
+            let memory_properties = instance.get_physical_device_memory_properties(physical_device);
+            assert_eq!(memory_properties.memory_type_count, 2);
+
+            let extensions = instance
+                .enumerate_device_extension_properties(physical_device)
+                .unwrap();
+            assert!(supports(&extensions, ash::khr::portability_subset::NAME));
+            assert!(supports(&extensions, ash::ext::metal_objects::NAME));
+
+            let mvk = mvk::Instance::new(&entry, &instance);
+            let metal_features = mvk
+                .get_physical_device_metal_features(physical_device)
+                .unwrap();
+            assert_eq!(
+                metal_features.msl_version,
+                expected.metal_features.msl_version
+            );
+            assert_eq!(
+                mvk.get_moltenvk_configuration().unwrap().log_level,
+                default_moltenvk_configuration().log_level
+            );
+
+            instance.destroy_instance(None);
+        });
+    }
+
+    #[test]
+    fn instances_keep_their_config() {
+        let entry = MoltenEntry::load().unwrap();
+        let mut config = MockConfig {
+            physical_devices: vec![MockPhysicalDevice::default(); 2],
+            ..MockConfig::default()
+        };
+        config.moltenvk_configuration.log_level = mvk::ConfigLogLevel::ERROR;
+
+        let first = with_config(config.clone(), || unsafe { create_instance(&entry) });
+        config.physical_devices.truncate(1);
+        config.moltenvk_configuration.log_level = mvk::ConfigLogLevel::DEBUG;
+        with_config(config, || unsafe {
+            let second = create_instance(&entry);
+            assert_eq!(first.enumerate_physical_devices().unwrap().len(), 2);
+            assert_eq!(second.enumerate_physical_devices().unwrap().len(), 1);
+
+            // Setting the MoltenVK configuration only affects the instance it is set on
+            let first_mvk = mvk::Instance::new(&entry, &first);
+            let second_mvk = mvk::Instance::new(&entry, &second);
+            let mut configuration = first_mvk.get_moltenvk_configuration().unwrap();
+            assert_eq!(configuration.log_level, mvk::ConfigLogLevel::ERROR);
+            configuration.log_level = mvk::ConfigLogLevel::NONE;
+            first_mvk
+                .set_moltenvk_configuration(&configuration)
+                .unwrap();
+            assert_eq!(
+                first_mvk.get_moltenvk_configuration().unwrap().log_level,
+                mvk::ConfigLogLevel::NONE
+            );
+            assert_eq!(
+                second_mvk.get_moltenvk_configuration().unwrap().log_level,
+                mvk::ConfigLogLevel::DEBUG
+            );
+            assert_eq!(
+                super::config().moltenvk_configuration.log_level,
+                mvk::ConfigLogLevel::DEBUG
+            );
+
+            second.destroy_instance(None);
+            first.destroy_instance(None);
+        });
+    }
+
+    #[test]
+    fn exports_metal_objects() {
+        let entry = MoltenEntry::load().unwrap();
+        unsafe {
+            let instance = create_instance(&entry);
+            let physical_device = instance.enumerate_physical_devices().unwrap()[0];
+            let queue_create_info = vk::DeviceQueueCreateInfo::default().queue_priorities(&[1.0]);
+            let device = instance
+                .create_device(
+                    physical_device,
+                    &vk::DeviceCreateInfo::default()
+                        .queue_create_infos(&[queue_create_info])
+                        .enabled_extension_names(&[ash::ext::metal_objects::NAME.as_ptr()]),
+                    None,
+                )
+                .unwrap();
+            let queue = device.get_device_queue(0, 0);
+
+            let metal = crate::metal::MetalObjects::new(&instance, &device);
+            assert_eq!(
+                metal.export_device(),
+                Some(device.handle().as_raw() as vk::MTLDevice_id)
+            );
+            assert_eq!(
+                metal.export_command_queue(queue),
+                Some(queue.as_raw() as vk::MTLCommandQueue_id)
+            );
+
+            device.destroy_device(None);
+            instance.destroy_instance(None);
+        }
+    }
+
+    #[test]
+    fn creates_surfaces() {
+        let entry = MoltenEntry::load().unwrap();
+        unsafe {
+            let instance = create_instance(&entry);
+            let physical_device = instance.enumerate_physical_devices().unwrap()[0];
+            let headless = ash::ext::headless_surface::Instance::new(&entry, &instance);
+            let metal = ash::ext::metal_surface::Instance::new(&entry, &instance);
+            let surface_fn = ash::khr::surface::Instance::new(&entry, &instance);
+
+            // MoltenVK needs a `CAMetalLayer`
+            assert_eq!(
+                metal.create_metal_surface(&vk::MetalSurfaceCreateInfoEXT::default(), None),
+                Err(vk::Result::ERROR_INITIALIZATION_FAILED)
+            );
+
+            let surface = headless
+                .create_headless_surface(&vk::HeadlessSurfaceCreateInfoEXT::default(), None)
+                .unwrap();
+            assert!(surface_fn
+                .get_physical_device_surface_support(physical_device, 0, surface)
+                .unwrap());
+            let capabilities = surface_fn
+                .get_physical_device_surface_capabilities(physical_device, surface)
+                .unwrap();
+            assert_eq!(capabilities.current_extent.width, u32::MAX);
+            assert!(!surface_fn
+                .get_physical_device_surface_formats(physical_device, surface)
+                .unwrap()
+                .is_empty());
+
+            surface_fn.destroy_surface(surface, None);
+            assert_eq!(
+                surface_fn
+                    .get_physical_device_surface_capabilities(physical_device, surface)
+                    .err(),
+                Some(vk::Result::ERROR_SURFACE_LOST_KHR)
+            );
+
+            instance.destroy_instance(None);
+        }
+    }
+}
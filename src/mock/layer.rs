@@ -0,0 +1,357 @@
+//! A passthrough layer for exercising the [`layers`](crate::layers) host, enabled with the
+//! `mock` and `layers` features.
+//!
+//! The layer forwards every call to the next layer in the chain, finding its dispatch tables
+//! through the dispatch keys of handles like a real layer does, and records the calls it
+//! intercepts.
+//!
+//! ```
+//! use ash_molten::{layers::LayerHost, mock::layer};
+//!
+//! let entry = LayerHost::empty()
+//!     .static_layer(layer::properties(), layer::negotiate_loader_layer_interface_version)
+//!     .install()
+//!     .unwrap();
+//! let instance = unsafe {
+//!     ash_molten::InstanceBuilder::new()
+//!         .layer(layer::NAME)
+//!         .create(&entry)
+//! }
+//! .unwrap();
+//! assert!(layer::calls().contains(&"vkCreateInstance"));
+//! ```
+
+use super::find_in_chain;
+use crate::layers::{
+    LayerDeviceCreateInfo, LayerInstanceCreateInfo, LayerProperties, NegotiateLayerInterface,
+    LAYER_LINK_INFO,
+};
+use ash::vk;
+use std::{
+    ffi::{c_char, CStr},
+    mem::transmute,
+    sync::Mutex,
+};
+
+/// The name of the layer
+pub const NAME: &CStr = c"VK_LAYER_ASH_MOLTEN_mock";
+
+/// The properties to add the layer with
+pub fn properties() -> LayerProperties {
+    LayerProperties {
+        description: "ash-molten passthrough layer".to_owned(),
+        ..LayerProperties::new(NAME)
+    }
+}
+
+/// The Vulkan functions the layer intercepted, in the order they were called
+pub fn calls() -> Vec<&'static str> {
+    CALLS.lock().unwrap().clone()
+}
+
+/// Forgets the recorded calls
+pub fn clear_calls() {
+    CALLS.lock().unwrap().clear();
+}
+
+static CALLS: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+fn record(name: &'static str) {
+    CALLS.lock().unwrap().push(name);
+}
+
+struct InstanceDispatch {
+    /// The dispatch key shared by the instance and its physical devices
+    key: usize,
+    instance: vk::Instance,
+    get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+    destroy_instance: vk::PFN_vkDestroyInstance,
+    enumerate_physical_devices: vk::PFN_vkEnumeratePhysicalDevices,
+}
+
+struct DeviceDispatch {
+    /// The dispatch key shared by the device, its queues and command buffers
+    key: usize,
+    get_device_proc_addr: vk::PFN_vkGetDeviceProcAddr,
+    destroy_device: vk::PFN_vkDestroyDevice,
+    get_device_queue: vk::PFN_vkGetDeviceQueue,
+}
+
+static INSTANCES: Mutex<Vec<InstanceDispatch>> = Mutex::new(Vec::new());
+static DEVICES: Mutex<Vec<DeviceDispatch>> = Mutex::new(Vec::new());
+
+unsafe fn key(handle: impl vk::Handle) -> usize {
+    *(handle.as_raw() as *const usize)
+}
+
+/// Runs `f` with the dispatch table of the instance or physical device `handle`
+unsafe fn with_instance<R>(handle: impl vk::Handle, f: impl FnOnce(&InstanceDispatch) -> R) -> R {
+    let key = key(handle);
+    let instances = INSTANCES.lock().unwrap();
+    f(instances
+        .iter()
+        .find(|dispatch| dispatch.key == key)
+        .expect("unknown instance"))
+}
+
+/// Runs `f` with the dispatch table of the device, queue or command buffer `handle`
+unsafe fn with_device<R>(handle: impl vk::Handle, f: impl FnOnce(&DeviceDispatch) -> R) -> R {
+    let key = key(handle);
+    let devices = DEVICES.lock().unwrap();
+    f(devices
+        .iter()
+        .find(|dispatch| dispatch.key == key)
+        .expect("unknown device"))
+}
+
+unsafe fn load<F>(function: vk::PFN_vkVoidFunction) -> Option<F> {
+    function.map(|function| std::mem::transmute_copy(&function))
+}
+
+macro_rules! void_fn {
+    ($f:expr, $pfn:ty) => {
+        Some(transmute::<$pfn, unsafe extern "system" fn()>($f))
+    };
+}
+
+/// The `vkNegotiateLoaderLayerInterfaceVersion` of the layer
+///
+/// # Safety
+///
+/// Must be called as specified by the Vulkan loader-layer interface.
+pub unsafe extern "system" fn negotiate_loader_layer_interface_version(
+    p_version_struct: *mut NegotiateLayerInterface,
+) -> vk::Result {
+    let interface = &mut *p_version_struct;
+    if interface.loader_layer_interface_version < 2 {
+        return vk::Result::ERROR_INITIALIZATION_FAILED;
+    }
+
+    interface.loader_layer_interface_version = 2;
+    interface.pfn_get_instance_proc_addr = Some(get_instance_proc_addr);
+    interface.pfn_get_device_proc_addr = Some(get_device_proc_addr);
+    interface.pfn_get_physical_device_proc_addr = None;
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn get_instance_proc_addr(
+    instance: vk::Instance,
+    p_name: *const c_char,
+) -> vk::PFN_vkVoidFunction {
+    let intercepted = match CStr::from_ptr(p_name).to_bytes() {
+        b"vkGetInstanceProcAddr" => {
+            void_fn!(get_instance_proc_addr, vk::PFN_vkGetInstanceProcAddr)
+        }
+        b"vkCreateInstance" => void_fn!(create_instance, vk::PFN_vkCreateInstance),
+        b"vkDestroyInstance" => void_fn!(destroy_instance, vk::PFN_vkDestroyInstance),
+        b"vkEnumeratePhysicalDevices" => void_fn!(
+            enumerate_physical_devices,
+            vk::PFN_vkEnumeratePhysicalDevices
+        ),
+        b"vkCreateDevice" => void_fn!(create_device, vk::PFN_vkCreateDevice),
+        b"vkGetDeviceProcAddr" => void_fn!(get_device_proc_addr, vk::PFN_vkGetDeviceProcAddr),
+        _ => None,
+    };
+
+    if intercepted.is_some() || instance == vk::Instance::null() {
+        intercepted
+    } else {
+        let next = with_instance(instance, |dispatch| dispatch.get_instance_proc_addr);
+        next(instance, p_name)
+    }
+}
+
+unsafe extern "system" fn get_device_proc_addr(
+    device: vk::Device,
+    p_name: *const c_char,
+) -> vk::PFN_vkVoidFunction {
+    match CStr::from_ptr(p_name).to_bytes() {
+        b"vkGetDeviceProcAddr" => {
+            void_fn!(get_device_proc_addr, vk::PFN_vkGetDeviceProcAddr)
+        }
+        b"vkDestroyDevice" => void_fn!(destroy_device, vk::PFN_vkDestroyDevice),
+        b"vkGetDeviceQueue" => void_fn!(get_device_queue, vk::PFN_vkGetDeviceQueue),
+        _ => {
+            let next = with_device(device, |dispatch| dispatch.get_device_proc_addr);
+            next(device, p_name)
+        }
+    }
+}
+
+unsafe extern "system" fn create_instance(
+    p_create_info: *const vk::InstanceCreateInfo<'_>,
+    p_allocator: *const vk::AllocationCallbacks<'_>,
+    p_instance: *mut vk::Instance,
+) -> vk::Result {
+    record("vkCreateInstance");
+
+    // Take this layer's link off the chain for the next layer
+    let mut link_info = find_in_chain(
+        (*p_create_info).p_next,
+        vk::StructureType::LOADER_INSTANCE_CREATE_INFO,
+    )
+    .cast::<LayerInstanceCreateInfo>()
+    .cast_mut();
+    while !link_info.is_null() && (*link_info).function != LAYER_LINK_INFO {
+        link_info = find_in_chain(
+            (*link_info).p_next,
+            vk::StructureType::LOADER_INSTANCE_CREATE_INFO,
+        )
+        .cast::<LayerInstanceCreateInfo>()
+        .cast_mut();
+    }
+    if link_info.is_null() {
+        return vk::Result::ERROR_INITIALIZATION_FAILED;
+    }
+    let link = &*(*link_info).u.p_layer_info;
+    (*link_info).u.p_layer_info = link.p_next;
+
+    let next = link.pfn_next_get_instance_proc_addr;
+    let Some(create) =
+        load::<vk::PFN_vkCreateInstance>(next(vk::Instance::null(), c"vkCreateInstance".as_ptr()))
+    else {
+        return vk::Result::ERROR_INITIALIZATION_FAILED;
+    };
+    let result = create(p_create_info, p_allocator, p_instance);
+    if result != vk::Result::SUCCESS {
+        return result;
+    }
+
+    let instance = *p_instance;
+    let (Some(destroy_instance), Some(enumerate_physical_devices)) = (
+        load(next(instance, c"vkDestroyInstance".as_ptr())),
+        load(next(instance, c"vkEnumeratePhysicalDevices".as_ptr())),
+    ) else {
+        return vk::Result::ERROR_INITIALIZATION_FAILED;
+    };
+    INSTANCES.lock().unwrap().push(InstanceDispatch {
+        key: key(instance),
+        instance,
+        get_instance_proc_addr: next,
+        destroy_instance,
+        enumerate_physical_devices,
+    });
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn destroy_instance(
+    instance: vk::Instance,
+    p_allocator: *const vk::AllocationCallbacks<'_>,
+) {
+    record("vkDestroyInstance");
+    if instance == vk::Instance::null() {
+        return;
+    }
+
+    let key = key(instance);
+    let mut instances = INSTANCES.lock().unwrap();
+    let Some(index) = instances.iter().position(|dispatch| dispatch.key == key) else {
+        return;
+    };
+    let dispatch = instances.remove(index);
+    drop(instances);
+    (dispatch.destroy_instance)(instance, p_allocator);
+}
+
+unsafe extern "system" fn enumerate_physical_devices(
+    instance: vk::Instance,
+    p_physical_device_count: *mut u32,
+    p_physical_devices: *mut vk::PhysicalDevice,
+) -> vk::Result {
+    record("vkEnumeratePhysicalDevices");
+    let next = with_instance(instance, |dispatch| dispatch.enumerate_physical_devices);
+    next(instance, p_physical_device_count, p_physical_devices)
+}
+
+unsafe extern "system" fn create_device(
+    physical_device: vk::PhysicalDevice,
+    p_create_info: *const vk::DeviceCreateInfo<'_>,
+    p_allocator: *const vk::AllocationCallbacks<'_>,
+    p_device: *mut vk::Device,
+) -> vk::Result {
+    record("vkCreateDevice");
+
+    let mut link_info = find_in_chain(
+        (*p_create_info).p_next,
+        vk::StructureType::LOADER_DEVICE_CREATE_INFO,
+    )
+    .cast::<LayerDeviceCreateInfo>()
+    .cast_mut();
+    while !link_info.is_null() && (*link_info).function != LAYER_LINK_INFO {
+        link_info = find_in_chain(
+            (*link_info).p_next,
+            vk::StructureType::LOADER_DEVICE_CREATE_INFO,
+        )
+        .cast::<LayerDeviceCreateInfo>()
+        .cast_mut();
+    }
+    if link_info.is_null() {
+        return vk::Result::ERROR_INITIALIZATION_FAILED;
+    }
+    let link = &*(*link_info).u.p_layer_info;
+    (*link_info).u.p_layer_info = link.p_next;
+
+    let instance = with_instance(physical_device, |dispatch| dispatch.instance);
+    let next_get_device_proc_addr = link.pfn_next_get_device_proc_addr;
+    let Some(create) = load::<vk::PFN_vkCreateDevice>((link.pfn_next_get_instance_proc_addr)(
+        instance,
+        c"vkCreateDevice".as_ptr(),
+    )) else {
+        return vk::Result::ERROR_INITIALIZATION_FAILED;
+    };
+    let result = create(physical_device, p_create_info, p_allocator, p_device);
+    if result != vk::Result::SUCCESS {
+        return result;
+    }
+
+    let device = *p_device;
+    let (Some(destroy_device), Some(get_device_queue)) = (
+        load(next_get_device_proc_addr(
+            device,
+            c"vkDestroyDevice".as_ptr(),
+        )),
+        load(next_get_device_proc_addr(
+            device,
+            c"vkGetDeviceQueue".as_ptr(),
+        )),
+    ) else {
+        return vk::Result::ERROR_INITIALIZATION_FAILED;
+    };
+    DEVICES.lock().unwrap().push(DeviceDispatch {
+        key: key(device),
+        get_device_proc_addr: next_get_device_proc_addr,
+        destroy_device,
+        get_device_queue,
+    });
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn destroy_device(
+    device: vk::Device,
+    p_allocator: *const vk::AllocationCallbacks<'_>,
+) {
+    record("vkDestroyDevice");
+    if device == vk::Device::null() {
+        return;
+    }
+
+    let key = key(device);
+    let mut devices = DEVICES.lock().unwrap();
+    let Some(index) = devices.iter().position(|dispatch| dispatch.key == key) else {
+        return;
+    };
+    let dispatch = devices.remove(index);
+    drop(devices);
+    (dispatch.destroy_device)(device, p_allocator);
+}
+
+unsafe extern "system" fn get_device_queue(
+    device: vk::Device,
+    queue_family_index: u32,
+    queue_index: u32,
+    p_queue: *mut vk::Queue,
+) {
+    record("vkGetDeviceQueue");
+    let next = with_device(device, |dispatch| dispatch.get_device_queue);
+    next(device, queue_family_index, queue_index, p_queue);
+}
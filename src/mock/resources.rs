@@ -0,0 +1,1002 @@
+//! The memory, buffers, images, pipelines, descriptors, synchronization objects and command
+//! buffers of the mock driver.
+//!
+//! Device memory is host memory, so it can be mapped, and buffer copies, fills and updates
+//! execute when their command buffer is submitted. Every other command is accepted and
+//! ignored. Submits complete immediately, signaling their fence.
+
+use super::{as_object, into_handle, next_handle, DeviceObject, LoaderData, QueueObject};
+use ash::vk::{self, Handle as _};
+use std::{
+    collections::HashMap,
+    ffi::{c_void, CStr},
+    mem::transmute,
+    sync::{Mutex, MutexGuard},
+};
+
+/// Images are backed by this many bytes per texel, enough for every uncompressed format
+const IMAGE_BYTES_PER_TEXEL: u64 = 16;
+
+/// Memory requirements are aligned to this
+const ALIGNMENT: u64 = 256;
+
+/// The objects of a device
+#[derive(Default)]
+pub(super) struct Resources {
+    memory: HashMap<vk::DeviceMemory, Memory>,
+    buffers: HashMap<vk::Buffer, Binding>,
+    images: HashMap<vk::Image, Binding>,
+    fences: HashMap<vk::Fence, bool>,
+    shader_modules: HashMap<vk::ShaderModule, Vec<u32>>,
+    command_pools: HashMap<vk::CommandPool, Vec<vk::CommandBuffer>>,
+    /// The objects without state, like samplers and pipelines, with their type
+    objects: HashMap<u64, vk::ObjectType>,
+}
+
+/// Host memory backing a `VkDeviceMemory`
+struct Memory {
+    data: *mut u8,
+    size: u64,
+}
+
+impl Memory {
+    fn new(size: u64) -> Self {
+        let data = vec![0_u8; size as usize].into_boxed_slice();
+        Self {
+            data: Box::into_raw(data).cast::<u8>(),
+            size,
+        }
+    }
+}
+
+impl Drop for Memory {
+    fn drop(&mut self) {
+        unsafe {
+            drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+                self.data,
+                self.size as usize,
+            )));
+        }
+    }
+}
+
+/// The size of a buffer or image, and the memory it's bound to
+struct Binding {
+    size: u64,
+    memory: Option<(vk::DeviceMemory, u64)>,
+}
+
+#[repr(C)]
+struct CommandBufferObject {
+    loader_data: LoaderData,
+    /// The commands with effects, in recording order
+    transfers: Mutex<Vec<Transfer>>,
+}
+
+/// A command executed on submission
+enum Transfer {
+    Copy {
+        src: vk::Buffer,
+        dst: vk::Buffer,
+        regions: Vec<vk::BufferCopy>,
+    },
+    Fill {
+        dst: vk::Buffer,
+        offset: u64,
+        size: u64,
+        data: u32,
+    },
+    Update {
+        dst: vk::Buffer,
+        offset: u64,
+        data: Vec<u8>,
+    },
+}
+
+impl Resources {
+    /// The host address of `size` bytes at `offset` in the memory `buffer` is bound to
+    fn buffer_data(&self, buffer: vk::Buffer, offset: u64, size: u64) -> Option<*mut u8> {
+        let binding = self.buffers.get(&buffer)?;
+        let (memory, memory_offset) = binding.memory?;
+        let memory = self.memory.get(&memory)?;
+        let start = memory_offset + offset;
+        (offset + size <= binding.size && start + size <= memory.size)
+            .then(|| unsafe { memory.data.add(start as usize) })
+    }
+
+    unsafe fn execute(&self, transfer: &Transfer) {
+        match transfer {
+            Transfer::Copy { src, dst, regions } => {
+                for region in regions {
+                    let src = self.buffer_data(*src, region.src_offset, region.size);
+                    let dst = self.buffer_data(*dst, region.dst_offset, region.size);
+                    if let (Some(src), Some(dst)) = (src, dst) {
+                        std::ptr::copy(src, dst, region.size as usize);
+                    }
+                }
+            }
+            Transfer::Fill {
+                dst,
+                offset,
+                size,
+                data,
+            } => {
+                let size = if *size == vk::WHOLE_SIZE {
+                    self.buffers
+                        .get(dst)
+                        .map_or(0, |binding| binding.size.saturating_sub(*offset) & !3)
+                } else {
+                    *size
+                };
+                if let Some(dst) = self.buffer_data(*dst, *offset, size) {
+                    for index in 0..(size / 4) as usize {
+                        dst.cast::<u32>().add(index).write_unaligned(*data);
+                    }
+                }
+            }
+            Transfer::Update { dst, offset, data } => {
+                if let Some(dst) = self.buffer_data(*dst, *offset, data.len() as u64) {
+                    std::ptr::copy_nonoverlapping(data.as_ptr(), dst, data.len());
+                }
+            }
+        }
+    }
+}
+
+unsafe fn resources<'a>(device: vk::Device) -> MutexGuard<'a, Resources> {
+    as_object::<DeviceObject>(device).resources.lock().unwrap()
+}
+
+unsafe fn command_buffer<'a>(command_buffer: vk::CommandBuffer) -> &'a CommandBufferObject {
+    as_object(command_buffer)
+}
+
+unsafe fn record(command_buffer_handle: vk::CommandBuffer, transfer: Transfer) {
+    command_buffer(command_buffer_handle)
+        .transfers
+        .lock()
+        .unwrap()
+        .push(transfer);
+}
+
+fn requirements(size: u64) -> vk::MemoryRequirements {
+    vk::MemoryRequirements {
+        size: size.div_ceil(ALIGNMENT) * ALIGNMENT,
+        alignment: ALIGNMENT,
+        // Both memory types of the default physical device
+        memory_type_bits: 0b11,
+    }
+}
+
+/// Resolves the device functions implemented by this module
+pub(super) unsafe fn get_device_proc_addr(name: &CStr) -> vk::PFN_vkVoidFunction {
+    match name.to_bytes() {
+        b"vkAllocateMemory" => void_fn!(allocate_memory, vk::PFN_vkAllocateMemory),
+        b"vkFreeMemory" => void_fn!(free_memory, vk::PFN_vkFreeMemory),
+        b"vkMapMemory" => void_fn!(map_memory, vk::PFN_vkMapMemory),
+        b"vkUnmapMemory" => void_fn!(unmap_memory, vk::PFN_vkUnmapMemory),
+        b"vkFlushMappedMemoryRanges" => void_fn!(
+            flush_mapped_memory_ranges,
+            vk::PFN_vkFlushMappedMemoryRanges
+        ),
+        b"vkInvalidateMappedMemoryRanges" => void_fn!(
+            flush_mapped_memory_ranges,
+            vk::PFN_vkInvalidateMappedMemoryRanges
+        ),
+        b"vkCreateBuffer" => void_fn!(create_buffer, vk::PFN_vkCreateBuffer),
+        b"vkDestroyBuffer" => void_fn!(destroy_buffer, vk::PFN_vkDestroyBuffer),
+        b"vkGetBufferMemoryRequirements" => void_fn!(
+            get_buffer_memory_requirements,
+            vk::PFN_vkGetBufferMemoryRequirements
+        ),
+        b"vkBindBufferMemory" => void_fn!(bind_buffer_memory, vk::PFN_vkBindBufferMemory),
+        b"vkCreateImage" => void_fn!(create_image, vk::PFN_vkCreateImage),
+        b"vkDestroyImage" => void_fn!(destroy_image, vk::PFN_vkDestroyImage),
+        b"vkGetImageMemoryRequirements" => void_fn!(
+            get_image_memory_requirements,
+            vk::PFN_vkGetImageMemoryRequirements
+        ),
+        b"vkBindImageMemory" => void_fn!(bind_image_memory, vk::PFN_vkBindImageMemory),
+        b"vkCreateImageView" => void_fn!(create_image_view, vk::PFN_vkCreateImageView),
+        b"vkDestroyImageView" => void_fn!(destroy_image_view, vk::PFN_vkDestroyImageView),
+        b"vkCreateSampler" => void_fn!(create_sampler, vk::PFN_vkCreateSampler),
+        b"vkDestroySampler" => void_fn!(destroy_sampler, vk::PFN_vkDestroySampler),
+        b"vkCreateShaderModule" => void_fn!(create_shader_module, vk::PFN_vkCreateShaderModule),
+        b"vkDestroyShaderModule" => {
+            void_fn!(destroy_shader_module, vk::PFN_vkDestroyShaderModule)
+        }
+        b"vkCreatePipelineCache" => {
+            void_fn!(create_pipeline_cache, vk::PFN_vkCreatePipelineCache)
+        }
+        b"vkDestroyPipelineCache" => {
+            void_fn!(destroy_pipeline_cache, vk::PFN_vkDestroyPipelineCache)
+        }
+        b"vkCreateDescriptorSetLayout" => void_fn!(
+            create_descriptor_set_layout,
+            vk::PFN_vkCreateDescriptorSetLayout
+        ),
+        b"vkDestroyDescriptorSetLayout" => void_fn!(
+            destroy_descriptor_set_layout,
+            vk::PFN_vkDestroyDescriptorSetLayout
+        ),
+        b"vkCreatePipelineLayout" => {
+            void_fn!(create_pipeline_layout, vk::PFN_vkCreatePipelineLayout)
+        }
+        b"vkDestroyPipelineLayout" => {
+            void_fn!(destroy_pipeline_layout, vk::PFN_vkDestroyPipelineLayout)
+        }
+        b"vkCreateRenderPass" => void_fn!(create_render_pass, vk::PFN_vkCreateRenderPass),
+        b"vkDestroyRenderPass" => void_fn!(destroy_render_pass, vk::PFN_vkDestroyRenderPass),
+        b"vkCreateFramebuffer" => void_fn!(create_framebuffer, vk::PFN_vkCreateFramebuffer),
+        b"vkDestroyFramebuffer" => void_fn!(destroy_framebuffer, vk::PFN_vkDestroyFramebuffer),
+        b"vkCreateComputePipelines" => {
+            void_fn!(create_compute_pipelines, vk::PFN_vkCreateComputePipelines)
+        }
+        b"vkCreateGraphicsPipelines" => {
+            void_fn!(create_graphics_pipelines, vk::PFN_vkCreateGraphicsPipelines)
+        }
+        b"vkDestroyPipeline" => void_fn!(destroy_pipeline, vk::PFN_vkDestroyPipeline),
+        b"vkCreateDescriptorPool" => {
+            void_fn!(create_descriptor_pool, vk::PFN_vkCreateDescriptorPool)
+        }
+        b"vkDestroyDescriptorPool" => {
+            void_fn!(destroy_descriptor_pool, vk::PFN_vkDestroyDescriptorPool)
+        }
+        b"vkResetDescriptorPool" => {
+            void_fn!(reset_descriptor_pool, vk::PFN_vkResetDescriptorPool)
+        }
+        b"vkAllocateDescriptorSets" => {
+            void_fn!(allocate_descriptor_sets, vk::PFN_vkAllocateDescriptorSets)
+        }
+        b"vkFreeDescriptorSets" => void_fn!(free_descriptor_sets, vk::PFN_vkFreeDescriptorSets),
+        b"vkUpdateDescriptorSets" => {
+            void_fn!(update_descriptor_sets, vk::PFN_vkUpdateDescriptorSets)
+        }
+        b"vkCreateFence" => void_fn!(create_fence, vk::PFN_vkCreateFence),
+        b"vkDestroyFence" => void_fn!(destroy_fence, vk::PFN_vkDestroyFence),
+        b"vkResetFences" => void_fn!(reset_fences, vk::PFN_vkResetFences),
+        b"vkGetFenceStatus" => void_fn!(get_fence_status, vk::PFN_vkGetFenceStatus),
+        b"vkWaitForFences" => void_fn!(wait_for_fences, vk::PFN_vkWaitForFences),
+        b"vkCreateSemaphore" => void_fn!(create_semaphore, vk::PFN_vkCreateSemaphore),
+        b"vkDestroySemaphore" => void_fn!(destroy_semaphore, vk::PFN_vkDestroySemaphore),
+        b"vkCreateCommandPool" => void_fn!(create_command_pool, vk::PFN_vkCreateCommandPool),
+        b"vkDestroyCommandPool" => void_fn!(destroy_command_pool, vk::PFN_vkDestroyCommandPool),
+        b"vkResetCommandPool" => void_fn!(reset_command_pool, vk::PFN_vkResetCommandPool),
+        b"vkAllocateCommandBuffers" => {
+            void_fn!(allocate_command_buffers, vk::PFN_vkAllocateCommandBuffers)
+        }
+        b"vkFreeCommandBuffers" => void_fn!(free_command_buffers, vk::PFN_vkFreeCommandBuffers),
+        b"vkBeginCommandBuffer" => void_fn!(begin_command_buffer, vk::PFN_vkBeginCommandBuffer),
+        b"vkEndCommandBuffer" => void_fn!(end_command_buffer, vk::PFN_vkEndCommandBuffer),
+        b"vkResetCommandBuffer" => void_fn!(reset_command_buffer, vk::PFN_vkResetCommandBuffer),
+        b"vkCmdCopyBuffer" => void_fn!(cmd_copy_buffer, vk::PFN_vkCmdCopyBuffer),
+        b"vkCmdFillBuffer" => void_fn!(cmd_fill_buffer, vk::PFN_vkCmdFillBuffer),
+        b"vkCmdUpdateBuffer" => void_fn!(cmd_update_buffer, vk::PFN_vkCmdUpdateBuffer),
+        b"vkCmdBindPipeline" => void_fn!(cmd_bind_pipeline, vk::PFN_vkCmdBindPipeline),
+        b"vkCmdBindDescriptorSets" => {
+            void_fn!(cmd_bind_descriptor_sets, vk::PFN_vkCmdBindDescriptorSets)
+        }
+        b"vkCmdPushConstants" => void_fn!(cmd_push_constants, vk::PFN_vkCmdPushConstants),
+        b"vkCmdDispatch" => void_fn!(cmd_dispatch, vk::PFN_vkCmdDispatch),
+        b"vkCmdPipelineBarrier" => void_fn!(cmd_pipeline_barrier, vk::PFN_vkCmdPipelineBarrier),
+        b"vkCmdBeginRenderPass" => {
+            void_fn!(cmd_begin_render_pass, vk::PFN_vkCmdBeginRenderPass)
+        }
+        b"vkCmdEndRenderPass" => void_fn!(cmd_end_render_pass, vk::PFN_vkCmdEndRenderPass),
+        b"vkCmdBindVertexBuffers" => {
+            void_fn!(cmd_bind_vertex_buffers, vk::PFN_vkCmdBindVertexBuffers)
+        }
+        b"vkCmdBindIndexBuffer" => void_fn!(cmd_bind_index_buffer, vk::PFN_vkCmdBindIndexBuffer),
+        b"vkCmdSetViewport" => void_fn!(cmd_set_viewport, vk::PFN_vkCmdSetViewport),
+        b"vkCmdSetScissor" => void_fn!(cmd_set_scissor, vk::PFN_vkCmdSetScissor),
+        b"vkCmdDraw" => void_fn!(cmd_draw, vk::PFN_vkCmdDraw),
+        b"vkCmdDrawIndexed" => void_fn!(cmd_draw_indexed, vk::PFN_vkCmdDrawIndexed),
+        b"vkQueueSubmit" => void_fn!(queue_submit, vk::PFN_vkQueueSubmit),
+        b"vkQueueWaitIdle" => void_fn!(queue_wait_idle, vk::PFN_vkQueueWaitIdle),
+        _ => None,
+    }
+}
+
+unsafe extern "system" fn allocate_memory(
+    device: vk::Device,
+    p_allocate_info: *const vk::MemoryAllocateInfo<'_>,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+    p_memory: *mut vk::DeviceMemory,
+) -> vk::Result {
+    let handle = next_handle();
+    resources(device)
+        .memory
+        .insert(handle, Memory::new((*p_allocate_info).allocation_size));
+    *p_memory = handle;
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn free_memory(
+    device: vk::Device,
+    memory: vk::DeviceMemory,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+) {
+    resources(device).memory.remove(&memory);
+}
+
+unsafe extern "system" fn map_memory(
+    device: vk::Device,
+    memory: vk::DeviceMemory,
+    offset: vk::DeviceSize,
+    _size: vk::DeviceSize,
+    _flags: vk::MemoryMapFlags,
+    pp_data: *mut *mut c_void,
+) -> vk::Result {
+    match resources(device).memory.get(&memory) {
+        Some(memory) if offset < memory.size => {
+            *pp_data = memory.data.add(offset as usize).cast();
+            vk::Result::SUCCESS
+        }
+        _ => vk::Result::ERROR_MEMORY_MAP_FAILED,
+    }
+}
+
+unsafe extern "system" fn unmap_memory(_device: vk::Device, _memory: vk::DeviceMemory) {}
+
+/// Memory is coherent, so flushing and invalidating does nothing
+unsafe extern "system" fn flush_mapped_memory_ranges(
+    _device: vk::Device,
+    _memory_range_count: u32,
+    _p_memory_ranges: *const vk::MappedMemoryRange<'_>,
+) -> vk::Result {
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn create_buffer(
+    device: vk::Device,
+    p_create_info: *const vk::BufferCreateInfo<'_>,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+    p_buffer: *mut vk::Buffer,
+) -> vk::Result {
+    let handle = next_handle();
+    resources(device).buffers.insert(
+        handle,
+        Binding {
+            size: (*p_create_info).size,
+            memory: None,
+        },
+    );
+    *p_buffer = handle;
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn destroy_buffer(
+    device: vk::Device,
+    buffer: vk::Buffer,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+) {
+    resources(device).buffers.remove(&buffer);
+}
+
+unsafe extern "system" fn get_buffer_memory_requirements(
+    device: vk::Device,
+    buffer: vk::Buffer,
+    p_memory_requirements: *mut vk::MemoryRequirements,
+) {
+    let size = resources(device)
+        .buffers
+        .get(&buffer)
+        .map_or(0, |binding| binding.size);
+    *p_memory_requirements = requirements(size);
+}
+
+unsafe extern "system" fn bind_buffer_memory(
+    device: vk::Device,
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    memory_offset: vk::DeviceSize,
+) -> vk::Result {
+    match resources(device).buffers.get_mut(&buffer) {
+        Some(binding) => {
+            binding.memory = Some((memory, memory_offset));
+            vk::Result::SUCCESS
+        }
+        None => vk::Result::ERROR_UNKNOWN,
+    }
+}
+
+unsafe extern "system" fn create_image(
+    device: vk::Device,
+    p_create_info: *const vk::ImageCreateInfo<'_>,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+    p_image: *mut vk::Image,
+) -> vk::Result {
+    let create_info = &*p_create_info;
+    let extent = create_info.extent;
+    let texels = u64::from(extent.width)
+        * u64::from(extent.height)
+        * u64::from(extent.depth)
+        * u64::from(create_info.array_layers);
+    // The mip chain takes less than a third more than the first level
+    let levels = if create_info.mip_levels > 1 { 2 } else { 1 };
+
+    let handle = next_handle();
+    resources(device).images.insert(
+        handle,
+        Binding {
+            size: texels * IMAGE_BYTES_PER_TEXEL * levels,
+            memory: None,
+        },
+    );
+    *p_image = handle;
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn destroy_image(
+    device: vk::Device,
+    image: vk::Image,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+) {
+    resources(device).images.remove(&image);
+}
+
+unsafe extern "system" fn get_image_memory_requirements(
+    device: vk::Device,
+    image: vk::Image,
+    p_memory_requirements: *mut vk::MemoryRequirements,
+) {
+    let size = resources(device)
+        .images
+        .get(&image)
+        .map_or(0, |binding| binding.size);
+    *p_memory_requirements = requirements(size);
+}
+
+unsafe extern "system" fn bind_image_memory(
+    device: vk::Device,
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    memory_offset: vk::DeviceSize,
+) -> vk::Result {
+    match resources(device).images.get_mut(&image) {
+        Some(binding) => {
+            binding.memory = Some((memory, memory_offset));
+            vk::Result::SUCCESS
+        }
+        None => vk::Result::ERROR_UNKNOWN,
+    }
+}
+
+/// Creation and destruction of objects without state
+macro_rules! stateless_objects {
+    ($($create:ident, $destroy:ident: $create_info:ty => $handle:ty;)*) => {
+        $(
+            unsafe extern "system" fn $create(
+                device: vk::Device,
+                _p_create_info: *const $create_info,
+                _p_allocator: *const vk::AllocationCallbacks<'_>,
+                p_object: *mut $handle,
+            ) -> vk::Result {
+                let handle = next_handle::<$handle>();
+                resources(device)
+                    .objects
+                    .insert(handle.as_raw(), <$handle as vk::Handle>::TYPE);
+                *p_object = handle;
+                vk::Result::SUCCESS
+            }
+
+            unsafe extern "system" fn $destroy(
+                device: vk::Device,
+                object: $handle,
+                _p_allocator: *const vk::AllocationCallbacks<'_>,
+            ) {
+                resources(device).objects.remove(&object.as_raw());
+            }
+        )*
+    };
+}
+
+stateless_objects! {
+    create_image_view, destroy_image_view: vk::ImageViewCreateInfo<'_> => vk::ImageView;
+    create_sampler, destroy_sampler: vk::SamplerCreateInfo<'_> => vk::Sampler;
+    create_pipeline_cache, destroy_pipeline_cache:
+        vk::PipelineCacheCreateInfo<'_> => vk::PipelineCache;
+    create_descriptor_set_layout, destroy_descriptor_set_layout:
+        vk::DescriptorSetLayoutCreateInfo<'_> => vk::DescriptorSetLayout;
+    create_pipeline_layout, destroy_pipeline_layout:
+        vk::PipelineLayoutCreateInfo<'_> => vk::PipelineLayout;
+    create_render_pass, destroy_render_pass: vk::RenderPassCreateInfo<'_> => vk::RenderPass;
+    create_framebuffer, destroy_framebuffer: vk::FramebufferCreateInfo<'_> => vk::Framebuffer;
+    create_descriptor_pool, destroy_descriptor_pool:
+        vk::DescriptorPoolCreateInfo<'_> => vk::DescriptorPool;
+    create_semaphore, destroy_semaphore: vk::SemaphoreCreateInfo<'_> => vk::Semaphore;
+}
+
+unsafe extern "system" fn create_shader_module(
+    device: vk::Device,
+    p_create_info: *const vk::ShaderModuleCreateInfo<'_>,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+    p_shader_module: *mut vk::ShaderModule,
+) -> vk::Result {
+    let create_info = &*p_create_info;
+    if !create_info.code_size.is_multiple_of(4) || create_info.p_code.is_null() {
+        return vk::Result::ERROR_INVALID_SHADER_NV;
+    }
+
+    let code = std::slice::from_raw_parts(create_info.p_code, create_info.code_size / 4);
+    let handle = next_handle();
+    resources(device)
+        .shader_modules
+        .insert(handle, code.to_vec());
+    *p_shader_module = handle;
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn destroy_shader_module(
+    device: vk::Device,
+    shader_module: vk::ShaderModule,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+) {
+    resources(device).shader_modules.remove(&shader_module);
+}
+
+unsafe fn create_pipelines(
+    device: vk::Device,
+    create_info_count: u32,
+    p_pipelines: *mut vk::Pipeline,
+) -> vk::Result {
+    let mut resources = resources(device);
+    for index in 0..create_info_count as usize {
+        let handle = next_handle::<vk::Pipeline>();
+        resources
+            .objects
+            .insert(handle.as_raw(), vk::ObjectType::PIPELINE);
+        *p_pipelines.add(index) = handle;
+    }
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn create_compute_pipelines(
+    device: vk::Device,
+    _pipeline_cache: vk::PipelineCache,
+    create_info_count: u32,
+    _p_create_infos: *const vk::ComputePipelineCreateInfo<'_>,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+    p_pipelines: *mut vk::Pipeline,
+) -> vk::Result {
+    create_pipelines(device, create_info_count, p_pipelines)
+}
+
+unsafe extern "system" fn create_graphics_pipelines(
+    device: vk::Device,
+    _pipeline_cache: vk::PipelineCache,
+    create_info_count: u32,
+    _p_create_infos: *const vk::GraphicsPipelineCreateInfo<'_>,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+    p_pipelines: *mut vk::Pipeline,
+) -> vk::Result {
+    create_pipelines(device, create_info_count, p_pipelines)
+}
+
+unsafe extern "system" fn destroy_pipeline(
+    device: vk::Device,
+    pipeline: vk::Pipeline,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+) {
+    resources(device).objects.remove(&pipeline.as_raw());
+}
+
+unsafe extern "system" fn reset_descriptor_pool(
+    _device: vk::Device,
+    _descriptor_pool: vk::DescriptorPool,
+    _flags: vk::DescriptorPoolResetFlags,
+) -> vk::Result {
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn allocate_descriptor_sets(
+    device: vk::Device,
+    p_allocate_info: *const vk::DescriptorSetAllocateInfo<'_>,
+    p_descriptor_sets: *mut vk::DescriptorSet,
+) -> vk::Result {
+    let mut resources = resources(device);
+    for index in 0..(*p_allocate_info).descriptor_set_count as usize {
+        let handle = next_handle::<vk::DescriptorSet>();
+        resources
+            .objects
+            .insert(handle.as_raw(), vk::ObjectType::DESCRIPTOR_SET);
+        *p_descriptor_sets.add(index) = handle;
+    }
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn free_descriptor_sets(
+    device: vk::Device,
+    _descriptor_pool: vk::DescriptorPool,
+    descriptor_set_count: u32,
+    p_descriptor_sets: *const vk::DescriptorSet,
+) -> vk::Result {
+    let mut resources = resources(device);
+    for index in 0..descriptor_set_count as usize {
+        resources
+            .objects
+            .remove(&(*p_descriptor_sets.add(index)).as_raw());
+    }
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn update_descriptor_sets(
+    _device: vk::Device,
+    _descriptor_write_count: u32,
+    _p_descriptor_writes: *const vk::WriteDescriptorSet<'_>,
+    _descriptor_copy_count: u32,
+    _p_descriptor_copies: *const vk::CopyDescriptorSet<'_>,
+) {
+}
+
+unsafe extern "system" fn create_fence(
+    device: vk::Device,
+    p_create_info: *const vk::FenceCreateInfo<'_>,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+    p_fence: *mut vk::Fence,
+) -> vk::Result {
+    let signaled = (*p_create_info)
+        .flags
+        .contains(vk::FenceCreateFlags::SIGNALED);
+    let handle = next_handle();
+    resources(device).fences.insert(handle, signaled);
+    *p_fence = handle;
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn destroy_fence(
+    device: vk::Device,
+    fence: vk::Fence,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+) {
+    resources(device).fences.remove(&fence);
+}
+
+unsafe extern "system" fn reset_fences(
+    device: vk::Device,
+    fence_count: u32,
+    p_fences: *const vk::Fence,
+) -> vk::Result {
+    let mut resources = resources(device);
+    for fence in std::slice::from_raw_parts(p_fences, fence_count as usize) {
+        if let Some(signaled) = resources.fences.get_mut(fence) {
+            *signaled = false;
+        }
+    }
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn get_fence_status(device: vk::Device, fence: vk::Fence) -> vk::Result {
+    match resources(device).fences.get(&fence) {
+        Some(true) => vk::Result::SUCCESS,
+        Some(false) => vk::Result::NOT_READY,
+        None => vk::Result::ERROR_DEVICE_LOST,
+    }
+}
+
+/// Submits complete immediately, so waiting for a fence that isn't signaled would never end
+unsafe extern "system" fn wait_for_fences(
+    device: vk::Device,
+    fence_count: u32,
+    p_fences: *const vk::Fence,
+    wait_all: vk::Bool32,
+    _timeout: u64,
+) -> vk::Result {
+    let resources = resources(device);
+    let fences = std::slice::from_raw_parts(p_fences, fence_count as usize);
+    let signaled = |fence| resources.fences.get(fence).copied().unwrap_or_default();
+    let done = if wait_all == vk::TRUE {
+        fences.iter().all(signaled)
+    } else {
+        fences.iter().any(signaled)
+    };
+
+    if done {
+        vk::Result::SUCCESS
+    } else {
+        vk::Result::TIMEOUT
+    }
+}
+
+unsafe extern "system" fn create_command_pool(
+    device: vk::Device,
+    _p_create_info: *const vk::CommandPoolCreateInfo<'_>,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+    p_command_pool: *mut vk::CommandPool,
+) -> vk::Result {
+    let handle = next_handle();
+    resources(device).command_pools.insert(handle, Vec::new());
+    *p_command_pool = handle;
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn destroy_command_pool(
+    device: vk::Device,
+    command_pool: vk::CommandPool,
+    _p_allocator: *const vk::AllocationCallbacks<'_>,
+) {
+    let command_buffers = resources(device).command_pools.remove(&command_pool);
+    for command_buffer in command_buffers.unwrap_or_default() {
+        drop(Box::from_raw(
+            command_buffer.as_raw() as *mut CommandBufferObject
+        ));
+    }
+}
+
+unsafe extern "system" fn reset_command_pool(
+    device: vk::Device,
+    command_pool: vk::CommandPool,
+    _flags: vk::CommandPoolResetFlags,
+) -> vk::Result {
+    if let Some(command_buffers) = resources(device).command_pools.get(&command_pool) {
+        for &handle in command_buffers {
+            command_buffer(handle).transfers.lock().unwrap().clear();
+        }
+    }
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn allocate_command_buffers(
+    device: vk::Device,
+    p_allocate_info: *const vk::CommandBufferAllocateInfo<'_>,
+    p_command_buffers: *mut vk::CommandBuffer,
+) -> vk::Result {
+    let allocate_info = &*p_allocate_info;
+    let mut resources = resources(device);
+    let Some(pool) = resources.command_pools.get_mut(&allocate_info.command_pool) else {
+        return vk::Result::ERROR_UNKNOWN;
+    };
+
+    for index in 0..allocate_info.command_buffer_count as usize {
+        let handle = into_handle(Box::new(CommandBufferObject {
+            loader_data: LoaderData::new(),
+            transfers: Mutex::new(Vec::new()),
+        }));
+        pool.push(handle);
+        *p_command_buffers.add(index) = handle;
+    }
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn free_command_buffers(
+    device: vk::Device,
+    command_pool: vk::CommandPool,
+    command_buffer_count: u32,
+    p_command_buffers: *const vk::CommandBuffer,
+) {
+    let mut resources = resources(device);
+    let Some(pool) = resources.command_pools.get_mut(&command_pool) else {
+        return;
+    };
+
+    for &handle in std::slice::from_raw_parts(p_command_buffers, command_buffer_count as usize) {
+        if handle != vk::CommandBuffer::null() && pool.contains(&handle) {
+            pool.retain(|other| *other != handle);
+            drop(Box::from_raw(handle.as_raw() as *mut CommandBufferObject));
+        }
+    }
+}
+
+unsafe extern "system" fn begin_command_buffer(
+    handle: vk::CommandBuffer,
+    _p_begin_info: *const vk::CommandBufferBeginInfo<'_>,
+) -> vk::Result {
+    command_buffer(handle).transfers.lock().unwrap().clear();
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn end_command_buffer(_command_buffer: vk::CommandBuffer) -> vk::Result {
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn reset_command_buffer(
+    handle: vk::CommandBuffer,
+    _flags: vk::CommandBufferResetFlags,
+) -> vk::Result {
+    command_buffer(handle).transfers.lock().unwrap().clear();
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn cmd_copy_buffer(
+    command_buffer: vk::CommandBuffer,
+    src_buffer: vk::Buffer,
+    dst_buffer: vk::Buffer,
+    region_count: u32,
+    p_regions: *const vk::BufferCopy,
+) {
+    record(
+        command_buffer,
+        Transfer::Copy {
+            src: src_buffer,
+            dst: dst_buffer,
+            regions: std::slice::from_raw_parts(p_regions, region_count as usize).to_vec(),
+        },
+    );
+}
+
+unsafe extern "system" fn cmd_fill_buffer(
+    command_buffer: vk::CommandBuffer,
+    dst_buffer: vk::Buffer,
+    dst_offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+    data: u32,
+) {
+    record(
+        command_buffer,
+        Transfer::Fill {
+            dst: dst_buffer,
+            offset: dst_offset,
+            size,
+            data,
+        },
+    );
+}
+
+unsafe extern "system" fn cmd_update_buffer(
+    command_buffer: vk::CommandBuffer,
+    dst_buffer: vk::Buffer,
+    dst_offset: vk::DeviceSize,
+    data_size: vk::DeviceSize,
+    p_data: *const c_void,
+) {
+    record(
+        command_buffer,
+        Transfer::Update {
+            dst: dst_buffer,
+            offset: dst_offset,
+            data: std::slice::from_raw_parts(p_data.cast::<u8>(), data_size as usize).to_vec(),
+        },
+    );
+}
+
+unsafe extern "system" fn cmd_bind_pipeline(
+    _command_buffer: vk::CommandBuffer,
+    _pipeline_bind_point: vk::PipelineBindPoint,
+    _pipeline: vk::Pipeline,
+) {
+}
+
+unsafe extern "system" fn cmd_bind_descriptor_sets(
+    _command_buffer: vk::CommandBuffer,
+    _pipeline_bind_point: vk::PipelineBindPoint,
+    _layout: vk::PipelineLayout,
+    _first_set: u32,
+    _descriptor_set_count: u32,
+    _p_descriptor_sets: *const vk::DescriptorSet,
+    _dynamic_offset_count: u32,
+    _p_dynamic_offsets: *const u32,
+) {
+}
+
+unsafe extern "system" fn cmd_push_constants(
+    _command_buffer: vk::CommandBuffer,
+    _layout: vk::PipelineLayout,
+    _stage_flags: vk::ShaderStageFlags,
+    _offset: u32,
+    _size: u32,
+    _p_values: *const c_void,
+) {
+}
+
+unsafe extern "system" fn cmd_dispatch(
+    _command_buffer: vk::CommandBuffer,
+    _group_count_x: u32,
+    _group_count_y: u32,
+    _group_count_z: u32,
+) {
+}
+
+unsafe extern "system" fn cmd_pipeline_barrier(
+    _command_buffer: vk::CommandBuffer,
+    _src_stage_mask: vk::PipelineStageFlags,
+    _dst_stage_mask: vk::PipelineStageFlags,
+    _dependency_flags: vk::DependencyFlags,
+    _memory_barrier_count: u32,
+    _p_memory_barriers: *const vk::MemoryBarrier<'_>,
+    _buffer_memory_barrier_count: u32,
+    _p_buffer_memory_barriers: *const vk::BufferMemoryBarrier<'_>,
+    _image_memory_barrier_count: u32,
+    _p_image_memory_barriers: *const vk::ImageMemoryBarrier<'_>,
+) {
+}
+
+unsafe extern "system" fn cmd_begin_render_pass(
+    _command_buffer: vk::CommandBuffer,
+    _p_render_pass_begin: *const vk::RenderPassBeginInfo<'_>,
+    _contents: vk::SubpassContents,
+) {
+}
+
+unsafe extern "system" fn cmd_end_render_pass(_command_buffer: vk::CommandBuffer) {}
+
+unsafe extern "system" fn cmd_bind_vertex_buffers(
+    _command_buffer: vk::CommandBuffer,
+    _first_binding: u32,
+    _binding_count: u32,
+    _p_buffers: *const vk::Buffer,
+    _p_offsets: *const vk::DeviceSize,
+) {
+}
+
+unsafe extern "system" fn cmd_bind_index_buffer(
+    _command_buffer: vk::CommandBuffer,
+    _buffer: vk::Buffer,
+    _offset: vk::DeviceSize,
+    _index_type: vk::IndexType,
+) {
+}
+
+unsafe extern "system" fn cmd_set_viewport(
+    _command_buffer: vk::CommandBuffer,
+    _first_viewport: u32,
+    _viewport_count: u32,
+    _p_viewports: *const vk::Viewport,
+) {
+}
+
+unsafe extern "system" fn cmd_set_scissor(
+    _command_buffer: vk::CommandBuffer,
+    _first_scissor: u32,
+    _scissor_count: u32,
+    _p_scissors: *const vk::Rect2D,
+) {
+}
+
+unsafe extern "system" fn cmd_draw(
+    _command_buffer: vk::CommandBuffer,
+    _vertex_count: u32,
+    _instance_count: u32,
+    _first_vertex: u32,
+    _first_instance: u32,
+) {
+}
+
+unsafe extern "system" fn cmd_draw_indexed(
+    _command_buffer: vk::CommandBuffer,
+    _index_count: u32,
+    _instance_count: u32,
+    _first_index: u32,
+    _vertex_offset: i32,
+    _first_instance: u32,
+) {
+}
+
+unsafe extern "system" fn queue_submit(
+    queue: vk::Queue,
+    submit_count: u32,
+    p_submits: *const vk::SubmitInfo<'_>,
+    fence: vk::Fence,
+) -> vk::Result {
+    let device = &*as_object::<QueueObject>(queue).device;
+    let mut resources = device.resources.lock().unwrap();
+
+    let submits = if p_submits.is_null() {
+        &[]
+    } else {
+        std::slice::from_raw_parts(p_submits, submit_count as usize)
+    };
+    for submit in submits {
+        let command_buffers = if submit.p_command_buffers.is_null() {
+            &[]
+        } else {
+            std::slice::from_raw_parts(
+                submit.p_command_buffers,
+                submit.command_buffer_count as usize,
+            )
+        };
+        for &handle in command_buffers {
+            for transfer in command_buffer(handle).transfers.lock().unwrap().iter() {
+                resources.execute(transfer);
+            }
+        }
+    }
+
+    if let Some(signaled) = resources.fences.get_mut(&fence) {
+        *signaled = true;
+    }
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn queue_wait_idle(_queue: vk::Queue) -> vk::Result {
+    vk::Result::SUCCESS
+}
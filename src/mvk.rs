@@ -0,0 +1,800 @@
+//! Bindings for MoltenVK's private API, declared in `vk_mvk_moltenvk.h`.
+//!
+//! The layout follows [`ash::khr`] and friends: [`Instance`] and [`Device`] hold the function
+//! tables, loaded through `vkGetInstanceProcAddr` and `vkGetDeviceProcAddr` respectively, and
+//! the `#[repr(C)]` structs mirror the header of the MoltenVK release selected with the `v*`
+//! features.
+//!
+//! Every query takes the size of the struct it fills in, MoltenVK only copies the part both
+//! sides know about and returns `VK_INCOMPLETE` if the sizes differ. The wrappers treat that as
+//! success, since the fields that were filled in are still valid.
+//!
+//! Metal objects are exchanged as raw `id` pointers.
+
+// crate-specific exceptions: these are raw FFI bindings, documented like ash's, which defers
+// safety requirements to the MoltenVK and Vulkan documentation
+#![allow(unsafe_code, clippy::missing_safety_doc)]
+
+use ash::{prelude::VkResult, vk};
+use std::{
+    ffi::{c_char, c_void, CStr},
+    mem,
+};
+
+macro_rules! mvk_enum {
+    ($(#[doc = $doc:literal])* $name:ident { $($(#[doc = $vdoc:literal])* $variant:ident = $value:literal,)* }) => {
+        $(#[doc = $doc])*
+        #[repr(transparent)]
+        #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+        pub struct $name(pub i32);
+
+        impl $name {
+            $($(#[doc = $vdoc])* pub const $variant: Self = Self($value);)*
+        }
+    };
+}
+
+mvk_enum! {
+    /// `MVKConfigLogLevel`
+    ConfigLogLevel {
+        NONE = 0,
+        ERROR = 1,
+        WARNING = 2,
+        INFO = 3,
+        DEBUG = 4,
+    }
+}
+
+mvk_enum! {
+    /// `MVKConfigTraceVulkanCalls`
+    ConfigTraceVulkanCalls {
+        NONE = 0,
+        ENTER = 1,
+        ENTER_EXIT = 2,
+        DURATION = 3,
+    }
+}
+
+mvk_enum! {
+    /// `MVKConfigAutoGPUCaptureScope`
+    ConfigAutoGpuCaptureScope {
+        NONE = 0,
+        DEVICE = 1,
+        FRAME = 2,
+    }
+}
+
+mvk_enum! {
+    /// `MVKConfigAdvertiseExtensionBits`, these are flags and can be combined
+    ConfigAdvertiseExtensions {
+        ALL = 0x1,
+        WSI = 0x2,
+        PORTABILITY = 0x4,
+    }
+}
+
+mvk_enum! {
+    /// `MVKConfigFastMath`
+    ConfigFastMath {
+        NEVER = 0,
+        ALWAYS = 1,
+        ON_DEMAND = 2,
+    }
+}
+
+mvk_enum! {
+    /// `MVKVkSemaphoreSupportStyle`
+    SemaphoreSupportStyle {
+        SINGLE_QUEUE = 0,
+        METAL_EVENTS_WHERE_SAFE = 1,
+        METAL_EVENTS = 2,
+        CALLBACK = 3,
+    }
+}
+
+mvk_enum! {
+    /// `MVKConfigCompressionAlgorithm`
+    ConfigCompressionAlgorithm {
+        NONE = 0,
+        LZFSE = 1,
+        ZLIB = 2,
+        LZ4 = 3,
+        LZMA = 4,
+    }
+}
+
+mvk_enum! {
+    /// `MVKPrefillMetalCommandBuffersStyle`
+    PrefillMetalCommandBuffersStyle {
+        NO_PREFILL = 0,
+        DEFERRED_ENCODING = 1,
+        IMMEDIATE_ENCODING = 2,
+        IMMEDIATE_ENCODING_NO_AUTORELEASE = 3,
+    }
+}
+
+mvk_enum! {
+    /// `MVKConfigActivityPerformanceLoggingStyle`
+    ConfigActivityPerformanceLoggingStyle {
+        FRAME_COUNT = 0,
+        IMMEDIATE = 1,
+        DEVICE_LIFETIME = 2,
+    }
+}
+
+/// `MVKConfiguration`, MoltenVK's process-wide configuration.
+///
+/// See the MoltenVK documentation of the corresponding `MVK_CONFIG_*` environment variables
+/// for the meaning of each field.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Configuration {
+    pub debug_mode: vk::Bool32,
+    pub shader_conversion_flip_vertex_y: vk::Bool32,
+    pub synchronous_queue_submits: vk::Bool32,
+    pub prefill_metal_command_buffers: PrefillMetalCommandBuffersStyle,
+    pub max_active_metal_command_buffers_per_queue: u32,
+    pub support_large_query_pools: vk::Bool32,
+    pub present_with_command_buffer: vk::Bool32,
+    pub swapchain_min_mag_filter_use_nearest: vk::Bool32,
+    pub metal_compile_timeout: u64,
+    pub performance_tracking: vk::Bool32,
+    pub performance_logging_frame_count: u32,
+    pub display_watermark: vk::Bool32,
+    pub specialized_queue_families: vk::Bool32,
+    pub switch_system_gpu: vk::Bool32,
+    pub full_image_view_swizzle: vk::Bool32,
+    pub default_gpu_capture_scope_queue_family_index: u32,
+    pub default_gpu_capture_scope_queue_index: u32,
+    pub fast_math_enabled: ConfigFastMath,
+    pub log_level: ConfigLogLevel,
+    pub trace_vulkan_calls: ConfigTraceVulkanCalls,
+    pub force_low_power_gpu: vk::Bool32,
+    /// Obsolete, kept by MoltenVK for backwards compatibility
+    pub semaphore_use_mtl_fence: vk::Bool32,
+    pub semaphore_support_style: SemaphoreSupportStyle,
+    pub auto_gpu_capture_scope: ConfigAutoGpuCaptureScope,
+    pub auto_gpu_capture_output_filepath: *const c_char,
+    pub texture_1d_as_2d: vk::Bool32,
+    pub preallocate_descriptors: vk::Bool32,
+    pub use_command_pooling: vk::Bool32,
+    pub use_mtl_heap: vk::Bool32,
+    #[cfg(any(feature = "v1_1_5", feature = "v1_1_10"))]
+    pub log_activity_performance_inline: vk::Bool32,
+    #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+    pub activity_performance_logging_style: ConfigActivityPerformanceLoggingStyle,
+    pub api_version_to_advertise: u32,
+    pub advertise_extensions: ConfigAdvertiseExtensions,
+    pub resume_lost_device: vk::Bool32,
+    pub use_metal_argument_buffers: vk::Bool32,
+    #[cfg(not(feature = "v1_1_5"))]
+    pub shader_source_compression_algorithm: ConfigCompressionAlgorithm,
+    #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+    pub should_maximize_concurrent_compilation: vk::Bool32,
+    #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+    pub timestamp_period_low_pass_alpha: f32,
+    #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+    pub use_metal_private_api: vk::Bool32,
+}
+
+/// Checks the size and some field offsets of a struct at compile time
+macro_rules! assert_layout {
+    ($ty:ty, $size:literal, { $($field:ident: $offset:literal,)* }) => {
+        const _: () = {
+            assert!(mem::size_of::<$ty>() == $size);
+            $(assert!(mem::offset_of!($ty, $field) == $offset);)*
+        };
+    };
+}
+
+// The layouts of `MVKConfiguration` in the `mvk_config.h` of each supported MoltenVK release,
+// which `vkGetMoltenVKConfigurationMVK` checks the size passed against
+#[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+assert_layout!(Configuration, 168, {
+    metal_compile_timeout: 32,
+    force_low_power_gpu: 84,
+    semaphore_use_mtl_fence: 88,
+    semaphore_support_style: 92,
+    auto_gpu_capture_output_filepath: 104,
+    use_mtl_heap: 124,
+    activity_performance_logging_style: 128,
+    api_version_to_advertise: 132,
+    use_metal_argument_buffers: 144,
+    shader_source_compression_algorithm: 148,
+    use_metal_private_api: 160,
+});
+#[cfg(feature = "v1_1_10")]
+assert_layout!(Configuration, 152, {
+    metal_compile_timeout: 32,
+    semaphore_support_style: 92,
+    auto_gpu_capture_output_filepath: 104,
+    log_activity_performance_inline: 128,
+    api_version_to_advertise: 132,
+    use_metal_argument_buffers: 144,
+    shader_source_compression_algorithm: 148,
+});
+#[cfg(feature = "v1_1_5")]
+assert_layout!(Configuration, 152, {
+    metal_compile_timeout: 32,
+    semaphore_support_style: 92,
+    auto_gpu_capture_output_filepath: 104,
+    log_activity_performance_inline: 128,
+    api_version_to_advertise: 132,
+    use_metal_argument_buffers: 144,
+});
+
+// SAFETY: `auto_gpu_capture_output_filepath` points to a string owned by whoever set it, the
+// same as the `p_next` pointers ash's structs are `Send` and `Sync` with
+unsafe impl Send for Configuration {}
+unsafe impl Sync for Configuration {}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        // SAFETY: every field is an integer, a float or a nullable pointer
+        unsafe { mem::zeroed() }
+    }
+}
+
+/// `MVKPhysicalDeviceMetalFeatures`, the Metal capabilities of a physical device
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+pub struct PhysicalDeviceMetalFeatures {
+    pub msl_version: u32,
+    pub indirect_drawing: vk::Bool32,
+    pub base_vertex_instance_drawing: vk::Bool32,
+    pub dynamic_mtl_buffer_size: u32,
+    pub shader_specialization: vk::Bool32,
+    pub io_surfaces: vk::Bool32,
+    pub texel_buffers: vk::Bool32,
+    pub layered_rendering: vk::Bool32,
+    pub present_mode_immediate: vk::Bool32,
+    pub stencil_views: vk::Bool32,
+    pub multisample_array_textures: vk::Bool32,
+    pub sampler_clamp_to_border: vk::Bool32,
+    pub max_texture_dimension: u32,
+    pub max_per_stage_buffer_count: u32,
+    pub max_per_stage_texture_count: u32,
+    pub max_per_stage_sampler_count: u32,
+    pub max_mtl_buffer_size: vk::DeviceSize,
+    pub mtl_buffer_alignment: vk::DeviceSize,
+    pub max_query_buffer_size: vk::DeviceSize,
+    pub mtl_copy_buffer_alignment: vk::DeviceSize,
+    pub supported_sample_counts: vk::SampleCountFlags,
+    pub min_swapchain_image_count: u32,
+    pub max_swapchain_image_count: u32,
+    pub combined_store_resolve_action: vk::Bool32,
+    pub array_of_textures: vk::Bool32,
+    pub array_of_samplers: vk::Bool32,
+    /// `MTLLanguageVersion`
+    pub msl_version_enum: u64,
+    pub depth_sample_compare: vk::Bool32,
+    pub events: vk::Bool32,
+    pub memory_barriers: vk::Bool32,
+    pub multisample_layered_rendering: vk::Bool32,
+    pub stencil_feedback: vk::Bool32,
+    pub texture_buffers: vk::Bool32,
+    pub post_depth_coverage: vk::Bool32,
+    pub fences: vk::Bool32,
+    pub raster_order_groups: vk::Bool32,
+    pub native_3d_compressed_textures: vk::Bool32,
+    pub native_texture_swizzle: vk::Bool32,
+    pub placement_heaps: vk::Bool32,
+    pub push_constant_size_alignment: vk::DeviceSize,
+    pub max_texture_layers: u32,
+    pub max_subgroup_size: u32,
+    pub vertex_stride_alignment: vk::DeviceSize,
+    pub indirect_tessellation_drawing: vk::Bool32,
+    pub non_uniform_threadgroups: vk::Bool32,
+    pub render_without_attachments: vk::Bool32,
+    pub deferred_store_actions: vk::Bool32,
+    pub shared_linear_textures: vk::Bool32,
+    pub depth_resolve: vk::Bool32,
+    pub stencil_resolve: vk::Bool32,
+    pub max_per_stage_dynamic_mtl_buffer_count: u32,
+    pub max_per_stage_storage_texture_count: u32,
+    pub astc_hdr_textures: vk::Bool32,
+    pub render_linear_textures: vk::Bool32,
+    pub pull_model_interpolation: vk::Bool32,
+    pub sampler_mirror_clamp_to_edge: vk::Bool32,
+    pub quad_permute: vk::Bool32,
+    pub simd_permute: vk::Bool32,
+    pub simd_reduction: vk::Bool32,
+    pub min_subgroup_size: u32,
+    pub texture_barriers: vk::Bool32,
+    pub tile_based_deferred_rendering: vk::Bool32,
+    pub argument_buffers: vk::Bool32,
+    pub descriptor_set_argument_buffers: vk::Bool32,
+    #[cfg(not(feature = "v1_1_5"))]
+    /// `MVKFloatRounding`
+    pub clear_color_float_rounding: i32,
+    #[cfg(not(feature = "v1_1_5"))]
+    /// `MVKCounterSamplingFlags`
+    pub counter_sampling_points: u32,
+    #[cfg(not(feature = "v1_1_5"))]
+    pub programmable_sample_positions: vk::Bool32,
+    #[cfg(not(feature = "v1_1_5"))]
+    pub shader_barycentric_coordinates: vk::Bool32,
+    #[cfg(not(feature = "v1_1_5"))]
+    /// `MTLArgumentBuffersTier`
+    pub argument_buffers_tier: u64,
+    #[cfg(not(feature = "v1_1_5"))]
+    pub needs_sample_dref_lod_array_workaround: vk::Bool32,
+    #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+    pub host_memory_page_size: vk::DeviceSize,
+    #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+    pub dynamic_vertex_stride: vk::Bool32,
+    #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+    pub needs_cube_grad_workaround: vk::Bool32,
+    #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+    pub native_texture_atomics: vk::Bool32,
+    #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+    pub needs_argument_buffer_encoders: vk::Bool32,
+}
+
+#[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+assert_layout!(PhysicalDeviceMetalFeatures, 344, {
+    max_mtl_buffer_size: 64,
+    msl_version_enum: 120,
+    push_constant_size_alignment: 176,
+    vertex_stride_alignment: 192,
+    descriptor_set_argument_buffers: 280,
+    argument_buffers_tier: 304,
+    needs_sample_dref_lod_array_workaround: 312,
+    host_memory_page_size: 320,
+    needs_argument_buffer_encoders: 340,
+});
+#[cfg(feature = "v1_1_10")]
+assert_layout!(PhysicalDeviceMetalFeatures, 320, {
+    max_mtl_buffer_size: 64,
+    msl_version_enum: 120,
+    push_constant_size_alignment: 176,
+    vertex_stride_alignment: 192,
+    descriptor_set_argument_buffers: 280,
+    argument_buffers_tier: 304,
+    needs_sample_dref_lod_array_workaround: 312,
+});
+#[cfg(feature = "v1_1_5")]
+assert_layout!(PhysicalDeviceMetalFeatures, 288, {
+    max_mtl_buffer_size: 64,
+    msl_version_enum: 120,
+    push_constant_size_alignment: 176,
+    vertex_stride_alignment: 192,
+    descriptor_set_argument_buffers: 280,
+});
+
+/// `MVKPerformanceTracker`, durations are in milliseconds
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PerformanceTracker {
+    pub count: u32,
+    pub latest: f64,
+    pub average: f64,
+    pub minimum: f64,
+    pub maximum: f64,
+}
+
+/// `MVKShaderCompilationPerformance`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ShaderCompilationPerformance {
+    pub hash_shader_code: PerformanceTracker,
+    pub spirv_to_msl: PerformanceTracker,
+    pub msl_compile: PerformanceTracker,
+    pub msl_load: PerformanceTracker,
+    pub msl_compress: PerformanceTracker,
+    pub msl_decompress: PerformanceTracker,
+    pub shader_library_from_cache: PerformanceTracker,
+    pub function_retrieval: PerformanceTracker,
+    pub function_specialization: PerformanceTracker,
+    pub pipeline_compile: PerformanceTracker,
+    pub glsl_to_spirv: PerformanceTracker,
+}
+
+/// `MVKPipelineCachePerformance`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PipelineCachePerformance {
+    pub size_pipeline_cache: PerformanceTracker,
+    pub write_pipeline_cache: PerformanceTracker,
+    pub read_pipeline_cache: PerformanceTracker,
+}
+
+/// `MVKQueuePerformance`
+#[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct QueuePerformance {
+    pub retrieve_mtl_command_buffer: PerformanceTracker,
+    pub command_buffer_encoding: PerformanceTracker,
+    pub submit_command_buffers: PerformanceTracker,
+    pub mtl_command_buffer_execution: PerformanceTracker,
+    pub retrieve_ca_metal_drawable: PerformanceTracker,
+    pub present_swapchains: PerformanceTracker,
+    pub frame_interval: PerformanceTracker,
+}
+
+/// `MVKQueuePerformance`
+#[cfg(any(feature = "v1_1_5", feature = "v1_1_10"))]
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct QueuePerformance {
+    pub mtl_queue_access: PerformanceTracker,
+    pub mtl_command_buffer_completion: PerformanceTracker,
+    pub next_ca_metal_drawable: PerformanceTracker,
+    pub frame_interval: PerformanceTracker,
+}
+
+/// `MVKDevicePerformance`
+#[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DevicePerformance {
+    pub gpu_memory_allocated: PerformanceTracker,
+}
+
+/// `MVKPerformanceStatistics`, collected when [`Configuration::performance_tracking`] is
+/// enabled
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PerformanceStatistics {
+    pub shader_compilation: ShaderCompilationPerformance,
+    pub pipeline_cache: PipelineCachePerformance,
+    pub queue: QueuePerformance,
+    #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+    pub device: DevicePerformance,
+}
+
+#[allow(non_camel_case_types)]
+pub type PFN_vkGetMoltenVKConfigurationMVK = unsafe extern "system" fn(
+    instance: vk::Instance,
+    p_configuration: *mut Configuration,
+    p_configuration_size: *mut usize,
+) -> vk::Result;
+#[allow(non_camel_case_types)]
+pub type PFN_vkSetMoltenVKConfigurationMVK = unsafe extern "system" fn(
+    instance: vk::Instance,
+    p_configuration: *const Configuration,
+    p_configuration_size: *mut usize,
+) -> vk::Result;
+#[allow(non_camel_case_types)]
+pub type PFN_vkGetPhysicalDeviceMetalFeaturesMVK = unsafe extern "system" fn(
+    physical_device: vk::PhysicalDevice,
+    p_metal_features: *mut PhysicalDeviceMetalFeatures,
+    p_metal_features_size: *mut usize,
+) -> vk::Result;
+#[allow(non_camel_case_types)]
+pub type PFN_vkGetPerformanceStatisticsMVK = unsafe extern "system" fn(
+    device: vk::Device,
+    p_perf: *mut PerformanceStatistics,
+    p_perf_size: *mut usize,
+) -> vk::Result;
+#[allow(non_camel_case_types)]
+pub type PFN_vkGetVersionStringsMVK = unsafe extern "system" fn(
+    p_molten_version_string_buffer: *mut c_char,
+    molten_version_string_buffer_length: u32,
+    p_vulkan_version_string_buffer: *mut c_char,
+    vulkan_version_string_buffer_length: u32,
+);
+#[allow(non_camel_case_types)]
+pub type PFN_vkGetMTLDeviceMVK =
+    unsafe extern "system" fn(physical_device: vk::PhysicalDevice, p_mtl_device: *mut *mut c_void);
+#[allow(non_camel_case_types)]
+pub type PFN_vkSetMTLTextureMVK =
+    unsafe extern "system" fn(image: vk::Image, mtl_texture: *mut c_void) -> vk::Result;
+#[allow(non_camel_case_types)]
+pub type PFN_vkGetMTLTextureMVK =
+    unsafe extern "system" fn(image: vk::Image, p_mtl_texture: *mut *mut c_void);
+#[allow(non_camel_case_types)]
+pub type PFN_vkGetMTLBufferMVK =
+    unsafe extern "system" fn(buffer: vk::Buffer, p_mtl_buffer: *mut *mut c_void);
+#[allow(non_camel_case_types)]
+pub type PFN_vkUseIOSurfaceMVK =
+    unsafe extern "system" fn(image: vk::Image, io_surface: *mut c_void) -> vk::Result;
+#[allow(non_camel_case_types)]
+pub type PFN_vkGetIOSurfaceMVK =
+    unsafe extern "system" fn(image: vk::Image, p_io_surface: *mut *mut c_void);
+#[allow(non_camel_case_types)]
+pub type PFN_vkGetMTLCommandQueueMVK =
+    unsafe extern "system" fn(queue: vk::Queue, p_mtl_command_queue: *mut *mut c_void);
+
+/// Loads a function pointer, falling back to a stub that panics like ash does
+macro_rules! load_fn {
+    ($f:ident, $name:literal, $pfn:ty, ($($arg:ty),*) $(-> $ret:ty)?) => {{
+        unsafe extern "system" fn unavailable($(_: $arg),*) $(-> $ret)? {
+            panic!(concat!("Unable to load ", $name))
+        }
+
+        let ptr = $f(CStr::from_bytes_with_nul_unchecked(concat!($name, "\0").as_bytes()));
+        if ptr.is_null() {
+            unavailable as $pfn
+        } else {
+            mem::transmute::<*const c_void, $pfn>(ptr)
+        }
+    }};
+}
+
+/// Maps the results of the size-checked queries, see the [module docs](self)
+fn sized_result(result: vk::Result) -> VkResult<()> {
+    match result {
+        vk::Result::SUCCESS | vk::Result::INCOMPLETE => Ok(()),
+        err => Err(err),
+    }
+}
+
+/// Raw MoltenVK instance-level function pointers
+#[derive(Clone)]
+pub struct InstanceFn {
+    pub get_moltenvk_configuration_mvk: PFN_vkGetMoltenVKConfigurationMVK,
+    pub set_moltenvk_configuration_mvk: PFN_vkSetMoltenVKConfigurationMVK,
+    pub get_physical_device_metal_features_mvk: PFN_vkGetPhysicalDeviceMetalFeaturesMVK,
+    pub get_version_strings_mvk: PFN_vkGetVersionStringsMVK,
+    pub get_mtl_device_mvk: PFN_vkGetMTLDeviceMVK,
+}
+
+impl InstanceFn {
+    pub fn load<F: FnMut(&CStr) -> *const c_void>(mut f: F) -> Self {
+        unsafe {
+            Self {
+                get_moltenvk_configuration_mvk: load_fn!(
+                    f,
+                    "vkGetMoltenVKConfigurationMVK",
+                    PFN_vkGetMoltenVKConfigurationMVK, (vk::Instance, *mut Configuration, *mut usize) -> vk::Result),
+                set_moltenvk_configuration_mvk: load_fn!(
+                    f,
+                    "vkSetMoltenVKConfigurationMVK",
+                    PFN_vkSetMoltenVKConfigurationMVK, (vk::Instance, *const Configuration, *mut usize) -> vk::Result),
+                get_physical_device_metal_features_mvk: load_fn!(
+                    f,
+                    "vkGetPhysicalDeviceMetalFeaturesMVK",
+                    PFN_vkGetPhysicalDeviceMetalFeaturesMVK, (vk::PhysicalDevice, *mut PhysicalDeviceMetalFeatures, *mut usize) -> vk::Result),
+                get_version_strings_mvk: load_fn!(
+                    f,
+                    "vkGetVersionStringsMVK",
+                    PFN_vkGetVersionStringsMVK,
+                    (*mut c_char, u32, *mut c_char, u32)
+                ),
+                get_mtl_device_mvk: load_fn!(
+                    f,
+                    "vkGetMTLDeviceMVK",
+                    PFN_vkGetMTLDeviceMVK,
+                    (vk::PhysicalDevice, *mut *mut c_void)
+                ),
+            }
+        }
+    }
+}
+
+/// MoltenVK instance-level functions
+#[derive(Clone)]
+pub struct Instance {
+    fp: InstanceFn,
+    handle: vk::Instance,
+}
+
+impl Instance {
+    pub fn new(entry: &ash::Entry, instance: &ash::Instance) -> Self {
+        let handle = instance.handle();
+        let fp = InstanceFn::load(|name| unsafe {
+            mem::transmute(entry.get_instance_proc_addr(handle, name.as_ptr()))
+        });
+        Self { fp, handle }
+    }
+
+    #[inline]
+    pub fn fp(&self) -> &InstanceFn {
+        &self.fp
+    }
+
+    #[inline]
+    pub fn instance(&self) -> vk::Instance {
+        self.handle
+    }
+
+    /// `vkGetMoltenVKConfigurationMVK`
+    #[inline]
+    pub unsafe fn get_moltenvk_configuration(&self) -> VkResult<Configuration> {
+        let mut configuration = Configuration::default();
+        let mut size = mem::size_of::<Configuration>();
+        sized_result((self.fp.get_moltenvk_configuration_mvk)(
+            self.handle,
+            &mut configuration,
+            &mut size,
+        ))?;
+        Ok(configuration)
+    }
+
+    /// `vkSetMoltenVKConfigurationMVK`, which affects the whole process
+    #[inline]
+    pub unsafe fn set_moltenvk_configuration(&self, configuration: &Configuration) -> VkResult<()> {
+        let mut size = mem::size_of::<Configuration>();
+        sized_result((self.fp.set_moltenvk_configuration_mvk)(
+            self.handle,
+            configuration,
+            &mut size,
+        ))
+    }
+
+    /// `vkGetPhysicalDeviceMetalFeaturesMVK`
+    #[inline]
+    pub unsafe fn get_physical_device_metal_features(
+        &self,
+        physical_device: vk::PhysicalDevice,
+    ) -> VkResult<PhysicalDeviceMetalFeatures> {
+        let mut features = PhysicalDeviceMetalFeatures::default();
+        let mut size = mem::size_of::<PhysicalDeviceMetalFeatures>();
+        sized_result((self.fp.get_physical_device_metal_features_mvk)(
+            physical_device,
+            &mut features,
+            &mut size,
+        ))?;
+        Ok(features)
+    }
+
+    /// `vkGetVersionStringsMVK`, returning the MoltenVK and Vulkan versions, eg.
+    /// `("1.2.8", "1.2.275")`
+    #[inline]
+    pub unsafe fn get_version_strings(&self) -> (String, String) {
+        let mut molten = [0 as c_char; 64];
+        let mut vulkan = [0 as c_char; 64];
+        (self.fp.get_version_strings_mvk)(
+            molten.as_mut_ptr(),
+            molten.len() as u32,
+            vulkan.as_mut_ptr(),
+            vulkan.len() as u32,
+        );
+
+        let to_string = |buffer: &[c_char]| {
+            CStr::from_ptr(buffer.as_ptr())
+                .to_string_lossy()
+                .into_owned()
+        };
+        (to_string(&molten), to_string(&vulkan))
+    }
+
+    /// `vkGetMTLDeviceMVK`, returning the `id<MTLDevice>` backing `physical_device`
+    #[inline]
+    pub unsafe fn get_mtl_device(&self, physical_device: vk::PhysicalDevice) -> *mut c_void {
+        let mut mtl_device = std::ptr::null_mut();
+        (self.fp.get_mtl_device_mvk)(physical_device, &mut mtl_device);
+        mtl_device
+    }
+}
+
+/// Raw MoltenVK device-level function pointers
+#[derive(Clone)]
+pub struct DeviceFn {
+    pub get_performance_statistics_mvk: PFN_vkGetPerformanceStatisticsMVK,
+    pub set_mtl_texture_mvk: PFN_vkSetMTLTextureMVK,
+    pub get_mtl_texture_mvk: PFN_vkGetMTLTextureMVK,
+    pub get_mtl_buffer_mvk: PFN_vkGetMTLBufferMVK,
+    pub use_io_surface_mvk: PFN_vkUseIOSurfaceMVK,
+    pub get_io_surface_mvk: PFN_vkGetIOSurfaceMVK,
+    pub get_mtl_command_queue_mvk: PFN_vkGetMTLCommandQueueMVK,
+}
+
+impl DeviceFn {
+    pub fn load<F: FnMut(&CStr) -> *const c_void>(mut f: F) -> Self {
+        unsafe {
+            Self {
+                get_performance_statistics_mvk: load_fn!(
+                    f,
+                    "vkGetPerformanceStatisticsMVK",
+                    PFN_vkGetPerformanceStatisticsMVK, (vk::Device, *mut PerformanceStatistics, *mut usize) -> vk::Result),
+                set_mtl_texture_mvk: load_fn!(f, "vkSetMTLTextureMVK", PFN_vkSetMTLTextureMVK, (vk::Image, *mut c_void) -> vk::Result),
+                get_mtl_texture_mvk: load_fn!(
+                    f,
+                    "vkGetMTLTextureMVK",
+                    PFN_vkGetMTLTextureMVK,
+                    (vk::Image, *mut *mut c_void)
+                ),
+                get_mtl_buffer_mvk: load_fn!(
+                    f,
+                    "vkGetMTLBufferMVK",
+                    PFN_vkGetMTLBufferMVK,
+                    (vk::Buffer, *mut *mut c_void)
+                ),
+                use_io_surface_mvk: load_fn!(f, "vkUseIOSurfaceMVK", PFN_vkUseIOSurfaceMVK, (vk::Image, *mut c_void) -> vk::Result),
+                get_io_surface_mvk: load_fn!(
+                    f,
+                    "vkGetIOSurfaceMVK",
+                    PFN_vkGetIOSurfaceMVK,
+                    (vk::Image, *mut *mut c_void)
+                ),
+                get_mtl_command_queue_mvk: load_fn!(
+                    f,
+                    "vkGetMTLCommandQueueMVK",
+                    PFN_vkGetMTLCommandQueueMVK,
+                    (vk::Queue, *mut *mut c_void)
+                ),
+            }
+        }
+    }
+}
+
+/// MoltenVK device-level functions
+#[derive(Clone)]
+pub struct Device {
+    fp: DeviceFn,
+    handle: vk::Device,
+}
+
+impl Device {
+    pub fn new(instance: &ash::Instance, device: &ash::Device) -> Self {
+        let handle = device.handle();
+        let fp = DeviceFn::load(|name| unsafe {
+            mem::transmute(instance.get_device_proc_addr(handle, name.as_ptr()))
+        });
+        Self { fp, handle }
+    }
+
+    #[inline]
+    pub fn fp(&self) -> &DeviceFn {
+        &self.fp
+    }
+
+    #[inline]
+    pub fn device(&self) -> vk::Device {
+        self.handle
+    }
+
+    /// `vkGetPerformanceStatisticsMVK`
+    #[inline]
+    pub unsafe fn get_performance_statistics(&self) -> VkResult<PerformanceStatistics> {
+        let mut statistics = PerformanceStatistics::default();
+        let mut size = mem::size_of::<PerformanceStatistics>();
+        sized_result((self.fp.get_performance_statistics_mvk)(
+            self.handle,
+            &mut statistics,
+            &mut size,
+        ))?;
+        Ok(statistics)
+    }
+
+    /// `vkSetMTLTextureMVK`, backing `image` with an `id<MTLTexture>`
+    #[inline]
+    pub unsafe fn set_mtl_texture(
+        &self,
+        image: vk::Image,
+        mtl_texture: *mut c_void,
+    ) -> VkResult<()> {
+        (self.fp.set_mtl_texture_mvk)(image, mtl_texture).result()
+    }
+
+    /// `vkGetMTLTextureMVK`, returning the `id<MTLTexture>` backing `image`
+    #[inline]
+    pub unsafe fn get_mtl_texture(&self, image: vk::Image) -> *mut c_void {
+        let mut mtl_texture = std::ptr::null_mut();
+        (self.fp.get_mtl_texture_mvk)(image, &mut mtl_texture);
+        mtl_texture
+    }
+
+    /// `vkGetMTLBufferMVK`, returning the `id<MTLBuffer>` backing `buffer`
+    #[inline]
+    pub unsafe fn get_mtl_buffer(&self, buffer: vk::Buffer) -> *mut c_void {
+        let mut mtl_buffer = std::ptr::null_mut();
+        (self.fp.get_mtl_buffer_mvk)(buffer, &mut mtl_buffer);
+        mtl_buffer
+    }
+
+    /// `vkUseIOSurfaceMVK`, backing `image` with an `IOSurfaceRef`, or a new one if null
+    #[inline]
+    pub unsafe fn use_io_surface(&self, image: vk::Image, io_surface: *mut c_void) -> VkResult<()> {
+        (self.fp.use_io_surface_mvk)(image, io_surface).result()
+    }
+
+    /// `vkGetIOSurfaceMVK`, returning the `IOSurfaceRef` backing `image`
+    #[inline]
+    pub unsafe fn get_io_surface(&self, image: vk::Image) -> *mut c_void {
+        let mut io_surface = std::ptr::null_mut();
+        (self.fp.get_io_surface_mvk)(image, &mut io_surface);
+        io_surface
+    }
+
+    /// `vkGetMTLCommandQueueMVK`, returning the `id<MTLCommandQueue>` backing `queue`
+    #[inline]
+    pub unsafe fn get_mtl_command_queue(&self, queue: vk::Queue) -> *mut c_void {
+        let mut mtl_command_queue = std::ptr::null_mut();
+        (self.fp.get_mtl_command_queue_mvk)(queue, &mut mtl_command_queue);
+        mtl_command_queue
+    }
+}
@@ -0,0 +1,413 @@
+use crate::mvk;
+use ash::prelude::VkResult;
+use std::{collections::BTreeMap, time::Instant};
+
+/// The target of the events emitted by [`PerformanceSummary::emit`]
+#[cfg(feature = "tracing")]
+pub const STATISTICS_TARGET: &str = "moltenvk::statistics";
+
+/// Lists the timing trackers of `statistics` by their name, eg. `queue.frame_interval`
+fn trackers(
+    statistics: &mvk::PerformanceStatistics,
+) -> Vec<(&'static str, mvk::PerformanceTracker)> {
+    macro_rules! trackers {
+        ($($(#[$cfg:meta])* $group:ident.$tracker:ident,)*) => {
+            vec![$(
+                $(#[$cfg])*
+                (
+                    concat!(stringify!($group), ".", stringify!($tracker)),
+                    statistics.$group.$tracker,
+                ),
+            )*]
+        };
+    }
+
+    trackers! {
+        shader_compilation.hash_shader_code,
+        shader_compilation.spirv_to_msl,
+        shader_compilation.msl_compile,
+        shader_compilation.msl_load,
+        shader_compilation.msl_compress,
+        shader_compilation.msl_decompress,
+        shader_compilation.shader_library_from_cache,
+        shader_compilation.function_retrieval,
+        shader_compilation.function_specialization,
+        shader_compilation.pipeline_compile,
+        shader_compilation.glsl_to_spirv,
+        pipeline_cache.size_pipeline_cache,
+        pipeline_cache.write_pipeline_cache,
+        pipeline_cache.read_pipeline_cache,
+        #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+        queue.retrieve_mtl_command_buffer,
+        #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+        queue.command_buffer_encoding,
+        #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+        queue.submit_command_buffers,
+        #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+        queue.mtl_command_buffer_execution,
+        #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+        queue.retrieve_ca_metal_drawable,
+        #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+        queue.present_swapchains,
+        #[cfg(any(feature = "v1_1_5", feature = "v1_1_10"))]
+        queue.mtl_queue_access,
+        #[cfg(any(feature = "v1_1_5", feature = "v1_1_10"))]
+        queue.mtl_command_buffer_completion,
+        #[cfg(any(feature = "v1_1_5", feature = "v1_1_10"))]
+        queue.next_ca_metal_drawable,
+        queue.frame_interval,
+    }
+}
+
+/// The measurements one MoltenVK performance tracker took between two polls
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TrackerDelta {
+    /// The number of measurements
+    pub count: u32,
+    /// The sum of the measurements, in milliseconds
+    pub total_ms: f64,
+    /// The mean of the measurements, in milliseconds
+    pub average_ms: f64,
+    /// The most recent measurement, in milliseconds
+    pub latest_ms: f64,
+}
+
+impl TrackerDelta {
+    /// The measurements `current` took since `previous`, MoltenVK only reports running
+    /// averages so the total is derived from them
+    fn new(previous: &mvk::PerformanceTracker, current: &mvk::PerformanceTracker) -> Self {
+        // The statistics were reset in between
+        let previous = if current.count < previous.count {
+            &mvk::PerformanceTracker::default()
+        } else {
+            previous
+        };
+
+        let count = current.count - previous.count;
+        let total_ms = (current.average * f64::from(current.count)
+            - previous.average * f64::from(previous.count))
+        .max(0.0);
+
+        Self {
+            count,
+            total_ms,
+            average_ms: if count > 0 {
+                total_ms / f64::from(count)
+            } else {
+                0.0
+            },
+            latest_ms: current.latest,
+        }
+    }
+}
+
+/// What MoltenVK measured between two polls of a [`PerformanceCollector`], usually one frame
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PerformanceSummary {
+    /// The number of the poll, starting at 1
+    pub frame: u64,
+    /// The wall clock time since the previous poll, in milliseconds
+    pub elapsed_ms: f64,
+    /// The trackers that took measurements, by their name, eg. `queue.frame_interval`
+    pub trackers: BTreeMap<&'static str, TrackerDelta>,
+    /// The GPU memory allocated by the device, in kilobytes, `None` for MoltenVK releases that
+    /// don't track it
+    pub gpu_memory_allocated_kb: Option<f64>,
+}
+
+impl PerformanceSummary {
+    /// The measurements of a tracker, `None` if it took none
+    pub fn tracker(&self, name: &str) -> Option<&TrackerDelta> {
+        self.trackers.get(name)
+    }
+
+    /// Emits one `tracing` event per tracker with [`STATISTICS_TARGET`], carrying the frame,
+    /// the tracker name and its measurements as fields, and one for the allocated GPU memory
+    #[cfg(feature = "tracing")]
+    pub fn emit(&self) {
+        if let Some(gpu_memory_allocated_kb) = self.gpu_memory_allocated_kb {
+            tracing::info!(
+                target: STATISTICS_TARGET,
+                frame = self.frame,
+                elapsed_ms = self.elapsed_ms,
+                gpu_memory_allocated_kb,
+            );
+        }
+        for (tracker, delta) in &self.trackers {
+            tracing::info!(
+                target: STATISTICS_TARGET,
+                frame = self.frame,
+                tracker,
+                count = delta.count,
+                total_ms = delta.total_ms,
+                average_ms = delta.average_ms,
+                latest_ms = delta.latest_ms,
+            );
+        }
+    }
+}
+
+/// Polls `vkGetPerformanceStatisticsMVK` for a device and summarizes what changed in between.
+///
+/// MoltenVK only collects the statistics with
+/// [`MoltenVkSettings::performance_tracking`](crate::MoltenVkSettings::performance_tracking)
+/// enabled, otherwise every summary is empty.
+///
+/// ```no_run
+/// # unsafe fn frames(instance: &ash::Instance, device: &ash::Device) -> ash::prelude::VkResult<()> {
+/// let mut collector = ash_molten::PerformanceCollector::new(instance, device)?;
+/// loop {
+///     // Render and present a frame
+///     let summary = collector.poll()?;
+///     if let Some(compile) = summary.tracker("shader_compilation.msl_compile") {
+///         println!("compiled {} shaders in {}ms", compile.count, compile.total_ms);
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct PerformanceCollector {
+    device: mvk::Device,
+    previous: mvk::PerformanceStatistics,
+    previous_time: Instant,
+    frame: u64,
+}
+
+impl PerformanceCollector {
+    /// Creates a collector for `device`, taking the statistics so far as the baseline
+    ///
+    /// # Safety
+    ///
+    /// `device` must have been created from `instance` and outlive the collector.
+    #[allow(unsafe_code)]
+    pub unsafe fn new(instance: &ash::Instance, device: &ash::Device) -> VkResult<Self> {
+        Self::from_mvk(mvk::Device::new(instance, device))
+    }
+
+    /// Creates a collector from already loaded MoltenVK device functions, see
+    /// [`PerformanceCollector::new`]
+    ///
+    /// # Safety
+    ///
+    /// The device must outlive the collector.
+    #[allow(unsafe_code)]
+    pub unsafe fn from_mvk(device: mvk::Device) -> VkResult<Self> {
+        let previous = device.get_performance_statistics()?;
+        Ok(Self {
+            device,
+            previous,
+            previous_time: Instant::now(),
+            frame: 0,
+        })
+    }
+
+    /// Fetches the statistics and summarizes what changed since the previous poll
+    ///
+    /// # Safety
+    ///
+    /// The device must still be alive.
+    #[allow(unsafe_code)]
+    pub unsafe fn poll(&mut self) -> VkResult<PerformanceSummary> {
+        let current = self.device.get_performance_statistics()?;
+        let now = Instant::now();
+        self.frame += 1;
+
+        let trackers = trackers(&self.previous)
+            .into_iter()
+            .zip(trackers(&current))
+            .map(|((name, previous), (_, current))| (name, TrackerDelta::new(&previous, &current)))
+            .filter(|(_, delta)| delta.count > 0)
+            .collect();
+
+        #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+        let gpu_memory_allocated_kb = Some(current.device.gpu_memory_allocated.latest);
+        #[cfg(any(feature = "v1_1_5", feature = "v1_1_10"))]
+        let gpu_memory_allocated_kb = None;
+
+        let summary = PerformanceSummary {
+            frame: self.frame,
+            elapsed_ms: now.duration_since(self.previous_time).as_secs_f64() * 1000.0,
+            trackers,
+            gpu_memory_allocated_kb,
+        };
+
+        self.previous = current;
+        self.previous_time = now;
+        Ok(summary)
+    }
+
+    /// The cumulative statistics of the latest poll
+    pub fn statistics(&self) -> &mvk::PerformanceStatistics {
+        &self.previous
+    }
+}
+
+impl std::fmt::Debug for PerformanceCollector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PerformanceCollector")
+            .field("device", &self.device.device())
+            .field("frame", &self.frame)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+#[allow(unsafe_code)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{self, MockConfig},
+        DeviceBuilder, InstanceBuilder,
+    };
+
+    fn tracker(count: u32, average: f64, latest: f64) -> mvk::PerformanceTracker {
+        mvk::PerformanceTracker {
+            count,
+            latest,
+            average,
+            ..Default::default()
+        }
+    }
+
+    /// Creates a device whose statistics start at `initial`, and runs `f` with a collector for
+    /// it and a function replacing its statistics
+    fn with_collector(
+        initial: &mvk::PerformanceStatistics,
+        f: impl FnOnce(&mut PerformanceCollector, &dyn Fn(&mvk::PerformanceStatistics)),
+    ) {
+        let config = MockConfig {
+            performance_statistics: *initial,
+            ..MockConfig::default()
+        };
+        mock::with_config(config, || unsafe {
+            let entry = crate::MoltenEntry::load().unwrap();
+            let instance = InstanceBuilder::new().create(&entry).unwrap();
+            let physical_device = instance.enumerate_physical_devices().unwrap()[0];
+            let device = DeviceBuilder::new(physical_device)
+                .instance_api_version(instance.api_version())
+                .queue(0, &[1.0])
+                .create(&entry, &instance)
+                .unwrap();
+
+            let mut collector = PerformanceCollector::new(&instance, &device).unwrap();
+            let handle = device.handle();
+            f(&mut collector, &|statistics| {
+                mock::set_performance_statistics(handle, *statistics);
+            });
+
+            device.destroy_device(None);
+            instance.destroy_instance(None);
+        });
+    }
+
+    #[test]
+    fn first_poll_starts_at_baseline() {
+        let mut initial = mvk::PerformanceStatistics::default();
+        initial.shader_compilation.msl_compile = tracker(2, 3.0, 4.0);
+
+        with_collector(&initial, |collector, set_statistics| {
+            let mut statistics = initial;
+            statistics.shader_compilation.msl_compile = tracker(6, 4.0, 8.0);
+            statistics.queue.frame_interval = tracker(1, 16.0, 16.0);
+            set_statistics(&statistics);
+
+            let summary = unsafe { collector.poll() }.unwrap();
+            assert_eq!(summary.frame, 1);
+            assert_eq!(summary.trackers.len(), 2);
+            // 6 * 4ms - 2 * 3ms over the 4 new compilations
+            assert_eq!(
+                summary.tracker("shader_compilation.msl_compile"),
+                Some(&TrackerDelta {
+                    count: 4,
+                    total_ms: 18.0,
+                    average_ms: 4.5,
+                    latest_ms: 8.0,
+                })
+            );
+            assert_eq!(
+                summary.tracker("queue.frame_interval"),
+                Some(&TrackerDelta {
+                    count: 1,
+                    total_ms: 16.0,
+                    average_ms: 16.0,
+                    latest_ms: 16.0,
+                })
+            );
+            assert_eq!(collector.statistics(), &statistics);
+        });
+    }
+
+    #[test]
+    fn steady_state_reports_only_changes() {
+        let mut initial = mvk::PerformanceStatistics::default();
+        initial.queue.frame_interval = tracker(10, 16.0, 16.0);
+        initial.pipeline_cache.read_pipeline_cache = tracker(1, 2.0, 2.0);
+
+        with_collector(&initial, |collector, set_statistics| {
+            let summary = unsafe { collector.poll() }.unwrap();
+            assert_eq!(summary.frame, 1);
+            assert!(summary.trackers.is_empty());
+
+            let mut statistics = initial;
+            statistics.queue.frame_interval = tracker(11, 16.0, 16.0);
+            set_statistics(&statistics);
+            let summary = unsafe { collector.poll() }.unwrap();
+            assert_eq!(summary.frame, 2);
+            assert_eq!(
+                summary.trackers.keys().copied().collect::<Vec<_>>(),
+                ["queue.frame_interval"]
+            );
+            assert_eq!(summary.tracker("queue.frame_interval").unwrap().count, 1);
+            assert_eq!(
+                summary.tracker("queue.frame_interval").unwrap().total_ms,
+                16.0
+            );
+
+            let summary = unsafe { collector.poll() }.unwrap();
+            assert_eq!(summary.frame, 3);
+            assert!(summary.trackers.is_empty());
+        });
+    }
+
+    #[test]
+    fn counter_reset_starts_over() {
+        let mut initial = mvk::PerformanceStatistics::default();
+        initial.shader_compilation.spirv_to_msl = tracker(100, 1.0, 1.0);
+
+        with_collector(&initial, |collector, set_statistics| {
+            // Fewer measurements than before, so the statistics were reset in between
+            let mut statistics = initial;
+            statistics.shader_compilation.spirv_to_msl = tracker(3, 2.0, 3.0);
+            set_statistics(&statistics);
+
+            let summary = unsafe { collector.poll() }.unwrap();
+            assert_eq!(
+                summary.tracker("shader_compilation.spirv_to_msl"),
+                Some(&TrackerDelta {
+                    count: 3,
+                    total_ms: 6.0,
+                    average_ms: 2.0,
+                    latest_ms: 3.0,
+                })
+            );
+        });
+    }
+
+    #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+    #[test]
+    fn reports_gpu_memory() {
+        let mut initial = mvk::PerformanceStatistics::default();
+        initial.device.gpu_memory_allocated = tracker(1, 512.0, 512.0);
+
+        with_collector(&initial, |collector, set_statistics| {
+            let mut statistics = initial;
+            statistics.device.gpu_memory_allocated = tracker(2, 768.0, 1024.0);
+            set_statistics(&statistics);
+
+            let summary = unsafe { collector.poll() }.unwrap();
+            assert_eq!(summary.gpu_memory_allocated_kb, Some(1024.0));
+        });
+    }
+}
@@ -0,0 +1,786 @@
+use crate::mvk;
+use ash::vk;
+use std::{
+    ffi::{c_char, CStr, CString},
+    fmt,
+    marker::PhantomData,
+    time::Duration,
+};
+
+/// The layer name MoltenVK reads its `VK_EXT_layer_settings` under
+pub const LAYER_NAME: &CStr = c"MoltenVK";
+
+/// `MVK_CONFIG_LOG_LEVEL`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum LogLevel {
+    None,
+    Error,
+    Warning,
+    Info,
+    Debug,
+}
+
+impl From<LogLevel> for mvk::ConfigLogLevel {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::None => Self::NONE,
+            LogLevel::Error => Self::ERROR,
+            LogLevel::Warning => Self::WARNING,
+            LogLevel::Info => Self::INFO,
+            LogLevel::Debug => Self::DEBUG,
+        }
+    }
+}
+
+/// `MVK_CONFIG_FAST_MATH_ENABLED`, whether the Metal shader compiler may use fast math
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum FastMath {
+    Never,
+    Always,
+    /// Only if the shader doesn't need IEEE 754 compliance
+    OnDemand,
+}
+
+impl From<FastMath> for mvk::ConfigFastMath {
+    fn from(fast_math: FastMath) -> Self {
+        match fast_math {
+            FastMath::Never => Self::NEVER,
+            FastMath::Always => Self::ALWAYS,
+            FastMath::OnDemand => Self::ON_DEMAND,
+        }
+    }
+}
+
+/// `MVK_CONFIG_AUTO_GPU_CAPTURE_SCOPE`, what to capture with Xcode's GPU frame capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum GpuCaptureScope {
+    None,
+    /// Everything between device creation and destruction
+    Device,
+    /// The first frame
+    Frame,
+}
+
+impl From<GpuCaptureScope> for mvk::ConfigAutoGpuCaptureScope {
+    fn from(scope: GpuCaptureScope) -> Self {
+        match scope {
+            GpuCaptureScope::None => Self::NONE,
+            GpuCaptureScope::Device => Self::DEVICE,
+            GpuCaptureScope::Frame => Self::FRAME,
+        }
+    }
+}
+
+/// A MoltenVK setting value, as passed through `VK_EXT_layer_settings`
+enum Value {
+    Bool(bool),
+    U32(u32),
+    U64(u64),
+    F32(f32),
+    String(String),
+}
+
+trait SettingValue: Sized {
+    fn to_value(&self) -> Value;
+
+    /// Parses the `MVK_CONFIG_*` environment variable representation
+    fn parse(value: &str) -> Option<Self>;
+}
+
+impl SettingValue for bool {
+    fn to_value(&self) -> Value {
+        Value::Bool(*self)
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" => Some(true),
+            "0" | "false" | "no" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+impl SettingValue for u32 {
+    fn to_value(&self) -> Value {
+        Value::U32(*self)
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        value.trim().parse().ok()
+    }
+}
+
+impl SettingValue for f32 {
+    fn to_value(&self) -> Value {
+        Value::F32(*self)
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        value.trim().parse().ok()
+    }
+}
+
+impl SettingValue for String {
+    fn to_value(&self) -> Value {
+        Value::String(self.clone())
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        Some(value.to_owned())
+    }
+}
+
+/// In nanoseconds, like MoltenVK
+impl SettingValue for Duration {
+    fn to_value(&self) -> Value {
+        Value::U64(self.as_nanos().try_into().unwrap_or(u64::MAX))
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        value.trim().parse().ok().map(Duration::from_nanos)
+    }
+}
+
+/// Enums are passed by their numeric value, and can be parsed from either the value or the
+/// name
+macro_rules! enum_setting {
+    ($ty:ident => $mvk:ty { $($variant:ident = $name:literal,)* }) => {
+        impl SettingValue for $ty {
+            fn to_value(&self) -> Value {
+                Value::U32(<$mvk>::from(*self).0 as u32)
+            }
+
+            fn parse(value: &str) -> Option<Self> {
+                let value = value.trim().to_ascii_lowercase();
+                [$(Self::$variant,)*].into_iter().find(|variant| {
+                    let name = match variant {
+                        $(Self::$variant => $name,)*
+                    };
+                    value == name || value.parse() == Ok(<$mvk>::from(*variant).0)
+                })
+            }
+        }
+    };
+}
+
+enum_setting!(LogLevel => mvk::ConfigLogLevel {
+    None = "none",
+    Error = "error",
+    Warning = "warning",
+    Info = "info",
+    Debug = "debug",
+});
+enum_setting!(FastMath => mvk::ConfigFastMath {
+    Never = "never",
+    Always = "always",
+    OnDemand = "on_demand",
+});
+enum_setting!(GpuCaptureScope => mvk::ConfigAutoGpuCaptureScope {
+    None = "none",
+    Device = "device",
+    Frame = "frame",
+});
+
+/// A setting couldn't be loaded
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SettingsError {
+    /// A setting had a value that couldn't be parsed
+    InvalidValue { name: &'static str, value: String },
+    /// The TOML source couldn't be parsed
+    #[cfg(feature = "toml")]
+    Toml(toml::de::Error),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidValue { name, value } => {
+                write!(f, "invalid value {value:?} for the MoltenVK setting {name}")
+            }
+            #[cfg(feature = "toml")]
+            Self::Toml(err) => write!(f, "failed to parse MoltenVK settings: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "toml")]
+            Self::Toml(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Runtime configuration for MoltenVK, passed through `VK_EXT_layer_settings`.
+///
+/// Every setting is optional, MoltenVK falls back to its `MVK_CONFIG_*` environment variables
+/// and then its defaults for the ones that aren't set. Pass the settings to
+/// [`InstanceBuilder::settings`](crate::InstanceBuilder::settings), or chain
+/// [`LayerSettings::create_info`] into instance creation yourself.
+///
+/// ```no_run
+/// use ash_molten::{LogLevel, MoltenVkSettings};
+///
+/// let settings = MoltenVkSettings::new()
+///     .log_level(LogLevel::Debug)
+///     .use_metal_argument_buffers(true)
+///     .timestamp_period_low_pass_alpha(0.05);
+/// let layer_settings = settings.layer_settings();
+/// let mut settings_info = layer_settings.create_info();
+/// let create_info = ash::vk::InstanceCreateInfo::default().push_next(&mut settings_info);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct MoltenVkSettings {
+    /// `MVK_CONFIG_DEBUG`, enables debug mode with more logging
+    pub debug: Option<bool>,
+    /// `MVK_CONFIG_LOG_LEVEL`
+    pub log_level: Option<LogLevel>,
+    /// `MVK_CONFIG_USE_METAL_ARGUMENT_BUFFERS`, whether descriptor sets are implemented with
+    /// Metal argument buffers
+    pub use_metal_argument_buffers: Option<bool>,
+    /// `MVK_CONFIG_FAST_MATH_ENABLED`
+    pub fast_math: Option<FastMath>,
+    /// `MVK_CONFIG_METAL_COMPILE_TIMEOUT`, how long to wait for the Metal shader compiler, in
+    /// nanoseconds when deserialized
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_nanos"))]
+    pub metal_compile_timeout: Option<Duration>,
+    /// `MVK_CONFIG_SHOULD_MAXIMIZE_CONCURRENT_COMPILATION`, lets the Metal shader compiler use
+    /// more threads
+    pub should_maximize_concurrent_compilation: Option<bool>,
+    /// `MVK_CONFIG_TIMESTAMP_PERIOD_LOWPASS_ALPHA`, how much the reported timestamp period
+    /// follows the latest measurement, from `0.0` to `1.0`
+    pub timestamp_period_low_pass_alpha: Option<f32>,
+    /// `MVK_CONFIG_AUTO_GPU_CAPTURE_SCOPE`
+    pub auto_gpu_capture_scope: Option<GpuCaptureScope>,
+    /// `MVK_CONFIG_AUTO_GPU_CAPTURE_OUTPUT_FILE`, writes GPU captures to this file instead of
+    /// opening Xcode
+    pub auto_gpu_capture_output_file: Option<String>,
+    /// `MVK_CONFIG_SWAPCHAIN_MIN_MAG_FILTER_USE_NEAREST`, whether swapchain images are scaled
+    /// with nearest filtering
+    pub swapchain_min_mag_filter_use_nearest: Option<bool>,
+    /// `MVK_CONFIG_PRESENT_WITH_COMMAND_BUFFER`, whether swapchain presentation is encoded in a
+    /// command buffer
+    pub present_with_command_buffer: Option<bool>,
+    /// `MVK_CONFIG_SYNCHRONOUS_QUEUE_SUBMITS`
+    pub synchronous_queue_submits: Option<bool>,
+    /// `MVK_CONFIG_PERFORMANCE_TRACKING`, collects the statistics returned by
+    /// [`mvk::Device::get_performance_statistics`]
+    pub performance_tracking: Option<bool>,
+    /// `MVK_CONFIG_PERFORMANCE_LOGGING_FRAME_COUNT`, logs performance statistics every this
+    /// many frames
+    pub performance_logging_frame_count: Option<u32>,
+    /// `MVK_CONFIG_API_VERSION_TO_ADVERTISE`
+    pub api_version_to_advertise: Option<u32>,
+}
+
+#[cfg(feature = "serde")]
+fn deserialize_nanos<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    use serde::Deserialize as _;
+
+    Ok(Option::<u64>::deserialize(deserializer)?.map(Duration::from_nanos))
+}
+
+/// Generates the builder methods and the conversions from the table of settings in
+/// `ash-molten-build`, which the build script writes to `OUT_DIR`
+macro_rules! settings {
+    ($($field:ident: $ty:ty => $name:literal,)*) => {
+        impl MoltenVkSettings {
+            $(
+                #[doc = concat!("Sets [`MoltenVkSettings::", stringify!($field), "`]")]
+                pub fn $field(mut self, value: impl Into<$ty>) -> Self {
+                    self.$field = Some(value.into());
+                    self
+                }
+            )*
+
+            /// The settings that are set, by their MoltenVK name
+            fn values(&self) -> Vec<(&'static str, Value)> {
+                // Fails to compile unless the table lists every field
+                let Self { $($field,)* } = self;
+                let mut values = Vec::new();
+                $(
+                    if let Some(value) = $field {
+                        values.push(($name, value.to_value()));
+                    }
+                )*
+                values
+            }
+
+            /// Loads the settings from `var`, which is called with the MoltenVK name of each
+            /// setting, eg. `LOG_LEVEL`
+            pub fn from_vars(
+                mut var: impl FnMut(&str) -> Option<String>,
+            ) -> Result<Self, SettingsError> {
+                let mut settings = Self::default();
+                $(
+                    if let Some(value) = var($name) {
+                        settings.$field = Some(
+                            <$ty as SettingValue>::parse(&value)
+                                .ok_or(SettingsError::InvalidValue { name: $name, value })?,
+                        );
+                    }
+                )*
+                Ok(settings)
+            }
+
+            /// Overrides the settings in `self` with the ones set in `other`
+            pub fn merge(mut self, other: Self) -> Self {
+                $(
+                    if other.$field.is_some() {
+                        self.$field = other.$field;
+                    }
+                )*
+                self
+            }
+        }
+    };
+}
+
+include!(concat!(env!("OUT_DIR"), "/settings.rs"));
+
+impl MoltenVkSettings {
+    /// No settings, MoltenVK uses its environment variables and defaults
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The defaults baked in at build time with the `ASH_MOLTEN_MVK_CONFIG_*` environment
+    /// variables, when MoltenVK wasn't built from source with them, see
+    /// [`MOLTENVK_RUNTIME_CONFIG`](crate::MOLTENVK_RUNTIME_CONFIG).
+    ///
+    /// [`InstanceBuilder`](crate::InstanceBuilder) applies these underneath its own settings.
+    /// The build script validates the values against the same table of settings, so every
+    /// value is valid.
+    pub fn build_defaults() -> Self {
+        crate::MOLTENVK_RUNTIME_CONFIG
+            .unwrap_or_default()
+            .split(';')
+            .filter_map(|pair| {
+                let (name, value) = pair.split_once('=')?;
+                Self::from_vars(|var| (var == name).then(|| value.to_owned())).ok()
+            })
+            .fold(Self::default(), Self::merge)
+    }
+
+    /// Loads the settings from the `MVK_CONFIG_*` environment variables MoltenVK reads itself,
+    /// eg. to override them with [`MoltenVkSettings::merge`]
+    pub fn from_env() -> Result<Self, SettingsError> {
+        Self::from_env_prefix("MVK_CONFIG_")
+    }
+
+    /// Loads the settings from environment variables named `prefix` followed by the MoltenVK
+    /// name of each setting, eg. `MYAPP_MVK_LOG_LEVEL`
+    pub fn from_env_prefix(prefix: &str) -> Result<Self, SettingsError> {
+        Self::from_vars(|name| std::env::var(format!("{prefix}{name}")).ok())
+    }
+
+    /// Loads the settings from a TOML document, using the field names of [`MoltenVkSettings`]
+    ///
+    /// ```toml
+    /// log_level = "debug"
+    /// use_metal_argument_buffers = true
+    /// auto_gpu_capture_scope = "frame"
+    /// ```
+    #[cfg(feature = "toml")]
+    pub fn from_toml(source: &str) -> Result<Self, SettingsError> {
+        toml::from_str(source).map_err(SettingsError::Toml)
+    }
+
+    /// Whether no setting is set
+    pub fn is_empty(&self) -> bool {
+        self.values().is_empty()
+    }
+
+    /// Writes the settings into a configuration for
+    /// [`mvk::Instance::set_moltenvk_configuration`], for MoltenVK releases without
+    /// `VK_EXT_layer_settings`.
+    ///
+    /// [`MoltenVkSettings::auto_gpu_capture_output_file`] is skipped, as the configuration only
+    /// borrows the string, as are the settings the configuration of older releases lacks.
+    pub fn apply_to(&self, configuration: &mut mvk::Configuration) {
+        let bool32 = |value: bool| vk::Bool32::from(value);
+
+        if let Some(debug) = self.debug {
+            configuration.debug_mode = bool32(debug);
+        }
+        if let Some(log_level) = self.log_level {
+            configuration.log_level = log_level.into();
+        }
+        if let Some(enable) = self.use_metal_argument_buffers {
+            configuration.use_metal_argument_buffers = bool32(enable);
+        }
+        if let Some(fast_math) = self.fast_math {
+            configuration.fast_math_enabled = fast_math.into();
+        }
+        if let Some(timeout) = self.metal_compile_timeout {
+            configuration.metal_compile_timeout = timeout.as_nanos().try_into().unwrap_or(u64::MAX);
+        }
+        #[cfg(not(any(feature = "v1_1_5", feature = "v1_1_10")))]
+        {
+            if let Some(enable) = self.should_maximize_concurrent_compilation {
+                configuration.should_maximize_concurrent_compilation = bool32(enable);
+            }
+            if let Some(alpha) = self.timestamp_period_low_pass_alpha {
+                configuration.timestamp_period_low_pass_alpha = alpha;
+            }
+        }
+        if let Some(scope) = self.auto_gpu_capture_scope {
+            configuration.auto_gpu_capture_scope = scope.into();
+        }
+        if let Some(enable) = self.swapchain_min_mag_filter_use_nearest {
+            configuration.swapchain_min_mag_filter_use_nearest = bool32(enable);
+        }
+        if let Some(enable) = self.present_with_command_buffer {
+            configuration.present_with_command_buffer = bool32(enable);
+        }
+        if let Some(enable) = self.synchronous_queue_submits {
+            configuration.synchronous_queue_submits = bool32(enable);
+        }
+        if let Some(enable) = self.performance_tracking {
+            configuration.performance_tracking = bool32(enable);
+        }
+        if let Some(count) = self.performance_logging_frame_count {
+            configuration.performance_logging_frame_count = count;
+        }
+        if let Some(version) = self.api_version_to_advertise {
+            configuration.api_version_to_advertise = version;
+        }
+    }
+
+    /// Converts the settings into the [`vk::LayerSettingEXT`]s MoltenVK expects
+    pub fn layer_settings(&self) -> LayerSettings {
+        let mut storage = Vec::new();
+        let settings = self
+            .values()
+            .into_iter()
+            .map(|(name, value)| {
+                let name = CString::new(name).expect("setting names have no nul bytes");
+                let (ty, value) = match value {
+                    Value::Bool(value) => (
+                        vk::LayerSettingTypeEXT::BOOL32,
+                        Storage::U32(Box::new(value.into())),
+                    ),
+                    Value::U32(value) => (
+                        vk::LayerSettingTypeEXT::UINT32,
+                        Storage::U32(Box::new(value)),
+                    ),
+                    Value::U64(value) => (
+                        vk::LayerSettingTypeEXT::UINT64,
+                        Storage::U64(Box::new(value)),
+                    ),
+                    Value::F32(value) => (
+                        vk::LayerSettingTypeEXT::FLOAT32,
+                        Storage::F32(Box::new(value)),
+                    ),
+                    Value::String(value) => {
+                        let value =
+                            CString::new(value.replace('\0', "")).expect("nul bytes were removed");
+                        let ptr = Box::new(value.as_ptr());
+                        (vk::LayerSettingTypeEXT::STRING, Storage::String(value, ptr))
+                    }
+                };
+
+                // The pointers stay valid as long as `storage` is alive, as they point into
+                // heap allocations that aren't moved when the vectors are
+                let setting = vk::LayerSettingEXT {
+                    p_layer_name: LAYER_NAME.as_ptr(),
+                    p_setting_name: name.as_ptr(),
+                    ty,
+                    value_count: 1,
+                    p_values: value.as_ptr(),
+                    _marker: PhantomData,
+                };
+                storage.push((name, value));
+                setting
+            })
+            .collect();
+
+        LayerSettings { settings, storage }
+    }
+}
+
+enum Storage {
+    U32(Box<u32>),
+    U64(Box<u64>),
+    F32(Box<f32>),
+    /// The string, and the pointer to it the setting points to
+    String(#[allow(dead_code)] CString, Box<*const c_char>),
+}
+
+impl Storage {
+    fn as_ptr(&self) -> *const std::ffi::c_void {
+        match self {
+            Self::U32(value) => (&**value as *const u32).cast(),
+            Self::U64(value) => (&**value as *const u64).cast(),
+            Self::F32(value) => (&**value as *const f32).cast(),
+            Self::String(_, ptr) => (&**ptr as *const *const c_char).cast(),
+        }
+    }
+}
+
+/// The [`vk::LayerSettingEXT`]s for a [`MoltenVkSettings`], along with the values they point
+/// to
+pub struct LayerSettings {
+    settings: Vec<vk::LayerSettingEXT<'static>>,
+    /// The names and values `settings` point to
+    storage: Vec<(CString, Storage)>,
+}
+
+impl LayerSettings {
+    /// The individual settings
+    pub fn settings(&self) -> &[vk::LayerSettingEXT<'_>] {
+        &self.settings
+    }
+
+    /// The struct to chain into [`vk::InstanceCreateInfo`], which also requires
+    /// `VK_EXT_layer_settings` to be enabled
+    pub fn create_info(&self) -> vk::LayerSettingsCreateInfoEXT<'_> {
+        vk::LayerSettingsCreateInfoEXT::default().settings(&self.settings)
+    }
+}
+
+impl fmt::Debug for LayerSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.storage.iter().map(|(name, _)| name))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+#[allow(unsafe_code)]
+mod tests {
+    use super::*;
+
+    /// Every setting, none of them at MoltenVK's default
+    fn all_settings() -> MoltenVkSettings {
+        MoltenVkSettings::new()
+            .debug(true)
+            .log_level(LogLevel::Debug)
+            .use_metal_argument_buffers(true)
+            .fast_math(FastMath::Always)
+            .metal_compile_timeout(Duration::from_secs(5))
+            .should_maximize_concurrent_compilation(true)
+            .timestamp_period_low_pass_alpha(0.25)
+            .auto_gpu_capture_scope(GpuCaptureScope::Frame)
+            .auto_gpu_capture_output_file("/tmp/capture.gputrace")
+            .swapchain_min_mag_filter_use_nearest(false)
+            .present_with_command_buffer(false)
+            .synchronous_queue_submits(false)
+            .performance_tracking(true)
+            .performance_logging_frame_count(300u32)
+            .api_version_to_advertise(vk::API_VERSION_1_1)
+    }
+
+    #[test]
+    fn reads_env_prefix() {
+        // Unique to this test, as the environment is shared by the whole process
+        std::env::set_var("ASH_MOLTEN_SETTINGS_TEST_LOG_LEVEL", "Warning");
+        std::env::set_var("ASH_MOLTEN_SETTINGS_TEST_DEBUG", "yes");
+        std::env::set_var("ASH_MOLTEN_SETTINGS_TEST_METAL_COMPILE_TIMEOUT", " 1000 ");
+        std::env::set_var("ASH_MOLTEN_SETTINGS_TEST_FAST_MATH_ENABLED", "2");
+        let settings = MoltenVkSettings::from_env_prefix("ASH_MOLTEN_SETTINGS_TEST_").unwrap();
+        assert_eq!(
+            settings,
+            MoltenVkSettings::new()
+                .log_level(LogLevel::Warning)
+                .debug(true)
+                .metal_compile_timeout(Duration::from_nanos(1000))
+                .fast_math(FastMath::OnDemand)
+        );
+
+        std::env::set_var(
+            "ASH_MOLTEN_SETTINGS_INVALID_TEST_PERFORMANCE_TRACKING",
+            "maybe",
+        );
+        let err = MoltenVkSettings::from_env_prefix("ASH_MOLTEN_SETTINGS_INVALID_TEST_");
+        assert!(matches!(
+            err,
+            Err(SettingsError::InvalidValue { name: "PERFORMANCE_TRACKING", value }) if value == "maybe"
+        ));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn reads_toml() {
+        let settings = MoltenVkSettings::from_toml(
+            r#"
+            log_level = "info"
+            use_metal_argument_buffers = true
+            metal_compile_timeout = 2000
+            auto_gpu_capture_scope = "device"
+            auto_gpu_capture_output_file = "capture.gputrace"
+            timestamp_period_low_pass_alpha = 0.5
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            settings,
+            MoltenVkSettings::new()
+                .log_level(LogLevel::Info)
+                .use_metal_argument_buffers(true)
+                .metal_compile_timeout(Duration::from_nanos(2000))
+                .auto_gpu_capture_scope(GpuCaptureScope::Device)
+                .auto_gpu_capture_output_file("capture.gputrace")
+                .timestamp_period_low_pass_alpha(0.5)
+        );
+
+        assert!(matches!(
+            MoltenVkSettings::from_toml("log_levels = \"info\""),
+            Err(SettingsError::Toml(_))
+        ));
+        assert!(matches!(
+            MoltenVkSettings::from_toml("log_level = \"verbose\""),
+            Err(SettingsError::Toml(_))
+        ));
+    }
+
+    #[test]
+    fn merge_prefers_other() {
+        let base = MoltenVkSettings::new()
+            .log_level(LogLevel::Info)
+            .debug(true);
+        let overrides = MoltenVkSettings::new()
+            .log_level(LogLevel::Error)
+            .fast_math(FastMath::Never);
+
+        assert_eq!(
+            base.clone().merge(overrides.clone()),
+            MoltenVkSettings::new()
+                .log_level(LogLevel::Error)
+                .debug(true)
+                .fast_math(FastMath::Never)
+        );
+        assert_eq!(
+            overrides.clone().merge(base.clone()),
+            MoltenVkSettings::new()
+                .log_level(LogLevel::Info)
+                .debug(true)
+                .fast_math(FastMath::Never)
+        );
+        assert_eq!(base.clone().merge(MoltenVkSettings::new()), base);
+        assert_eq!(MoltenVkSettings::new().merge(base.clone()), base);
+    }
+
+    #[test]
+    fn layer_settings_outlive_settings() {
+        let settings = all_settings();
+        // Moving the settings must not invalidate the pointers, nor dropping what they came from
+        let layer_settings = Box::new(settings.layer_settings());
+        drop(settings);
+
+        assert_eq!(layer_settings.settings().len(), 15);
+        let find = |name: &CStr| {
+            let setting = layer_settings
+                .settings()
+                .iter()
+                .find(|setting| unsafe { CStr::from_ptr(setting.p_setting_name) } == name)
+                .unwrap();
+            assert_eq!(unsafe { CStr::from_ptr(setting.p_layer_name) }, LAYER_NAME);
+            assert_eq!(setting.value_count, 1);
+            *setting
+        };
+
+        let debug = find(c"DEBUG");
+        assert!(debug.ty == vk::LayerSettingTypeEXT::BOOL32);
+        assert_eq!(unsafe { *debug.p_values.cast::<vk::Bool32>() }, vk::TRUE);
+
+        let log_level = find(c"LOG_LEVEL");
+        assert!(log_level.ty == vk::LayerSettingTypeEXT::UINT32);
+        assert_eq!(unsafe { *log_level.p_values.cast::<u32>() }, 4);
+
+        let timeout = find(c"METAL_COMPILE_TIMEOUT");
+        assert!(timeout.ty == vk::LayerSettingTypeEXT::UINT64);
+        assert_eq!(unsafe { *timeout.p_values.cast::<u64>() }, 5_000_000_000);
+
+        let alpha = find(c"TIMESTAMP_PERIOD_LOWPASS_ALPHA");
+        assert!(alpha.ty == vk::LayerSettingTypeEXT::FLOAT32);
+        assert_eq!(unsafe { *alpha.p_values.cast::<f32>() }, 0.25);
+
+        let file = find(c"AUTO_GPU_CAPTURE_OUTPUT_FILE");
+        assert!(file.ty == vk::LayerSettingTypeEXT::STRING);
+        let file = unsafe { CStr::from_ptr(*file.p_values.cast::<*const c_char>()) };
+        assert_eq!(file, c"/tmp/capture.gputrace");
+
+        let create_info = layer_settings.create_info();
+        assert_eq!(create_info.setting_count, 15);
+        assert_eq!(create_info.p_settings, layer_settings.settings().as_ptr());
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn apply_to_matches_layer_settings() {
+        use crate::{
+            mock::{self, MockConfig},
+            InstanceBuilder,
+        };
+
+        let entry = crate::MoltenEntry::load().unwrap();
+        let configuration = |instance: &ash::Instance| unsafe {
+            mvk::Instance::new(&entry, instance)
+                .get_moltenvk_configuration()
+                .unwrap()
+        };
+
+        let (through_layer, through_apply) = mock::with_config(MockConfig::default(), || {
+            let instance = unsafe {
+                InstanceBuilder::new()
+                    .settings(all_settings())
+                    .create(&entry)
+            }
+            .unwrap();
+            let through_layer = configuration(&instance);
+            unsafe { instance.destroy_instance(None) };
+
+            let instance = unsafe { InstanceBuilder::new().create(&entry) }.unwrap();
+            let mut through_apply = configuration(&instance);
+            all_settings().apply_to(&mut through_apply);
+            unsafe {
+                mvk::Instance::new(&entry, &instance)
+                    .set_moltenvk_configuration(&through_apply)
+                    .unwrap();
+            }
+            let through_apply = configuration(&instance);
+            unsafe { instance.destroy_instance(None) };
+
+            (through_layer, through_apply)
+        });
+
+        assert_eq!(through_apply.log_level, mvk::ConfigLogLevel::DEBUG);
+        assert_eq!(through_apply.fast_math_enabled, mvk::ConfigFastMath::ALWAYS);
+        assert_eq!(through_apply.metal_compile_timeout, 5_000_000_000);
+        assert_eq!(through_apply.performance_logging_frame_count, 300);
+        assert_eq!(
+            through_apply.swapchain_min_mag_filter_use_nearest,
+            vk::FALSE
+        );
+        // The configuration isn't `PartialEq`
+        assert_eq!(format!("{through_layer:?}"), format!("{through_apply:?}"));
+    }
+}
@@ -0,0 +1,157 @@
+//! Creating surfaces for `raw-window-handle` windows, enabled with the `surface` feature.
+//!
+//! MoltenVK presents to a `CAMetalLayer` through `VK_EXT_metal_surface`. [`create_surface`]
+//! reuses the layer of an `NSView` or `UIView` if it already is a `CAMetalLayer`, like the
+//! layer of an `MTKView`, and otherwise adds one as a sublayer that follows the bounds and
+//! contents scale of the view. [`create_headless_surface`] creates a surface that isn't shown
+//! anywhere through `VK_EXT_headless_surface`, for tests and offscreen rendering.
+//!
+//! ```no_run
+//! # unsafe fn surface(display: raw_window_handle::RawDisplayHandle, window: raw_window_handle::RawWindowHandle) -> Result<(), Box<dyn std::error::Error>> {
+//! use ash_molten::surface;
+//!
+//! let entry = ash_molten::MoltenEntry::load()?;
+//! let instance = ash_molten::InstanceBuilder::new()
+//!     .extensions(surface::required_extensions(display)?.iter().copied())
+//!     .create(&entry)?;
+//! let surface = surface::create_surface(&entry, &instance, window, None)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use ash::{prelude::VkResult, vk};
+use raw_window_handle::{RawDisplayHandle, RawWindowHandle};
+use std::{
+    ffi::{c_void, CStr},
+    fmt,
+};
+
+/// The instance extensions [`create_metal_layer_surface`] needs
+pub const METAL_EXTENSIONS: [&CStr; 2] = [ash::khr::surface::NAME, ash::ext::metal_surface::NAME];
+
+/// The instance extensions [`create_headless_surface`] needs
+pub const HEADLESS_EXTENSIONS: [&CStr; 2] =
+    [ash::khr::surface::NAME, ash::ext::headless_surface::NAME];
+
+/// Failed to create a surface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SurfaceError {
+    /// The display isn't an AppKit or UIKit display
+    UnsupportedDisplay,
+    /// The window isn't an AppKit or UIKit view, or the target isn't macOS or iOS
+    UnsupportedWindow,
+    /// A Vulkan call failed
+    Vulkan(vk::Result),
+}
+
+impl fmt::Display for SurfaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedDisplay => f.write_str("only AppKit and UIKit displays are supported"),
+            Self::UnsupportedWindow => f.write_str("only AppKit and UIKit windows are supported"),
+            Self::Vulkan(result) => write!(f, "failed to create surface: {result}"),
+        }
+    }
+}
+
+impl std::error::Error for SurfaceError {}
+
+impl From<vk::Result> for SurfaceError {
+    fn from(result: vk::Result) -> Self {
+        Self::Vulkan(result)
+    }
+}
+
+/// The instance extensions [`create_surface`] needs for windows of `display`
+pub fn required_extensions(
+    display: RawDisplayHandle,
+) -> Result<&'static [&'static CStr], SurfaceError> {
+    match display {
+        RawDisplayHandle::AppKit(_) | RawDisplayHandle::UiKit(_) => Ok(&METAL_EXTENSIONS),
+        _ => Err(SurfaceError::UnsupportedDisplay),
+    }
+}
+
+/// Creates a surface presenting to the view of `window`.
+///
+/// # Panics
+///
+/// AppKit and UIKit views may only be accessed on the main thread, so this panics if called
+/// from another thread.
+///
+/// # Safety
+///
+/// [`METAL_EXTENSIONS`] must be enabled on `instance`, and `window` must be a valid view that
+/// outlives the surface.
+#[allow(unsafe_code)]
+#[cfg_attr(not(target_vendor = "apple"), allow(unused_variables))]
+pub unsafe fn create_surface(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    window: RawWindowHandle,
+    allocation_callbacks: Option<&vk::AllocationCallbacks<'_>>,
+) -> Result<vk::SurfaceKHR, SurfaceError> {
+    // MoltenVK retains the layer for as long as the surface exists
+    match window {
+        #[cfg(target_vendor = "apple")]
+        RawWindowHandle::AppKit(handle) => {
+            let layer = raw_window_metal::Layer::from_ns_view(handle.ns_view);
+            let surface = create_metal_layer_surface(
+                entry,
+                instance,
+                layer.as_ptr().as_ptr(),
+                allocation_callbacks,
+            )?;
+            Ok(surface)
+        }
+        #[cfg(target_vendor = "apple")]
+        RawWindowHandle::UiKit(handle) => {
+            let layer = raw_window_metal::Layer::from_ui_view(handle.ui_view);
+            let surface = create_metal_layer_surface(
+                entry,
+                instance,
+                layer.as_ptr().as_ptr(),
+                allocation_callbacks,
+            )?;
+            Ok(surface)
+        }
+        _ => Err(SurfaceError::UnsupportedWindow),
+    }
+}
+
+/// Creates a surface presenting to an existing `CAMetalLayer`
+///
+/// # Safety
+///
+/// [`METAL_EXTENSIONS`] must be enabled on `instance`, and `layer` must be a valid
+/// `CAMetalLayer`.
+#[allow(unsafe_code)]
+pub unsafe fn create_metal_layer_surface(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    layer: *const c_void,
+    allocation_callbacks: Option<&vk::AllocationCallbacks<'_>>,
+) -> VkResult<vk::SurfaceKHR> {
+    let create_info = vk::MetalSurfaceCreateInfoEXT::default().layer(layer);
+    ash::ext::metal_surface::Instance::new(entry, instance)
+        .create_metal_surface(&create_info, allocation_callbacks)
+}
+
+/// Creates a surface that isn't presented anywhere, whose swapchain images have to be sized
+/// by the application
+///
+/// # Safety
+///
+/// [`HEADLESS_EXTENSIONS`] must be enabled on `instance`.
+#[allow(unsafe_code)]
+pub unsafe fn create_headless_surface(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    allocation_callbacks: Option<&vk::AllocationCallbacks<'_>>,
+) -> VkResult<vk::SurfaceKHR> {
+    ash::ext::headless_surface::Instance::new(entry, instance).create_headless_surface(
+        &vk::HeadlessSurfaceCreateInfoEXT::default(),
+        allocation_callbacks,
+    )
+}
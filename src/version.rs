@@ -0,0 +1,110 @@
+use ash::vk;
+use std::fmt;
+
+/// The MoltenVK release that was linked, eg. `"1.2.8"`.
+///
+/// With the `external` feature this is the release `ash-molten` would otherwise have used, as
+/// the supplied library can't be inspected at build time.
+pub const MOLTENVK_VERSION: &str = env!("MOLTENVK_VERSION");
+
+/// The MoltenVK artifact that was linked, in the form of "x.x.x" or if a patch was applied
+/// "x.x.x#yyyyyyy"
+pub const MOLTENVK_ARTIFACT_TAG: &str = env!("MOLTENVK_ARTIFACT_TAG");
+
+/// Where the linked MoltenVK came from, one of `"built"`, `"prebuilt"`, `"external"` or
+/// `"local"`, or `"mock"` with the `mock` feature and `"unsupported"` on targets MoltenVK
+/// doesn't support, where nothing is linked
+pub const MOLTENVK_SOURCE: &str = env!("MOLTENVK_SOURCE");
+
+/// The MoltenVK commit that was linked, if it is known
+pub const MOLTENVK_COMMIT: Option<&str> = option_env!("MOLTENVK_COMMIT");
+
+/// The default MoltenVK configuration baked in at build time that MoltenVK wasn't compiled
+/// with, as `NAME=value` pairs separated by `;`.
+///
+/// [`InstanceBuilder`](crate::InstanceBuilder) applies it, see
+/// [`MoltenVkSettings::build_defaults`](crate::MoltenVkSettings::build_defaults).
+pub const MOLTENVK_RUNTIME_CONFIG: Option<&str> = option_env!("MOLTENVK_RUNTIME_CONFIG");
+
+/// A MoltenVK release version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    /// The version of the MoltenVK that was linked at build time, see [`MOLTENVK_VERSION`]
+    pub fn linked() -> Self {
+        Self::parse(MOLTENVK_VERSION).expect("MOLTENVK_VERSION is always a valid version")
+    }
+
+    /// Parses a version in the form of "x.y.z"
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.split('.').map(|part| part.parse().ok());
+        let version = Self {
+            major: parts.next()??,
+            minor: parts.next()??,
+            patch: parts.next()??,
+        };
+
+        parts.next().is_none().then_some(version)
+    }
+
+    /// Decodes the `driverVersion` MoltenVK reports in [`vk::PhysicalDeviceProperties`], which
+    /// is encoded as `major * 10000 + minor * 100 + patch`
+    pub fn from_driver_version(driver_version: u32) -> Self {
+        Self {
+            major: driver_version / 10000,
+            minor: (driver_version % 10000) / 100,
+            patch: driver_version % 100,
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The MoltenVK version reported by a physical device doesn't match the version that was
+/// linked at build time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionMismatch {
+    /// The version that was linked, see [`MOLTENVK_VERSION`]
+    pub linked: Version,
+    /// The version the driver reports
+    pub driver: Version,
+}
+
+impl fmt::Display for VersionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "MoltenVK {} was linked, but the driver reports {}",
+            self.linked, self.driver
+        )
+    }
+}
+
+impl std::error::Error for VersionMismatch {}
+
+/// Checks that the MoltenVK version reported by a physical device matches the one that was
+/// linked at build time, returning the driver's version.
+///
+/// This mostly matters with the `external` feature, where the linked library isn't the one
+/// `ash-molten` resolved itself.
+pub fn check_driver_version(
+    properties: &vk::PhysicalDeviceProperties,
+) -> Result<Version, VersionMismatch> {
+    let linked = Version::linked();
+    let driver = Version::from_driver_version(properties.driver_version);
+
+    if linked == driver {
+        Ok(driver)
+    } else {
+        Err(VersionMismatch { linked, driver })
+    }
+}